@@ -1,10 +1,24 @@
-use js_sys::Uint8Array;
+use futures_util::{StreamExt, TryStreamExt};
+use js_sys::{Promise, Uint8Array};
+use std::{cell::Cell, rc::Rc};
 use util::{js_error, js_to_js_error, to_future};
 use wasm_bindgen::{prelude::*, JsCast, JsValue};
-use web_sys::{Cache, Request, RequestInit, RequestMode, Response};
+use web_sys::{
+    AbortController, AbortSignal, Cache, Request, RequestCredentials, RequestInit, RequestMode,
+    Response,
+};
 
+mod logging;
+mod revision;
+mod sharded;
+mod store;
 mod util;
 
+pub use logging::*;
+pub use revision::*;
+pub use sharded::*;
+pub use store::*;
+
 #[cfg(test)]
 use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
 
@@ -25,10 +39,34 @@ pub enum RepoType {
     Space,
 }
 
+const HF_BASE_URL: &str = "https://huggingface.co";
+const CACHE_NAME: &str = "ratchet-cache";
+/// Browsers allow 6 connections per host over HTTP/1.1.
+const DEFAULT_MAX_CONCURRENT: usize = 6;
+
+#[derive(Debug, Clone)]
+enum ApiSource {
+    Hub {
+        repo_id: String,
+        ty: RepoType,
+        revision: Revision,
+    },
+    Custom {
+        endpoint: String,
+        revision: Option<Revision>,
+    },
+}
+
 #[wasm_bindgen]
 pub struct ApiBuilder {
-    endpoint: String,
+    source: ApiSource,
+    base_url: String,
     cached: bool,
+    mode: RequestMode,
+    credentials: RequestCredentials,
+    headers: Vec<(String, String)>,
+    max_concurrent: usize,
+    revalidate: bool,
 }
 
 #[wasm_bindgen]
@@ -36,42 +74,110 @@ impl ApiBuilder {
     /// Build an Api from a HF hub repository.
     #[wasm_bindgen]
     pub fn from_hf(repo_id: &str, ty: RepoType) -> Self {
-        Self {
-            cached: true,
-            endpoint: Self::endpoint(repo_id, ty),
-        }
+        Self::hub(repo_id.to_string(), ty, Revision::main())
     }
 
     pub fn endpoint(repo_id: &str, ty: RepoType) -> String {
-        match ty {
-            RepoType::Model => {
-                format!("https://huggingface.co/{repo_id}/resolve/main")
-            }
-            RepoType::Dataset => {
-                format!("https://huggingface.co/datasets/{repo_id}/resolve/main")
-            }
-            RepoType::Space => {
-                format!("https://huggingface.co/spaces/{repo_id}/resolve/main")
-            }
-        }
+        Self::endpoint_at(HF_BASE_URL, repo_id, ty, Revision::main().as_str())
     }
 
     /// Build an Api from a HF hub repository at a specific revision.
+    /// A full commit SHA is taken as a commit, anything else as a branch.
     #[wasm_bindgen]
     pub fn from_hf_with_revision(repo_id: String, revision: String) -> Self {
-        Self {
-            cached: true,
-            endpoint: format!("https://huggingface.co/{repo_id}/resolve/{revision}"),
-        }
+        Self::hub(repo_id, RepoType::Model, Revision::parse(revision))
     }
 
     /// Build an Api from a custom URL.
     #[wasm_bindgen]
     pub fn from_custom(endpoint: String) -> Self {
         Self {
+            source: ApiSource::Custom {
+                endpoint,
+                revision: None,
+            },
+            base_url: HF_BASE_URL.to_string(),
             cached: true,
-            endpoint,
+            mode: RequestMode::Cors,
+            credentials: RequestCredentials::SameOrigin,
+            headers: vec![],
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            revalidate: false,
+        }
+    }
+
+    /// Use a different host for HF hub repositories, e.g a mirror such as `https://hf-mirror.com`.
+    /// Has no effect on Apis built with `from_custom`.
+    #[wasm_bindgen]
+    pub fn with_base_url(mut self, base: String) -> Self {
+        self.base_url = base.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Pin the Api to a specific revision.
+    /// For HF hub repositories this replaces the revision, for custom endpoints
+    /// the revision is appended as an extra path segment, e.g `{endpoint}/{revision}`.
+    /// A full commit SHA is taken as a commit, anything else as a branch.
+    #[wasm_bindgen]
+    pub fn with_revision(self, revision: String) -> Self {
+        self.at_revision(&Revision::parse(revision))
+    }
+
+    /// Pin the Api to a typed revision, see [ApiBuilder::with_revision].
+    /// Files of a commit never change, so they are never revalidated.
+    #[wasm_bindgen]
+    pub fn at_revision(mut self, revision: &Revision) -> Self {
+        match &mut self.source {
+            ApiSource::Hub { revision: r, .. } => *r = revision.clone(),
+            ApiSource::Custom { revision: r, .. } => *r = Some(revision.clone()),
         }
+        self
+    }
+
+    /// Check cached files are still current with a HEAD request, comparing their `ETag`.
+    /// Stale files are downloaded again. Skipped for commits, whose files never change.
+    #[wasm_bindgen]
+    pub fn revalidate(mut self, revalidate: bool) -> Self {
+        self.revalidate = revalidate;
+        self
+    }
+
+    /// The mode of every request, `cors` by default.
+    /// `same-origin` suits endpoints served alongside the app, `no-cors` can't be used as
+    /// its responses are opaque.
+    #[wasm_bindgen]
+    pub fn request_mode(mut self, mode: RequestMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Send cookies with every request, including cross-origin ones, e.g for an auth proxy.
+    /// By default they are only sent to the same origin.
+    #[wasm_bindgen]
+    pub fn with_credentials(mut self, include: bool) -> Self {
+        self.credentials = if include {
+            RequestCredentials::Include
+        } else {
+            RequestCredentials::SameOrigin
+        };
+        self
+    }
+
+    /// Add a header to every request, e.g `X-Api-Key` for a gateway. Headers accumulate,
+    /// invalid names or values fail the request rather than the build.
+    /// Cross-origin endpoints must allow the header, as it makes requests preflighted.
+    #[wasm_bindgen]
+    pub fn with_header(mut self, name: String, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Bound the number of simultaneous fetches made by `get_many`, the rest are queued.
+    /// 6 by default, at least 1.
+    #[wasm_bindgen]
+    pub fn max_concurrent(mut self, n: usize) -> Self {
+        self.max_concurrent = n.max(1);
+        self
     }
 
     /// Disable caching
@@ -84,46 +190,311 @@ impl ApiBuilder {
     /// Build the Api.
     #[wasm_bindgen]
     pub fn build(&self) -> Api {
+        let (endpoint, revision) = match &self.source {
+            ApiSource::Hub {
+                repo_id,
+                ty,
+                revision,
+            } => (
+                Self::endpoint_at(&self.base_url, repo_id, *ty, revision.as_str()),
+                Some(revision),
+            ),
+            ApiSource::Custom { endpoint, revision } => match revision {
+                Some(r) => (
+                    format!("{}/{}", endpoint.trim_end_matches('/'), r.as_str()),
+                    Some(r),
+                ),
+                None => (endpoint.clone(), None),
+            },
+        };
+        let immutable = revision.is_some_and(Revision::is_immutable);
         Api {
-            endpoint: self.endpoint.clone(),
+            endpoint,
+            fallbacks: vec![],
             cached: self.cached,
+            mode: self.mode,
+            credentials: self.credentials,
+            headers: self.headers.clone(),
+            max_concurrent: self.max_concurrent,
+            revalidate: self.revalidate && !immutable,
+            metrics: Rc::default(),
+        }
+    }
+}
+
+impl ApiBuilder {
+    /// [ApiBuilder::endpoint] on any host, at any revision.
+    fn endpoint_at(base_url: &str, repo_id: &str, ty: RepoType, revision: &str) -> String {
+        match ty {
+            RepoType::Model => {
+                format!("{base_url}/{repo_id}/resolve/{revision}")
+            }
+            RepoType::Dataset => {
+                format!("{base_url}/datasets/{repo_id}/resolve/{revision}")
+            }
+            RepoType::Space => {
+                format!("{base_url}/spaces/{repo_id}/resolve/{revision}")
+            }
+        }
+    }
+
+    fn hub(repo_id: String, ty: RepoType, revision: Revision) -> Self {
+        Self {
+            source: ApiSource::Hub {
+                repo_id,
+                ty,
+                revision,
+            },
+            base_url: HF_BASE_URL.to_string(),
+            cached: true,
+            mode: RequestMode::Cors,
+            credentials: RequestCredentials::SameOrigin,
+            headers: vec![],
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            revalidate: false,
         }
     }
 }
 
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct Api {
     endpoint: String,
+    fallbacks: Vec<String>,
     cached: bool,
+    mode: RequestMode,
+    credentials: RequestCredentials,
+    headers: Vec<(String, String)>,
+    max_concurrent: usize,
+    //Never set for commits
+    revalidate: bool,
+    //Shared between clones, e.g those made by `get_cancellable`.
+    metrics: Rc<Cell<CacheMetrics>>,
 }
 
 #[wasm_bindgen]
 impl Api {
+    /// Cache hits and misses of every request made by this Api so far.
+    #[wasm_bindgen]
+    pub fn cache_metrics(&self) -> CacheMetrics {
+        self.metrics.get()
+    }
+
+    /// Endpoints to try, in order, if a request to the primary endpoint fails.
+    /// Endpoints are complete, e.g `https://hf-mirror.com/{repo_id}/resolve/{revision}`,
+    /// see [ApiBuilder::endpoint].
+    #[wasm_bindgen]
+    pub fn with_fallback(mut self, endpoints: Vec<String>) -> Api {
+        self.fallbacks = endpoints
+            .into_iter()
+            .map(|e| e.trim_end_matches('/').to_string())
+            .collect();
+        self
+    }
+
     /// Get a file from the repository
     #[wasm_bindgen]
     pub async fn get(&self, file_name: &str) -> Result<ApiResponse, JsError> {
         self.get_internal(file_name).await.map_err(js_to_js_error)
     }
 
+    /// # Get many
+    ///
+    /// Get several files from the repository, e.g the shards of a model. At most
+    /// [ApiBuilder::max_concurrent] fetches are in flight at once, the rest are queued.
+    /// Responses are in the order of `file_names`, the first failure rejects.
+    #[wasm_bindgen]
+    pub async fn get_many(&self, file_names: Vec<String>) -> Result<Vec<ApiResponse>, JsError> {
+        self.get_many_internal(&file_names)
+            .await
+            .map_err(js_to_js_error)
+    }
+
+    async fn get_many_internal(&self, file_names: &[String]) -> Result<Vec<ApiResponse>, JsValue> {
+        futures_util::stream::iter(file_names)
+            .map(|file_name| self.get_internal(file_name))
+            .buffered(self.max_concurrent)
+            .try_collect()
+            .await
+    }
+
+    /// Get a file from the repository, skipping the cache lookup.
+    /// The downloaded file replaces any cached copy, e.g to refresh a single stale file.
+    #[wasm_bindgen]
+    pub async fn get_fresh(&self, file_name: &str) -> Result<ApiResponse, JsError> {
+        self.get_fresh_internal(file_name)
+            .await
+            .map_err(js_to_js_error)
+    }
+
+    /// Get a file from the repository, with the option of aborting the download.
+    /// Aborting rejects the pending promise, or the body read if the response has already arrived.
+    #[wasm_bindgen]
+    pub fn get_cancellable(&self, file_name: String) -> Result<CancellableGet, JsError> {
+        let controller = AbortController::new().map_err(js_to_js_error)?;
+        let signal = controller.signal();
+        let api = self.clone();
+        let promise = wasm_bindgen_futures::future_to_promise(async move {
+            let response = api
+                .get_internal_with_signal(&file_name, Some(&signal), false)
+                .await?;
+            Ok(JsValue::from(response))
+        });
+        Ok(CancellableGet {
+            promise,
+            handle: AbortHandle { controller },
+        })
+    }
+
+    /// # Resolve model file
+    ///
+    /// Picks the first of `preferred` that exists in the repository, e.g
+    /// `["ggml-tiny.bin", "ggml-tiny-q5_1.bin"]` for a repo that may only ship the quantized file.
+    /// Cached files are taken without a request, others are checked with a HEAD request.
+    #[wasm_bindgen]
+    pub async fn resolve_model_file(&self, preferred: Vec<String>) -> Result<String, JsError> {
+        self.resolve_model_file_internal(&preferred)
+            .await
+            .map_err(js_to_js_error)
+    }
+
+    async fn resolve_model_file_internal(&self, preferred: &[String]) -> Result<String, JsValue> {
+        let caches = web_sys::window()
+            .ok_or(js_error("Couldn't get window handle"))?
+            .caches()?;
+        let cache: Cache = to_future(caches.open(CACHE_NAME)).await?;
+        for file_name in preferred {
+            let file_url = format!("{}/{}", self.endpoint, file_name);
+            if self.cached {
+                let hit = to_future::<JsValue>(cache.match_with_str(&file_url)).await?;
+                if !hit.is_undefined() {
+                    return Ok(file_name.clone());
+                }
+            }
+            if util::exists(&file_url, self.mode, self.credentials, &self.headers).await? {
+                return Ok(file_name.clone());
+            }
+        }
+        Err(js_error(&format!(
+            "None of {:?} exist in {}",
+            preferred, self.endpoint
+        ))
+        .into())
+    }
+
+    /// List every file in the cache, including those downloaded by other Apis.
+    #[wasm_bindgen]
+    pub async fn list_cached(&self) -> Result<Vec<CachedEntry>, JsError> {
+        Self::list_cached_internal().await.map_err(js_to_js_error)
+    }
+
+    async fn list_cached_internal() -> Result<Vec<CachedEntry>, JsValue> {
+        let caches = web_sys::window()
+            .ok_or(js_error("Couldn't get window handle"))?
+            .caches()?;
+        let cache: Cache = to_future(caches.open(CACHE_NAME)).await?;
+        let keys: js_sys::Array = to_future(cache.keys()).await?;
+
+        let mut entries = Vec::with_capacity(keys.length() as usize);
+        for key in keys.iter() {
+            let request: Request = key.dyn_into()?;
+            let hit = to_future::<JsValue>(cache.match_with_request(&request)).await?;
+            if hit.is_undefined() {
+                continue;
+            }
+            let response: Response = hit.dyn_into()?;
+            let headers = response.headers();
+            let content_length = util::content_length(&headers)?;
+            //Without a Content-Length, fall back to reading the body.
+            let size = match content_length {
+                Some(size) => size,
+                None => to_future::<web_sys::Blob>(response.blob()?).await?.size(),
+            };
+            entries.push(CachedEntry {
+                url: request.url(),
+                size,
+                cached_at: headers.get("date")?,
+            });
+        }
+        Ok(entries)
+    }
+
     async fn get_internal(&self, file_name: &str) -> Result<ApiResponse, JsValue> {
-        let file_url = format!("{}/{}", self.endpoint, file_name);
+        self.get_internal_with_signal(file_name, None, false).await
+    }
+
+    async fn get_fresh_internal(&self, file_name: &str) -> Result<ApiResponse, JsValue> {
+        self.get_internal_with_signal(file_name, None, true).await
+    }
+
+    /// `fresh` skips the cache lookup, the response is still written to the cache.
+    async fn get_internal_with_signal(
+        &self,
+        file_name: &str,
+        signal: Option<&AbortSignal>,
+        fresh: bool,
+    ) -> Result<ApiResponse, JsValue> {
+        let mut endpoints = std::iter::once(&self.endpoint).chain(self.fallbacks.iter());
+        let mut endpoint = endpoints.next().unwrap();
+        loop {
+            match self.get_from(endpoint, file_name, signal, fresh).await {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    let aborted = signal.is_some_and(|s| s.aborted());
+                    match endpoints.next() {
+                        Some(next) if !aborted => {
+                            log::warn!("Failed to get {file_name} from {endpoint}, trying {next}");
+                            endpoint = next;
+                        }
+                        _ => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn get_from(
+        &self,
+        endpoint: &str,
+        file_name: &str,
+        signal: Option<&AbortSignal>,
+        fresh: bool,
+    ) -> Result<ApiResponse, JsValue> {
+        let file_url = format!("{}/{}", endpoint, file_name);
 
         let caches = web_sys::window()
             .ok_or(js_error("Couldn't get window handle"))?
             .caches()?;
-        let cache: Cache = to_future(caches.open("ratchet-cache")).await?;
+        let cache: Cache = to_future(caches.open(CACHE_NAME)).await?;
 
         let mut opts = RequestInit::new();
         opts.method("GET");
-        opts.mode(RequestMode::Cors);
+        opts.mode(self.mode);
+        opts.credentials(self.credentials);
 
         let request = Request::new_with_str_and_init(&file_url, &opts)?;
 
         let promise = cache.match_with_request(&request);
         let cache_hit: JsValue = to_future(promise).await?;
 
-        let (raw, cached) = if cache_hit.is_undefined() || !self.cached {
-            let raw_response = util::fetch(file_url.as_str()).await?;
+        let stale = match cache_hit.dyn_ref::<Response>() {
+            Some(hit) if self.cached && self.revalidate && !fresh => {
+                self.is_stale(&file_url, hit).await?
+            }
+            _ => false,
+        };
+
+        let (raw, cached) = if cache_hit.is_undefined() || !self.cached || fresh || stale {
+            //`fetch` follows redirects and rejects failed responses, so what gets cached is the
+            //final resolved response. It's keyed on `file_url` as that's what we look up above.
+            let raw_response = util::fetch(
+                file_url.as_str(),
+                self.mode,
+                self.credentials,
+                &self.headers,
+                signal,
+            )
+            .await?;
             let _ =
                 to_future::<JsValue>(cache.put_with_str(file_url.as_str(), &raw_response.clone()?))
                     .await;
@@ -133,8 +504,130 @@ impl Api {
             (raw_response, true)
         };
 
+        let bytes = util::content_length(&raw.headers())?.unwrap_or(0.);
+        let mut metrics = self.metrics.get();
+        if cached {
+            metrics.hits += 1;
+            metrics.bytes_from_cache += bytes;
+        } else {
+            metrics.misses += 1;
+            metrics.bytes_from_network += bytes;
+        }
+        self.metrics.set(metrics);
+
         Ok(ApiResponse { raw, cached })
     }
+
+    /// Whether the `ETag` of `cached` no longer matches the file at `file_url`.
+    /// Without an `ETag` to compare, the cached file is kept.
+    async fn is_stale(&self, file_url: &str, cached: &Response) -> Result<bool, JsValue> {
+        let Some(etag) = cached.headers().get("etag")? else {
+            return Ok(false);
+        };
+        let current = util::head(file_url, self.mode, self.credentials, &self.headers).await?;
+        if !current.ok() {
+            return Ok(false);
+        }
+        Ok(current.headers().get("etag")?.is_some_and(|e| e != etag))
+    }
+}
+
+/// A file in the cache, see [Api::list_cached].
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct CachedEntry {
+    url: String,
+    size: f64,
+    cached_at: Option<String>,
+}
+
+#[wasm_bindgen]
+impl CachedEntry {
+    #[wasm_bindgen(getter)]
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Size in bytes.
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> f64 {
+        self.size
+    }
+
+    /// The `Date` header of the response, i.e when it was downloaded.
+    #[wasm_bindgen(getter)]
+    pub fn cached_at(&self) -> Option<String> {
+        self.cached_at.clone()
+    }
+}
+
+/// Aggregate cache usage of an [Api], see [Api::cache_metrics].
+/// Bytes are taken from the `Content-Length` header, responses without one count as 0 bytes.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheMetrics {
+    hits: u32,
+    misses: u32,
+    bytes_from_cache: f64,
+    bytes_from_network: f64,
+}
+
+#[wasm_bindgen]
+impl CacheMetrics {
+    #[wasm_bindgen(getter)]
+    pub fn hits(&self) -> u32 {
+        self.hits
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn misses(&self) -> u32 {
+        self.misses
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn bytes_from_cache(&self) -> f64 {
+        self.bytes_from_cache
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn bytes_from_network(&self) -> f64 {
+        self.bytes_from_network
+    }
+}
+
+/// Aborts the download it was created alongside.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct AbortHandle {
+    controller: AbortController,
+}
+
+#[wasm_bindgen]
+impl AbortHandle {
+    #[wasm_bindgen]
+    pub fn abort(&self) {
+        self.controller.abort();
+    }
+}
+
+/// A pending download, `promise` resolves to an [ApiResponse].
+#[wasm_bindgen]
+pub struct CancellableGet {
+    promise: Promise,
+    handle: AbortHandle,
+}
+
+#[wasm_bindgen]
+impl CancellableGet {
+    #[wasm_bindgen(getter)]
+    pub fn promise(&self) -> Promise {
+        self.promise.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn handle(&self) -> AbortHandle {
+        self.handle.clone()
+    }
 }
 
 #[wasm_bindgen]
@@ -187,4 +680,186 @@ mod tests {
         assert!(length == 8388776, "Length was {length}");
         Ok(())
     }
+
+    #[wasm_bindgen_test]
+    async fn lists_cached() -> Result<(), JsValue> {
+        let model_repo = ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model).build();
+        model_repo.get_internal("model.safetensors").await?;
+        let entries = Api::list_cached_internal().await?;
+        let entry = entries
+            .iter()
+            .find(|e| {
+                e.url
+                    .ends_with("jantxu/ratchet-test/resolve/main/model.safetensors")
+            })
+            .expect("Downloaded file should be cached");
+        assert_eq!(entry.size, 8388776.0);
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    async fn fresh_get_skips_cache() -> Result<(), JsValue> {
+        let model_repo = ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model).build();
+        model_repo.get_internal("model.safetensors").await?;
+        let cached = model_repo.get_internal("model.safetensors").await?;
+        assert!(cached.is_cached());
+        let fresh = model_repo.get_fresh_internal("model.safetensors").await?;
+        assert!(!fresh.is_cached());
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    async fn cancelled_download() {
+        let model_repo = ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model)
+            .uncached()
+            .build();
+        let pending = model_repo
+            .get_cancellable("model.safetensors".to_string())
+            .unwrap();
+        pending.handle().abort();
+        let result = wasm_bindgen_futures::JsFuture::from(pending.promise()).await;
+        assert!(result.is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn mirror_endpoint() {
+        let api = ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Dataset)
+            .with_base_url("https://hf-mirror.com/".to_string())
+            .build();
+        assert_eq!(
+            api.endpoint,
+            "https://hf-mirror.com/datasets/jantxu/ratchet-test/resolve/main"
+        );
+        assert_eq!(
+            ApiBuilder::endpoint("jantxu/ratchet-test", RepoType::Dataset),
+            "https://huggingface.co/datasets/jantxu/ratchet-test/resolve/main"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn fallback_endpoint() -> Result<(), JsValue> {
+        let model_repo = ApiBuilder::from_custom("https://unreachable.invalid".to_string())
+            .uncached()
+            .build()
+            .with_fallback(vec![
+                "https://huggingface.co/jantxu/ratchet-test/resolve/main/".to_string(),
+            ]);
+        let model = model_repo.get_internal("model.safetensors").await?;
+        assert!(!model.is_cached());
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    fn request_options() {
+        let api = ApiBuilder::from_custom("/models".to_string())
+            .request_mode(RequestMode::SameOrigin)
+            .with_credentials(true)
+            .build();
+        assert_eq!(api.mode, RequestMode::SameOrigin);
+        assert_eq!(api.credentials, RequestCredentials::Include);
+    }
+
+    #[wasm_bindgen_test]
+    async fn custom_headers() -> Result<(), JsValue> {
+        let api = ApiBuilder::from_custom("/models".to_string())
+            .with_header("X-Api-Key".to_string(), "secret".to_string())
+            .with_header("X-Client".to_string(), "ratchet".to_string())
+            .build();
+        assert_eq!(
+            api.headers,
+            [
+                ("X-Api-Key".to_string(), "secret".to_string()),
+                ("X-Client".to_string(), "ratchet".to_string()),
+            ]
+        );
+
+        let invalid = ApiBuilder::from_custom("/models".to_string())
+            .uncached()
+            .with_header("Bad Name".to_string(), "value".to_string())
+            .build();
+        assert!(invalid.get_internal("model.safetensors").await.is_err());
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    fn pinned_revision() {
+        let custom = ApiBuilder::from_custom("https://models.example.com/whisper/".to_string())
+            .with_revision("v1.2.0".to_string())
+            .build();
+        assert_eq!(custom.endpoint, "https://models.example.com/whisper/v1.2.0");
+
+        let hub = ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model)
+            .with_revision("abc123".to_string())
+            .build();
+        assert_eq!(
+            hub.endpoint,
+            "https://huggingface.co/jantxu/ratchet-test/resolve/abc123"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn commits_skip_revalidation() -> Result<(), JsValue> {
+        let builder = || ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model);
+        let sha = "0123456789abcdef0123456789abcdef01234567".to_string();
+        let commit = Revision::commit(sha.clone())?;
+        let pinned = builder().at_revision(&commit).revalidate(true).build();
+        assert!(pinned.endpoint.ends_with(&sha));
+        assert!(!pinned.revalidate);
+        //Untyped SHAs are commits too
+        assert!(
+            !builder()
+                .with_revision(sha)
+                .revalidate(true)
+                .build()
+                .revalidate
+        );
+
+        let branch = builder().revalidate(true).build();
+        assert!(branch.revalidate);
+        branch.get_internal("model.safetensors").await?;
+        let cached = branch.get_internal("model.safetensors").await?;
+        assert!(cached.is_cached());
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    async fn resolves_first_existing_file() -> Result<(), JsValue> {
+        let model_repo = ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model).build();
+        let candidates = vec!["missing.bin".to_string(), "model.safetensors".to_string()];
+        let resolved = model_repo.resolve_model_file_internal(&candidates).await?;
+        assert_eq!(resolved, "model.safetensors");
+        let missing = vec!["missing.bin".to_string()];
+        assert!(model_repo
+            .resolve_model_file_internal(&missing)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    async fn get_many_bounded() -> Result<(), JsValue> {
+        let builder = || ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model);
+        assert_eq!(builder().max_concurrent(0).max_concurrent, 1);
+        let model_repo = builder().max_concurrent(2).build();
+        let files = vec!["model.safetensors".to_string(); 3];
+        let responses = model_repo.get_many_internal(&files).await?;
+        assert_eq!(responses.len(), 3);
+
+        let missing = vec!["model.safetensors".to_string(), "missing.bin".to_string()];
+        assert!(model_repo.get_many_internal(&missing).await.is_err());
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    async fn counts_cache_hits() -> Result<(), JsValue> {
+        let model_repo = ApiBuilder::from_hf("jantxu/ratchet-test", RepoType::Model).build();
+        assert_eq!(model_repo.cache_metrics(), CacheMetrics::default());
+        model_repo.get_fresh_internal("model.safetensors").await?;
+        model_repo.get_internal("model.safetensors").await?;
+        let metrics = model_repo.clone().cache_metrics();
+        assert_eq!((metrics.hits, metrics.misses), (1, 1));
+        assert_eq!(metrics.bytes_from_network, 8388776.0);
+        assert_eq!(metrics.bytes_from_cache, 8388776.0);
+        Ok(())
+    }
 }
@@ -0,0 +1,25 @@
+use std::str::FromStr;
+use std::sync::Once;
+
+use log::LevelFilter;
+use wasm_bindgen::prelude::*;
+
+use crate::util::js_error;
+
+static INIT_LOGGER: Once = Once::new();
+
+/// Route `log` output to the browser console, at the requested level.
+///
+/// Accepts "off", "error", "warn", "info", "debug" or "trace" (case insensitive).
+/// May be called repeatedly to change the level.
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), JsError> {
+    let filter = LevelFilter::from_str(level)
+        .map_err(|_| js_error(&format!("Unknown log level: {}", level)))?;
+    //The logger can only be installed once, the level is then controlled by the max level
+    INIT_LOGGER.call_once(|| {
+        let _ = console_log::init_with_level(log::Level::Trace);
+    });
+    log::set_max_level(filter);
+    Ok(())
+}
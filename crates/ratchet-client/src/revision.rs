@@ -0,0 +1,124 @@
+use wasm_bindgen::prelude::*;
+
+use crate::util::js_error;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevisionKind {
+    /// A branch, e.g `main`. Branches move, so cached files may go stale.
+    Branch,
+    /// A tag, e.g `v1.0`. Tags can be moved, though they rarely are.
+    Tag,
+    /// A commit, the files it refers to never change.
+    Commit,
+}
+
+/// A revision of a repository, see [crate::ApiBuilder::at_revision].
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    kind: RevisionKind,
+    name: String,
+}
+
+#[wasm_bindgen]
+impl Revision {
+    #[wasm_bindgen]
+    pub fn branch(name: String) -> Result<Revision, JsError> {
+        Self::named(RevisionKind::Branch, name)
+    }
+
+    #[wasm_bindgen]
+    pub fn tag(name: String) -> Result<Revision, JsError> {
+        Self::named(RevisionKind::Tag, name)
+    }
+
+    /// A commit, given as its full 40 character SHA.
+    #[wasm_bindgen]
+    pub fn commit(sha: String) -> Result<Revision, JsError> {
+        if sha.len() != 40 || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(js_error(&format!("{sha} is not a full commit SHA")));
+        }
+        Ok(Self {
+            kind: RevisionKind::Commit,
+            name: sha.to_ascii_lowercase(),
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> RevisionKind {
+        self.kind
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Only commits are immutable, their cached files never need revalidating.
+    #[wasm_bindgen]
+    pub fn is_immutable(&self) -> bool {
+        self.kind == RevisionKind::Commit
+    }
+}
+
+impl Revision {
+    pub(crate) fn main() -> Self {
+        Self {
+            kind: RevisionKind::Branch,
+            name: "main".to_string(),
+        }
+    }
+
+    /// Untyped revisions are commits if they are a full SHA, otherwise branches.
+    /// Branch names aren't validated, as they never have been.
+    pub(crate) fn parse(revision: String) -> Self {
+        Self::commit(revision.clone()).unwrap_or(Self {
+            kind: RevisionKind::Branch,
+            name: revision,
+        })
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    fn named(kind: RevisionKind, name: String) -> Result<Self, JsError> {
+        let invalid = name.is_empty()
+            || name.chars().any(|c| c.is_whitespace() || c.is_control())
+            || name
+                .split('/')
+                .any(|s| s.is_empty() || s == "." || s == "..");
+        if invalid {
+            return Err(js_error(&format!("{name:?} is not a valid {kind:?} name")));
+        }
+        Ok(Self { kind, name })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{Revision, RevisionKind};
+
+    #[wasm_bindgen_test]
+    fn validated_revisions() {
+        let sha = "0123456789ABCDEF0123456789abcdef01234567".to_string();
+        let commit = Revision::commit(sha.clone()).unwrap();
+        assert_eq!(commit.name(), sha.to_ascii_lowercase());
+        assert!(commit.is_immutable());
+        assert!(Revision::commit("0123abc".to_string()).is_err());
+
+        assert!(!Revision::branch("refs/pr/1".to_string())
+            .unwrap()
+            .is_immutable());
+        assert!(Revision::branch("".to_string()).is_err());
+        assert!(Revision::tag("v1 .0".to_string()).is_err());
+        assert!(Revision::tag("../main".to_string()).is_err());
+
+        assert_eq!(Revision::parse(sha).kind(), RevisionKind::Commit);
+        let dev = Revision::parse("dev".to_string());
+        assert_eq!((dev.kind(), dev.as_str()), (RevisionKind::Branch, "dev"));
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeSet, HashMap};
+
+use js_sys::{Array, JsString, Object, Reflect, Uint8Array, JSON};
+use wasm_bindgen::{prelude::*, JsCast, JsValue};
+
+use crate::util::{js_error, js_to_js_error, to_future};
+use crate::Api;
+
+/// Where a tensor lives within its shard, as described by the safetensors header.
+#[derive(Debug, Clone)]
+struct TensorEntry {
+    dtype: String,
+    shape: Vec<u32>,
+    start: u32,
+    end: u32,
+}
+
+/// A single `.safetensors` file. The layout is an 8 byte little endian header length,
+/// the JSON header, and then the data. Header offsets are relative to the data.
+struct Shard {
+    bytes: Uint8Array,
+    data_start: u32,
+    tensors: HashMap<String, TensorEntry>,
+}
+
+impl Shard {
+    fn parse(bytes: Uint8Array) -> Result<Self, JsValue> {
+        let len = bytes.length() as u64;
+        if len < 8 {
+            return Err(js_error("Safetensors file is too short").into());
+        }
+        let mut header_len = [0u8; 8];
+        bytes.subarray(0, 8).copy_to(&mut header_len);
+        let data_start = 8 + u64::from_le_bytes(header_len);
+        if data_start > len {
+            return Err(js_error("Safetensors header exceeds the file").into());
+        }
+        let data_start = data_start as u32;
+        let header = String::from_utf8(bytes.subarray(8, data_start).to_vec())
+            .map_err(|e| js_error(&e.to_string()))?;
+        let header: Object = JSON::parse(&header)?.dyn_into()?;
+
+        let mut tensors = HashMap::new();
+        for entry in Object::entries(&header).iter() {
+            let entry: Array = entry.dyn_into()?;
+            let name = entry.get(0).as_string().unwrap_or_default();
+            if name == "__metadata__" {
+                continue;
+            }
+            let info = entry.get(1);
+            let field = |key: &str| Reflect::get(&info, &JsValue::from_str(key));
+            let dtype = field("dtype")?
+                .as_string()
+                .ok_or(js_error(&format!("Tensor {name} has no dtype")))?;
+            let shape = numbers(&field("shape")?)?;
+            let [start, end] = numbers(&field("data_offsets")?)?[..] else {
+                return Err(js_error(&format!("Tensor {name} has invalid offsets")).into());
+            };
+            if start > end || data_start as u64 + end as u64 > len {
+                return Err(js_error(&format!("Tensor {name} exceeds the file")).into());
+            }
+            let entry = TensorEntry {
+                dtype,
+                shape,
+                start,
+                end,
+            };
+            tensors.insert(name, entry);
+        }
+        Ok(Self {
+            bytes,
+            data_start,
+            tensors,
+        })
+    }
+}
+
+fn numbers(value: &JsValue) -> Result<Vec<u32>, JsValue> {
+    let array: &Array = value
+        .dyn_ref()
+        .ok_or(js_error("Expected an array of numbers"))?;
+    array
+        .iter()
+        .map(|v| {
+            v.as_f64()
+                .map(|n| n as u32)
+                .ok_or(js_error("Expected an array of numbers").into())
+        })
+        .collect()
+}
+
+/// Reads the `weight_map` of a `model.safetensors.index.json`, tensor name to shard file.
+fn parse_index(index: &str) -> Result<HashMap<String, String>, JsValue> {
+    let index = JSON::parse(index)?;
+    let weight_map: Object = Reflect::get(&index, &JsValue::from_str("weight_map"))?
+        .dyn_into()
+        .map_err(|_| js_error("Index has no weight_map"))?;
+    Object::entries(&weight_map)
+        .iter()
+        .map(|entry| {
+            let entry: Array = entry.dyn_into()?;
+            match (entry.get(0).as_string(), entry.get(1).as_string()) {
+                (Some(tensor), Some(shard)) => Ok((tensor, shard)),
+                _ => Err(js_error("Invalid weight_map entry").into()),
+            }
+        })
+        .collect()
+}
+
+/// A model split across several safetensors files, see [Api::get_sharded].
+/// The shards are kept as JS buffers, tensors are views into them.
+#[wasm_bindgen]
+pub struct ShardedModel {
+    weight_map: HashMap<String, String>,
+    shards: HashMap<String, Shard>,
+}
+
+impl ShardedModel {
+    /// `shards` holds the contents of every shard file named in `index`.
+    fn from_parts(index: &str, shards: HashMap<String, Uint8Array>) -> Result<Self, JsValue> {
+        let weight_map = parse_index(index)?;
+        let shards = shards
+            .into_iter()
+            .map(|(file, bytes)| Ok((file, Shard::parse(bytes)?)))
+            .collect::<Result<HashMap<_, _>, JsValue>>()?;
+        for (tensor, file) in &weight_map {
+            let shard = shards
+                .get(file)
+                .ok_or(js_error(&format!("Shard {file} is missing")))?;
+            if !shard.tensors.contains_key(tensor) {
+                return Err(js_error(&format!("Tensor {tensor} is not in {file}")).into());
+            }
+        }
+        Ok(Self { weight_map, shards })
+    }
+
+    fn load_tensor_internal(&self, name: &str) -> Result<ShardTensor, JsError> {
+        let file = self
+            .weight_map
+            .get(name)
+            .ok_or(js_error(&format!("Unknown tensor {name}")))?;
+        let shard = &self.shards[file];
+        let entry = &shard.tensors[name];
+        Ok(ShardTensor {
+            dtype: entry.dtype.clone(),
+            shape: entry.shape.clone(),
+            data: shard
+                .bytes
+                .subarray(shard.data_start + entry.start, shard.data_start + entry.end),
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl ShardedModel {
+    /// Every tensor in the model, sorted by name.
+    #[wasm_bindgen]
+    pub fn tensor_names(&self) -> Vec<String> {
+        let mut names = self.weight_map.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Load a tensor from whichever shard holds it.
+    #[wasm_bindgen]
+    pub fn load_tensor(&self, name: &str) -> Result<ShardTensor, JsError> {
+        self.load_tensor_internal(name)
+    }
+}
+
+/// A tensor read from a [ShardedModel], `data` is a view into its shard.
+#[wasm_bindgen]
+pub struct ShardTensor {
+    dtype: String,
+    shape: Vec<u32>,
+    data: Uint8Array,
+}
+
+#[wasm_bindgen]
+impl ShardTensor {
+    /// The safetensors dtype, e.g `F32` or `BF16`.
+    #[wasm_bindgen(getter)]
+    pub fn dtype(&self) -> String {
+        self.dtype.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn shape(&self) -> Vec<u32> {
+        self.shape.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Uint8Array {
+        self.data.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl Api {
+    /// # Get sharded
+    ///
+    /// Get a model split across several safetensors files from its index,
+    /// e.g `model.safetensors.index.json`. Shards are resolved relative to the index,
+    /// and every shard it references is fetched (and cached) with [Api::get_many].
+    #[wasm_bindgen]
+    pub async fn get_sharded(&self, index_file: &str) -> Result<ShardedModel, JsError> {
+        self.get_sharded_internal(index_file)
+            .await
+            .map_err(js_to_js_error)
+    }
+
+    async fn get_sharded_internal(&self, index_file: &str) -> Result<ShardedModel, JsValue> {
+        let index = self.get_internal(index_file).await?;
+        let index: JsString = to_future(index.raw.text()?).await?;
+        let index = String::from(index);
+
+        let dir = match index_file.rsplit_once('/') {
+            Some((dir, _)) => format!("{dir}/"),
+            None => String::new(),
+        };
+        let files = parse_index(&index)?
+            .into_values()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let paths = files
+            .iter()
+            .map(|file| format!("{dir}{file}"))
+            .collect::<Vec<_>>();
+        let responses = self.get_many_internal(&paths).await?;
+
+        let mut shards = HashMap::with_capacity(files.len());
+        for (file, response) in files.into_iter().zip(responses) {
+            shards.insert(file, response.to_uint8().await?);
+        }
+        ShardedModel::from_parts(&index, shards)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use js_sys::Uint8Array;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::ShardedModel;
+
+    fn safetensors(tensors: &[(&str, &[f32])]) -> Uint8Array {
+        let mut header = vec![];
+        let mut data = vec![];
+        for (name, values) in tensors {
+            let start = data.len();
+            data.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+            header.push(format!(
+                r#""{name}":{{"dtype":"F32","shape":[{}],"data_offsets":[{start},{}]}}"#,
+                values.len(),
+                data.len()
+            ));
+        }
+        let header = format!(r#"{{"__metadata__":{{}},{}}}"#, header.join(","));
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        Uint8Array::from(bytes.as_slice())
+    }
+
+    #[wasm_bindgen_test]
+    fn loads_from_owning_shard() -> Result<(), JsValue> {
+        let index = r#"{"metadata": {}, "weight_map": {
+            "a": "model-00001-of-00002.safetensors",
+            "b": "model-00001-of-00002.safetensors",
+            "c": "model-00002-of-00002.safetensors"
+        }}"#;
+        let shards = HashMap::from([
+            (
+                "model-00001-of-00002.safetensors".to_string(),
+                safetensors(&[("a", &[1., 2.]), ("b", &[3.])]),
+            ),
+            (
+                "model-00002-of-00002.safetensors".to_string(),
+                safetensors(&[("c", &[4., 5., 6.])]),
+            ),
+        ]);
+        let model = ShardedModel::from_parts(index, shards)?;
+        assert_eq!(model.tensor_names(), ["a", "b", "c"]);
+
+        let c = model.load_tensor_internal("c").unwrap();
+        assert_eq!(c.dtype(), "F32");
+        assert_eq!(c.shape(), [3]);
+        let expected = [4f32, 5., 6.]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(c.data().to_vec(), expected);
+        assert!(model.load_tensor_internal("d").is_err());
+
+        let missing = HashMap::from([(
+            "model-00001-of-00002.safetensors".to_string(),
+            safetensors(&[("a", &[1., 2.]), ("b", &[3.])]),
+        )]);
+        assert!(ShardedModel::from_parts(index, missing).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use js_sys::{Promise, Uint8Array};
+use wasm_bindgen::{prelude::*, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransactionMode};
+
+use crate::util::{js_error, js_to_js_error};
+
+const DB_NAME: &str = "ratchet";
+const DB_VERSION: u32 = 1;
+const STORE_NAME: &str = "tensors";
+
+/// Stamped in front of every entry, bump `FORMAT_VERSION` whenever the layout of
+/// stored data changes. Entries with a different stamp are discarded on load.
+const MAGIC: [u8; 4] = *b"RTCH";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: u32 = 8;
+
+fn header() -> [u8; HEADER_LEN as usize] {
+    let mut header = [0u8; HEADER_LEN as usize];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header
+}
+
+/// Persistent storage for derived data that is expensive to recompute,
+/// e.g encoder features or dequantized weights.
+///
+/// Downloaded files belong in the Cache API (see [crate::Api]), this is backed by IndexedDB
+/// which handles large binary blobs better. Keys are arbitrary strings, they should
+/// identify both the model and the configuration used to produce the data.
+#[wasm_bindgen]
+pub struct TensorStore {
+    db: IdbDatabase,
+}
+
+#[wasm_bindgen]
+impl TensorStore {
+    /// Open (and create if required) the store.
+    #[wasm_bindgen]
+    pub async fn open() -> Result<TensorStore, JsError> {
+        Self::open_internal().await.map_err(js_to_js_error)
+    }
+
+    async fn open_internal() -> Result<TensorStore, JsValue> {
+        let factory = web_sys::window()
+            .ok_or(js_error("Couldn't get window handle"))?
+            .indexed_db()?
+            .ok_or(js_error("IndexedDB is unavailable"))?;
+        let request: IdbOpenDbRequest = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+
+        let onupgradeneeded = Closure::once_into_js(move |event: web_sys::Event| {
+            let db = event
+                .target()
+                .and_then(|t| t.dyn_into::<IdbRequest>().ok())
+                .and_then(|r| r.result().ok())
+                .and_then(|r| r.dyn_into::<IdbDatabase>().ok());
+            if let Some(db) = db {
+                if !db.object_store_names().contains(STORE_NAME) {
+                    let _ = db.create_object_store(STORE_NAME);
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(onupgradeneeded.unchecked_ref()));
+
+        let db = await_request(&request).await?.dyn_into::<IdbDatabase>()?;
+        Ok(TensorStore { db })
+    }
+
+    /// Store `bytes` under `key`, replacing any existing entry.
+    /// Entries written by a build with a different format version are ignored.
+    #[wasm_bindgen]
+    pub async fn store_tensor(&self, key: &str, bytes: Uint8Array) -> Result<(), JsError> {
+        self.store_internal(key, bytes)
+            .await
+            .map_err(js_to_js_error)
+    }
+
+    async fn store_internal(&self, key: &str, bytes: Uint8Array) -> Result<(), JsValue> {
+        let tx = self
+            .db
+            .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?;
+        let stamped = Uint8Array::new_with_length(HEADER_LEN + bytes.length());
+        stamped.copy_from(&header());
+        stamped.set(&bytes, HEADER_LEN);
+        let request = tx
+            .object_store(STORE_NAME)?
+            .put_with_key(&stamped, &JsValue::from_str(key))?;
+        await_request(&request).await?;
+        Ok(())
+    }
+
+    /// Load the bytes stored under `key`, `undefined` if there is no such entry.
+    #[wasm_bindgen]
+    pub async fn load_tensor(&self, key: &str) -> Result<Option<Uint8Array>, JsError> {
+        self.load_internal(key).await.map_err(js_to_js_error)
+    }
+
+    async fn load_internal(&self, key: &str) -> Result<Option<Uint8Array>, JsValue> {
+        let tx = self
+            .db
+            .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readonly)?;
+        let request = tx.object_store(STORE_NAME)?.get(&JsValue::from_str(key))?;
+        let value = await_request(&request).await?;
+        if value.is_undefined() {
+            return Ok(None);
+        }
+        let stamped = value.dyn_into::<Uint8Array>()?;
+        let current =
+            stamped.length() >= HEADER_LEN && stamped.subarray(0, HEADER_LEN).to_vec() == header();
+        if !current {
+            log::warn!("Discarding {key}, it was stored by an incompatible version");
+            self.delete_internal(key).await?;
+            return Ok(None);
+        }
+        Ok(Some(stamped.slice(HEADER_LEN, stamped.length())))
+    }
+
+    /// Remove the entry stored under `key`, if any.
+    #[wasm_bindgen]
+    pub async fn delete_tensor(&self, key: &str) -> Result<(), JsError> {
+        self.delete_internal(key).await.map_err(js_to_js_error)
+    }
+
+    async fn delete_internal(&self, key: &str) -> Result<(), JsValue> {
+        let tx = self
+            .db
+            .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?;
+        let request = tx
+            .object_store(STORE_NAME)?
+            .delete(&JsValue::from_str(key))?;
+        await_request(&request).await?;
+        Ok(())
+    }
+}
+
+/// IndexedDB reports completion through events rather than promises,
+/// this resolves with the request's result on `success` and rejects on `error`.
+async fn await_request(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let success_request = request.clone();
+        let onsuccess = Closure::once_into_js(move |_: web_sys::Event| {
+            let result = success_request.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::NULL, &result);
+        });
+        let error_request = request.clone();
+        let onerror = Closure::once_into_js(move |_: web_sys::Event| {
+            let error = error_request
+                .error()
+                .ok()
+                .flatten()
+                .map(JsValue::from)
+                .unwrap_or(JsValue::from_str("IndexedDB request failed"));
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+        request.set_onsuccess(Some(onsuccess.unchecked_ref()));
+        request.set_onerror(Some(onerror.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    async fn store_roundtrip() -> Result<(), JsValue> {
+        let store = TensorStore::open_internal().await?;
+        let key = "test/roundtrip";
+        let bytes = Uint8Array::from(&[1u8, 2, 3, 4, 5][..]);
+        store.store_internal(key, bytes).await?;
+
+        let loaded = store.load_internal(key).await?.expect("Entry should exist");
+        assert_eq!(loaded.to_vec(), vec![1, 2, 3, 4, 5]);
+
+        store.delete_internal(key).await?;
+        assert!(store.load_internal(key).await?.is_none());
+        Ok(())
+    }
+
+    #[wasm_bindgen_test]
+    async fn stale_entry_discarded() -> Result<(), JsValue> {
+        let store = TensorStore::open_internal().await?;
+        let key = "test/stale";
+        let tx = store
+            .db
+            .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?;
+        let unstamped = Uint8Array::from(&[1u8, 2, 3, 4, 5, 6, 7, 8, 9][..]);
+        let request = tx
+            .object_store(STORE_NAME)?
+            .put_with_key(&unstamped, &JsValue::from_str(key))?;
+        await_request(&request).await?;
+
+        assert!(store.load_internal(key).await?.is_none());
+        Ok(())
+    }
+}
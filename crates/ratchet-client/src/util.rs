@@ -2,7 +2,10 @@ use js_sys::JSON;
 
 use wasm_bindgen::{prelude::*, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, RequestMode, Response};
+use web_sys::{
+    AbortSignal, Headers, Request, RequestCredentials, RequestInit, RequestMode, RequestRedirect,
+    Response, ResponseType,
+};
 
 pub(crate) fn js_to_js_error(value: JsValue) -> JsError {
     JsError::new(
@@ -29,14 +32,103 @@ where
     result.dyn_into::<T>()
 }
 
-pub(crate) async fn fetch(url: &str) -> Result<Response, JsValue> {
+pub(crate) async fn fetch(
+    url: &str,
+    mode: RequestMode,
+    credentials: RequestCredentials,
+    headers: &[(String, String)],
+    signal: Option<&AbortSignal>,
+) -> Result<Response, JsValue> {
     let mut opts = RequestInit::new();
     opts.method("GET");
-    opts.mode(RequestMode::Cors);
+    opts.mode(mode);
+    opts.credentials(credentials);
+    let headers = to_headers(headers)?;
+    opts.headers(&headers);
+    //HF `resolve` URLs redirect to a CDN, follow them transparently.
+    opts.redirect(RequestRedirect::Follow);
+    opts.signal(signal);
 
     let request = Request::new_with_str_and_init(url, &opts)?;
 
-    let window = web_sys::window().unwrap();
+    let window = web_sys::window().ok_or(js_error("Couldn't get window handle"))?;
     let promise = window.fetch_with_request(&request);
-    to_future(promise).await
+    //fetch only rejects on network failures, the usual culprit is a redirect
+    //target that doesn't send CORS headers.
+    let response: Response = to_future(promise).await.map_err(|_| {
+        js_error(&format!(
+            "Failed to fetch {url}. The host, or the host it redirects to, may not allow cross-origin requests."
+        ))
+    })?;
+    check_response(url, &response)?;
+    if response.redirected() {
+        log::debug!("{} redirected to {}", url, response.url());
+    }
+    Ok(response)
+}
+
+/// Whether `url` exists, following redirects. Only the headers are transferred.
+pub(crate) async fn exists(
+    url: &str,
+    mode: RequestMode,
+    credentials: RequestCredentials,
+    headers: &[(String, String)],
+) -> Result<bool, JsValue> {
+    Ok(head(url, mode, credentials, headers).await?.ok())
+}
+
+/// A HEAD request for `url`, following redirects.
+pub(crate) async fn head(
+    url: &str,
+    mode: RequestMode,
+    credentials: RequestCredentials,
+    headers: &[(String, String)],
+) -> Result<Response, JsValue> {
+    let mut opts = RequestInit::new();
+    opts.method("HEAD");
+    opts.mode(mode);
+    opts.credentials(credentials);
+    let headers = to_headers(headers)?;
+    opts.headers(&headers);
+    opts.redirect(RequestRedirect::Follow);
+
+    let request = Request::new_with_str_and_init(url, &opts)?;
+    let window = web_sys::window().ok_or(js_error("Couldn't get window handle"))?;
+    to_future(window.fetch_with_request(&request)).await
+}
+
+/// Rejects invalid header names or values.
+fn to_headers(headers: &[(String, String)]) -> Result<Headers, JsValue> {
+    let h = Headers::new()?;
+    for (name, value) in headers {
+        h.append(name, value)?;
+    }
+    Ok(h)
+}
+
+/// The `Content-Length` header in bytes, if present.
+pub(crate) fn content_length(headers: &Headers) -> Result<Option<f64>, JsValue> {
+    Ok(headers
+        .get("content-length")?
+        .and_then(|len| len.parse::<f64>().ok()))
+}
+
+/// Rejects responses we can't read or shouldn't cache.
+fn check_response(url: &str, response: &Response) -> Result<(), JsError> {
+    if matches!(
+        response.type_(),
+        ResponseType::Opaque | ResponseType::Opaqueredirect
+    ) {
+        return Err(js_error(&format!(
+            "Response for {url} is opaque, the redirect target must allow cross-origin requests."
+        )));
+    }
+    if !response.ok() {
+        return Err(js_error(&format!(
+            "Failed to fetch {url} (resolved to {}): status {}",
+            response.url(),
+            response.status()
+        )));
+    }
+    Ok(())
 }
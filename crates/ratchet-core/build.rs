@@ -25,14 +25,19 @@ impl std::fmt::Display for KernelElement {
     }
 }
 
+#[derive(Debug, Clone, Copy, strum_macros::EnumIter, PartialEq)]
 pub enum WgslDType {
     F32,
+    I32,
+    U32,
 }
 
 impl std::fmt::Display for WgslDType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             WgslDType::F32 => write!(f, "f32"),
+            WgslDType::I32 => write!(f, "i32"),
+            WgslDType::U32 => write!(f, "u32"),
         }
     }
 }
@@ -74,6 +79,29 @@ impl BinaryOp {
     }
 }
 
+#[derive(Debug, Clone, strum_macros::EnumIter)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    pub fn mapping(&self) -> (&'static str, &'static str) {
+        match self {
+            CmpOp::Eq => ("eq", "=="),
+            CmpOp::Ne => ("ne", "!="),
+            CmpOp::Lt => ("lt", "<"),
+            CmpOp::Le => ("le", "<="),
+            CmpOp::Gt => ("gt", ">"),
+            CmpOp::Ge => ("ge", ">="),
+        }
+    }
+}
+
 #[derive(Debug, Clone, strum_macros::EnumIter)]
 pub enum UnaryOp {
     Gelu,
@@ -113,6 +141,7 @@ pub enum ReindexOp {
     Permute,
     Slice,
     Broadcast,
+    Repeat,
 }
 
 impl std::fmt::Display for ReindexOp {
@@ -121,6 +150,7 @@ impl std::fmt::Display for ReindexOp {
             ReindexOp::Permute => "permute",
             ReindexOp::Slice => "slice",
             ReindexOp::Broadcast => "broadcast",
+            ReindexOp::Repeat => "repeat",
         };
         write!(f, "{}", s)
     }
@@ -143,6 +173,9 @@ impl ReindexOp {
     var src_index = select(dst_index, vec4<u32>(0u), metadata.src_shape == vec4<u32>(1u));
     "#
             .to_string(),
+            ReindexOp::Repeat => r#"
+    var src_index = dst_index % metadata.src_shape;"#
+                .to_string(),
         }
     }
 }
@@ -150,12 +183,14 @@ impl ReindexOp {
 #[derive(Debug, Clone, strum_macros::EnumIter)]
 pub enum NormOp {
     LayerNorm,
+    RMSNorm,
 }
 
 impl std::fmt::Display for NormOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
             NormOp::LayerNorm => "layernorm",
+            NormOp::RMSNorm => "rmsnorm",
         };
         write!(f, "{}", s)
     }
@@ -185,6 +220,91 @@ impl KernelGenerator {
         self.generate_binary()?;
         self.generate_reindex()?;
         self.generate_norm()?;
+        self.generate_cast()?;
+        self.generate_clamp()?;
+        self.generate_where()?;
+        self.generate_cmp()?;
+        Ok(())
+    }
+
+    fn generate_clamp(&mut self) -> anyhow::Result<()> {
+        for ke in KernelElement::iter() {
+            let path = self.templates_path.join("clamp.wgsl");
+            self.tera.add_template_file(path, Some("clamp"))?;
+
+            let mut context = Context::new();
+            context.insert("elem", &ke.as_wgsl(WgslDType::F32));
+            context.insert("elem_size", &ke.as_size());
+            let rendered = self.tera.render("clamp", &context)?;
+
+            let kernel_fname = format!("clamp_{}.wgsl", ke);
+            let mut file = File::create(self.dest_path.join(kernel_fname))?;
+            file.write_all(rendered.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn generate_cmp(&mut self) -> anyhow::Result<()> {
+        for op in CmpOp::iter() {
+            let (op_name, op) = op.mapping();
+            for ke in KernelElement::iter() {
+                let path = self.templates_path.join("cmp.wgsl");
+                self.tera.add_template_file(path, Some("cmp"))?;
+
+                let mut context = Context::new();
+                context.insert("op", op);
+                context.insert("elem", &ke.as_wgsl(WgslDType::F32));
+                context.insert("out_elem", &ke.as_wgsl(WgslDType::U32));
+                context.insert("elem_size", &ke.as_size());
+                let rendered = self.tera.render("cmp", &context)?;
+
+                let kernel_fname = format!("{}_{}.wgsl", op_name, ke);
+                let mut file = File::create(self.dest_path.join(kernel_fname))?;
+                file.write_all(rendered.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_where(&mut self) -> anyhow::Result<()> {
+        for cond in WgslDType::iter() {
+            for ke in KernelElement::iter() {
+                let path = self.templates_path.join("where.wgsl");
+                self.tera.add_template_file(path, Some("where"))?;
+
+                let mut context = Context::new();
+                context.insert("cond_elem", &ke.as_wgsl(cond));
+                context.insert("elem", &ke.as_wgsl(WgslDType::F32));
+                context.insert("elem_size", &ke.as_size());
+                let rendered = self.tera.render("where", &context)?;
+
+                let kernel_fname = format!("where_{}_{}.wgsl", cond, ke);
+                let mut file = File::create(self.dest_path.join(kernel_fname))?;
+                file.write_all(rendered.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_cast(&mut self) -> anyhow::Result<()> {
+        for src in WgslDType::iter() {
+            for dst in WgslDType::iter().filter(|dst| *dst != src) {
+                for ke in KernelElement::iter() {
+                    let path = self.templates_path.join("cast.wgsl");
+                    self.tera.add_template_file(path, Some("cast"))?;
+
+                    let mut context = Context::new();
+                    context.insert("src_elem", &ke.as_wgsl(src));
+                    context.insert("dst_elem", &ke.as_wgsl(dst));
+                    context.insert("elem_size", &ke.as_size());
+                    let rendered = self.tera.render("cast", &context)?;
+
+                    let kernel_fname = format!("cast_{}_{}_{}.wgsl", src, dst, ke);
+                    let mut file = File::create(self.dest_path.join(kernel_fname))?;
+                    file.write_all(rendered.as_bytes())?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -211,7 +331,10 @@ impl KernelGenerator {
                 self.tera.add_template_file(path, Some("layernorm"))?;
 
                 let mut context = Context::new();
+                context.insert("op", &op.to_string());
                 context.insert("elem", &ke.as_wgsl(WgslDType::F32));
+                //Statistics stay in f32 even once the elements are half precision
+                context.insert("acc", &ke.as_wgsl(WgslDType::F32));
                 context.insert("elem_size", &ke.as_size());
                 let reduction_len = match ke {
                     KernelElement::Scalar => "metadata.N",
@@ -0,0 +1,31 @@
+@group(0) @binding(0)
+var<storage, read> C: array<{{ cond_elem }}>;
+
+@group(0) @binding(1)
+var<storage, read> A: array<{{ elem }}>;
+
+@group(0) @binding(2)
+var<storage, read> B: array<{{ elem }}>;
+
+@group(0) @binding(3)
+var<storage, read_write> Y: array<{{ elem }}>;
+
+struct Meta {
+    numel: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+@compute @workgroup_size(8,8,1)
+fn main( 
+        @builtin(local_invocation_index) local_index: u32,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+        @builtin(num_workgroups) num_groups: vec3<u32>
+) {
+    let index = (group_id.y * num_groups.x * 64u) + group_id.x * 64u + local_index;
+    if (index >= metadata.numel / {{ elem_size }}u) {
+        return;
+    }
+    Y[index] = select(B[index], A[index], C[index] != {{ cond_elem }}(0));
+}
@@ -0,0 +1,54 @@
+@group(0) @binding(0)
+var<storage, read> A: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read> B: array<f32>;
+
+@group(0) @binding(2)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    numel: u32,
+    op: u32,
+    dst_shape: vec4<u32>,
+    lhs_stride: vec4<u32>,
+    rhs_stride: vec4<u32>,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+fn apply(a: f32, b: f32) -> f32 {
+    switch metadata.op {
+        case 0u: { return a + b; }
+        case 1u: { return a - b; }
+        case 2u: { return a * b; }
+        default: { return a / b; }
+    }
+}
+
+//Operands are read through their strides, a stride of 0 repeats the element.
+@compute @workgroup_size(8, 8, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(num_workgroups) num_groups: vec3<u32>
+) {
+    let x_offset = group_id.x * 64u;
+    let index = (group_id.y * num_groups.x * 64u) + x_offset + local_index;
+    if (index >= metadata.numel) {
+        return;
+    }
+
+    var rem = index;
+    var lhs_index = 0u;
+    var rhs_index = 0u;
+    for (var dim = 3; dim >= 0; dim--) {
+        let coord = rem % metadata.dst_shape[dim];
+        rem = rem / metadata.dst_shape[dim];
+        lhs_index += coord * metadata.lhs_stride[dim];
+        rhs_index += coord * metadata.rhs_stride[dim];
+    }
+    Y[index] = apply(A[lhs_index], B[rhs_index]);
+}
@@ -0,0 +1,72 @@
+//Inclusive prefix sum, one workgroup per line along the scanned dim.
+//Each chunk of BLOCK_SIZE elements is scanned in shared memory (Hillis-Steele),
+//and the running total of the previous chunks is carried forward.
+@group(0) @binding(0)
+var<storage, read> X: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    N: u32,
+    inner: u32,
+    num_lines: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+const BLOCK_SIZE = 256u;
+
+var<workgroup> smem: array<f32, BLOCK_SIZE>;
+var<workgroup> carry: f32;
+
+@compute @workgroup_size(256, 1, 1)
+fn main(
+        @builtin(local_invocation_id) local_id: vec3<u32>,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+        @builtin(num_workgroups) num_groups: vec3<u32>
+) {
+    let line = group_id.y * num_groups.x + group_id.x;
+    if (line >= metadata.num_lines) {
+        return;
+    }
+    let N = metadata.N;
+    let stride = metadata.inner;
+    let base = (line / stride) * N * stride + line % stride;
+    let index = local_id.x;
+
+    if (index == 0u) {
+        carry = 0.0;
+    }
+    workgroupBarrier();
+
+    for (var start: u32 = 0u; start < N; start += BLOCK_SIZE) {
+        let i = start + index;
+        var val = 0.0;
+        if (i < N) {
+            val = X[base + i * stride];
+        }
+        smem[index] = val;
+        workgroupBarrier();
+
+        for (var offset: u32 = 1u; offset < BLOCK_SIZE; offset <<= 1u) {
+            var t = 0.0;
+            if (index >= offset) {
+                t = smem[index - offset];
+            }
+            workgroupBarrier();
+            smem[index] += t;
+            workgroupBarrier();
+        }
+
+        if (i < N) {
+            Y[base + i * stride] = smem[index] + carry;
+        }
+        workgroupBarrier();
+        if (index == BLOCK_SIZE - 1u) {
+            carry += smem[index];
+        }
+        workgroupBarrier();
+    }
+}
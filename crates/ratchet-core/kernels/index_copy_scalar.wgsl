@@ -0,0 +1,43 @@
+@group(0) @binding(0)
+var<storage, read_write> D: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read> I: array<i32>;
+
+@group(0) @binding(2)
+var<storage, read> S: array<f32>;
+
+struct Meta {
+    src_numel: u32,
+    right_numel: u32,
+    ids_numel: u32,
+    dst_dim_numel: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+@compute @workgroup_size(8,8,1)
+fn main( 
+        @builtin(local_invocation_id) local_id: vec3<u32>,
+        @builtin(local_invocation_index) local_index: u32,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+        @builtin(num_workgroups) num_groups: vec3<u32>
+) {
+    let x_offset = group_id.x * 64u;
+    let tid = (group_id.y * num_groups.x * 64u) + x_offset + local_index;
+    if (tid >= metadata.src_numel) {
+        return;
+    }
+    let id_i = (tid / metadata.right_numel) % metadata.ids_numel;
+    let dst_dim_i = u32(I[id_i]);
+    //Out of range indices are skipped, negative ones wrap to large values
+    if (dst_dim_i >= metadata.dst_dim_numel) {
+        return;
+    }
+    let right_rank_i = tid % metadata.right_numel;
+    let left_rank_i = tid / (metadata.right_numel * metadata.ids_numel);
+
+    let dst_i = left_rank_i * metadata.dst_dim_numel * metadata.right_numel + dst_dim_i * metadata.right_numel + right_rank_i;
+    D[dst_i] = S[tid];
+}
@@ -0,0 +1,54 @@
+@group(0) @binding(0)
+var<storage, read> X: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    src_shape: vec4<u32>,
+    src_stride: vec4<u32>,
+    dst_stride: vec4<u32>,
+    pad_before: vec4<u32>,
+    dst_numel: u32,
+    value: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+//Converts 1D offset into 4D index
+fn offsetToNdIndex(offset: u32, stride: vec4<u32>) -> vec4<u32> {
+    var index: vec4<u32> = vec4<u32>(0u, 0u, 0u, 0u);
+    var remaining = offset;
+
+    for (var i: i32 = 0; i < 3; i++) {
+        let idx = remaining / stride[i];
+        index[i] = idx;
+        remaining -= idx * stride[i];
+    }
+    index.w = remaining;
+    return index;
+}
+
+@compute @workgroup_size(8,8,1)
+fn main( 
+        @builtin(local_invocation_index) local_index: u32,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+        @builtin(num_workgroups) num_groups: vec3<u32>
+) {
+    let x_offset = group_id.x * 64u;
+    let dst_offset = (group_id.y * num_groups.x * 64u) + x_offset + local_index;
+    if (dst_offset >= metadata.dst_numel) {
+        return;
+    }
+    let dst_index = offsetToNdIndex(dst_offset, metadata.dst_stride);
+
+    //Inside the source iff pad_before <= dst_index < pad_before + src_shape on every axis.
+    //Underflow wraps, so a single comparison per axis catches both sides.
+    let src_index = dst_index - metadata.pad_before;
+    if (any(src_index >= metadata.src_shape)) {
+        Y[dst_offset] = metadata.value;
+        return;
+    }
+    Y[dst_offset] = X[dot(src_index, metadata.src_stride)];
+}
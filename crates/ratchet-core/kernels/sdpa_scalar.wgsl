@@ -0,0 +1,102 @@
+//Fused attention, each invocation owns a single query row.
+//K & V are streamed through workgroup memory TILE_N rows at a time, and the softmax
+//is computed online (running max & sum), so the [M, N] scores are never written out.
+@group(0) @binding(0)
+var<storage, read> Q: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read> K: array<f32>;
+
+@group(0) @binding(2)
+var<storage, read> V: array<f32>;
+
+@group(0) @binding(3)
+var<storage, read_write> O: array<f32>;
+
+struct Meta {
+    M: u32,
+    N: u32,
+    D: u32,
+    scale: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+const BLOCK_SIZE = 64u;
+const TILE_N = 8u;
+const MAX_D = 128u;
+const minFloat: f32 = -3.402823e+38f;
+
+var<workgroup> k_tile: array<f32, 1024>; // TILE_N * MAX_D
+var<workgroup> v_tile: array<f32, 1024>;
+
+var<private> q: array<f32, MAX_D>;
+var<private> acc: array<f32, MAX_D>;
+
+fn score(j: u32) -> f32 {
+    var s = 0.0;
+    for (var d: u32 = 0u; d < metadata.D; d++) {
+        s += q[d] * k_tile[j * MAX_D + d];
+    }
+    return s;
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn main(
+        @builtin(local_invocation_id) local_id: vec3<u32>,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+) {
+    let M = metadata.M;
+    let N = metadata.N;
+    let D = metadata.D;
+
+    let row = group_id.x * BLOCK_SIZE + local_id.x;
+    let q_offset = group_id.y * M * D;
+    let kv_offset = group_id.y * N * D;
+    let valid = row < M;
+
+    if valid {
+        for (var d: u32 = 0u; d < D; d++) {
+            q[d] = Q[q_offset + row * D + d] * metadata.scale;
+            acc[d] = 0.0;
+        }
+    }
+
+    var running_max = minFloat;
+    var running_sum = 0.0;
+
+    for (var tile_start: u32 = 0u; tile_start < N; tile_start += TILE_N) {
+        let tile_len = min(TILE_N, N - tile_start);
+        for (var i: u32 = local_id.x; i < tile_len * D; i += BLOCK_SIZE) {
+            let key = i / D;
+            let d = i % D;
+            let src = kv_offset + (tile_start + key) * D + d;
+            k_tile[key * MAX_D + d] = K[src];
+            v_tile[key * MAX_D + d] = V[src];
+        }
+        workgroupBarrier();
+
+        if valid {
+            for (var j: u32 = 0u; j < tile_len; j++) {
+                let s = score(j);
+                let new_max = max(running_max, s);
+                let correction = exp(running_max - new_max);
+                let p = exp(s - new_max);
+                running_sum = running_sum * correction + p;
+                for (var d: u32 = 0u; d < D; d++) {
+                    acc[d] = acc[d] * correction + p * v_tile[j * MAX_D + d];
+                }
+                running_max = new_max;
+            }
+        }
+        workgroupBarrier();
+    }
+
+    if valid {
+        //Every key masked out leaves no mass, the row attends to nothing
+        for (var d: u32 = 0u; d < D; d++) {
+            O[q_offset + row * D + d] = select(0.0, acc[d] / running_sum, running_sum > 0.0);
+        }
+    }
+}
@@ -82,6 +82,7 @@ fn main(
 
     for(var i: u32 = index; i < metadata.N; i += BLOCK_SIZE) {
         var val = X[row_start + i];
-        X[row_start + i] = exp(val - maximum) / sum;
+        //A fully masked row has no mass, write zeros rather than 0 / 0
+        X[row_start + i] = select(0.0, exp(val - maximum) / sum, sum > 0.0);
     }
 }
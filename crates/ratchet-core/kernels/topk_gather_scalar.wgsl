@@ -0,0 +1,47 @@
+//Gathers the values of the indices picked by the topk kernel, one workgroup per line.
+//Lines with fewer than k non-NaN elements have -1 indices, their values are 0.
+@group(0) @binding(0)
+var<storage, read> X: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read> I: array<i32>;
+
+@group(0) @binding(2)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    N: u32,
+    inner: u32,
+    num_lines: u32,
+    k: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+const BLOCK_SIZE = 256u;
+
+@compute @workgroup_size(256, 1, 1)
+fn main(
+        @builtin(local_invocation_id) local_id: vec3<u32>,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+        @builtin(num_workgroups) num_groups: vec3<u32>
+) {
+    let line = group_id.y * num_groups.x + group_id.x;
+    if (line >= metadata.num_lines) {
+        return;
+    }
+    let stride = metadata.inner;
+    let src_base = (line / stride) * metadata.N * stride + line % stride;
+    let dst_base = (line / stride) * metadata.k * stride + line % stride;
+
+    for (var i: u32 = local_id.x; i < metadata.k; i += BLOCK_SIZE) {
+        let dst = dst_base + i * stride;
+        let idx = I[dst];
+        if (idx < 0) {
+            Y[dst] = 0.0;
+        } else {
+            Y[dst] = X[src_base + u32(idx) * stride];
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//Top-k along a dim, one workgroup per line.
+//Elements are ordered by (value desc, index asc), each of the k rounds selects the best element
+//strictly after the previous pick in that order, so no exclusion list is needed. NaNs are skipped.
+//Only the indices are written, topk_gather reads the values back from X.
+@group(0) @binding(0)
+var<storage, read> X: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<u32>;
+
+struct Meta {
+    N: u32,
+    inner: u32,
+    num_lines: u32,
+    k: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+const BLOCK_SIZE = 256u;
+const INVALID = 0xFFFFFFFFu;
+
+var<workgroup> smem_val: array<f32, BLOCK_SIZE>;
+var<workgroup> smem_idx: array<u32, BLOCK_SIZE>;
+var<workgroup> prev_val: f32;
+var<workgroup> prev_idx: u32;
+
+fn better(a_val: f32, a_idx: u32, b_val: f32, b_idx: u32) -> bool {
+    if (a_idx == INVALID) {
+        return false;
+    }
+    return b_idx == INVALID || a_val > b_val || (a_val == b_val && a_idx < b_idx);
+}
+
+fn block_reduce(index: u32, stride: u32) {
+    if (index < stride) {
+        let o = index + stride;
+        if (better(smem_val[o], smem_idx[o], smem_val[index], smem_idx[index])) {
+            smem_val[index] = smem_val[o];
+            smem_idx[index] = smem_idx[o];
+        }
+    }
+    workgroupBarrier();
+}
+
+@compute @workgroup_size(256, 1, 1)
+fn main(
+        @builtin(local_invocation_id) local_id: vec3<u32>,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+        @builtin(num_workgroups) num_groups: vec3<u32>
+) {
+    let line = group_id.y * num_groups.x + group_id.x;
+    if (line >= metadata.num_lines) {
+        return;
+    }
+    let N = metadata.N;
+    let k = metadata.k;
+    let stride = metadata.inner;
+    let src_base = (line / stride) * N * stride + line % stride;
+    let dst_base = (line / stride) * k * stride + line % stride;
+    let index = local_id.x;
+
+    for (var round: u32 = 0u; round < k; round++) {
+        var best_val = 0.0;
+        var best_idx = INVALID;
+        for (var i: u32 = index; i < N; i += BLOCK_SIZE) {
+            let x = X[src_base + i * stride];
+            if (x != x) {
+                continue;
+            }
+            let eligible = round == 0u || x < prev_val || (x == prev_val && i > prev_idx);
+            if (eligible && better(x, i, best_val, best_idx)) {
+                best_val = x;
+                best_idx = i;
+            }
+        }
+        smem_val[index] = best_val;
+        smem_idx[index] = best_idx;
+        workgroupBarrier();
+
+        block_reduce(index, 128u);
+        block_reduce(index, 64u);
+        block_reduce(index, 32u);
+        block_reduce(index, 16u);
+        block_reduce(index, 8u);
+        block_reduce(index, 4u);
+        block_reduce(index, 2u);
+        block_reduce(index, 1u);
+
+        if (index == 0u) {
+            prev_val = smem_val[0];
+            prev_idx = smem_idx[0];
+            Y[dst_base + round * stride] = prev_idx;
+        }
+        workgroupBarrier();
+    }
+}
@@ -0,0 +1,50 @@
+@group(0) @binding(0)
+var<storage, read> X: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    src_shape: vec4<u32>,
+    dst_shape: vec4<u32>,
+    src_stride: vec4<u32>,
+    dst_stride: vec4<u32>,
+    src_numel: u32,
+    dst_numel: u32,
+    perm: vec4<u32>,
+    src_offsets: vec4<u32>,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+const TILE_DIM: u32 = 16u;
+
+//Padded by 1 column to avoid bank conflicts when reading transposed
+var<workgroup> tile: array<array<f32, 17>, 16>;
+
+@compute @workgroup_size(16,16,1)
+fn main( 
+        @builtin(local_invocation_id) local_id: vec3<u32>,
+        @builtin(workgroup_id) group_id: vec3<u32>,
+) {
+    //Shapes are left padded to rank 4, [1, 1, rows, cols]
+    let rows = metadata.src_shape.z;
+    let cols = metadata.src_shape.w;
+
+    //Coalesced read of a tile from the [rows, cols] input
+    let x = group_id.x * TILE_DIM + local_id.x;
+    let y = group_id.y * TILE_DIM + local_id.y;
+    if (x < cols && y < rows) {
+        tile[local_id.y][local_id.x] = X[y * cols + x];
+    }
+
+    workgroupBarrier();
+
+    //Coalesced write of the transposed tile into the [cols, rows] output
+    let out_x = group_id.y * TILE_DIM + local_id.x;
+    let out_y = group_id.x * TILE_DIM + local_id.y;
+    if (out_x < rows && out_y < cols) {
+        Y[out_y * rows + out_x] = tile[local_id.x][local_id.y];
+    }
+}
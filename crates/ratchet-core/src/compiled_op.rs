@@ -1,6 +1,6 @@
 use crate::gpu::{
-    BindGroupDescriptor, BindGroupLayoutHandle, ComputePipelineHandle, GpuBindGroup, WgpuDevice,
-    WorkgroupCount,
+    BindGroupDescriptor, BindGroupLayoutHandle, ComputePipelineHandle, GpuBindGroup,
+    PooledGPUBuffer, WgpuDevice, WorkgroupCount,
 };
 use crate::{drvec, rvec, OperationError, RVec, Tensor};
 use derive_new::new;
@@ -15,6 +15,8 @@ pub struct CompiledOp {
     workgroup_count: WorkgroupCount,
     storage_groups: RVec<GpuBindGroup>,
     offset: DynamicOffset, //offset into the metadata uniform buffer
+    #[new(default)]
+    clear: Option<PooledGPUBuffer>,
 }
 
 impl CompiledOp {
@@ -75,4 +77,13 @@ impl CompiledOp {
     pub fn pipeline_handle(&self) -> ComputePipelineHandle {
         self.pipeline_handle
     }
+
+    /// Zeroes `buffer` in the command stream, after every earlier op and before this one.
+    pub(crate) fn clear_before(&mut self, buffer: PooledGPUBuffer) {
+        self.clear = Some(buffer);
+    }
+
+    pub fn clear(&self) -> Option<&PooledGPUBuffer> {
+        self.clear.as_ref()
+    }
 }
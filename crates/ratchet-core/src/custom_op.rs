@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::gpu::{CpuUniform, WgpuDevice};
+use crate::{CompiledOp, KernelElement, MetaOperation, OperationError, RVec, Tensor, KERNELS};
+
+lazy_static::lazy_static! {
+    static ref CUSTOM_KERNELS: RwLock<HashMap<String, &'static str>> = RwLock::new(HashMap::new());
+}
+
+/// # Register kernel
+///
+/// Registers the WGSL source for a custom op, under the [MetaOperation::kernel_name] and
+/// [KernelElement] it is compiled with. Pipelines are cached, so a kernel can't be
+/// replaced once registered, nor can a built in kernel be shadowed.
+pub fn register_kernel(
+    kernel_name: &'static str,
+    kernel_element: KernelElement,
+    source: &'static str,
+) -> Result<(), OperationError> {
+    let key = format!("{}_{}", kernel_name, kernel_element.as_str());
+    if KERNELS.contains_key(key.as_str()) {
+        return Err(OperationError::CompileError(format!(
+            "{key} is a built in kernel"
+        )));
+    }
+    let mut kernels = CUSTOM_KERNELS.write();
+    match kernels.get(&key) {
+        Some(&existing) if existing != source => Err(OperationError::CompileError(format!(
+            "{key} is already registered"
+        ))),
+        _ => {
+            kernels.insert(key, source);
+            Ok(())
+        }
+    }
+}
+
+/// Looks up a built in or registered kernel by its key, e.g `clamp_vec4`.
+pub(crate) fn kernel_source(key: &str) -> Option<&'static str> {
+    KERNELS
+        .get(key)
+        .copied()
+        .or_else(|| CUSTOM_KERNELS.read().get(key).copied())
+}
+
+/// The parts of [MetaOperation] the graph needs, without its associated metadata type.
+trait ErasedOp: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    fn srcs(&self) -> RVec<&Tensor>;
+    fn supports_inplace(&self) -> bool;
+    fn has_gpu_kernel(&self, dst: &Tensor) -> bool;
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError>;
+    fn compile(
+        &self,
+        dst: &Tensor,
+        uniform: &mut CpuUniform,
+        device: &WgpuDevice,
+        can_inplace: bool,
+    ) -> Result<CompiledOp, OperationError>;
+}
+
+impl<O: MetaOperation + Send + Sync> ErasedOp for O {
+    fn name(&self) -> &'static str {
+        self.kernel_name()
+    }
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        MetaOperation::srcs(self)
+    }
+
+    fn supports_inplace(&self) -> bool {
+        MetaOperation::supports_inplace(self)
+    }
+
+    fn has_gpu_kernel(&self, dst: &Tensor) -> bool {
+        MetaOperation::has_gpu_kernel(self, dst)
+    }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        MetaOperation::apply_cpu(self, srcs, dst)
+    }
+
+    fn compile(
+        &self,
+        dst: &Tensor,
+        uniform: &mut CpuUniform,
+        device: &WgpuDevice,
+        can_inplace: bool,
+    ) -> Result<CompiledOp, OperationError> {
+        MetaOperation::compile(self, dst, uniform, device, can_inplace)
+    }
+}
+
+/// # Custom
+///
+/// An op defined outside of this crate, see [Tensor::custom].
+#[derive(Debug, Clone)]
+pub struct Custom {
+    op: Arc<dyn ErasedOp>,
+}
+
+impl Custom {
+    pub(crate) fn new<O: MetaOperation + Send + Sync>(op: O) -> Self {
+        Self { op: Arc::new(op) }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.op.name()
+    }
+
+    pub fn srcs(&self) -> RVec<&Tensor> {
+        self.op.srcs()
+    }
+
+    pub fn supports_inplace(&self) -> bool {
+        self.op.supports_inplace()
+    }
+
+    pub fn has_gpu_kernel(&self, dst: &Tensor) -> bool {
+        self.op.has_gpu_kernel(dst)
+    }
+
+    pub fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        self.op.apply_cpu(srcs, dst)
+    }
+
+    pub(crate) fn compile(
+        &self,
+        dst: &Tensor,
+        uniform: &mut CpuUniform,
+        device: &WgpuDevice,
+        can_inplace: bool,
+    ) -> Result<CompiledOp, OperationError> {
+        self.op.compile(dst, uniform, device, can_inplace)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::gpu::{AllocatorError, PoolError, WgpuDevice};
+use crate::{Tensor, TensorError};
 
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum DeviceError {
@@ -16,6 +17,8 @@ pub enum DeviceError {
     InvalidBufferUsage(wgpu::BufferUsages, wgpu::BufferUsages),
     #[error("Failed to transfer buffer with error: {0:?}")]
     BufferTransferFailed(#[from] wgpu::BufferAsyncError),
+    #[error("Failed to read buffer contents: {0}")]
+    ReadFailed(String),
 }
 
 pub enum DeviceRequest {
@@ -70,6 +73,57 @@ impl Device {
         format!("{:?}", self)
     }
 
+    /// # Read back
+    ///
+    /// Copies `tensors` from this device to the host, waiting on the device once for all of them.
+    /// Each copy is queued before any is waited on, see [Tensor::to_async].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_back(&self, tensors: &[&Tensor]) -> Result<Vec<Tensor>, TensorError> {
+        let pending = self.start_read_back(tensors)?;
+        pending.into_iter().map(pollster::block_on).collect()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub async fn read_back(&self, tensors: &[&Tensor]) -> Result<Vec<Tensor>, TensorError> {
+        let mut host = Vec::with_capacity(tensors.len());
+        for read in self.start_read_back(tensors)? {
+            host.push(read.await?);
+        }
+        Ok(host)
+    }
+
+    fn start_read_back(
+        &self,
+        tensors: &[&Tensor],
+    ) -> Result<Vec<impl std::future::Future<Output = Result<Tensor, TensorError>>>, DeviceError>
+    {
+        if let Some(t) = tensors.iter().find(|t| t.device() != self) {
+            return Err(DeviceError::DeviceMismatch(
+                self.label(),
+                t.device().label(),
+            ));
+        }
+        Ok(tensors.iter().map(|t| t.to_async(&Device::CPU)).collect())
+    }
+
+    /// Returns true if work submitted to the device is still executing, without blocking.
+    /// Always false on the CPU, see [WgpuDevice::poll_nonblocking].
+    pub fn poll_nonblocking(&self) -> bool {
+        match self {
+            Device::CPU => false,
+            Device::GPU(gpu) => gpu.poll_nonblocking(),
+        }
+    }
+
+    /// Uploads made within `f` are waited on once, see [WgpuDevice::batch_uploads].
+    /// On the CPU `f` is simply called.
+    pub fn batch_uploads<R>(&self, f: impl FnOnce() -> R) -> R {
+        match self {
+            Device::CPU => f(),
+            Device::GPU(gpu) => gpu.batch_uploads(f),
+        }
+    }
+
     pub fn try_gpu(&self) -> Result<&WgpuDevice, DeviceError> {
         match self {
             Device::GPU(gpu) => Ok(gpu),
@@ -31,6 +31,33 @@ impl DType {
         }
     }
 
+    /// Size of a single element in bytes, the same as [DType::size_of].
+    pub fn size_in_bytes(self) -> usize {
+        self.size_of()
+    }
+
+    /// True for the floating point types, quantized types are not floats.
+    pub fn is_float(self) -> bool {
+        matches!(self, DType::F16 | DType::BF16 | DType::F32)
+    }
+
+    pub fn is_quantized(self) -> bool {
+        matches!(self, DType::Q8 | DType::WQ8)
+    }
+
+    /// Lowercase name of the type, e.g `"f32"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DType::Q8 => "q8",
+            DType::F16 => "f16",
+            DType::BF16 => "bf16",
+            DType::F32 => "f32",
+            DType::I32 => "i32",
+            DType::U32 => "u32",
+            DType::WQ8 => "wq8",
+        }
+    }
+
     //TODO: alignof?
 
     //TODO: use a different method, total_bytes won't work with padding
@@ -60,6 +87,35 @@ impl DType {
     }
 }
 
+impl std::fmt::Display for DType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Unknown dtype: {0}")]
+pub struct ParseDTypeError(String);
+
+impl std::str::FromStr for DType {
+    type Err = ParseDTypeError;
+
+    /// Parses the dtype names found in safetensors and GGUF headers (`"F32"`, `"BF16"`,
+    /// `"Q8_0"`) as well as our own lowercase names. Matching is case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "f32" | "float32" => Ok(DType::F32),
+            "f16" | "float16" => Ok(DType::F16),
+            "bf16" | "bfloat16" => Ok(DType::BF16),
+            "i32" | "int32" => Ok(DType::I32),
+            "u32" | "uint32" => Ok(DType::U32),
+            "q8" | "q8_0" => Ok(DType::Q8),
+            "wq8" => Ok(DType::WQ8),
+            _ => Err(ParseDTypeError(s.to_string())),
+        }
+    }
+}
+
 #[cfg(feature = "testing")]
 impl DType {
     fn handle_type_str(ts: npyz::TypeStr) -> DType {
@@ -144,3 +200,27 @@ map_type!(i32, I32);
 map_type!(u32, U32);
 map_half_type!(f16, F16);
 map_half_type!(bf16, BF16);
+
+#[cfg(test)]
+mod tests {
+    use super::DType;
+
+    #[test]
+    fn parse_header_names() {
+        for dt in [
+            DType::Q8,
+            DType::F16,
+            DType::BF16,
+            DType::F32,
+            DType::I32,
+            DType::U32,
+            DType::WQ8,
+        ] {
+            assert_eq!(dt.as_str().parse::<DType>().unwrap(), dt);
+        }
+        assert_eq!("BF16".parse::<DType>().unwrap(), DType::BF16);
+        assert_eq!("Q8_0".parse::<DType>().unwrap(), DType::Q8);
+        assert_eq!("F16".parse::<DType>().unwrap().size_in_bytes(), 2);
+        assert!("F64".parse::<DType>().is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::RangeInclusive;
+use std::ops::{Range, RangeInclusive};
 
 use crate::{DType, RVec, Shape, Tensor};
 
@@ -32,8 +32,18 @@ pub enum InvariantError {
     UnsupportedDType(DType),
     #[error("Duplicate dims in permutation.")]
     DuplicateDims,
+    #[error("Dim {dim} out of range for rank {rank}.")]
+    DimOutOfRange { dim: usize, rank: usize },
     #[error("Broadcasting failed: {0:?}")]
     BroadcastingFailed(Vec<Shape>),
+    #[error("Tensor with strides {0:?} is not contiguous, see `Tensor::contiguous`.")]
+    NonContiguous(Vec<isize>),
+    #[error("Invalid slice {range:?} for dim {dim} of size {size}.")]
+    InvalidSlice {
+        dim: usize,
+        range: Range<usize>,
+        size: usize,
+    },
 }
 
 /// # Enforcer
@@ -89,6 +99,27 @@ impl Enforcer {
         Ok(())
     }
 
+    /// Checks that `dims` is a permutation of the axes of a tensor of rank `rank`.
+    pub fn check_permutation(dims: &[usize], rank: usize) -> Result<(), InvariantError> {
+        if dims.len() != rank {
+            return Err(InvariantError::RankMismatch {
+                accepted: rank..=rank,
+                actual: dims.len(),
+            });
+        }
+        let mut seen = vec![false; rank];
+        for &dim in dims {
+            if dim >= rank {
+                return Err(InvariantError::DimOutOfRange { dim, rank });
+            }
+            if seen[dim] {
+                return Err(InvariantError::DuplicateDims);
+            }
+            seen[dim] = true;
+        }
+        Ok(())
+    }
+
     pub fn assert_rank(tensor: &Tensor, rank: usize) -> Result<(), InvariantError> {
         if tensor.rank() != rank {
             return Err(InvariantError::RankMismatch {
@@ -99,6 +130,13 @@ impl Enforcer {
         Ok(())
     }
 
+    pub fn assert_contiguous(tensor: &Tensor) -> Result<(), InvariantError> {
+        if !tensor.is_contiguous() {
+            return Err(InvariantError::NonContiguous(tensor.strides().to_vec()));
+        }
+        Ok(())
+    }
+
     pub fn assert_dtype(tensor: &Tensor, expected: DType) -> Result<(), InvariantError> {
         let actual = tensor.dt();
         if actual != expected {
@@ -30,12 +30,22 @@ impl Executable {
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        {
+        //Clears can't be recorded inside a compute pass, each one ends the pass
+        let mut start = 0;
+        while start < self.steps.len() {
+            if let Some(buffer) = self.steps[start].clear() {
+                encoder.clear_buffer(&buffer.inner, 0, None);
+            }
+            let end = self.steps[start + 1..]
+                .iter()
+                .position(|step| step.clear().is_some())
+                .map_or(self.steps.len(), |p| start + 1 + p);
+
             let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
                 label: None,
                 timestamp_writes: None,
             });
-            for step in self.steps.iter() {
+            for step in self.steps[start..end].iter() {
                 cpass.set_pipeline(pipeline_resources.get(step.pipeline_handle())?);
 
                 for (group_index, bind_group) in step.storage_groups().iter().enumerate() {
@@ -49,7 +59,9 @@ impl Executable {
                 let [x_count, y_count, z_count] = step.workgroup_count().as_slice();
                 cpass.dispatch_workgroups(x_count, y_count, z_count);
             }
+            drop(cpass);
+            start = end;
         }
-        Ok(device.queue().submit(Some(encoder.finish())))
+        Ok(device.submit(encoder.finish()))
     }
 }
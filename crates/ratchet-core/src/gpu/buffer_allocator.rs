@@ -1,14 +1,15 @@
-use parking_lot::RwLock;
-use rustc_hash::FxHashMap;
+use parking_lot::{Mutex, RwLock};
+use rustc_hash::{FxHashMap, FxHashSet};
 use wgpu::BufferUsages;
 
 use crate::{
     gpu::{BufferDescriptor, BufferPool, GpuBufferHandle, PooledGPUBuffer},
     DeviceError, Tensor, TensorId,
 };
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use super::{BufferUsagesExt, CpuUniform, WgpuDevice, UNIFORM_ALIGN};
+use super::{CpuUniform, UniformRing, WgpuDevice, MIN_STORAGE_BUFFER_SIZE};
 
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum AllocatorError {
@@ -16,19 +17,126 @@ pub enum AllocatorError {
     BufferNotFound,
 }
 
+/// Buffer sizes, and the buffer index of each tensor. See [BufferAllocator::plan_cfg].
+pub(crate) type BufferPlan = (Vec<u64>, Vec<(TensorId, usize)>);
+
+/// Passed to the callback registered with [BufferAllocator::on_memory_pressure].
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryPressure {
+    /// Bytes currently held by the pool, including buffers awaiting reuse.
+    pub allocated: u64,
+    pub budget: u64,
+}
+
+type PressureCallback = Arc<dyn Fn(MemoryPressure) + Send + Sync>;
+
+struct PressureHook {
+    budget: u64,
+    watermark: u64,
+    callback: PressureCallback,
+    above: bool,
+}
+
+/// # Buffer allocator
+///
+/// ## Concurrency
+///
+/// One allocator is shared by every graph resolved on a device, possibly from several threads.
+/// Buffers are handed out at two levels, and neither can give a buffer that is still in use
+/// to another graph:
+/// 1. Within a graph, [BufferAllocator::allocate_cfg] reuses buffers released earlier in that
+///    same graph. Its free list lives for the duration of the call, and is never shared.
+/// 2. Across graphs, the pool reuses a buffer only once nothing holds it: no tensor storage,
+///    and no graph assignment. A buffer released during pass `N` is reusable during pass `N+1`,
+///    and is destroyed when pass `N+2` begins if it wasn't reused.
+///
+/// To carry a buffer over beyond that, hold on to it, e.g by retaining the tensor that owns it.
+/// Streams sharing a device may all call [BufferAllocator::begin_pass] with the same indices,
+/// even out of step, the pool is only aged once per pass. Pass indices must only increase.
 pub struct BufferAllocator {
     pool: RwLock<BufferPool>,
+    zero_on_reuse: AtomicBool,
+    pressure: Mutex<Option<PressureHook>>,
+    uniform_ring: Mutex<UniformRing>,
 }
 
 impl BufferAllocator {
     pub fn new() -> Self {
         Self {
             pool: BufferPool::new().into(),
+            zero_on_reuse: AtomicBool::new(false),
+            pressure: Mutex::new(None),
+            uniform_ring: Mutex::new(UniformRing::new()),
+        }
+    }
+
+    /// # Memory pressure
+    ///
+    /// Registers `callback`, invoked when the bytes held by the pool cross
+    /// `watermark` (a fraction, e.g 0.8) of `budget`. It fires once per crossing,
+    /// and again only after usage has dropped back below the watermark.
+    ///
+    /// The callback runs synchronously inside the allocation, it should only record the
+    /// event (e.g set a flag checked between decoding steps), not resolve tensors.
+    pub fn on_memory_pressure(
+        &self,
+        budget: u64,
+        watermark: f32,
+        callback: impl Fn(MemoryPressure) + Send + Sync + 'static,
+    ) {
+        assert!(
+            (0.0..=1.0).contains(&watermark),
+            "Watermark must be a fraction of the budget"
+        );
+        *self.pressure.lock() = Some(PressureHook {
+            budget,
+            watermark: (budget as f64 * watermark as f64) as u64,
+            callback: Arc::new(callback),
+            above: false,
+        });
+    }
+
+    fn check_pressure(&self) {
+        let allocated = self.pool.read().total_gpu_size_in_bytes();
+        let fire = {
+            let mut guard = self.pressure.lock();
+            let Some(hook) = guard.as_mut() else {
+                return;
+            };
+            let was_above = std::mem::replace(&mut hook.above, allocated >= hook.watermark);
+            (hook.above && !was_above).then(|| (hook.callback.clone(), hook.budget))
+        };
+        //Called without holding the lock, so the callback may re-register itself.
+        if let Some((callback, budget)) = fire {
+            log::warn!(
+                "Memory pressure: {}kb of {}kb",
+                allocated / 1024,
+                budget / 1024
+            );
+            callback(MemoryPressure { allocated, budget });
         }
     }
 
+    /// # Zero on reuse
+    ///
+    /// Debugging aid, sits between the default and `RATCHET_DEBUG` (which disables reuse).
+    /// When enabled, an op reading uninitialized memory reads zeros instead of stale data:
+    /// - Buffers the pool reclaims from an earlier pass are cleared as they are handed out.
+    /// - Within a graph, a buffer written by an earlier op is cleared in the command stream,
+    ///   right before the next op that writes it (see [crate::CompiledOp::clear_before]).
+    pub fn set_zero_on_reuse(&self, zero: bool) {
+        self.zero_on_reuse.store(zero, Ordering::Relaxed);
+    }
+
+    pub(crate) fn zero_on_reuse(&self) -> bool {
+        self.zero_on_reuse.load(Ordering::Relaxed)
+    }
+
+    /// Begins pass `pass_index`, see [BufferAllocator#concurrency].
     pub fn begin_pass(&self, pass_index: u64) {
-        self.pool.write().begin_pass(pass_index);
+        if self.pool.write().begin_pass(pass_index) {
+            self.check_pressure();
+        }
     }
 
     pub fn get(&self, handle: GpuBufferHandle) -> PooledGPUBuffer {
@@ -36,7 +144,16 @@ impl BufferAllocator {
     }
 
     pub fn create_buffer(&self, desc: &BufferDescriptor, device: &WgpuDevice) -> PooledGPUBuffer {
-        self.pool.write().get_or_create(desc, device)
+        let (buf, reused) = self.pool.write().get_or_create_reused(desc, device);
+        //Ordered after any earlier submission, which is all a reclaimed buffer could be in use by
+        if reused && self.zero_on_reuse() && buf.descriptor.usage.contains(BufferUsages::COPY_DST) {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            encoder.clear_buffer(&buf.inner, 0, None);
+            device.queue().submit(Some(encoder.finish()));
+        }
+        self.check_pressure();
+        buf
     }
 
     pub fn create_buffer_init(
@@ -45,30 +162,43 @@ impl BufferAllocator {
         contents: &[u8],
         device: &WgpuDevice,
     ) -> PooledGPUBuffer {
-        let buf = self.pool.write().get_or_create(desc, device);
+        let buf = self.create_buffer(desc, device);
         device.queue().write_buffer(&buf.inner, 0, contents);
-        device.queue().submit(None);
-        device.poll(wgpu::Maintain::Wait);
+        if !device.uploads_batched() {
+            device.queue().submit(None);
+            device.poll(wgpu::Maintain::Wait);
+        }
         buf
     }
 
-    pub fn create_uniform_init(&self, uniform: CpuUniform, device: &WgpuDevice) -> PooledGPUBuffer {
+    /// Writes `uniform` into the uniform ring, see [UniformRing].
+    /// Returns the ring's buffer and the offset the uniform was written at.
+    pub fn create_uniform_init(
+        &self,
+        uniform: CpuUniform,
+        device: &WgpuDevice,
+    ) -> (PooledGPUBuffer, u64) {
+        //The last write is bound with a size of UNIFORM_ALIGN, padding up to the next multiple
+        //of the alignment (>= UNIFORM_ALIGN) keeps that binding in bounds.
+        let alignment = uniform.alignment();
         let mut uniform = uniform.into_inner();
-        uniform.resize(
-            uniform.len() + UNIFORM_ALIGN - uniform.len() % UNIFORM_ALIGN,
-            0u8,
-        );
-        let desc = BufferDescriptor::new(
-            uniform.len() as _,
-            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-            false,
-        );
+        uniform.resize(uniform.len() + alignment - uniform.len() % alignment, 0u8);
 
-        let resource = self.pool.write().get_or_create(&desc, device);
+        let (resource, offset) =
+            self.uniform_ring
+                .lock()
+                .allocate(uniform.len() as _, alignment as _, |capacity| {
+                    let desc = BufferDescriptor::new(
+                        capacity,
+                        BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                        false,
+                    );
+                    self.create_buffer(&desc, device)
+                });
         device
             .queue()
-            .write_buffer(&resource.inner, 0, uniform.as_slice());
-        resource
+            .write_buffer(&resource.inner, offset, uniform.as_slice());
+        (resource, offset)
     }
 
     /// # Graph memory allocation
@@ -82,13 +212,24 @@ impl BufferAllocator {
         free: &mut Vec<GraphBuffer>,
         device: &WgpuDevice,
     ) -> GraphBuffer {
-        let required_size = descriptor.size as _;
+        if std::env::var("RATCHET_DEBUG").is_ok() {
+            return GraphBuffer::from(self.create_buffer(&descriptor, device));
+        }
+
+        match Self::take_closest_free(free, descriptor.size) {
+            Some(buffer) => buffer,
+            None => GraphBuffer::from(self.create_buffer(&descriptor, device)),
+        }
+    }
+
+    /// Removes the smallest free buffer that can hold `required_size` bytes.
+    fn take_closest_free<B: GraphAllocation>(free: &mut Vec<B>, required_size: u64) -> Option<B> {
         let mut closest_index = None;
-        let mut closest_size_diff: Option<usize> = None;
+        let mut closest_size_diff: Option<u64> = None;
         for (idx, buffer) in free.iter().enumerate() {
-            let current_size = buffer.0.descriptor.size as _;
+            let current_size = buffer.size();
             if current_size >= required_size {
-                let size_diff = usize::abs_diff(current_size, required_size);
+                let size_diff = current_size - required_size;
 
                 if closest_size_diff.map_or(true, |diff| size_diff < diff) {
                     closest_index = Some(idx);
@@ -96,15 +237,7 @@ impl BufferAllocator {
                 }
             }
         }
-
-        if std::env::var("RATCHET_DEBUG").is_ok() {
-            return GraphBuffer::from(self.create_buffer(&descriptor, device));
-        }
-
-        match closest_index {
-            Some(idx) => free.remove(idx),
-            None => GraphBuffer::from(self.create_buffer(&descriptor, device)),
-        }
+        closest_index.map(|idx| free.remove(idx))
     }
 
     /// # Inplace operations
@@ -116,18 +249,23 @@ impl BufferAllocator {
     /// 1. We reach an operation that does not support inplace
     /// 2. We reach an operation that has more than one consumer
     /// 3. We reach an operation that has more than one source (this condition is wrong)
+    /// 4. We reach a retained tensor, or the next source is retained
     fn determine_tensor_source(source: &Tensor) -> &Tensor {
         let mut true_source = source;
         loop {
             let cant_inplace = !true_source.op().supports_inplace();
             let multiple_consumers = Arc::strong_count(&true_source.inner) > 1;
             log::debug!("Conditions: {:?} {:?}", cant_inplace, multiple_consumers);
-            if cant_inplace || multiple_consumers {
+            if cant_inplace || multiple_consumers || true_source.retained() {
                 break;
             }
 
-            true_source = true_source.op().srcs()[0]; //TODO: this shouldn't be 0, operations
-                                                      //should define their inplace source
+            let next = true_source.op().srcs()[0]; //TODO: this shouldn't be 0, operations
+                                                   //should define their inplace source
+            if next.retained() {
+                break;
+            }
+            true_source = next;
         }
         log::debug!("Traversed to true source: {:?}", true_source.id());
         true_source
@@ -143,48 +281,145 @@ impl BufferAllocator {
     ///        the "true" buffer source (i.e the first non-inplace operation).
     /// 3. We release our **output** buffer, because the value is no longer needed,
     ///    and earlier tensors can use it.
+    ///
+    /// A graph may have several outputs (see [Tensor::resolve_all]), each is assigned a buffer
+    /// up front. Only the last tensor in `execution_order` releases its buffer.
+    ///
+    /// ## Determinism
+    ///
+    /// Given the same `execution_order`, the assignment is always the same.
+    /// Every decision is made walking `execution_order` or the free list (a `Vec`),
+    /// the returned map is only ever used for lookups. Keep it that way, allocator bugs
+    /// (e.g an inplace op aliasing a live buffer) are only reproducible if the assignment is.
     pub fn allocate_cfg(
         &self,
         execution_order: &[&Tensor],
         device: &WgpuDevice,
     ) -> Result<FxHashMap<TensorId, GraphBuffer>, DeviceError> {
+        let const_buffer = |t: &Tensor| -> Result<GraphBuffer, DeviceError> {
+            let storage_guard = t.storage();
+            let pooled = storage_guard
+                .as_ref()
+                .ok_or(AllocatorError::BufferNotFound)?
+                .try_gpu()?
+                .inner
+                .clone();
+            Ok(GraphBuffer::from(pooled))
+        };
+        let allocate = |descriptor: BufferDescriptor, free: &mut Vec<GraphBuffer>| {
+            self.graph_allocate(descriptor, free, device)
+        };
+        let assignments = Self::assign_buffers(execution_order, const_buffer, allocate)?;
+
+        if log::log_enabled!(log::Level::Debug) {
+            for t in execution_order {
+                if let Some(buf) = assignments.get(&t.id()) {
+                    log::debug!("{:?} ({}) -> {:?}", t.id(), t.op().name(), buf.global_id());
+                }
+            }
+        }
+
+        log::info!(
+            "Total bytes allocated: {}kb",
+            self.pool.read().total_gpu_size_in_bytes() / 1024,
+        );
+        log::info!(
+            "Total buffers allocated: {}",
+            self.pool.read().num_resources()
+        );
+
+        Ok(assignments)
+    }
+
+    /// # Sizing only allocation
+    ///
+    /// Runs the same assignment as [BufferAllocator::allocate_cfg], without creating any buffers.
+    /// Returns the size of each activation buffer that would be created, and for each
+    /// unresolved tensor in `execution_order` the index of the buffer it would be assigned.
+    pub(crate) fn plan_cfg(execution_order: &[&Tensor]) -> Result<BufferPlan, DeviceError> {
+        let mut created = vec![];
+        let const_buffer = |t: &Tensor| -> Result<PlannedBuffer, DeviceError> {
+            Ok(PlannedBuffer::new(usize::MAX, t.num_bytes() as _))
+        };
+        let allocate = |descriptor: BufferDescriptor, free: &mut Vec<PlannedBuffer>| {
+            Self::take_closest_free(free, descriptor.size).unwrap_or_else(|| {
+                let size = descriptor.size.max(MIN_STORAGE_BUFFER_SIZE as _);
+                created.push(size);
+                PlannedBuffer::new(created.len() - 1, size)
+            })
+        };
+        let assignments = Self::assign_buffers(execution_order, const_buffer, allocate)?;
+        let ordered = execution_order
+            .iter()
+            .filter(|t| !t.resolved())
+            .filter_map(|t| assignments.get(&t.id()).map(|b| (t.id(), b.global_id())))
+            .collect();
+        Ok((created, ordered))
+    }
+
+    /// Tensors in `execution_order` that no other tensor in it consumes.
+    /// A graph built from a single tensor has one, the tensor itself.
+    fn graph_outputs<'a>(execution_order: &[&'a Tensor]) -> Vec<&'a Tensor> {
+        let consumed = execution_order
+            .iter()
+            .flat_map(|t| t.op().srcs().into_iter().map(|s| s.id()))
+            .collect::<FxHashSet<_>>();
+        execution_order
+            .iter()
+            .filter(|t| !t.resolved() && !consumed.contains(&t.id()))
+            .copied()
+            .collect()
+    }
+
+    fn assign_buffers<B, C, A>(
+        execution_order: &[&Tensor],
+        const_buffer: C,
+        mut allocate: A,
+    ) -> Result<FxHashMap<TensorId, B>, DeviceError>
+    where
+        B: GraphAllocation,
+        C: Fn(&Tensor) -> Result<B, DeviceError>,
+        A: FnMut(BufferDescriptor, &mut Vec<B>) -> B,
+    {
         let mut free = Vec::new(); //TODO: switch to BTreeMap
+        let mut no_reuse = Vec::new(); //Retained tensors never take a buffer that is reused
         let mut assignments = FxHashMap::default();
         //Assignments already needs all of the constants in it.
         for t in execution_order.iter().rev() {
             if t.resolved() {
                 //Consts are immediately resolved
-                let storage_guard = t.storage();
-                let pooled = storage_guard
-                    .as_ref()
-                    .ok_or(AllocatorError::BufferNotFound)?
-                    .try_gpu()?
-                    .inner
-                    .clone();
-                assignments.insert(t.id(), GraphBuffer::from(pooled));
+                assignments.insert(t.id(), const_buffer(t)?);
             }
         }
 
-        //The output never gets allocated in the below loop, because it is not a source.
-        //We know we need an allocation for the output.
+        //The outputs never get allocated in the below loop, because they are not sources.
+        //We know we need an allocation for each output.
         //We traverse upwards until we find the first non-inplace operation, and use it's buffer.
-        let output = execution_order.last().unwrap();
-        let output_source = Self::determine_tensor_source(output);
-        let output_buffer = assignments
-            .get(&output_source.id())
-            .cloned()
-            .unwrap_or_else(|| {
-                self.graph_allocate(
-                    BufferDescriptor::new(
-                        output_source.num_bytes() as _,
-                        BufferUsages::standard(),
-                        false,
-                    ),
-                    &mut free,
-                    device,
-                )
-            });
-        assignments.insert(output.id(), output_buffer);
+        let outputs = Self::graph_outputs(execution_order);
+        for output in outputs.iter() {
+            let output_source = Self::determine_tensor_source(output);
+            let output_buffer = assignments
+                .get(&output_source.id())
+                .cloned()
+                .unwrap_or_else(|| {
+                    //A retained output may need usages a reused buffer lacks
+                    let pool = if output_source.retained() {
+                        &mut no_reuse
+                    } else {
+                        &mut free
+                    };
+                    allocate(
+                        BufferDescriptor::new(
+                            output_source.num_bytes() as _,
+                            output_source.buffer_usages(),
+                            false,
+                        ),
+                        pool,
+                    )
+                });
+            assignments.insert(output.id(), output_buffer);
+        }
+        let last = execution_order.last().map(|t| t.id());
 
         for t in execution_order.iter().rev() {
             if t.resolved() {
@@ -202,60 +437,110 @@ impl BufferAllocator {
                 let true_source = Self::determine_tensor_source(source);
                 log::debug!("Inserting assingment: {:?}", true_source.id());
                 assignments.entry(true_source.id()).or_insert_with(|| {
-                    self.graph_allocate(
+                    let pool = if true_source.retained() {
+                        &mut no_reuse
+                    } else {
+                        &mut free
+                    };
+                    allocate(
                         BufferDescriptor::new(
                             true_source.num_bytes() as _,
-                            BufferUsages::standard(),
+                            true_source.buffer_usages(),
                             false,
                         ),
-                        &mut free,
-                        device,
+                        pool,
                     )
                 });
                 let just_allocated = &assignments[&true_source.id()];
                 log::debug!(
                     "Assigned: {:?} -> {:?}",
                     true_source.id(),
-                    just_allocated.inner().global_id(),
+                    just_allocated.global_id(),
                 );
 
                 if true_source.id() != source.id() {
                     log::debug!(
                         "Double Assignment: {:?} -> {:?}",
                         source.id(),
-                        just_allocated.inner().global_id(),
+                        just_allocated.global_id(),
                     );
-                    assignments.insert(source.id(), just_allocated.clone());
+                    let just_allocated = just_allocated.clone();
+                    assignments.insert(source.id(), just_allocated);
                 }
             }
 
+            //Only the last tensor is computed after every other, any other output
+            //would be overwritten by whoever reused its buffer.
+            let earlier_output = Some(t.id()) != last && outputs.iter().any(|o| o.id() == t.id());
+            if t.retained() || earlier_output {
+                continue;
+            }
+
             //My buffer is no longer needed, since we traverse in reverse order
             //Earlier tensors can use my buffer
             if let Some(buf) = assignments.get(&t.id()) {
-                log::debug!(
-                    "Tensor: {:?} refcount: {}",
-                    t.id(),
-                    Arc::strong_count(buf.inner())
-                );
+                log::debug!("Tensor: {:?} refcount: {}", t.id(), buf.ref_count());
                 //if value == 1, he's the last one and we can release
                 //TODO: this won't work for inplace operations, count never reaches 1
-                if Arc::strong_count(buf.inner()) == 1 {
-                    log::debug!("Releasing buffer: {:?}", buf.inner().global_id());
+                if buf.ref_count() == 1 {
+                    log::debug!("Releasing buffer: {:?}", buf.global_id());
                     free.push(buf.clone());
                 }
             }
         }
+        Ok(assignments)
+    }
+}
 
-        log::info!(
-            "Total bytes allocated: {}kb",
-            self.pool.read().total_gpu_size_in_bytes() / 1024,
-        );
-        log::info!(
-            "Total buffers allocated: {}",
-            self.pool.read().num_resources()
-        );
+/// A buffer assigned to one or more tensors in the graph.
+pub(crate) trait GraphAllocation: Clone {
+    type Id: std::fmt::Debug;
 
-        Ok(assignments)
+    fn size(&self) -> u64;
+    fn global_id(&self) -> Self::Id;
+    /// Number of assignments holding this buffer.
+    fn ref_count(&self) -> usize;
+}
+
+impl GraphAllocation for GraphBuffer {
+    type Id = wgpu::Id<wgpu::Buffer>;
+
+    fn size(&self) -> u64 {
+        self.0.descriptor.size
+    }
+
+    fn global_id(&self) -> Self::Id {
+        self.0.global_id()
+    }
+
+    fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+}
+
+/// Stand-in for a [GraphBuffer] when planning, see [BufferAllocator::plan_cfg].
+#[derive(Clone, Debug)]
+pub(crate) struct PlannedBuffer(Arc<(usize, u64)>);
+
+impl PlannedBuffer {
+    fn new(id: usize, size: u64) -> Self {
+        Self(Arc::new((id, size)))
+    }
+}
+
+impl GraphAllocation for PlannedBuffer {
+    type Id = usize;
+
+    fn size(&self) -> u64 {
+        self.0 .1
+    }
+
+    fn global_id(&self) -> Self::Id {
+        self.0 .0
+    }
+
+    fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.0)
     }
 }
 
@@ -275,3 +560,232 @@ impl From<PooledGPUBuffer> for GraphBuffer {
         Self(buf.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::gpu::BufferUsagesExt;
+    use crate::gpu::{BindGroupLayoutDescriptor, WorkgroupCount};
+    use crate::{
+        register_kernel, rvec, shape, wgc, Device, DeviceRequest, KernelElement, MetaOperation,
+        OpMetadata, Operation, OperationError, RVec, StorageView,
+    };
+    use encase::ShaderType;
+
+    const ACCUMULATE_KERNEL: &str = r#"
+@group(0) @binding(0)
+var<storage, read> X: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    numel: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+@compute @workgroup_size(64,1,1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= metadata.numel) {
+        return;
+    }
+    Y[index] = Y[index] + X[index];
+}
+"#;
+
+    /// Adds its input to whatever its output buffer holds, so it reads stale data unless
+    /// the buffer is zeroed.
+    #[derive(Debug, Clone)]
+    struct Accumulate(Tensor);
+
+    #[derive(Debug, ShaderType)]
+    struct AccumulateMeta {
+        numel: u32,
+    }
+
+    impl OpMetadata for AccumulateMeta {}
+
+    impl Operation for Accumulate {
+        fn check_invariants(_: &[&Tensor]) -> Result<(), OperationError> {
+            Ok(())
+        }
+
+        fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+            Ok(srcs[0].storage_view().clone())
+        }
+    }
+
+    impl MetaOperation for Accumulate {
+        type Meta = AccumulateMeta;
+
+        fn kernel_name(&self) -> &'static str {
+            "test_accumulate"
+        }
+
+        fn srcs(&self) -> RVec<&Tensor> {
+            rvec![&self.0]
+        }
+
+        fn kernel_element(&self, _: &Tensor) -> KernelElement {
+            KernelElement::Scalar
+        }
+
+        fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+            let groups = WorkgroupCount::div_ceil(dst.shape().numel(), 64);
+            Ok(wgc![groups as _, 1, 1])
+        }
+
+        fn storage_bind_group_layout(
+            &self,
+            _: bool,
+        ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+            Ok(BindGroupLayoutDescriptor::unary())
+        }
+
+        fn metadata(
+            &self,
+            dst: &Tensor,
+            _: &KernelElement,
+        ) -> Result<AccumulateMeta, OperationError> {
+            Ok(AccumulateMeta {
+                numel: dst.shape().numel() as _,
+            })
+        }
+    }
+
+    #[test]
+    fn zero_on_reuse_clears_in_stream() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let wgpu_device = device.try_gpu()?;
+        wgpu_device.set_zero_on_reuse(true);
+        let _ = register_kernel("test_accumulate", KernelElement::Scalar, ACCUMULATE_KERNEL);
+
+        let x = Tensor::randn::<f32>(shape![8, 8], Device::CPU);
+        let y = Tensor::randn::<f32>(shape![8, 8], Device::CPU);
+        let expected = x.matmul(&y)?.matmul(&y)?.resolve()?;
+        for pass in 0..3 {
+            wgpu_device.begin_pass(pass);
+            let (x, y) = (x.to(&device)?, y.to(&device)?);
+            let first = x.matmul(&y)?;
+            let second = first.matmul(&y)?;
+            let out = Tensor::custom(Accumulate(second))?;
+
+            //`first` is written into the buffer `out` accumulates into, earlier in the pass.
+            //From the second pass on, every buffer is also reclaimed from the pool.
+            let order = out.execution_order();
+            let assigned = wgpu_device.allocate_cfg(&order, wgpu_device)?;
+            assert_eq!(
+                assigned[&first.id()].global_id(),
+                assigned[&out.id()].global_id()
+            );
+            drop(assigned);
+
+            let ours = out.resolve()?.to(&Device::CPU)?;
+            ours.all_close(&expected, 1e-4, 1e-4)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn memory_pressure_fires_once_per_crossing() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let wgpu_device = device.try_gpu()?;
+        let allocator = BufferAllocator::new();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        allocator.on_memory_pressure(4 << 20, 0.5, move |pressure| {
+            assert!(pressure.allocated >= pressure.budget / 2);
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let desc = |size| BufferDescriptor::new(size, BufferUsages::standard(), false);
+        let _small = allocator.create_buffer(&desc(1 << 20), wgpu_device);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        let _large = allocator.create_buffer(&desc(2 << 20), wgpu_device);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let _larger = allocator.create_buffer(&desc(3 << 20), wgpu_device);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_graphs_share_pool() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        std::thread::scope(|scope| {
+            let streams = (0..4)
+                .map(|_| {
+                    let device = device.clone();
+                    scope.spawn(move || -> anyhow::Result<()> {
+                        for pass in 0..8 {
+                            device.try_gpu()?.begin_pass(pass);
+                            let a = Tensor::randn::<f32>(shape![16, 32], Device::CPU);
+                            let b = Tensor::randn::<f32>(shape![32, 8], Device::CPU);
+                            let expected = a.matmul(&b)?.gelu()?.resolve()?;
+                            let ours = a
+                                .to(&device)?
+                                .matmul(&b.to(&device)?)?
+                                .gelu()?
+                                .resolve()?
+                                .to(&Device::CPU)?;
+                            ours.all_close(&expected, 1e-4, 1e-4)?;
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            streams
+                .into_iter()
+                .try_for_each(|stream| stream.join().unwrap())
+        })
+    }
+
+    #[test]
+    fn interleaved_graphs_never_share_buffers() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let wgpu_device = device.try_gpu()?;
+        let graph = || -> anyhow::Result<Tensor> {
+            let a = Tensor::randn::<f32>(shape![64, 64], Device::CPU).to(&device)?;
+            a.gelu()?.matmul(&a)?.tanh()?.exp()
+        };
+        let (first, second) = (graph()?, graph()?);
+        let first_order = first.execution_order();
+        let second_order = second.execution_order();
+
+        let first_assigned = wgpu_device.allocate_cfg(&first_order, wgpu_device)?;
+        wgpu_device.begin_pass(1);
+        wgpu_device.begin_pass(2);
+        let second_assigned = wgpu_device.allocate_cfg(&second_order, wgpu_device)?;
+
+        let first_ids = first_assigned
+            .values()
+            .map(|b| b.global_id())
+            .collect::<FxHashSet<_>>();
+        assert!(second_assigned
+            .values()
+            .all(|b| !first_ids.contains(&b.global_id())));
+        Ok(())
+    }
+
+    #[test]
+    fn usage_hints_reach_the_buffer() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let wgpu_device = device.try_gpu()?;
+        let a = Tensor::randn::<f32>(shape![32, 32], Device::CPU).to(&device)?;
+        let hidden = a.gelu()?.with_usages(BufferUsages::VERTEX);
+        let out = hidden.tanh()?.with_usages(BufferUsages::INDEX);
+        let order = out.execution_order();
+
+        let assigned = wgpu_device.allocate_cfg(&order, wgpu_device)?;
+        let usage = |t: &Tensor| assigned[&t.id()].inner().descriptor.usage;
+        assert!(usage(&hidden).contains(BufferUsages::standard() | BufferUsages::VERTEX));
+        assert!(usage(&out).contains(BufferUsages::standard() | BufferUsages::INDEX));
+        assert_eq!(a.buffer_usages(), BufferUsages::standard());
+        Ok(())
+    }
+}
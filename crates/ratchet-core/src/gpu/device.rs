@@ -1,5 +1,7 @@
 use crate::{gpu::*, Tensor, TensorId};
+use parking_lot::{Mutex, MutexGuard};
 use rustc_hash::FxHashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use wgpu::{Adapter, DeviceType, Limits};
 
@@ -26,6 +28,9 @@ pub struct WgpuDevice {
     bind_group_layout_pool: Arc<BindGroupLayoutPool>,
     pipeline_layout_pool: Arc<PipelineLayoutPool>,
     compute_pipeline_pool: Arc<ComputePipelinePool>,
+    submission: Arc<Mutex<()>>,
+    in_flight: Arc<AtomicUsize>,
+    batched_uploads: Arc<AtomicUsize>,
 }
 
 impl std::ops::Deref for WgpuDevice {
@@ -93,6 +98,9 @@ impl WgpuDevice {
             bind_group_layout_pool: Arc::new(BindGroupLayoutPool::new()),
             pipeline_layout_pool: Arc::new(PipelineLayoutPool::new()),
             compute_pipeline_pool: Arc::new(ComputePipelinePool::new()),
+            submission: Arc::new(Mutex::new(())),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            batched_uploads: Arc::new(AtomicUsize::new(0)),
             device,
         })
     }
@@ -105,6 +113,76 @@ impl WgpuDevice {
         self.ordinal
     }
 
+    /// Submits `command_buffer`, counting it as in flight until the GPU has finished it.
+    /// See [WgpuDevice::poll_nonblocking].
+    pub(crate) fn submit(&self, command_buffer: wgpu::CommandBuffer) -> wgpu::SubmissionIndex {
+        let index = self.queue.submit(Some(command_buffer));
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        let in_flight = self.in_flight.clone();
+        self.queue.on_submitted_work_done(move || {
+            in_flight.fetch_sub(1, Ordering::AcqRel);
+        });
+        index
+    }
+
+    /// # Poll nonblocking
+    ///
+    /// Returns true if graphs or readbacks submitted to the device are still executing.
+    /// Natively this also processes completed work, e.g firing readback callbacks.
+    /// In the browser the queue progresses on its own, so this can be called every
+    /// `requestAnimationFrame` to wait on the GPU without blocking the UI thread.
+    pub fn poll_nonblocking(&self) -> bool {
+        self.poll(wgpu::Maintain::Poll);
+        self.in_flight.load(Ordering::Acquire) > 0
+    }
+
+    /// # Batch uploads
+    ///
+    /// Initialized buffers created within `f` only queue their writes, rather than each
+    /// waiting for its write to complete, e.g while loading every weight of a model.
+    /// The queue is submitted and waited on once when the outermost batch ends.
+    ///
+    /// Queue writes are ordered before any later submission, so the buffers can be used
+    /// within `f`. The written data is held in staging memory until the batch ends.
+    pub fn batch_uploads<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Batch<'a>(&'a WgpuDevice);
+
+        impl Drop for Batch<'_> {
+            fn drop(&mut self) {
+                if self.0.batched_uploads.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    self.0.queue.submit(None);
+                    self.0.poll(wgpu::Maintain::Wait);
+                }
+            }
+        }
+
+        self.batched_uploads.fetch_add(1, Ordering::AcqRel);
+        let _batch = Batch(self);
+        f()
+    }
+
+    /// Whether a [WgpuDevice::batch_uploads] is in progress.
+    pub(crate) fn uploads_batched(&self) -> bool {
+        self.batched_uploads.load(Ordering::Acquire) > 0
+    }
+
+    /// # Submission lock
+    ///
+    /// Held from writing a graph's uniforms until the graph is submitted.
+    /// Several streams may resolve graphs on one device concurrently, the buffer pool is locked
+    /// and each graph assigns buffers from its own free list. The uniform ring is not:
+    /// another stream wrapping the ring between our write and our submit would overwrite
+    /// our uniforms. Waiting on the submission happens after the lock is released.
+    pub(crate) fn lock_submission(&self) -> MutexGuard<'_, ()> {
+        self.submission.lock()
+    }
+
+    /// Alignment of dynamic offsets into the uniform buffer.
+    /// `min_uniform_buffer_offset_alignment` of the device, never less than [UNIFORM_ALIGN].
+    pub fn uniform_alignment(&self) -> usize {
+        (self.limits().min_uniform_buffer_offset_alignment as usize).max(UNIFORM_ALIGN)
+    }
+
     #[cfg(target_arch = "wasm32")]
     async fn select_adapter() -> Adapter {
         let instance = wgpu::Instance::default();
@@ -150,7 +228,7 @@ impl WgpuDevice {
             .create_buffer_init(desc, contents, self))
     }
 
-    pub fn create_uniform_init(&self, cpu_uniform: CpuUniform) -> PooledGPUBuffer {
+    pub fn create_uniform_init(&self, cpu_uniform: CpuUniform) -> (PooledGPUBuffer, u64) {
         self.buffer_allocator.create_uniform_init(cpu_uniform, self)
     }
 
@@ -212,7 +290,7 @@ impl WgpuDevice {
     }
 
     /// Allocates all buffers required for storage of activations.
-    /// Additionally, allocates buffer for leaf node, the tensor upon which resolve was called.
+    /// Additionally, allocates a buffer for each output, the tensors upon which resolve was called.
     pub fn allocate_cfg(
         &self,
         execution_order: &[&Tensor],
@@ -221,7 +299,27 @@ impl WgpuDevice {
         self.buffer_allocator.allocate_cfg(execution_order, device)
     }
 
+    /// See [BufferAllocator::on_memory_pressure].
+    pub fn on_memory_pressure(
+        &self,
+        budget: u64,
+        watermark: f32,
+        callback: impl Fn(MemoryPressure) + Send + Sync + 'static,
+    ) {
+        self.buffer_allocator
+            .on_memory_pressure(budget, watermark, callback);
+    }
+
     pub fn begin_pass(&self, pass_index: u64) {
         self.buffer_allocator.begin_pass(pass_index);
     }
+
+    /// See [BufferAllocator::set_zero_on_reuse].
+    pub fn set_zero_on_reuse(&self, zero: bool) {
+        self.buffer_allocator.set_zero_on_reuse(zero);
+    }
+
+    pub(crate) fn zero_on_reuse(&self) -> bool {
+        self.buffer_allocator.zero_on_reuse()
+    }
 }
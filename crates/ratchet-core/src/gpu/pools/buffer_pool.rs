@@ -51,7 +51,14 @@ impl BufferPool {
         }
     }
 
-    pub fn get_or_create(&self, desc: &BufferDescriptor, device: &WgpuDevice) -> PooledGPUBuffer {
+    /// Returns a buffer matching `desc`, and whether it was reclaimed from an earlier pass
+    /// rather than created, i.e whether it may hold stale data.
+    pub fn get_or_create_reused(
+        &self,
+        desc: &BufferDescriptor,
+        device: &WgpuDevice,
+    ) -> (PooledGPUBuffer, bool) {
+        let created = std::cell::Cell::new(false);
         let descriptor = if (desc.size as usize) < MIN_STORAGE_BUFFER_SIZE {
             BufferDescriptor {
                 size: MIN_STORAGE_BUFFER_SIZE as _,
@@ -61,7 +68,8 @@ impl BufferPool {
         } else {
             desc.clone()
         };
-        self.inner.get_or_create(&descriptor, |descriptor| {
+        let buffer = self.inner.get_or_create(&descriptor, |descriptor| {
+            created.set(true);
             let (size, usage, mapped_at_creation) = descriptor.fields();
             let buf = device.create_buffer(&wgpu::BufferDescriptor {
                 label: None,
@@ -72,11 +80,12 @@ impl BufferPool {
             device.queue().submit(None);
             device.poll(wgpu::Maintain::Wait);
             buf
-        })
+        });
+        (buffer, !created.get())
     }
 
-    pub fn begin_pass(&mut self, pass_index: u64) {
-        self.inner.begin_pass(pass_index, |res| res.destroy());
+    pub fn begin_pass(&mut self, pass_index: u64) -> bool {
+        self.inner.begin_pass(pass_index, |res| res.destroy())
     }
 
     /// Method to retrieve a resource from a weak handle (used by [`super::GpuBindGroupPool`])
@@ -56,7 +56,8 @@ struct DynamicResourcePoolProtectedState<Handle: Key, Desc: Debug, Res> {
 /// identified by its description, as the same description can apply to several different resources.
 pub(super) struct DynamicResourcePool<Handle: Key, Desc: Debug, Res> {
     state: RwLock<DynamicResourcePoolProtectedState<Handle, Desc, Res>>,
-    current_pass_index: u64,
+    /// `None` until the first pass begins.
+    current_pass_index: Option<u64>,
     total_resource_size_in_bytes: AtomicU64,
 }
 
@@ -139,11 +140,23 @@ where
             })
     }
 
-    pub fn begin_pass<D>(&mut self, pass_index: u64, mut destructor: D)
+    /// Ages the pool by one pass, returning false if `pass_index` isn't past the current pass.
+    ///
+    /// Resources released during a pass become reusable in the next, and are destroyed if
+    /// that pass doesn't reuse them. Several streams may share the pool, each beginning
+    /// the same passes, only the first to reach a pass ages the pool. A stream lagging
+    /// behind doesn't age it again, so pass indices must only increase.
+    pub fn begin_pass<D>(&mut self, pass_index: u64, mut destructor: D) -> bool
     where
         D: FnMut(&Res),
     {
-        self.current_pass_index = pass_index;
+        if self
+            .current_pass_index
+            .is_some_and(|current| pass_index <= current)
+        {
+            return false;
+        }
+        self.current_pass_index = Some(pass_index);
         let state = self.state.get_mut();
 
         let update_stats = |creation_desc: &Desc| {
@@ -191,6 +204,7 @@ where
                 true
             }
         });
+        true
     }
 
     pub fn num_resources(&self) -> usize {
@@ -310,6 +324,53 @@ mod tests {
         pool.begin_pass(1234, |_| {});
     }
 
+    // Beginning the current pass again neither reclaims nor drops anything.
+    #[test]
+    fn repeated_pass_is_noop() {
+        let mut pool = Pool::default();
+        let res0 = pool.get_or_create(&ConcreteResourceDesc(0), |_| ConcreteResource);
+        drop(res0);
+        assert!(pool.begin_pass(7, |_| {}));
+        let drop_counter_before = DROP_COUNTER.with(|c| c.get());
+        assert!(!pool.begin_pass(7, |_| {}));
+        assert!(!pool.begin_pass(7, |_| {}));
+        assert_eq!(drop_counter_before, DROP_COUNTER.with(|c| c.get()));
+
+        let reused = Cell::new(true);
+        let _res1 = pool.get_or_create(&ConcreteResourceDesc(0), |_| {
+            reused.set(false);
+            ConcreteResource
+        });
+        assert!(reused.get());
+    }
+
+    // Streams beginning passes out of step only age the pool as the furthest one advances.
+    #[test]
+    fn staggered_passes_age_once() {
+        let mut pool = Pool::default();
+        let res0 = pool.get_or_create(&ConcreteResourceDesc(0), |_| ConcreteResource);
+        drop(res0);
+        //Stream A reaches pass 2 before stream B begins pass 1
+        assert!(pool.begin_pass(1, |_| {}));
+        assert!(pool.begin_pass(2, |_| {}));
+        let drop_counter_before = DROP_COUNTER.with(|c| c.get());
+        assert!(!pool.begin_pass(1, |_| {}));
+        assert!(!pool.begin_pass(2, |_| {}));
+        assert_eq!(drop_counter_before, DROP_COUNTER.with(|c| c.get()));
+
+        //Released during pass 2, reusable until stream A begins pass 4
+        let res1 = pool.get_or_create(&ConcreteResourceDesc(1), |_| ConcreteResource);
+        drop(res1);
+        assert!(pool.begin_pass(3, |_| {}));
+        assert!(!pool.begin_pass(3, |_| {}));
+        let reused = Cell::new(true);
+        let _res2 = pool.get_or_create(&ConcreteResourceDesc(1), |_| {
+            reused.set(false);
+            ConcreteResource
+        });
+        assert!(reused.get());
+    }
+
     // A resource gets the same handle when re-used.
     // (important for BindGroup re-use!)
     #[test]
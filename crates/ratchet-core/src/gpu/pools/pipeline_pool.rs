@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use crate::{gpu::WgpuDevice, KernelElement, KERNELS};
+use crate::{gpu::WgpuDevice, kernel_source, KernelElement};
 
 use super::{
     PipelineLayoutHandle, StaticResourcePool, StaticResourcePoolAccessor,
@@ -43,8 +43,7 @@ impl ComputePipelinePool {
         self.inner.get_or_create(desc, |desc| {
             let kernel_key = desc.build_kernel_key();
             //println!("Kernel key: {}", kernel_key);
-            let shader = KERNELS
-                .get(kernel_key.as_str())
+            let shader = kernel_source(&kernel_key)
                 .unwrap_or_else(|| panic!("Kernel {} not found", kernel_key));
             let label = Some(kernel_key.as_str());
 
@@ -11,9 +11,14 @@ use encase::DynamicUniformBuffer;
 ///We use a single uniform buffer for all operations to hold their parameters.
 ///Every operation writes its metadata into this buffer, and an offset is returned.
 ///This offset is used when binding the buffer.
-pub struct CpuUniform(DynamicUniformBuffer<Vec<u8>>);
+pub struct CpuUniform {
+    buf: DynamicUniformBuffer<Vec<u8>>,
+    alignment: usize,
+}
 
 ///Uniforms must be 256-byte aligned, encase handles this for us.
+///Devices may require a larger alignment, see [WgpuDevice::uniform_alignment].
+///Every metadata struct must fit in this many bytes, it is the size of the uniform binding.
 pub const UNIFORM_ALIGN: usize = 256;
 
 impl Default for CpuUniform {
@@ -24,33 +29,117 @@ impl Default for CpuUniform {
 
 impl CpuUniform {
     pub fn new() -> Self {
-        Self(DynamicUniformBuffer::new(Vec::new()))
+        Self::with_alignment(UNIFORM_ALIGN)
+    }
+
+    /// Offsets of each write are multiples of `alignment`, which must be a power of two
+    /// no smaller than [UNIFORM_ALIGN].
+    pub fn with_alignment(alignment: usize) -> Self {
+        assert!(alignment.is_power_of_two() && alignment >= UNIFORM_ALIGN);
+        Self {
+            buf: DynamicUniformBuffer::new_with_alignment(Vec::new(), alignment as u64),
+            alignment,
+        }
+    }
+
+    pub fn alignment(&self) -> usize {
+        self.alignment
     }
 
     pub fn into_inner(self) -> Vec<u8> {
-        self.0.into_inner()
+        self.buf.into_inner()
     }
 
     /// Consumes the CPU repr of the uniform buffer and writes to the GPU.
     pub(crate) fn into_gpu(self, device: &WgpuDevice) -> Result<GpuUniform, OperationError> {
-        let buf = device.create_uniform_init(self);
+        if self.alignment % device.uniform_alignment() != 0 {
+            return Err(OperationError::CompileError(format!(
+                "Uniform alignment {} does not satisfy the device alignment {}",
+                self.alignment,
+                device.uniform_alignment()
+            )));
+        }
+        let (buf, offset) = device.create_uniform_init(self);
         let layout =
             device.get_or_create_bind_group_layout(&BindGroupLayoutDescriptor::uniform())?;
         let bind_group = device.get_or_create_bind_group(&BindGroupDescriptor {
             entries: rvec![BindGroupEntry {
                 handle: buf.handle,
-                offset: 0,
+                offset,
                 size: NonZeroU64::new(UNIFORM_ALIGN as u64),
             }],
             layout,
         })?;
 
-        Ok(GpuUniform { buf, bind_group })
+        Ok(GpuUniform {
+            _buf: buf,
+            bind_group,
+        })
+    }
+}
+
+/// # Uniform ring
+///
+/// Each pass writes its uniform into a region of one shared buffer, instead of taking a buffer
+/// of its own. Regions are handed out in order and wrap around at the end of the buffer.
+/// Queue writes land after every previously submitted pass, so a region is never overwritten
+/// whilst a pass is still reading it.
+pub(crate) struct UniformRing {
+    buffer: Option<PooledGPUBuffer>,
+    capacity: u64,
+    head: u64,
+}
+
+impl UniformRing {
+    const INITIAL_CAPACITY: u64 = 64 * 1024;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            buffer: None,
+            capacity: Self::INITIAL_CAPACITY,
+            head: 0,
+        }
+    }
+
+    /// Returns the buffer and offset of a region of `len` bytes, aligned to `alignment`.
+    /// `create` is called with the new capacity if the ring has to grow.
+    pub(crate) fn allocate(
+        &mut self,
+        len: u64,
+        alignment: u64,
+        create: impl FnOnce(u64) -> PooledGPUBuffer,
+    ) -> (PooledGPUBuffer, u64) {
+        if len > self.capacity {
+            self.capacity = len.next_power_of_two().max(self.capacity * 2);
+            self.buffer = None;
+        }
+        let buffer = match &self.buffer {
+            Some(buffer) => buffer.clone(),
+            None => {
+                self.head = 0;
+                let buffer = create(self.capacity);
+                self.buffer = Some(buffer.clone());
+                buffer
+            }
+        };
+        (buffer, self.reserve(len, alignment))
+    }
+
+    fn reserve(&mut self, len: u64, alignment: u64) -> u64 {
+        let start = self.head.next_multiple_of(alignment);
+        let start = if start + len > self.capacity {
+            0
+        } else {
+            start
+        };
+        self.head = start + len;
+        start
     }
 }
 
 pub struct GpuUniform {
-    buf: PooledGPUBuffer,
+    //Holds the ring buffer out of the pool for as long as the bind group references it
+    _buf: PooledGPUBuffer,
     bind_group: GpuBindGroup,
 }
 
@@ -64,12 +153,41 @@ impl std::ops::Deref for CpuUniform {
     type Target = DynamicUniformBuffer<Vec<u8>>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.buf
     }
 }
 
 impl std::ops::DerefMut for CpuUniform {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encase::ShaderType;
+
+    #[derive(ShaderType)]
+    struct Meta {
+        a: u32,
+    }
+
+    #[test]
+    fn ring_wraps() {
+        let mut ring = UniformRing::new();
+        let cap = UniformRing::INITIAL_CAPACITY;
+        assert_eq!(ring.reserve(300, 256), 0);
+        assert_eq!(ring.reserve(256, 256), 512);
+        assert_eq!(ring.reserve(cap - 512, 256), 0);
+        assert_eq!(ring.reserve(512, 256), cap - 512);
+        assert_eq!(ring.reserve(256, 256), 0);
+    }
+
+    #[test]
+    fn writes_respect_alignment() {
+        let mut uniform = CpuUniform::with_alignment(512);
+        assert_eq!(uniform.write(&Meta { a: 1 }).unwrap(), 0);
+        assert_eq!(uniform.write(&Meta { a: 2 }).unwrap(), 512);
     }
 }
@@ -3,7 +3,7 @@ use derive_new::new;
 #[macro_export]
 macro_rules! wgc {
     ($x:expr, $y:expr, $z:expr) => {
-        $crate::gpu::WorkgroupCount::new($x, $y, $z)
+        $crate::WorkgroupCount::new($x, $y, $z)
     };
 }
 
@@ -236,6 +236,241 @@ lazy_static! {
             "softmax_vec4",
             include_str!(r"../kernels/softmax_vec4.wgsl"),
         );
+        m.insert(
+            "cast_f32_i32_scalar",
+            include_str!(r"../kernels/generated/cast_f32_i32_scalar.wgsl"),
+        );
+        m.insert(
+            "cast_f32_i32_vec2",
+            include_str!(r"../kernels/generated/cast_f32_i32_vec2.wgsl"),
+        );
+        m.insert(
+            "cast_f32_i32_vec4",
+            include_str!(r"../kernels/generated/cast_f32_i32_vec4.wgsl"),
+        );
+        m.insert(
+            "cast_f32_u32_scalar",
+            include_str!(r"../kernels/generated/cast_f32_u32_scalar.wgsl"),
+        );
+        m.insert(
+            "cast_f32_u32_vec2",
+            include_str!(r"../kernels/generated/cast_f32_u32_vec2.wgsl"),
+        );
+        m.insert(
+            "cast_f32_u32_vec4",
+            include_str!(r"../kernels/generated/cast_f32_u32_vec4.wgsl"),
+        );
+        m.insert(
+            "cast_i32_f32_scalar",
+            include_str!(r"../kernels/generated/cast_i32_f32_scalar.wgsl"),
+        );
+        m.insert(
+            "cast_i32_f32_vec2",
+            include_str!(r"../kernels/generated/cast_i32_f32_vec2.wgsl"),
+        );
+        m.insert(
+            "cast_i32_f32_vec4",
+            include_str!(r"../kernels/generated/cast_i32_f32_vec4.wgsl"),
+        );
+        m.insert(
+            "cast_i32_u32_scalar",
+            include_str!(r"../kernels/generated/cast_i32_u32_scalar.wgsl"),
+        );
+        m.insert(
+            "cast_i32_u32_vec2",
+            include_str!(r"../kernels/generated/cast_i32_u32_vec2.wgsl"),
+        );
+        m.insert(
+            "cast_i32_u32_vec4",
+            include_str!(r"../kernels/generated/cast_i32_u32_vec4.wgsl"),
+        );
+        m.insert(
+            "cast_u32_f32_scalar",
+            include_str!(r"../kernels/generated/cast_u32_f32_scalar.wgsl"),
+        );
+        m.insert(
+            "cast_u32_f32_vec2",
+            include_str!(r"../kernels/generated/cast_u32_f32_vec2.wgsl"),
+        );
+        m.insert(
+            "cast_u32_f32_vec4",
+            include_str!(r"../kernels/generated/cast_u32_f32_vec4.wgsl"),
+        );
+        m.insert(
+            "cast_u32_i32_scalar",
+            include_str!(r"../kernels/generated/cast_u32_i32_scalar.wgsl"),
+        );
+        m.insert(
+            "cast_u32_i32_vec2",
+            include_str!(r"../kernels/generated/cast_u32_i32_vec2.wgsl"),
+        );
+        m.insert(
+            "cast_u32_i32_vec4",
+            include_str!(r"../kernels/generated/cast_u32_i32_vec4.wgsl"),
+        );
+        m.insert(
+            "clamp_scalar",
+            include_str!(r"../kernels/generated/clamp_scalar.wgsl"),
+        );
+        m.insert(
+            "clamp_vec2",
+            include_str!(r"../kernels/generated/clamp_vec2.wgsl"),
+        );
+        m.insert(
+            "clamp_vec4",
+            include_str!(r"../kernels/generated/clamp_vec4.wgsl"),
+        );
+        m.insert(
+            "transpose_scalar",
+            include_str!(r"../kernels/transpose_scalar.wgsl"),
+        );
+        m.insert(
+            "repeat_scalar",
+            include_str!(r"../kernels/generated/repeat_scalar.wgsl"),
+        );
+        m.insert("sdpa_scalar", include_str!(r"../kernels/sdpa_scalar.wgsl"));
+        m.insert(
+            "sdpa_masked_scalar",
+            include_str!(r"../kernels/sdpa_masked_scalar.wgsl"),
+        );
+        m.insert(
+            "where_f32_scalar",
+            include_str!(r"../kernels/generated/where_f32_scalar.wgsl"),
+        );
+        m.insert(
+            "where_f32_vec2",
+            include_str!(r"../kernels/generated/where_f32_vec2.wgsl"),
+        );
+        m.insert(
+            "where_f32_vec4",
+            include_str!(r"../kernels/generated/where_f32_vec4.wgsl"),
+        );
+        m.insert(
+            "where_i32_scalar",
+            include_str!(r"../kernels/generated/where_i32_scalar.wgsl"),
+        );
+        m.insert(
+            "where_i32_vec2",
+            include_str!(r"../kernels/generated/where_i32_vec2.wgsl"),
+        );
+        m.insert(
+            "where_i32_vec4",
+            include_str!(r"../kernels/generated/where_i32_vec4.wgsl"),
+        );
+        m.insert(
+            "where_u32_scalar",
+            include_str!(r"../kernels/generated/where_u32_scalar.wgsl"),
+        );
+        m.insert(
+            "where_u32_vec2",
+            include_str!(r"../kernels/generated/where_u32_vec2.wgsl"),
+        );
+        m.insert(
+            "where_u32_vec4",
+            include_str!(r"../kernels/generated/where_u32_vec4.wgsl"),
+        );
+        m.insert(
+            "eq_scalar",
+            include_str!(r"../kernels/generated/eq_scalar.wgsl"),
+        );
+        m.insert(
+            "eq_vec2",
+            include_str!(r"../kernels/generated/eq_vec2.wgsl"),
+        );
+        m.insert(
+            "eq_vec4",
+            include_str!(r"../kernels/generated/eq_vec4.wgsl"),
+        );
+        m.insert(
+            "ne_scalar",
+            include_str!(r"../kernels/generated/ne_scalar.wgsl"),
+        );
+        m.insert(
+            "ne_vec2",
+            include_str!(r"../kernels/generated/ne_vec2.wgsl"),
+        );
+        m.insert(
+            "ne_vec4",
+            include_str!(r"../kernels/generated/ne_vec4.wgsl"),
+        );
+        m.insert(
+            "lt_scalar",
+            include_str!(r"../kernels/generated/lt_scalar.wgsl"),
+        );
+        m.insert(
+            "lt_vec2",
+            include_str!(r"../kernels/generated/lt_vec2.wgsl"),
+        );
+        m.insert(
+            "lt_vec4",
+            include_str!(r"../kernels/generated/lt_vec4.wgsl"),
+        );
+        m.insert(
+            "le_scalar",
+            include_str!(r"../kernels/generated/le_scalar.wgsl"),
+        );
+        m.insert(
+            "le_vec2",
+            include_str!(r"../kernels/generated/le_vec2.wgsl"),
+        );
+        m.insert(
+            "le_vec4",
+            include_str!(r"../kernels/generated/le_vec4.wgsl"),
+        );
+        m.insert(
+            "gt_scalar",
+            include_str!(r"../kernels/generated/gt_scalar.wgsl"),
+        );
+        m.insert(
+            "gt_vec2",
+            include_str!(r"../kernels/generated/gt_vec2.wgsl"),
+        );
+        m.insert(
+            "gt_vec4",
+            include_str!(r"../kernels/generated/gt_vec4.wgsl"),
+        );
+        m.insert(
+            "ge_scalar",
+            include_str!(r"../kernels/generated/ge_scalar.wgsl"),
+        );
+        m.insert(
+            "ge_vec2",
+            include_str!(r"../kernels/generated/ge_vec2.wgsl"),
+        );
+        m.insert(
+            "ge_vec4",
+            include_str!(r"../kernels/generated/ge_vec4.wgsl"),
+        );
+        m.insert(
+            "cumsum_scalar",
+            include_str!(r"../kernels/cumsum_scalar.wgsl"),
+        );
+        m.insert("topk_scalar", include_str!(r"../kernels/topk_scalar.wgsl"));
+        m.insert(
+            "topk_gather_scalar",
+            include_str!(r"../kernels/topk_gather_scalar.wgsl"),
+        );
+        m.insert(
+            "rmsnorm_scalar",
+            include_str!(r"../kernels/generated/rmsnorm_scalar.wgsl"),
+        );
+        m.insert(
+            "rmsnorm_vec2",
+            include_str!(r"../kernels/generated/rmsnorm_vec2.wgsl"),
+        );
+        m.insert(
+            "rmsnorm_vec4",
+            include_str!(r"../kernels/generated/rmsnorm_vec4.wgsl"),
+        );
+        m.insert("pad_scalar", include_str!(r"../kernels/pad_scalar.wgsl"));
+        m.insert(
+            "index_copy_scalar",
+            include_str!(r"../kernels/index_copy_scalar.wgsl"),
+        );
+        m.insert(
+            "binary_strided_scalar",
+            include_str!(r"../kernels/binary_strided_scalar.wgsl"),
+        );
         m
     };
 }
@@ -1,5 +1,6 @@
 #![allow(non_snake_case)]
 mod compiled_op;
+mod custom_op;
 mod device;
 mod dtype;
 mod enforcer;
@@ -9,6 +10,7 @@ mod kernels;
 mod ndarray_ext;
 mod op;
 mod ops;
+mod plan;
 mod plot;
 mod quant;
 mod shape;
@@ -18,6 +20,7 @@ mod tensor;
 mod tensor_id;
 
 pub use compiled_op::*;
+pub use custom_op::*;
 pub use device::*;
 pub use dtype::*;
 pub use enforcer::*;
@@ -26,6 +29,7 @@ pub use kernels::*;
 pub use ndarray_ext::*;
 pub use op::*;
 pub use ops::*;
+pub use plan::*;
 pub use quant::*;
 pub use shape::*;
 pub use storage::*;
@@ -33,6 +37,8 @@ pub use strides::*;
 pub use tensor::*;
 pub use tensor_id::*;
 
+pub use gpu::{BindGroupLayoutDescriptor, MemoryPressure, WorkgroupCount};
+
 #[cfg(feature = "plotting")]
 pub use plot::render_to_file;
 
@@ -94,13 +100,28 @@ pub mod prelude {
 
 #[cfg(not(target_arch = "wasm32"))]
 pub mod test_util {
-    use crate::{DType, Tensor};
+    use crate::{ops, DType, Device, Tensor};
     use regex::Regex;
     use {
         numpy::PyArrayDyn,
         pyo3::{prelude::*, types::PyTuple},
     };
 
+    /// # CPU matmul
+    ///
+    /// Plain Rust reference for [Tensor::matmul], to check kernels against without Python.
+    /// Both operands must be F32 and on the CPU, a batch of 1 is broadcast against the other.
+    pub fn cpu_matmul(lhs: &Tensor, rhs: &Tensor) -> anyhow::Result<Tensor> {
+        anyhow::ensure!(
+            lhs.device().is_cpu() && rhs.device().is_cpu(),
+            "cpu_matmul operands must be on the CPU"
+        );
+        //Only used for shape inference, never resolved
+        let dst_shape = lhs.matmul(rhs)?.shape().clone();
+        let data = ops::cpu::matmul(lhs, rhs, &dst_shape)?;
+        Ok(Tensor::from_data(data, dst_shape, Device::CPU))
+    }
+
     /// It's a bit of a hack, but it's useful for testing.
     pub fn run_py_prg(
         prg: String,
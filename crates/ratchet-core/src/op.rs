@@ -7,7 +7,10 @@ use crate::gpu::{
     BindGroupLayoutDescriptor, ComputePipelineDescriptor, CpuUniform, PipelineLayoutDescriptor,
     PoolError, WgpuDevice, WorkgroupCount, UNIFORM_ALIGN,
 };
-use crate::{ops::*, rvec, CompiledOp, InvariantError, KernelElement, RVec, StorageView, Tensor};
+use crate::{
+    kernel_source, ops::*, rvec, CompiledOp, Custom, InvariantError, KernelElement, RVec,
+    StorageView, Tensor,
+};
 
 #[derive(Clone, Debug)]
 #[non_exhaustive]
@@ -15,30 +18,52 @@ pub enum LazyOp {
     Const,
     Matmul(Matmul),
     Binary(Binary),
+    Cmp(Cmp),
     Unary(Unary),
+    Cast(Cast),
+    Clamp(Clamp),
+    Cumsum(Cumsum),
+    TopK(TopK),
+    Sdpa(Sdpa),
+    WhereCond(WhereCond),
+    Pad(Pad),
     Reindex(Reindex),
     // ---- Everything below this line shouldn't exist ----
     Softmax(Softmax),
     Norm(Norm),
     View(View),             //Should be general class, metadata modification
+    Expand(Expand),         //Above, with strides
     Conv(Conv),             //Really it's a matmul
     Select(IndexSelect),    //Can probably be Reindex
     IndexWrite(IndexWrite), //Above 2 should be merged
+    IndexCopy(IndexCopy),
+    Custom(Custom),
 }
 
 impl LazyOp {
     pub fn name(&self) -> &'static str {
         match self {
             LazyOp::Binary(b) => b.name(),
+            LazyOp::Cmp(c) => c.name(),
             LazyOp::Matmul(m) => m.name(),
             LazyOp::Softmax(s) => s.name(),
             LazyOp::Unary(u) => u.name(),
+            LazyOp::Cast(c) => c.name(),
+            LazyOp::Clamp(c) => c.name(),
+            LazyOp::Cumsum(c) => c.name(),
+            LazyOp::TopK(t) => t.name(),
+            LazyOp::Sdpa(a) => a.name(),
+            LazyOp::WhereCond(w) => w.name(),
+            LazyOp::Pad(p) => p.name(),
             LazyOp::Reindex(r) => r.name(),
             LazyOp::Norm(n) => n.name(),
             LazyOp::Conv(c) => c.name(),
             LazyOp::Select(s) => s.name(),
             LazyOp::IndexWrite(iw) => iw.name(),
+            LazyOp::IndexCopy(ic) => ic.name(),
+            LazyOp::Custom(c) => c.name(),
             LazyOp::View(_) => "View",
+            LazyOp::Expand(_) => "Expand",
             LazyOp::Const => "Const",
         }
     }
@@ -46,15 +71,26 @@ impl LazyOp {
     pub fn srcs(&self) -> RVec<&Tensor> {
         match self {
             LazyOp::Binary(b) => b.srcs(),
+            LazyOp::Cmp(c) => c.srcs(),
             LazyOp::Matmul(m) => m.srcs(),
             LazyOp::Softmax(s) => s.srcs(),
             LazyOp::Unary(u) => u.srcs(),
+            LazyOp::Cast(c) => c.srcs(),
+            LazyOp::Clamp(c) => c.srcs(),
+            LazyOp::Cumsum(c) => c.srcs(),
+            LazyOp::TopK(t) => t.srcs(),
+            LazyOp::Sdpa(a) => a.srcs(),
+            LazyOp::WhereCond(w) => w.srcs(),
+            LazyOp::Pad(p) => p.srcs(),
             LazyOp::Reindex(r) => r.srcs(),
             LazyOp::Norm(n) => n.srcs(),
             LazyOp::Conv(c) => c.srcs(),
             LazyOp::Select(s) => s.srcs(),
             LazyOp::IndexWrite(iw) => iw.srcs(),
+            LazyOp::IndexCopy(ic) => ic.srcs(),
+            LazyOp::Custom(c) => c.srcs(),
             LazyOp::View(v) => rvec![v.input()],
+            LazyOp::Expand(e) => rvec![e.input()],
             LazyOp::Const => rvec![], //end of the line kid
         }
     }
@@ -62,15 +98,26 @@ impl LazyOp {
     pub fn supports_inplace(&self) -> bool {
         match self {
             LazyOp::Binary(b) => b.supports_inplace(),
+            LazyOp::Cmp(c) => c.supports_inplace(),
             LazyOp::Matmul(m) => m.supports_inplace(),
             LazyOp::Softmax(s) => s.supports_inplace(),
             LazyOp::Unary(u) => u.supports_inplace(),
+            LazyOp::Cast(c) => c.supports_inplace(),
+            LazyOp::Clamp(c) => c.supports_inplace(),
+            LazyOp::Cumsum(c) => c.supports_inplace(),
+            LazyOp::TopK(t) => t.supports_inplace(),
+            LazyOp::Sdpa(a) => a.supports_inplace(),
+            LazyOp::WhereCond(w) => w.supports_inplace(),
+            LazyOp::Pad(p) => p.supports_inplace(),
             LazyOp::Reindex(r) => r.supports_inplace(),
             LazyOp::Norm(n) => n.supports_inplace(),
             LazyOp::Conv(c) => c.supports_inplace(),
             LazyOp::Select(s) => s.supports_inplace(),
             LazyOp::IndexWrite(iw) => iw.supports_inplace(),
+            LazyOp::IndexCopy(ic) => ic.supports_inplace(),
+            LazyOp::Custom(c) => c.supports_inplace(),
             LazyOp::View(_v) => true,
+            LazyOp::Expand(_e) => true,
             LazyOp::Const => false,
         }
     }
@@ -78,12 +125,67 @@ impl LazyOp {
     pub fn is_const(&self) -> bool {
         matches!(self, LazyOp::Const)
     }
+
+    /// Ops that don't dispatch a kernel always report one.
+    pub fn has_gpu_kernel(&self, dst: &Tensor) -> bool {
+        match self {
+            LazyOp::Binary(b) => b.has_gpu_kernel(dst),
+            LazyOp::Cmp(c) => c.has_gpu_kernel(dst),
+            LazyOp::Matmul(m) => m.has_gpu_kernel(dst),
+            LazyOp::Softmax(s) => s.has_gpu_kernel(dst),
+            LazyOp::Unary(u) => u.has_gpu_kernel(dst),
+            LazyOp::Cast(c) => c.has_gpu_kernel(dst),
+            LazyOp::Clamp(c) => c.has_gpu_kernel(dst),
+            LazyOp::Cumsum(c) => c.has_gpu_kernel(dst),
+            LazyOp::TopK(t) => t.has_gpu_kernel(dst),
+            LazyOp::Sdpa(a) => a.has_gpu_kernel(dst),
+            LazyOp::WhereCond(w) => w.has_gpu_kernel(dst),
+            LazyOp::Pad(p) => p.has_gpu_kernel(dst),
+            LazyOp::Reindex(r) => r.has_gpu_kernel(dst),
+            LazyOp::Norm(n) => n.has_gpu_kernel(dst),
+            LazyOp::Conv(c) => c.has_gpu_kernel(dst),
+            LazyOp::Select(s) => s.has_gpu_kernel(dst),
+            LazyOp::IndexWrite(iw) => iw.has_gpu_kernel(dst),
+            LazyOp::IndexCopy(ic) => ic.has_gpu_kernel(dst),
+            LazyOp::Custom(c) => c.has_gpu_kernel(dst),
+            LazyOp::View(_) | LazyOp::Expand(_) | LazyOp::Const => true,
+        }
+    }
+
+    pub fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        match self {
+            LazyOp::Binary(b) => b.apply_cpu(srcs, dst),
+            LazyOp::Cmp(c) => c.apply_cpu(srcs, dst),
+            LazyOp::Matmul(m) => m.apply_cpu(srcs, dst),
+            LazyOp::Softmax(s) => s.apply_cpu(srcs, dst),
+            LazyOp::Unary(u) => u.apply_cpu(srcs, dst),
+            LazyOp::Cast(c) => c.apply_cpu(srcs, dst),
+            LazyOp::Clamp(c) => c.apply_cpu(srcs, dst),
+            LazyOp::Cumsum(c) => c.apply_cpu(srcs, dst),
+            LazyOp::TopK(t) => t.apply_cpu(srcs, dst),
+            LazyOp::Sdpa(a) => a.apply_cpu(srcs, dst),
+            LazyOp::WhereCond(w) => w.apply_cpu(srcs, dst),
+            LazyOp::Pad(p) => p.apply_cpu(srcs, dst),
+            LazyOp::Reindex(r) => r.apply_cpu(srcs, dst),
+            LazyOp::Norm(n) => n.apply_cpu(srcs, dst),
+            LazyOp::Conv(c) => c.apply_cpu(srcs, dst),
+            LazyOp::Select(s) => s.apply_cpu(srcs, dst),
+            LazyOp::IndexWrite(iw) => iw.apply_cpu(srcs, dst),
+            LazyOp::IndexCopy(ic) => ic.apply_cpu(srcs, dst),
+            LazyOp::Custom(c) => c.apply_cpu(srcs, dst),
+            LazyOp::View(_) | LazyOp::Expand(_) | LazyOp::Const => {
+                Err(OperationError::NoCpuImplementation(self.name()))
+            }
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum OperationError {
     #[error("Failed to compile operation: {0}")]
     CompileError(String),
+    #[error("{0} has no CPU implementation")]
+    NoCpuImplementation(&'static str),
     #[error("Failed to get storage layout: {0}")]
     StorageLayoutError(#[from] PoolError),
     #[error(transparent)]
@@ -143,12 +245,45 @@ pub trait MetaOperation: Debug + 'static {
         inplace: bool,
     ) -> Result<BindGroupLayoutDescriptor, OperationError>;
 
+    /// # Storage Bind Group Layouts
+    ///
+    /// Operations binding more than 4 buffers are split across multiple storage groups,
+    /// these should override this method to return one layout per group.
+    fn storage_bind_group_layouts(
+        &self,
+        inplace: bool,
+    ) -> Result<RVec<BindGroupLayoutDescriptor>, OperationError> {
+        Ok(rvec![self.storage_bind_group_layout(inplace)?])
+    }
+
     fn metadata(
         &self,
         dst: &Tensor,
         kernel_element: &KernelElement,
     ) -> Result<Self::Meta, OperationError>;
 
+    /// # Has GPU Kernel
+    ///
+    /// Whether a kernel is built in or registered (see [crate::register_kernel]) for this op,
+    /// ops without one run [MetaOperation::apply_cpu].
+    fn has_gpu_kernel(&self, dst: &Tensor) -> bool {
+        let key = format!(
+            "{}_{}",
+            self.kernel_name(),
+            self.kernel_element(dst).as_str()
+        );
+        kernel_source(&key).is_some()
+    }
+
+    /// # Apply CPU
+    ///
+    /// Computes the op on the host, `srcs` are host copies of [MetaOperation::srcs].
+    /// Ops without a GPU kernel implement this to remain runnable, the result is
+    /// uploaded into the buffer of `dst`.
+    fn apply_cpu(&self, _srcs: &[Tensor], _dst: &Tensor) -> Result<Tensor, OperationError> {
+        Err(OperationError::NoCpuImplementation(self.kernel_name()))
+    }
+
     fn compile(
         &self,
         dst: &Tensor,
@@ -162,12 +297,17 @@ pub trait MetaOperation: Debug + 'static {
 
         let workgroup_count = self.calculate_dispatch(dst)?;
 
-        let storage_layout = device
-            .get_or_create_bind_group_layout(&self.storage_bind_group_layout(can_inplace)?)?;
+        let storage_layouts = self
+            .storage_bind_group_layouts(can_inplace)?
+            .iter()
+            .map(|layout| device.get_or_create_bind_group_layout(layout))
+            .collect::<Result<RVec<_>, _>>()?;
         let uniform_layout =
             device.get_or_create_bind_group_layout(&BindGroupLayoutDescriptor::uniform())?;
+        let mut layout_entries = storage_layouts.clone();
+        layout_entries.push(uniform_layout);
         let pipeline_layout = device.get_or_create_pipeline_layout(&PipelineLayoutDescriptor {
-            entries: rvec![storage_layout, uniform_layout],
+            entries: layout_entries,
         })?;
 
         let pipeline_descriptor = ComputePipelineDescriptor {
@@ -181,7 +321,7 @@ pub trait MetaOperation: Debug + 'static {
         let storage_bind_groups = CompiledOp::create_storage_bind_groups(
             &self.srcs(),
             dst,
-            rvec![storage_layout],
+            storage_layouts,
             device,
             can_inplace,
             self.kernel_name(),
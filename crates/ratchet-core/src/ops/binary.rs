@@ -1,10 +1,11 @@
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
-    rvec, wgc, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata, Operation,
-    OperationError, RVec, Shape, StorageView, Strides, Tensor,
+    rvec, wgc, DType, Device, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, Shape, StorageView, Strides, Tensor,
 };
 #[cfg(test)]
 use test_strategy::Arbitrary;
@@ -27,6 +28,16 @@ impl BinaryOp {
             BinaryOp::Div => "div",
         }
     }
+
+    //Selects the op in the strided kernel
+    fn code(&self) -> u32 {
+        match self {
+            BinaryOp::Add => 0,
+            BinaryOp::Sub => 1,
+            BinaryOp::Mul => 2,
+            BinaryOp::Div => 3,
+        }
+    }
 }
 
 #[derive(new, Debug, Clone)]
@@ -44,11 +55,21 @@ impl Binary {
     pub fn op(&self) -> &BinaryOp {
         &self.op
     }
+
+    /// Either operand is an expanded view, which must be read through its strides.
+    fn strided(&self) -> bool {
+        !self.lhs.is_contiguous() || !self.rhs.is_contiguous()
+    }
 }
 
+//Only numel is read by the contiguous kernels.
 #[derive(Debug, ShaderType)]
 pub struct BinaryMeta {
     numel: u32,
+    op: u32,
+    dst_shape: glam::UVec4,
+    lhs_stride: glam::UVec4,
+    rhs_stride: glam::UVec4,
 }
 
 impl OpMetadata for BinaryMeta {}
@@ -74,6 +95,14 @@ impl Operation for Binary {
     fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
         Enforcer::check_input_arity(srcs, 2)?;
         Enforcer::check_dtype_match(srcs)?;
+        //Shapes and strides are padded to 4 dims in the metadata
+        for src in srcs {
+            Enforcer::assert_rank_range(src, 0..=4)?;
+        }
+        //The strided kernel is only generated for f32
+        if srcs.iter().any(|src| !src.is_contiguous()) {
+            Enforcer::assert_dtype(srcs[0], DType::F32)?;
+        }
         Ok(())
     }
 }
@@ -88,7 +117,9 @@ impl MetaOperation for Binary {
     fn kernel_element(&self, dst: &Tensor) -> KernelElement {
         let numel = dst.shape().numel();
 
-        if numel % 4 == 0 {
+        if self.strided() {
+            KernelElement::Scalar
+        } else if numel % 4 == 0 {
             KernelElement::Vec4
         } else if numel % 2 == 0 {
             KernelElement::Vec2
@@ -125,7 +156,11 @@ impl MetaOperation for Binary {
     }
 
     fn kernel_name(&self) -> &'static str {
-        self.op.kernel_name()
+        if self.strided() {
+            "binary_strided"
+        } else {
+            self.op.kernel_name()
+        }
     }
 
     fn metadata(
@@ -134,7 +169,38 @@ impl MetaOperation for Binary {
         _kernel_element: &KernelElement,
     ) -> Result<Self::Meta, OperationError> {
         let numel = dst.shape().numel() as _;
-        Ok(BinaryMeta { numel })
+        let mut dst_shape = dst.shape().clone();
+        dst_shape.left_pad_to(1, 4);
+        let padded_stride = |t: &Tensor| {
+            let mut stride = [0; 4];
+            let offset = 4 - t.rank();
+            for (dim, s) in t.strides().to_vec().into_iter().enumerate() {
+                stride[dim + offset] = s as u32;
+            }
+            glam::UVec4::from(stride)
+        };
+        Ok(BinaryMeta {
+            numel,
+            op: self.op.code(),
+            dst_shape: glam::UVec4::from(&dst_shape),
+            lhs_stride: padded_stride(&self.lhs),
+            rhs_stride: padded_stride(&self.rhs),
+        })
+    }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let (lhs, rhs) = (cpu::read_f32(&srcs[0])?, cpu::read_f32(&srcs[1])?);
+        let f: fn(f32, f32) -> f32 = match self.op {
+            BinaryOp::Add => |a, b| a + b,
+            BinaryOp::Sub => |a, b| a - b,
+            BinaryOp::Mul => |a, b| a * b,
+            BinaryOp::Div => |a, b| a / b,
+        };
+        //Either operand may be a scalar
+        let result = (0..dst.shape().numel())
+            .map(|i| f(lhs[i % lhs.len()], rhs[i % rhs.len()]))
+            .collect::<Vec<_>>();
+        Ok(Tensor::from_data(result, dst.shape().clone(), Device::CPU))
     }
 }
 
@@ -142,7 +208,9 @@ impl MetaOperation for Binary {
 mod tests {
     use test_strategy::{proptest, Arbitrary};
 
-    use crate::{shape, test_util::run_py_prg, BinaryOp, Device, DeviceRequest, Tensor};
+    use crate::{
+        shape, test_util::run_py_prg, BinaryOp, Device, DeviceRequest, GPUBuffer, Shape, Tensor,
+    };
 
     thread_local! {
         static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
@@ -201,4 +269,87 @@ def {}(a, b):
     fn test_binary(prob: BinaryProblem) {
         run_binary_trial(prob).unwrap();
     }
+
+    fn run_broadcast_trial(lhs: Shape, rhs: Shape, expected: Shape) -> anyhow::Result<()> {
+        let cpu_device = Device::request_device(DeviceRequest::CPU)?;
+        let a = Tensor::randn::<f32>(lhs, cpu_device.clone());
+        let b = Tensor::randn::<f32>(rhs, cpu_device.clone());
+        let ground = ground_truth(&a, &b, &BinaryOp::Add)?;
+        let device = GPU_DEVICE.with(|d| d.clone());
+
+        let c_gpu = a.to(&device)?.add(&b.to(&device)?)?;
+        assert_eq!(c_gpu.shape(), &expected);
+        let d_gpu = c_gpu.resolve()?.to(&Device::CPU)?;
+        ground.all_close(&d_gpu, 1e-4, 1e-4)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_broadcast_leading_dim() -> anyhow::Result<()> {
+        //e.g positional embedding added to the token embeddings
+        run_broadcast_trial(shape![1, 7, 384], shape![7, 384], shape![1, 7, 384])
+    }
+
+    #[test]
+    fn test_add_broadcast_both() -> anyhow::Result<()> {
+        run_broadcast_trial(shape![2, 1, 64], shape![5, 1], shape![2, 5, 64])
+    }
+
+    #[test]
+    fn test_add_expanded() -> anyhow::Result<()> {
+        let cpu_device = Device::request_device(DeviceRequest::CPU)?;
+        let a = Tensor::randn::<f32>(shape![2, 5, 64], cpu_device.clone());
+        let b = Tensor::randn::<f32>(shape![5, 1], cpu_device.clone());
+        let ground = ground_truth(&a, &b, &BinaryOp::Add)?;
+        let device = GPU_DEVICE.with(|d| d.clone());
+
+        let expanded = b.to(&device)?.expand(shape![2, 5, 64])?;
+        let c_gpu = a.to(&device)?.add(&expanded)?.resolve()?;
+        let d_gpu = c_gpu.to(&Device::CPU)?;
+        ground.all_close(&d_gpu, 1e-4, 1e-4)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_cpu_fallback() -> anyhow::Result<()> {
+        use crate::{Binary, MetaOperation};
+        let a = Tensor::from_data([1f32, 2., 3., 4.], shape![2, 2], Device::CPU);
+        let b = Tensor::from_data([4f32, 3., 2., 1.], shape![2, 2], Device::CPU);
+        let binary = Binary::new(a.clone(), b.clone(), BinaryOp::Div);
+        assert!(binary.has_gpu_kernel(&a));
+        let c = binary.apply_cpu(&[a.clone(), b], &a)?;
+        assert_eq!(c.to_vec::<f32>()?, vec![0.25, 2. / 3., 1.5, 4.]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_invariants() -> anyhow::Result<()> {
+        use crate::{Binary, Operation};
+        let rank5 = Tensor::randn::<f32>(shape![1, 1, 2, 2, 2], Device::CPU);
+        assert!(Binary::check_invariants(&[&rank5, &rank5]).is_err());
+
+        let ints = Tensor::from_data([1i32, 2, 3, 4], shape![2, 2], Device::CPU);
+        let column = Tensor::from_data([1i32, 2], shape![2, 1], Device::CPU);
+        let expanded = column.expand(shape![2, 2])?;
+        assert!(!expanded.is_contiguous());
+        assert!(Binary::check_invariants(&[&ints, &ints]).is_ok());
+        assert!(Binary::check_invariants(&[&ints, &expanded]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sub_isolated() -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let gpu = device.try_gpu()?;
+        let shape = shape![2, 3];
+        let template = Tensor::zeros::<f32>(&shape, &device);
+        let lhs = [1f32, 2., 3., 4., 5., 6.];
+        let rhs = [6f32, 5., 4., 3., 2., 1.];
+        let a = template.with_buffer(GPUBuffer::from_slice(&lhs, &shape, gpu))?;
+        let b = template.with_buffer(GPUBuffer::from_slice(&rhs, &shape, gpu))?;
+
+        let c = a.sub(&b)?.run_isolated()?.to(&Device::CPU)?;
+        assert_eq!(c.to_vec::<f32>()?, vec![-5., -3., -1., 1., 3., 5.]);
+        Ok(())
+    }
 }
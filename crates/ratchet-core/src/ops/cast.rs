@@ -0,0 +1,157 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, StorageView, Strides, Tensor,
+};
+
+/// # Cast
+///
+/// Converts between the 32 bit dtypes (F32, I32, U32).
+/// Float to integer conversions truncate towards zero, matching WGSL.
+#[derive(new, Debug, Clone)]
+pub struct Cast {
+    input: Tensor,
+    dst_dt: DType,
+}
+
+impl Cast {
+    pub fn name(&self) -> &'static str {
+        "cast"
+    }
+
+    pub fn dst_dt(&self) -> DType {
+        self.dst_dt
+    }
+
+    fn is_supported(dt: DType) -> bool {
+        matches!(dt, DType::F32 | DType::I32 | DType::U32)
+    }
+}
+
+#[derive(Debug, ShaderType)]
+pub struct CastMeta {
+    numel: u32,
+}
+
+impl OpMetadata for CastMeta {}
+
+impl Operation for Cast {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 1)?;
+        let src_dt = srcs[0].dt();
+        if !Self::is_supported(src_dt) {
+            return Err(InvariantError::UnsupportedDType(src_dt).into());
+        }
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        if !Self::is_supported(self.dst_dt) {
+            return Err(InvariantError::UnsupportedDType(self.dst_dt).into());
+        }
+        let shape = srcs[0].shape().clone();
+        let strides = Strides::from(&shape);
+        Ok(StorageView::new(shape, self.dst_dt, strides))
+    }
+}
+
+impl MetaOperation for Cast {
+    type Meta = CastMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        rvec![&self.input]
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        let numel = self.input.shape().numel();
+        if numel % 4 == 0 {
+            KernelElement::Vec4
+        } else if numel % 2 == 0 {
+            KernelElement::Vec2
+        } else {
+            KernelElement::Scalar
+        }
+    }
+
+    fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let numel = dst.shape().numel();
+        let x_groups = WorkgroupCount::div_ceil(numel as _, 64);
+        let (x_groups, y_groups) = if x_groups > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(x_groups, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (x_groups, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::unary())
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        match (self.input.dt(), self.dst_dt) {
+            (DType::F32, DType::I32) => "cast_f32_i32",
+            (DType::F32, DType::U32) => "cast_f32_u32",
+            (DType::I32, DType::F32) => "cast_i32_f32",
+            (DType::I32, DType::U32) => "cast_i32_u32",
+            (DType::U32, DType::F32) => "cast_u32_f32",
+            (DType::U32, DType::I32) => "cast_u32_i32",
+            (src, dst) => unreachable!("Unsupported cast {:?} -> {:?}", src, dst),
+        }
+    }
+
+    fn metadata(
+        &self,
+        _dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let numel = self.input.shape().numel() as u32;
+        Ok(CastMeta { numel })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{shape, DType, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    #[test]
+    fn test_cast_roundtrip() -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let data = vec![0.0f32, 1.9, -2.7, 3.0, 42.5, -7.0, 100.0];
+        let input = Tensor::from_data(data.clone(), shape![data.len()], Device::CPU).to(&device)?;
+
+        let as_int = input.cast(DType::I32)?.resolve()?;
+        assert_eq!(as_int.dt(), DType::I32);
+        let ints = as_int.to(&Device::CPU)?.to_vec::<i32>()?;
+        assert_eq!(ints, vec![0, 1, -2, 3, 42, -7, 100]);
+
+        let as_float = as_int.cast(DType::F32)?.resolve()?;
+        let floats = as_float.to(&Device::CPU)?.to_vec::<f32>()?;
+        assert_eq!(floats, vec![0.0, 1.0, -2.0, 3.0, 42.0, -7.0, 100.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cast_u32() -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let data = vec![0i32, 1, 2, 3, 4, 5, 6, 7];
+        let input = Tensor::from_data(data, shape![2, 4], Device::CPU).to(&device)?;
+
+        let as_uint = input.cast(DType::U32)?.resolve()?;
+        let as_float = as_uint.cast(DType::F32)?.resolve()?;
+        let floats = as_float.to(&Device::CPU)?.to_vec::<f32>()?;
+        assert_eq!(floats, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
+    OperationError, RVec, StorageView, Tensor,
+};
+
+/// # Clamp
+///
+/// Elementwise clamp of the input into the range [min, max].
+#[derive(new, Debug, Clone)]
+pub struct Clamp {
+    input: Tensor,
+    min: f32,
+    max: f32,
+}
+
+impl Clamp {
+    pub fn name(&self) -> &'static str {
+        "clamp"
+    }
+}
+
+#[derive(Debug, ShaderType)]
+pub struct ClampMeta {
+    numel: u32,
+    min: f32,
+    max: f32,
+}
+
+impl OpMetadata for ClampMeta {}
+
+impl Operation for Clamp {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 1)?;
+        Enforcer::assert_dtype(srcs[0], DType::F32)?;
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        Ok(srcs[0].storage_view().clone())
+    }
+}
+
+impl MetaOperation for Clamp {
+    type Meta = ClampMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        rvec![&self.input]
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        let numel = self.input.shape().numel();
+        if numel % 4 == 0 {
+            KernelElement::Vec4
+        } else if numel % 2 == 0 {
+            KernelElement::Vec2
+        } else {
+            KernelElement::Scalar
+        }
+    }
+
+    fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let numel = dst.shape().numel();
+        let x_groups = WorkgroupCount::div_ceil(numel as _, 64);
+        let (x_groups, y_groups) = if x_groups > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(x_groups, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (x_groups, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::unary())
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        "clamp"
+    }
+
+    fn metadata(
+        &self,
+        _dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let numel = self.input.shape().numel() as u32;
+        Ok(ClampMeta {
+            numel,
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::{shape, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct ClampProblem {
+        #[strategy(1..=4usize)]
+        B: usize,
+        #[strategy(1..=256usize)]
+        N: usize,
+        #[strategy(-2.0f32..0.0f32)]
+        min: f32,
+        #[strategy(0.0f32..2.0f32)]
+        max: f32,
+    }
+
+    fn run_clamp_trial(prob: ClampProblem) -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let ClampProblem { B, N, min, max } = prob;
+        let a = Tensor::randn::<f32>(shape![B, N], Device::CPU);
+        let expected = a
+            .to_vec::<f32>()?
+            .into_iter()
+            .map(|x| x.clamp(min, max))
+            .collect::<Vec<_>>();
+        let ground = Tensor::from_data(expected, shape![B, N], Device::CPU);
+
+        let c_gpu = a.to(&device)?.clamp(min, max)?.resolve()?;
+        let ours = c_gpu.to(&Device::CPU)?;
+        ground.all_close(&ours, 1e-6, 1e-6)?;
+        Ok(())
+    }
+
+    #[proptest(cases = 16)]
+    fn test_clamp(prob: ClampProblem) {
+        run_clamp_trial(prob).unwrap();
+    }
+}
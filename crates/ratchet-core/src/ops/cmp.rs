@@ -0,0 +1,218 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
+    OperationError, RVec, StorageView, Strides, Tensor,
+};
+#[cfg(test)]
+use test_strategy::Arbitrary;
+
+#[cfg_attr(test, derive(Arbitrary))]
+#[derive(Debug, Clone, Copy)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    pub fn kernel_name(&self) -> &'static str {
+        match self {
+            CmpOp::Eq => "eq",
+            CmpOp::Ne => "ne",
+            CmpOp::Lt => "lt",
+            CmpOp::Le => "le",
+            CmpOp::Gt => "gt",
+            CmpOp::Ge => "ge",
+        }
+    }
+}
+
+/// # Cmp
+///
+/// Elementwise comparison of 2 F32 tensors of the same shape.
+/// Produces a U32 tensor of 1s where the comparison holds and 0s elsewhere,
+/// suitable as the condition of [Tensor::where_cond].
+#[derive(new, Debug, Clone)]
+pub struct Cmp {
+    lhs: Tensor,
+    rhs: Tensor,
+    op: CmpOp,
+}
+
+impl Cmp {
+    pub fn name(&self) -> &'static str {
+        self.op.kernel_name()
+    }
+
+    pub fn op(&self) -> &CmpOp {
+        &self.op
+    }
+}
+
+#[derive(Debug, ShaderType)]
+pub struct CmpMeta {
+    numel: u32,
+}
+
+impl OpMetadata for CmpMeta {}
+
+impl Operation for Cmp {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 2)?;
+        Enforcer::assert_dtype(srcs[0], DType::F32)?;
+        Enforcer::assert_dtype(srcs[1], DType::F32)?;
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        let shape = srcs[0].shape().clone();
+        let strides = Strides::from(&shape);
+        Ok(StorageView::new(shape, DType::U32, strides))
+    }
+}
+
+impl MetaOperation for Cmp {
+    type Meta = CmpMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        rvec![&self.lhs, &self.rhs]
+    }
+
+    fn kernel_element(&self, dst: &Tensor) -> KernelElement {
+        let numel = dst.shape().numel();
+        if numel % 4 == 0 {
+            KernelElement::Vec4
+        } else if numel % 2 == 0 {
+            KernelElement::Vec2
+        } else {
+            KernelElement::Scalar
+        }
+    }
+
+    fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let numel = dst.shape().numel();
+        let x_groups = WorkgroupCount::div_ceil(numel as _, 64);
+        let (x_groups, y_groups) = if x_groups > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(x_groups, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (x_groups, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::binary())
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        self.op.kernel_name()
+    }
+
+    fn metadata(
+        &self,
+        dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let numel = dst.shape().numel() as _;
+        Ok(CmpMeta { numel })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::{shape, CmpOp, DType, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct CmpProblem {
+        op: CmpOp,
+        #[strategy(1..=4usize)]
+        B: usize,
+        #[strategy(1..=128usize)]
+        M: usize,
+        #[strategy(1..=128usize)]
+        N: usize,
+    }
+
+    fn cmp_cpu(op: CmpOp, a: f32, b: f32) -> u32 {
+        let holds = match op {
+            CmpOp::Eq => a == b,
+            CmpOp::Ne => a != b,
+            CmpOp::Lt => a < b,
+            CmpOp::Le => a <= b,
+            CmpOp::Gt => a > b,
+            CmpOp::Ge => a >= b,
+        };
+        holds as u32
+    }
+
+    fn run_cmp_trial(prob: CmpProblem) -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let CmpProblem { op, B, M, N } = prob;
+        //Round so that Eq & Ne see some equal elements
+        let round = |t: Tensor| -> anyhow::Result<Tensor> {
+            let data = t.to_vec::<f32>()?.into_iter().map(f32::round).collect();
+            Ok(Tensor::from_data::<f32, Vec<f32>>(
+                data,
+                t.shape().clone(),
+                Device::CPU,
+            ))
+        };
+        let a = round(Tensor::randn::<f32>(shape![B, M, N], Device::CPU))?;
+        let b = round(Tensor::randn::<f32>(shape![B, 1, N], Device::CPU))?;
+
+        let (a_data, b_data) = (a.to_vec::<f32>()?, b.to_vec::<f32>()?);
+        let expected = (0..B * M * N)
+            .map(|i| {
+                let (batch, col) = (i / (M * N), i % N);
+                cmp_cpu(op, a_data[i], b_data[batch * N + col])
+            })
+            .collect::<Vec<_>>();
+
+        let (a_gpu, b_gpu) = (a.to(&device)?, b.to(&device)?);
+        let c_gpu = match op {
+            CmpOp::Eq => a_gpu.eq(&b_gpu)?,
+            CmpOp::Ne => a_gpu.ne(&b_gpu)?,
+            CmpOp::Lt => a_gpu.lt(&b_gpu)?,
+            CmpOp::Le => a_gpu.le(&b_gpu)?,
+            CmpOp::Gt => a_gpu.gt(&b_gpu)?,
+            CmpOp::Ge => a_gpu.ge(&b_gpu)?,
+        }
+        .resolve()?;
+        assert_eq!(c_gpu.dt(), DType::U32);
+
+        let ours = c_gpu.to(&Device::CPU)?.to_vec::<u32>()?;
+        assert_eq!(ours, expected);
+        Ok(())
+    }
+
+    #[proptest(cases = 8)]
+    fn test_cmp(prob: CmpProblem) {
+        run_cmp_trial(prob).unwrap();
+    }
+
+    #[test]
+    fn test_gt_scalar() -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let a = Tensor::from_data(vec![-1.0f32, 0.5, 2.0, 0.1, 3.0], shape![5], Device::CPU);
+        let mask = a.to(&device)?.gt_scalar(0.4)?.resolve()?;
+        let ours = mask.to(&Device::CPU)?.to_vec::<u32>()?;
+        assert_eq!(ours, vec![0, 1, 1, 0, 1]);
+        Ok(())
+    }
+}
@@ -2,17 +2,23 @@
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
-    rvec, shape, wgc, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
-    OperationError, RVec, StorageView, Strides, Tensor,
+    rvec, shape, wgc, Device, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, StorageView, Strides, Tensor,
 };
 
+/// # Conv
+///
+/// 1D convolution over `[N, C_in, L_in]` with filters of shape `[C_out, C_in, KS]`.
+/// The kernel is specialised for the Whisper encoder stem: a batch size of 1,
+/// a kernel size of 3 and a padding of 1.
 #[derive(new, Debug, Clone)]
 pub struct Conv {
     input: Tensor,
     weight: Tensor,
-    bias: Option<Tensor>,
+    bias: Tensor,
     stride: usize,
     padding: usize,
     //dilation: usize, TODO: implement dilation
@@ -38,6 +44,9 @@ pub struct ConvMeta {
 
 impl OpMetadata for ConvMeta {}
 
+///Filters are staged in workgroup memory, see `F` in the kernel.
+const MAX_FILTER_ELEM: usize = 4096;
+
 impl Operation for Conv {
     fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
         let (input_t, weight_t) = (srcs[0], srcs[1]);
@@ -45,9 +54,38 @@ impl Operation for Conv {
         let calc_dim = |i_size, k_size, pad, dil, stride| {
             ((i_size + (2 * pad) - dil * (k_size - 1) - 1) / stride) + 1 //TODO: Missing floor
         };
-        let [N, _C_in, L_in]: [usize; 3] = input_shape.try_into()?;
-        let [C_out, _, KS]: [usize; 3] = weight_shape.try_into()?;
-        assert!(KS == 3, "Only 3 kernel size is supported");
+        let [N, C_in, L_in]: [usize; 3] = input_shape.try_into()?;
+        let [C_out, W_in, KS]: [usize; 3] = weight_shape.try_into()?;
+        if C_in != W_in {
+            return Err(InvariantError::ShapeMismatch {
+                left: 1,
+                right: 1,
+                a: C_in,
+                b: W_in,
+            })?;
+        }
+        if KS != 3 || self.padding != 1 {
+            return Err(anyhow::anyhow!(
+                "Conv1d only supports KS = 3 & padding = 1, got KS = {}, padding = {}",
+                KS,
+                self.padding
+            ))?;
+        }
+        if C_in * KS > MAX_FILTER_ELEM {
+            return Err(anyhow::anyhow!(
+                "Conv1d filters of {} elements exceed the maximum of {}",
+                C_in * KS,
+                MAX_FILTER_ELEM
+            ))?;
+        }
+        if self.bias.shape().numel() != C_out {
+            return Err(InvariantError::ShapeMismatch {
+                left: 0,
+                right: 0,
+                a: C_out,
+                b: self.bias.shape().numel(),
+            })?;
+        }
 
         let L_out = calc_dim(L_in, KS, self.padding, 1, self.stride);
         let out_shape = shape![N, C_out, L_out];
@@ -58,7 +96,12 @@ impl Operation for Conv {
     fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
         Enforcer::check_input_arity_range(srcs, 2..=3)?;
         Enforcer::assert_rank(srcs[0], 3)?;
-        //TODO: exhaustive checks
+        Enforcer::assert_rank(srcs[1], 3)?;
+        //Neither the kernel nor the CPU implementation index the batch
+        let N = srcs[0].shape()[0];
+        if N != 1 {
+            return Err(anyhow::anyhow!("Conv1d only supports N = 1, got N = {}", N))?;
+        }
         Ok(())
     }
 }
@@ -67,7 +110,7 @@ impl MetaOperation for Conv {
     type Meta = ConvMeta;
 
     fn srcs(&self) -> RVec<&Tensor> {
-        rvec![&self.input, &self.weight, self.bias.as_ref().unwrap()]
+        rvec![&self.input, &self.weight, &self.bias]
     }
 
     fn kernel_name(&self) -> &'static str {
@@ -117,6 +160,33 @@ impl MetaOperation for Conv {
             Fperthread as _,
         ))
     }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let input = cpu::read_f32(&srcs[0])?;
+        let weight = cpu::read_f32(&srcs[1])?;
+        let bias = cpu::read_f32(&srcs[2])?;
+        let [_, c_in, l_in]: [usize; 3] = srcs[0].shape().try_into()?;
+        let [c_out, _, ks]: [usize; 3] = srcs[1].shape().try_into()?;
+        let l_out = dst.shape()[2];
+
+        let mut result = vec![0f32; c_out * l_out];
+        for co in 0..c_out {
+            for l in 0..l_out {
+                let mut acc = bias[co];
+                for ci in 0..c_in {
+                    for k in 0..ks {
+                        let pos = (l * self.stride + k) as isize - self.padding as isize;
+                        if (0..l_in as isize).contains(&pos) {
+                            acc +=
+                                weight[(co * c_in + ci) * ks + k] * input[ci * l_in + pos as usize];
+                        }
+                    }
+                }
+                result[co * l_out + l] = acc;
+            }
+        }
+        Ok(Tensor::from_data(result, dst.shape().clone(), Device::CPU))
+    }
 }
 
 #[cfg(test)]
@@ -204,4 +274,21 @@ def conv(input, filters, bias, stride, padding):
         );
         run_conv_trial(&device, prob);
     }
+
+    #[test]
+    fn test_conv_invariants() {
+        let input = Tensor::randn::<f32>(shape![1, 8, 12], Device::CPU);
+        let weight = Tensor::randn::<f32>(shape![4, 8, 3], Device::CPU);
+        assert!(input.conv1d(&weight, None, 1, 1).is_ok());
+        assert!(input.conv1d(&weight, None, 1, 0).is_err());
+
+        let batched = Tensor::randn::<f32>(shape![2, 8, 12], Device::CPU);
+        assert!(batched.conv1d(&weight, None, 1, 1).is_err());
+        let wrong_cin = Tensor::randn::<f32>(shape![4, 6, 3], Device::CPU);
+        assert!(input.conv1d(&wrong_cin, None, 1, 1).is_err());
+        let wrong_ks = Tensor::randn::<f32>(shape![4, 8, 5], Device::CPU);
+        assert!(input.conv1d(&wrong_ks, None, 1, 1).is_err());
+        let wrong_bias = Tensor::randn::<f32>(shape![3], Device::CPU);
+        assert!(input.conv1d(&weight, Some(&wrong_bias), 1, 1).is_err());
+    }
 }
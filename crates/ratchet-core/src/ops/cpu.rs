@@ -0,0 +1,137 @@
+//! Host implementations of ops.
+//!
+//! Used to resolve graphs on [Device::CPU], and as the fallback for ops without a GPU kernel.
+//! They favour being obviously correct over being fast, and only support F32 data
+//! (plus I32 indices).
+use crate::{rvec, DType, Device, RVec, Shape, Tensor};
+
+/// Row-major coordinates of the `index`th element of `shape`.
+pub(crate) fn unravel(mut index: usize, shape: &[usize]) -> RVec<usize> {
+    let mut coords = rvec![0; shape.len()];
+    for (coord, &dim) in coords.iter_mut().zip(shape).rev() {
+        *coord = index % dim;
+        index /= dim;
+    }
+    coords
+}
+
+/// Row-major offset of `coords` in a contiguous tensor of `shape`.
+pub(crate) fn ravel(coords: &[usize], shape: &[usize]) -> usize {
+    coords
+        .iter()
+        .zip(shape)
+        .fold(0, |offset, (&coord, &dim)| offset * dim + coord)
+}
+
+/// Copies the elements of `t` out in logical order, following its strides.
+/// Expanded (zero stride) dimensions are repeated.
+pub(crate) fn contiguous_bytes(t: &Tensor) -> anyhow::Result<Vec<u8>> {
+    let guard = t.storage();
+    let buffer = guard
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("{:?} has no storage", t.id()))?
+        .try_cpu()?;
+    let bytes = buffer.inner().as_bytes();
+    let elem = t.dt().size_of();
+    if t.is_contiguous() {
+        return Ok(bytes[..t.num_bytes()].to_vec());
+    }
+
+    let shape = t.shape().to_vec();
+    let strides = t.strides().to_vec();
+    let mut out = Vec::with_capacity(t.num_bytes());
+    for index in 0..t.shape().numel() {
+        let offset = unravel(index, &shape)
+            .iter()
+            .zip(&strides)
+            .map(|(&coord, &stride)| coord as isize * stride)
+            .sum::<isize>() as usize
+            * elem;
+        out.extend_from_slice(&bytes[offset..offset + elem]);
+    }
+    Ok(out)
+}
+
+pub(crate) fn read_f32(t: &Tensor) -> anyhow::Result<Vec<f32>> {
+    anyhow::ensure!(t.dt() == DType::F32, "Expected F32, got {}", t.dt());
+    Ok(bytemuck::pod_collect_to_vec(&contiguous_bytes(t)?))
+}
+
+pub(crate) fn read_i32(t: &Tensor) -> anyhow::Result<Vec<i32>> {
+    anyhow::ensure!(t.dt() == DType::I32, "Expected I32, got {}", t.dt());
+    Ok(bytemuck::pod_collect_to_vec(&contiguous_bytes(t)?))
+}
+
+/// Builds `dst` by reading each of its elements from `src`.
+/// `src_coords` maps the coordinates of a `dst` element to those of the `src` element it copies.
+pub(crate) fn gather(
+    src: &Tensor,
+    dst: &Tensor,
+    src_coords: impl Fn(&[usize]) -> RVec<usize>,
+) -> anyhow::Result<Tensor> {
+    let bytes = contiguous_bytes(src)?;
+    let elem = src.dt().size_of();
+    let (src_shape, dst_shape) = (src.shape().to_vec(), dst.shape().to_vec());
+    let mut out = Vec::with_capacity(dst.num_bytes());
+    for index in 0..dst.shape().numel() {
+        let offset = ravel(&src_coords(&unravel(index, &dst_shape)), &src_shape) * elem;
+        out.extend_from_slice(&bytes[offset..offset + elem]);
+    }
+    Tensor::from_bytes(&out, src.dt(), dst.shape().clone(), Device::CPU)
+}
+
+/// Batched `[.., M, K] x [.., K, N]`, a batch of 1 is broadcast against the other operand.
+pub(crate) fn matmul(lhs: &Tensor, rhs: &Tensor, dst_shape: &Shape) -> anyhow::Result<Vec<f32>> {
+    let (a, b) = (read_f32(lhs)?, read_f32(rhs)?);
+    let (lr, rr) = (lhs.rank(), rhs.rank());
+    let (m, k, n) = (
+        lhs.shape()[lr - 2],
+        lhs.shape()[lr - 1],
+        rhs.shape()[rr - 1],
+    );
+    anyhow::ensure!(rhs.shape()[rr - 2] == k, "Matmul inner dimensions differ");
+
+    let batches = dst_shape.numel() / (m * n);
+    let (a_batches, b_batches) = (a.len() / (m * k), b.len() / (k * n));
+    let mut c = vec![0f32; dst_shape.numel()];
+    for batch in 0..batches {
+        let a = &a[(batch % a_batches) * m * k..][..m * k];
+        let b = &b[(batch % b_batches) * k * n..][..k * n];
+        let c = &mut c[batch * m * n..][..m * n];
+        for i in 0..m {
+            for p in 0..k {
+                let a_ip = a[i * k + p];
+                for j in 0..n {
+                    c[i * n + j] += a_ip * b[p * n + j];
+                }
+            }
+        }
+    }
+    Ok(c)
+}
+
+/// Softmax along `dim`, with the maximum subtracted for stability.
+/// A row that is entirely `-inf` (fully masked) is all zeros, as on the GPU.
+pub(crate) fn softmax(input: &[f32], shape: &Shape, dim: usize) -> Vec<f32> {
+    let n = shape[dim];
+    let inner = shape.to_vec()[dim + 1..].iter().product::<usize>();
+    let outer = shape.numel() / (n * inner);
+    let mut out = input.to_vec();
+    for o in 0..outer {
+        for i in 0..inner {
+            let idx = |j: usize| (o * n + j) * inner + i;
+            let max = (0..n)
+                .map(|j| input[idx(j)])
+                .fold(f32::NEG_INFINITY, f32::max);
+            if max == f32::NEG_INFINITY {
+                (0..n).for_each(|j| out[idx(j)] = 0.);
+                continue;
+            }
+            let sum = (0..n).map(|j| (input[idx(j)] - max).exp()).sum::<f32>();
+            for j in 0..n {
+                out[idx(j)] = (input[idx(j)] - max).exp() / sum;
+            }
+        }
+    }
+    out
+}
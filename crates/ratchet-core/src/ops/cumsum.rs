@@ -0,0 +1,155 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, StorageView, Tensor,
+};
+
+/// # Cumsum
+///
+/// Inclusive cumulative sum along `dim`.
+/// Every line along `dim` is scanned by a single workgroup, in chunks of 256 elements.
+#[derive(new, Debug, Clone)]
+pub struct Cumsum {
+    input: Tensor,
+    dim: usize,
+}
+
+impl Cumsum {
+    pub fn name(&self) -> &'static str {
+        "cumsum"
+    }
+
+    /// Number of independent lines to scan.
+    fn num_lines(&self) -> usize {
+        let shape = self.input.shape();
+        shape.numel() / shape[self.dim]
+    }
+}
+
+#[derive(Debug, derive_new::new, ShaderType)]
+pub struct CumsumMeta {
+    N: u32,
+    inner: u32,
+    num_lines: u32,
+}
+
+impl OpMetadata for CumsumMeta {}
+
+impl Operation for Cumsum {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 1)?;
+        Enforcer::assert_dtype(srcs[0], DType::F32)?;
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        let rank = srcs[0].rank();
+        if self.dim >= rank {
+            return Err(InvariantError::DimOutOfRange {
+                dim: self.dim,
+                rank,
+            }
+            .into());
+        }
+        Ok(srcs[0].storage_view().clone())
+    }
+}
+
+impl MetaOperation for Cumsum {
+    type Meta = CumsumMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        rvec![&self.input]
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        "cumsum"
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        KernelElement::Scalar
+    }
+
+    fn calculate_dispatch(&self, _dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let num_lines = self.num_lines();
+        let (x_groups, y_groups) = if num_lines > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(num_lines, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (num_lines, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::unary())
+    }
+
+    fn metadata(
+        &self,
+        _dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let shape = self.input.shape();
+        let N = shape[self.dim];
+        let inner = shape.slice(self.dim + 1..shape.rank()).numel();
+        Ok(CumsumMeta::new(N as _, inner as _, self.num_lines() as _))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::{shape, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    fn ground_truth(a: &Tensor, dim: usize) -> anyhow::Result<Tensor> {
+        let mut ground = a.to_ndarray_view::<f32>().to_owned();
+        ground.accumulate_axis_inplace(ndarray::Axis(dim), |&prev, cur| *cur += prev);
+        Ok(Tensor::from(ground))
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct CumsumProblem {
+        #[strategy(1..=4usize)]
+        B: usize,
+        #[strategy(1..=64usize)]
+        M: usize,
+        #[strategy(1..=700usize)]
+        N: usize,
+        #[strategy(0..=2usize)]
+        dim: usize,
+    }
+
+    fn run_cumsum_trial(prob: CumsumProblem) -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let CumsumProblem { B, M, N, dim } = prob;
+        let a = Tensor::randn::<f32>(shape![B, M, N], Device::CPU);
+        let ground = ground_truth(&a, dim)?;
+
+        let ours = a.to(&device)?.cumsum(dim)?.resolve()?.to(&Device::CPU)?;
+        ground.all_close(&ours, 1e-3, 1e-3)?;
+        Ok(())
+    }
+
+    #[proptest(cases = 16)]
+    fn test_cumsum(prob: CumsumProblem) {
+        run_cumsum_trial(prob).unwrap();
+    }
+
+    #[test]
+    fn test_cumsum_dim_out_of_range() {
+        let a = Tensor::randn::<f32>(shape![2, 3], Device::CPU);
+        assert!(a.cumsum(2).is_err());
+    }
+}
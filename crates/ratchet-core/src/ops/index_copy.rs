@@ -0,0 +1,164 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, StorageView, Tensor,
+};
+
+/// # IndexCopy
+///
+/// Writes slice `i` of `src` along `dim` into slice `indices[i]` of `dst`, in place.
+/// The inverse of [IndexSelect](crate::IndexSelect), e.g writing the K/V of the current
+/// position into a preallocated cache.
+///
+/// Indices outside of `dst` are skipped. If an index is repeated, which write lands is unspecified.
+#[derive(new, Debug, Clone)]
+pub struct IndexCopy {
+    dst: Tensor,
+    indices: Tensor,
+    src: Tensor,
+    dim: usize,
+}
+
+impl IndexCopy {
+    pub fn name(&self) -> &'static str {
+        "index_copy"
+    }
+}
+
+#[derive(Debug, derive_new::new, ShaderType)]
+pub struct IndexCopyMeta {
+    src_numel: u32,
+    right_numel: u32,
+    ids_numel: u32,
+    dst_dim_numel: u32,
+}
+
+impl OpMetadata for IndexCopyMeta {}
+
+impl Operation for IndexCopy {
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        let (dst, indices, src) = (srcs[0], srcs[1], srcs[2]);
+        let rank = dst.rank();
+        if self.dim >= rank {
+            return Err(InvariantError::DimOutOfRange {
+                dim: self.dim,
+                rank,
+            }
+            .into());
+        }
+        Enforcer::check_shape_pair(src, indices, self.dim, 0)?;
+        for d in (0..rank).filter(|&d| d != self.dim) {
+            Enforcer::check_shape_pair(dst, src, d, d)?;
+        }
+        Ok(dst.storage_view().clone())
+    }
+
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 3)?;
+        let (dst, indices, src) = (srcs[0], srcs[1], srcs[2]);
+        Enforcer::assert_dtype(dst, DType::F32)?;
+        Enforcer::assert_dtype(src, DType::F32)?;
+        Enforcer::assert_dtype(indices, DType::I32)?;
+        Enforcer::assert_rank(indices, 1)?;
+        Enforcer::assert_equal_ranks(&[dst, src])?;
+        Ok(())
+    }
+}
+
+impl MetaOperation for IndexCopy {
+    type Meta = IndexCopyMeta;
+
+    fn supports_inplace(&self) -> bool {
+        true
+    }
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        rvec![&self.dst, &self.indices, &self.src]
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        self.name()
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        KernelElement::Scalar
+    }
+
+    fn calculate_dispatch(&self, _: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let numel = self.src.shape().numel();
+        let x_groups = WorkgroupCount::div_ceil(numel as _, 64);
+        let (x_groups, y_groups) = if x_groups > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(x_groups, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (x_groups, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::ternary_inplace())
+    }
+
+    fn metadata(&self, _: &Tensor, _: &KernelElement) -> Result<Self::Meta, OperationError> {
+        let src_shape = self.src.shape();
+        let right_numel = src_shape[(self.dim + 1)..].iter().product::<usize>();
+        Ok(IndexCopyMeta {
+            src_numel: src_shape.numel() as _,
+            right_numel: right_numel as _,
+            ids_numel: self.indices.shape().numel() as _,
+            dst_dim_numel: self.dst.shape()[self.dim] as _,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{shape, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    #[test]
+    fn test_index_copy() -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let cache = Tensor::zeros::<f32>(&shape![2, 4, 3], &device);
+        let src = Tensor::from_data(
+            (1..=12).map(|x| x as f32).collect::<Vec<_>>(),
+            shape![2, 2, 3],
+            device.clone(),
+        );
+        let indices = Tensor::from_data(vec![3, 1], shape![2], device.clone());
+        let result = cache
+            .index_copy(1, &indices, &src)?
+            .resolve()?
+            .to(&Device::CPU)?;
+
+        #[rustfmt::skip]
+        let expected = Tensor::from_data(
+            vec![
+                0., 0., 0.,  4., 5., 6.,  0., 0., 0.,  1., 2., 3.,
+                0., 0., 0., 10., 11., 12., 0., 0., 0., 7., 8., 9.,
+            ],
+            shape![2, 4, 3],
+            Device::CPU,
+        );
+        expected.all_close(&result, 0.0, 0.0)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_copy_shape_mismatch() {
+        let cache = Tensor::zeros::<f32>(&shape![2, 4, 3], &Device::CPU);
+        let src = Tensor::zeros::<f32>(&shape![2, 2, 5], &Device::CPU);
+        let indices = Tensor::from_data(vec![0, 1], shape![2], Device::CPU);
+        assert!(cache.index_copy(1, &indices, &src).is_err());
+    }
+}
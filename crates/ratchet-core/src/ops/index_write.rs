@@ -1,10 +1,11 @@
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
-    rvec, wgc, KernelElement, MetaOperation, OpMetadata, Operation, OperationError, RVec, Shape,
-    StorageView, Strides, Tensor,
+    rvec, wgc, Device, KernelElement, MetaOperation, OpMetadata, Operation, OperationError, RVec,
+    Shape, StorageView, Strides, Tensor,
 };
 
 #[derive(new, Debug, Clone)]
@@ -98,6 +99,31 @@ impl MetaOperation for IndexWrite {
             write_start: start.into(),
         })
     }
+
+    /// Returns a copy of the destination with `src` written into it,
+    /// CPU resolution then swaps it into the destination, see [Tensor::resolve].
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let (target, src) = (&srcs[0], &srcs[1]);
+        let mut result = cpu::contiguous_bytes(target)?;
+        let src_bytes = cpu::contiguous_bytes(src)?;
+        let elem = target.dt().size_of();
+        let (target_shape, src_shape) = (target.shape().to_vec(), src.shape().to_vec());
+        for index in 0..src.shape().numel() {
+            let coords = cpu::unravel(index, &src_shape)
+                .iter()
+                .zip(self.write_start.iter())
+                .map(|(coord, start)| coord + start)
+                .collect::<RVec<_>>();
+            let offset = cpu::ravel(&coords, &target_shape) * elem;
+            result[offset..offset + elem].copy_from_slice(&src_bytes[index * elem..][..elem]);
+        }
+        Ok(Tensor::from_bytes(
+            &result,
+            dst.dt(),
+            dst.shape().clone(),
+            Device::CPU,
+        )?)
+    }
 }
 
 #[cfg(test)]
@@ -3,9 +3,10 @@ use std::cmp::Ordering;
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
-    rvec, wgc, DType, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    rvec, wgc, DType, Device, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
     Operation, OperationError, RVec, Shape, StorageView, Strides, Tensor,
 };
 
@@ -289,6 +290,9 @@ impl Operation for Matmul {
 
     fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
         Enforcer::check_input_arity(srcs, 2)?;
+        for src in srcs {
+            Enforcer::assert_contiguous(src)?;
+        }
         let allowed_pairs = [(DType::F32, DType::F32), (DType::F32, DType::WQ8)];
         if !allowed_pairs.contains(&(srcs[0].dt(), srcs[1].dt())) {
             //TODO: invariantError
@@ -361,13 +365,18 @@ impl MetaOperation for Matmul {
 
         Ok(MatmulMeta::new(M, N, K, a_offset, b_offset, c_offset))
     }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let result = cpu::matmul(&srcs[0], &srcs[1], dst.shape())?;
+        Ok(Tensor::from_data(result, dst.shape().clone(), Device::CPU))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use test_strategy::{proptest, Arbitrary};
 
-    use crate::test_util::run_py_prg;
+    use crate::test_util::{cpu_matmul, run_py_prg};
 
     use crate::{shape, Device, DeviceRequest, Quantization, Quantizer};
 
@@ -444,4 +453,32 @@ def matmul(a, b):
 
         Ok(())
     }
+
+    #[test]
+    fn cpu_reference_small() -> anyhow::Result<()> {
+        let a = Tensor::from_data([1f32, 2., 3., 4., 5., 6.], shape![2, 3], Device::CPU);
+        let b = Tensor::from_data([7f32, 8., 9., 10., 11., 12.], shape![3, 2], Device::CPU);
+        let c = cpu_matmul(&a, &b)?;
+        assert_eq!(c.shape(), &shape![2, 2]);
+        assert_eq!(c.to_vec::<f32>()?, vec![58., 64., 139., 154.]);
+        Ok(())
+    }
+
+    #[test]
+    fn gpu_matches_cpu_reference() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let problems = [
+            (shape![1, 7, 13], shape![1, 13, 5]),
+            (shape![3, 64, 33], shape![3, 33, 17]),
+            (shape![2, 16, 8], shape![1, 8, 24]),
+        ];
+        for (lhs, rhs) in problems {
+            let a = Tensor::randn::<f32>(lhs, Device::CPU);
+            let b = Tensor::randn::<f32>(rhs, Device::CPU);
+            let expected = cpu_matmul(&a, &b)?;
+            let ours = a.to(&device)?.matmul(&b.to(&device)?)?.resolve()?;
+            expected.all_close(&ours.to(&Device::CPU)?, 1e-4, 1e-4)?;
+        }
+        Ok(())
+    }
 }
@@ -1,24 +1,43 @@
 mod binary;
+mod cast;
+mod clamp;
+mod cmp;
 mod conv;
+pub(crate) mod cpu;
+mod cumsum;
+mod index_copy;
 mod index_write;
 mod matmul;
 mod norm;
+mod pad;
 mod reindex;
+mod sdpa;
 mod select;
 mod softmax;
+mod topk;
 mod unary;
+mod where_cond;
 
 pub use binary::*;
+pub use cast::*;
+pub use clamp::*;
+pub use cmp::*;
 pub use conv::*;
+pub use cumsum::*;
+pub use index_copy::*;
 pub use index_write::*;
 pub use matmul::*;
 pub use norm::*;
+pub use pad::*;
 pub use reindex::*;
+pub use sdpa::*;
 pub use select::*;
 pub use softmax::*;
+pub use topk::*;
 pub use unary::*;
+pub use where_cond::*;
 
-use crate::{Enforcer, Operation, Shape, StorageView, Strides, Tensor};
+use crate::{rvec, Enforcer, InvariantError, Operation, Shape, StorageView, Strides, Tensor};
 
 /// # KernelElement
 ///
@@ -81,3 +100,55 @@ impl Operation for View {
         Ok(StorageView::new(self.shape.clone(), srcs[0].dt(), strides))
     }
 }
+
+/// # Expand
+///
+/// A broadcasted view of `input`, size 1 dims are given a stride of 0.
+#[derive(Debug, derive_new::new, Clone)]
+pub struct Expand {
+    input: Tensor,
+    shape: Shape,
+}
+
+impl Expand {
+    pub fn input(&self) -> &Tensor {
+        &self.input
+    }
+}
+
+impl Operation for Expand {
+    fn check_invariants(srcs: &[&crate::Tensor]) -> Result<(), crate::OperationError> {
+        Enforcer::check_input_arity(srcs, 1)?;
+        Ok(())
+    }
+
+    //Only size 1 dims may be expanded, new dims can be added on the left.
+    fn infer_output(
+        &self,
+        srcs: &[&crate::Tensor],
+    ) -> Result<crate::StorageView, crate::OperationError> {
+        let src = srcs[0];
+        let (src_shape, src_strides) = (src.shape(), src.strides().to_vec());
+        let failed =
+            || InvariantError::BroadcastingFailed(vec![src_shape.clone(), self.shape.clone()]);
+        if self.shape.rank() < src_shape.rank() {
+            return Err(failed().into());
+        }
+
+        let offset = self.shape.rank() - src_shape.rank();
+        let mut strides = rvec![0; self.shape.rank()];
+        for (dim, &size) in src_shape.iter().enumerate() {
+            let target = self.shape[dim + offset];
+            if size == target {
+                strides[dim + offset] = src_strides[dim];
+            } else if size != 1 {
+                return Err(failed().into());
+            }
+        }
+        Ok(StorageView::new(
+            self.shape.clone(),
+            src.dt(),
+            Strides::from(strides),
+        ))
+    }
+}
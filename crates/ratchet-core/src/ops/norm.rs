@@ -2,10 +2,11 @@
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
-    rvec, wgc, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation, OperationError, RVec,
-    StorageView, Tensor,
+    rvec, wgc, Device, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
+    OperationError, RVec, StorageView, Tensor,
 };
 
 #[derive(new, Debug, Clone)]
@@ -26,15 +27,35 @@ impl Operation for LayerNorm {
     }
 }
 
+/// LayerNorm without the mean subtraction or bias, `x / sqrt(mean(x^2) + eps) * scale`.
+#[derive(new, Debug, Clone)]
+pub struct RMSNorm {
+    scale: Tensor,
+    eps: f32,
+}
+
+impl Operation for RMSNorm {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 2)?;
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        Ok(srcs[0].storage_view().clone())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum NormOp {
     LayerNorm(LayerNorm),
+    RMSNorm(RMSNorm),
 }
 
 impl NormOp {
     pub fn kernel_name(&self) -> &'static str {
         match self {
             NormOp::LayerNorm(_) => "layernorm",
+            NormOp::RMSNorm(_) => "rmsnorm",
         }
     }
 }
@@ -75,13 +96,12 @@ impl MetaOperation for Norm {
                 Some(bias) => rvec![&self.input, scale, bias],
                 None => rvec![&self.input, scale],
             },
+            NormOp::RMSNorm(RMSNorm { scale, .. }) => rvec![&self.input, scale],
         }
     }
 
     fn kernel_name(&self) -> &'static str {
-        match self.op {
-            NormOp::LayerNorm(_) => "layernorm",
-        }
+        self.op.kernel_name()
     }
 
     fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
@@ -110,7 +130,10 @@ impl MetaOperation for Norm {
         &self,
         _inplace: bool,
     ) -> Result<BindGroupLayoutDescriptor, OperationError> {
-        Ok(BindGroupLayoutDescriptor::ternary())
+        match self.op {
+            NormOp::LayerNorm(_) => Ok(BindGroupLayoutDescriptor::ternary()),
+            NormOp::RMSNorm(_) => Ok(BindGroupLayoutDescriptor::binary()),
+        }
     }
 
     fn metadata(
@@ -126,9 +149,33 @@ impl MetaOperation for Norm {
         let ND4 = N / 4;
         let eps = match &self.op {
             NormOp::LayerNorm(LayerNorm { eps, .. }) => *eps,
+            NormOp::RMSNorm(RMSNorm { eps, .. }) => *eps,
         };
         Ok(NormMeta::new(M, N, ND2, ND4, eps))
     }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let input = cpu::read_f32(&srcs[0])?;
+        let scale = cpu::read_f32(&srcs[1])?;
+        let bias = srcs.get(2).map(cpu::read_f32).transpose()?;
+        let n = srcs[0].shape()[srcs[0].rank() - 1];
+
+        let mut result = Vec::with_capacity(input.len());
+        for row in input.chunks_exact(n) {
+            let (mean, eps) = match &self.op {
+                NormOp::LayerNorm(ln) => (row.iter().sum::<f32>() / n as f32, ln.eps),
+                NormOp::RMSNorm(rms) => (0., rms.eps),
+            };
+            let var = row.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / n as f32;
+            let denom = (var + eps).sqrt();
+            result.extend(
+                row.iter().enumerate().map(|(i, x)| {
+                    (x - mean) / denom * scale[i] + bias.as_ref().map_or(0., |b| b[i])
+                }),
+            );
+        }
+        Ok(Tensor::from_data(result, dst.shape().clone(), Device::CPU))
+    }
 }
 
 #[cfg(test)]
@@ -187,4 +234,36 @@ def layernorm(input, scale, bias):
         println!("B = {}, M = {}, N = {}", B, M, N);
         run_norm_trial(&device, prob).unwrap();
     }
+
+    fn rms_ground_truth(input: &Tensor, scale: &Tensor) -> anyhow::Result<Tensor> {
+        let prg = r#"
+import torch
+
+def rmsnorm(input, scale):
+    (input, scale) = (torch.from_numpy(input), torch.from_numpy(scale))
+    rms = torch.rsqrt(input.pow(2).mean(-1, keepdim=True) + 1e-5)
+    return (input * rms * scale).numpy()
+"#;
+        run_py_prg(prg.to_string(), &[input, scale], &[])
+    }
+
+    #[proptest(cases = 16)]
+    fn test_rms_norm(prob: NormProblem) {
+        let device = Device::request_device(DeviceRequest::GPU).unwrap();
+        let NormProblem { B, M, N } = prob;
+        let input = Tensor::randn::<f32>(shape![B, M, N], Device::CPU);
+        let scale = Tensor::randn::<f32>(shape![N], Device::CPU);
+        let ground = rms_ground_truth(&input, &scale).unwrap();
+
+        let input_gpu = input.to(&device).unwrap();
+        let scale_gpu = scale.to(&device).unwrap();
+        let result = input_gpu
+            .rms_norm(&scale_gpu, 1e-5)
+            .unwrap()
+            .resolve()
+            .unwrap();
+
+        let ours = result.to(&Device::CPU).unwrap();
+        ground.all_close(&ours, 1e-4, 1e-4).unwrap();
+    }
 }
@@ -0,0 +1,199 @@
+use derive_new::new;
+use encase::ShaderType;
+use glam::UVec4;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, Shape, StorageView, Strides, Tensor,
+};
+
+/// # Pad
+///
+/// Constant padding, `padding[i]` elements are added before and after dimension `i`.
+/// Supports tensors of up to rank 4, like the reindex ops.
+#[derive(new, Debug, Clone)]
+pub struct Pad {
+    input: Tensor,
+    padding: RVec<(usize, usize)>,
+    value: f32,
+}
+
+impl Pad {
+    pub fn name(&self) -> &'static str {
+        "pad"
+    }
+
+    pub fn padding(&self) -> &[(usize, usize)] {
+        &self.padding
+    }
+}
+
+#[derive(Debug, ShaderType)]
+pub struct PadMeta {
+    src_shape: UVec4,
+    src_stride: UVec4,
+    dst_stride: UVec4,
+    pad_before: UVec4,
+    dst_numel: u32,
+    value: f32,
+}
+
+impl OpMetadata for PadMeta {}
+
+impl Operation for Pad {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 1)?;
+        Enforcer::assert_rank_range(srcs[0], 1..=4)?;
+        Enforcer::assert_dtype(srcs[0], DType::F32)?;
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        let src_shape = srcs[0].shape();
+        if self.padding.len() != src_shape.rank() {
+            return Err(InvariantError::RankMismatch {
+                accepted: src_shape.rank()..=src_shape.rank(),
+                actual: self.padding.len(),
+            })?;
+        }
+        let output_shape: Shape = src_shape
+            .iter()
+            .zip(self.padding.iter())
+            .map(|(dim, (before, after))| before + dim + after)
+            .collect::<RVec<_>>()
+            .into();
+        let strides = Strides::from(&output_shape);
+        Ok(StorageView::new(output_shape, srcs[0].dt(), strides))
+    }
+}
+
+impl MetaOperation for Pad {
+    type Meta = PadMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        rvec![&self.input]
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        "pad"
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        KernelElement::Scalar
+    }
+
+    fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let numel = dst.shape().numel();
+        let x_groups = WorkgroupCount::div_ceil(numel as _, 64);
+        let (x_groups, y_groups) = if x_groups > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(x_groups, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (x_groups, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::unary())
+    }
+
+    fn metadata(
+        &self,
+        dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let padder = |mut shape: Shape| {
+            shape.left_pad_to(1, 4);
+            let strides = Strides::from(&shape);
+            (shape, strides)
+        };
+        let (src_shape, src_strides) = padder(self.input.shape().clone());
+        let (dst_shape, dst_strides) = padder(dst.shape().clone());
+
+        let mut pad_before = [0u32; 4];
+        let offset = 4 - self.padding.len();
+        for (i, &(before, _)) in self.padding.iter().enumerate() {
+            pad_before[i + offset] = before as u32;
+        }
+
+        Ok(PadMeta {
+            src_shape: UVec4::try_from(&src_shape).unwrap(),
+            src_stride: UVec4::try_from(&src_strides).unwrap(),
+            dst_stride: UVec4::try_from(&dst_strides).unwrap(),
+            pad_before: UVec4::from_array(pad_before),
+            dst_numel: dst_shape.numel() as u32,
+            value: self.value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::{shape, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    fn ground_truth(a: &Tensor, padding: &[(usize, usize)], value: f32) -> Tensor {
+        let input = a.to_ndarray_view::<f32>();
+        let padded_shape = input
+            .shape()
+            .iter()
+            .zip(padding)
+            .map(|(dim, (before, after))| before + dim + after)
+            .collect::<Vec<_>>();
+        let mut ground = ndarray::ArrayD::from_elem(padded_shape, value);
+        ground
+            .slice_each_axis_mut(|ax| {
+                let before = padding[ax.axis.index()].0;
+                ndarray::Slice::from(before..before + input.shape()[ax.axis.index()])
+            })
+            .assign(&input);
+        Tensor::from(ground)
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct PadProblem {
+        #[strategy(1..=4usize)]
+        B: usize,
+        #[strategy(1..=64usize)]
+        M: usize,
+        #[strategy(1..=64usize)]
+        N: usize,
+        #[strategy(proptest::collection::vec((0..=4usize, 0..=4usize), 3))]
+        padding: Vec<(usize, usize)>,
+    }
+
+    fn run_pad_trial(prob: PadProblem) -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let PadProblem { B, M, N, padding } = prob;
+        let a = Tensor::randn::<f32>(shape![B, M, N], Device::CPU);
+        let ground = ground_truth(&a, &padding, -1.5);
+
+        let ours = a.to(&device)?.pad(&padding, -1.5)?.resolve()?;
+        let ours = ours.to(&Device::CPU)?;
+        ground.all_close(&ours, 1e-6, 1e-6)?;
+        Ok(())
+    }
+
+    #[proptest(cases = 16)]
+    fn test_pad(prob: PadProblem) {
+        run_pad_trial(prob).unwrap();
+    }
+
+    #[test]
+    fn test_pad_wrong_rank() {
+        let a = Tensor::randn::<f32>(shape![2, 3], Device::CPU);
+        assert!(a.pad(&[(1, 1)], 0.).is_err());
+        let padded = a.pad(&[(0, 0), (1, 2)], 0.).unwrap();
+        assert_eq!(padded.shape(), &shape![2, 6]);
+    }
+}
@@ -1,6 +1,8 @@
 use derive_new::new;
 
-use crate::{Enforcer, Operation, OperationError, Shape, StorageView, Strides, Tensor};
+use crate::{
+    Enforcer, InvariantError, Operation, OperationError, Shape, StorageView, Strides, Tensor,
+};
 
 #[derive(new, Debug, Clone)]
 pub struct Broadcast {
@@ -24,7 +26,12 @@ impl Operation for Broadcast {
             return Ok(src.storage_view().clone());
         }
 
-        //TODO: actually validate the shapes, currently faith based system
+        let broadcasted = src_shape.broadcast_with(&self.to);
+        if broadcasted.as_ref() != Some(&self.to) {
+            let failed = vec![src_shape.clone(), self.to.clone()];
+            return Err(InvariantError::BroadcastingFailed(failed).into());
+        }
+
         let strides = Strides::from(&self.to);
         Ok(StorageView::new(self.to.clone(), src.dt(), strides))
     }
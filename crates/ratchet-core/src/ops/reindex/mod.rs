@@ -1,14 +1,17 @@
 mod broadcast;
 mod permute;
+mod repeat;
 mod slice;
 
 pub use broadcast::Broadcast;
 pub use permute::Permute;
+pub use repeat::Repeat;
 pub use slice::Slice;
 
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
     rvec, wgc, KernelElement, MetaOperation, OpMetadata, OperationError, RVec, Shape, Strides,
@@ -21,14 +24,17 @@ pub enum ReindexOp {
     Permute(Permute),
     Slice(Slice),
     Broadcast(Broadcast),
+    Repeat(Repeat),
 }
 
 impl ReindexOp {
     pub fn kernel_name(&self) -> &'static str {
         match self {
+            ReindexOp::Permute(p) if p.is_transpose_2d() => "transpose",
             ReindexOp::Permute(_) => "permute",
             ReindexOp::Slice(_) => "slice",
             ReindexOp::Broadcast(_) => "broadcast",
+            ReindexOp::Repeat(_) => "repeat",
         }
     }
 }
@@ -77,6 +83,15 @@ impl MetaOperation for Reindex {
     }
 
     fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        if let ReindexOp::Permute(p) = &self.op {
+            if p.is_transpose_2d() {
+                //One workgroup per 16x16 tile of the input
+                let (rows, cols) = (self.input.shape()[0], self.input.shape()[1]);
+                let x_groups = WorkgroupCount::div_ceil(cols as _, 16);
+                let y_groups = WorkgroupCount::div_ceil(rows as _, 16);
+                return Ok(wgc![x_groups as _, y_groups as _, 1]);
+            }
+        }
         let numel = dst.shape().numel();
         let x_groups = WorkgroupCount::div_ceil(numel as _, 64);
         let (x_groups, y_groups) = if x_groups > WorkgroupCount::MAX_WGS_PER_DIM {
@@ -160,4 +175,45 @@ impl MetaOperation for Reindex {
         };
         Ok(meta)
     }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let input = &srcs[0];
+        let src_shape = input.shape().to_vec();
+        let rank = src_shape.len();
+        let result = match &self.op {
+            //Output dim `i` is input dim `dims[i]`
+            ReindexOp::Permute(p) => cpu::gather(input, dst, |coords| {
+                let mut src = rvec![0; rank];
+                for (i, &d) in p.dims.iter().enumerate() {
+                    src[d] = coords[i];
+                }
+                src
+            }),
+            ReindexOp::Slice(s) => cpu::gather(input, dst, |coords| {
+                let starts = s.indices().iter().map(|r| r.start);
+                coords
+                    .iter()
+                    .zip(starts.chain(std::iter::repeat(0)))
+                    .map(|(coord, start)| coord + start)
+                    .collect()
+            }),
+            //New dims are added on the left
+            ReindexOp::Broadcast(_) => cpu::gather(input, dst, |coords| {
+                let offset = coords.len() - rank;
+                (0..rank)
+                    .map(|i| {
+                        if src_shape[i] == 1 {
+                            0
+                        } else {
+                            coords[i + offset]
+                        }
+                    })
+                    .collect()
+            }),
+            ReindexOp::Repeat(_) => cpu::gather(input, dst, |coords| {
+                (0..rank).map(|i| coords[i] % src_shape[i]).collect()
+            }),
+        };
+        Ok(result?)
+    }
 }
@@ -1,8 +1,6 @@
-use std::collections::HashSet;
-
 use derive_new::new;
 
-use crate::{Enforcer, InvariantError, Operation, OperationError, StorageView, Strides, Tensor};
+use crate::{Enforcer, Operation, OperationError, StorageView, Strides, Tensor};
 
 #[derive(new, Debug, Clone)]
 pub struct Permute {
@@ -20,21 +18,17 @@ impl Permute {
         (0..pad_len).for_each(|x| perm.insert(0, x));
         perm
     }
+
+    /// A 2D transpose has a dedicated, coalesced kernel.
+    pub fn is_transpose_2d(&self) -> bool {
+        self.dims == [1, 0]
+    }
 }
 
 impl Operation for Permute {
     fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
         let input_shape = srcs[0].shape();
-        if input_shape.rank() != self.dims.len() {
-            return Err(InvariantError::RankMismatch {
-                accepted: input_shape.rank()..=input_shape.rank(),
-                actual: self.dims.len(),
-            })?;
-        }
-        let dup_set: HashSet<usize> = HashSet::from_iter(self.dims.iter().cloned());
-        if dup_set.len() != self.dims.len() {
-            return Err(InvariantError::DuplicateDims)?;
-        }
+        Enforcer::check_permutation(&self.dims, input_shape.rank())?;
 
         let mut output_shape = input_shape.clone();
         for i in 0..input_shape.rank() {
@@ -117,4 +111,31 @@ def permute(a):
     fn test_permute(prob: PermuteProblem) {
         run_reindex_trial(prob).unwrap();
     }
+
+    #[derive(Arbitrary, Debug)]
+    struct TransposeProblem {
+        #[strategy(1..=512usize)]
+        M: usize,
+        #[strategy(1..=512usize)]
+        N: usize,
+    }
+
+    #[proptest(cases = 16)]
+    fn test_transpose_2d(prob: TransposeProblem) {
+        let TransposeProblem { M, N } = prob;
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let a = Tensor::randn::<f32>(shape![M, N], Device::CPU);
+        let ground = ground_truth(&a, "[1, 0]").unwrap();
+        let ours = a.to(&device).unwrap().permute(&[1, 0]).unwrap();
+        let ours = ours.resolve().unwrap().to(&Device::CPU).unwrap();
+        ground.all_close(&ours, 1e-5, 1e-5).unwrap();
+    }
+
+    #[test]
+    fn test_permute_invalid() {
+        let a = Tensor::randn::<f32>(shape![2, 3, 4], Device::CPU);
+        assert!(a.permute(&[0, 1]).is_err());
+        assert!(a.permute(&[0, 1, 1]).is_err());
+        assert!(a.permute(&[0, 1, 3]).is_err());
+    }
 }
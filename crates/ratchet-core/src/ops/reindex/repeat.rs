@@ -0,0 +1,107 @@
+use derive_new::new;
+
+use crate::{
+    Enforcer, InvariantError, Operation, OperationError, RVec, Shape, StorageView, Strides, Tensor,
+};
+
+/// # Repeat
+///
+/// Tiles the input `repeats[i]` times along each dimension `i`, materializing the result.
+#[derive(new, Debug, Clone)]
+pub struct Repeat {
+    repeats: RVec<usize>,
+}
+
+impl Repeat {
+    pub fn repeats(&self) -> &[usize] {
+        &self.repeats
+    }
+}
+
+impl Operation for Repeat {
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        let src_shape = srcs[0].shape();
+        if self.repeats.len() != src_shape.rank() {
+            return Err(InvariantError::RankMismatch {
+                accepted: src_shape.rank()..=src_shape.rank(),
+                actual: self.repeats.len(),
+            })?;
+        }
+        let output_shape: Shape = src_shape
+            .iter()
+            .zip(self.repeats.iter())
+            .map(|(dim, repeat)| dim * repeat)
+            .collect::<RVec<_>>()
+            .into();
+        let strides = Strides::from(&output_shape);
+        Ok(StorageView::new(output_shape, srcs[0].dt(), strides))
+    }
+
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 1)?;
+        Enforcer::assert_rank_range(srcs[0], 1..=4)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::{shape, test_util::run_py_prg, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct RepeatProblem {
+        #[strategy(1..=4usize)]
+        M: usize,
+        #[strategy(1..=64usize)]
+        N: usize,
+        #[strategy(1..=4usize)]
+        B: usize,
+        #[strategy(1..=4usize)]
+        H: usize,
+        #[strategy(1..=3usize)]
+        R: usize,
+    }
+
+    fn ground_truth(a: &Tensor, args: &str) -> anyhow::Result<Tensor> {
+        let prg = format!(
+            r#"
+import torch
+import numpy as np
+def repeat(a):
+    return np.ascontiguousarray(torch.from_numpy(a).repeat({}).numpy())
+"#,
+            args
+        );
+        run_py_prg(prg.to_string(), &[a], &[])
+    }
+
+    fn run_repeat_trial(prob: RepeatProblem) -> anyhow::Result<()> {
+        let RepeatProblem { M, N, B, H, R } = prob;
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let a = Tensor::randn::<f32>(shape![1, M, N], Device::CPU);
+        let repeats = [B, H, R];
+
+        let ground = ground_truth(&a, &format!("{:?}", repeats))?;
+        let ours = a.to(&device)?.repeat(&repeats)?.resolve()?;
+        let ours = ours.to(&Device::CPU)?;
+        ground.all_close(&ours, 1e-5, 1e-5)?;
+        Ok(())
+    }
+
+    #[proptest(cases = 16)]
+    fn test_repeat(prob: RepeatProblem) {
+        run_repeat_trial(prob).unwrap();
+    }
+
+    #[test]
+    fn test_repeat_rank_mismatch() {
+        let a = Tensor::randn::<f32>(shape![2, 3], Device::CPU);
+        assert!(a.repeat(&[2]).is_err());
+    }
+}
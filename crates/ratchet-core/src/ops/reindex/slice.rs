@@ -23,13 +23,7 @@ impl Operation for Slice {
     }
 
     fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
-        //TODO: Check if slice is valid
-        let output_shape = self
-            .indices
-            .iter()
-            .map(|range| range.end - range.start)
-            .collect::<RVec<usize>>()
-            .into();
+        let output_shape = srcs[0].shape().slice_shape(&self.indices)?;
         let strides = Strides::from(&output_shape);
         Ok(StorageView::new(output_shape, srcs[0].dt(), strides))
     }
@@ -141,4 +135,29 @@ def slice(a):
     fn test_slice(prob: SliceProblem) {
         run_reindex_trial(prob).unwrap();
     }
+
+    #[test]
+    fn test_split() -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let a = Tensor::randn::<f32>(shape![2, 12, 5], Device::CPU);
+        let sizes = [4, 4, 4];
+
+        let splits = a.to(&device)?.split(1, &sizes)?;
+        assert_eq!(splits.len(), sizes.len());
+        let mut start = 0;
+        for (split, size) in splits.into_iter().zip(sizes) {
+            let ground = a
+                .to_ndarray_view::<f32>()
+                .slice(ndarray::s![.., start..start + size, ..])
+                .to_owned()
+                .into_dyn();
+            let ours = split.resolve()?.to(&Device::CPU)?;
+            Tensor::from(ground).all_close(&ours, 1e-6, 1e-6)?;
+            start += size;
+        }
+
+        assert!(a.split(1, &[4, 4]).is_err());
+        assert!(a.split(3, &[5]).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,263 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
+    OperationError, RVec, StorageView, Strides, Tensor,
+};
+
+/// # Scaled Dot Product Attention
+///
+/// Computes `softmax(Q @ K^T * scale + mask) @ V` in a single pass.
+///
+/// Q is [B, H, M, D], K and V are [B, H, N, D] and the optional additive mask is [M, N].
+/// Each thread owns a query row and streams K & V through workgroup memory a tile at a time,
+/// keeping a running max & sum (online softmax) so the [M, N] score matrix is never materialized.
+#[derive(new, Debug, Clone)]
+pub struct Sdpa {
+    q: Tensor,
+    k: Tensor,
+    v: Tensor,
+    mask: Option<Tensor>,
+    scale: f32,
+}
+
+impl Sdpa {
+    /// Largest head dimension supported by the kernel.
+    pub const MAX_HEAD_DIM: usize = 128;
+    const BLOCK_SIZE: usize = 64;
+
+    pub fn name(&self) -> &'static str {
+        "sdpa"
+    }
+}
+
+#[derive(Debug, derive_new::new, ShaderType)]
+pub struct SdpaMeta {
+    M: u32,
+    N: u32,
+    D: u32,
+    scale: f32,
+}
+
+impl OpMetadata for SdpaMeta {}
+
+impl Operation for Sdpa {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity_range(srcs, 3..=4)?;
+        let (q, k, v) = (srcs[0], srcs[1], srcs[2]);
+        for t in [q, k, v] {
+            Enforcer::assert_rank(t, 4)?;
+            Enforcer::assert_dtype(t, DType::F32)?;
+            Enforcer::assert_contiguous(t)?;
+        }
+        for dim in 0..2 {
+            Enforcer::check_shape_pair(q, k, dim, dim)?;
+            Enforcer::check_shape_pair(q, v, dim, dim)?;
+        }
+        Enforcer::check_shape_pair(q, k, 3, 3)?;
+        Enforcer::check_shape_pair(k, v, 2, 2)?;
+        Enforcer::check_shape_pair(k, v, 3, 3)?;
+
+        let head_dim = q.shape()[3];
+        if head_dim > Self::MAX_HEAD_DIM {
+            return Err(OperationError::CompileError(format!(
+                "Head dim {} exceeds maximum of {}",
+                head_dim,
+                Self::MAX_HEAD_DIM
+            )));
+        }
+
+        if let Some(mask) = srcs.get(3) {
+            Enforcer::assert_rank(mask, 2)?;
+            Enforcer::assert_dtype(mask, DType::F32)?;
+            Enforcer::assert_contiguous(mask)?;
+            Enforcer::check_shape_pair(q, mask, 2, 0)?;
+            Enforcer::check_shape_pair(k, mask, 2, 1)?;
+        }
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        let shape = srcs[0].shape().clone();
+        let strides = Strides::from(&shape);
+        Ok(StorageView::new(shape, DType::F32, strides))
+    }
+}
+
+impl MetaOperation for Sdpa {
+    type Meta = SdpaMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        match &self.mask {
+            Some(mask) => rvec![&self.q, &self.k, &self.v, mask],
+            None => rvec![&self.q, &self.k, &self.v],
+        }
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        match self.mask {
+            Some(_) => "sdpa_masked",
+            None => "sdpa",
+        }
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        KernelElement::Scalar
+    }
+
+    fn calculate_dispatch(&self, _dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let [B, H, M, _]: [usize; 4] = self.q.shape().try_into()?;
+        let x_groups = WorkgroupCount::div_ceil(M, Self::BLOCK_SIZE);
+        Ok(wgc![x_groups as _, (B * H) as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::ternary())
+    }
+
+    fn storage_bind_group_layouts(
+        &self,
+        inplace: bool,
+    ) -> Result<RVec<BindGroupLayoutDescriptor>, OperationError> {
+        match self.mask {
+            //Q, K, V & mask fill the first group, the output spills into the second
+            Some(_) => Ok(BindGroupLayoutDescriptor::quaternary()),
+            None => Ok(rvec![self.storage_bind_group_layout(inplace)?]),
+        }
+    }
+
+    fn metadata(
+        &self,
+        _dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let [_, _, M, D]: [usize; 4] = self.q.shape().try_into()?;
+        let N = self.k.shape()[2];
+        Ok(SdpaMeta::new(M as _, N as _, D as _, self.scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::test_util::run_py_prg;
+    use crate::{shape, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    fn ground_truth(q: &Tensor, k: &Tensor, v: &Tensor, mask: &Tensor) -> anyhow::Result<Tensor> {
+        let prg = r#"
+import torch
+import torch.nn.functional as F
+def sdpa(q, k, v, mask):
+    (q, k, v, mask) = (torch.from_numpy(x) for x in (q, k, v, mask))
+    return F.scaled_dot_product_attention(q, k, v, attn_mask=mask).numpy()
+"#;
+        run_py_prg(prg.to_string(), &[q, k, v, mask], &[])
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct SdpaProblem {
+        #[strategy(1..=2usize)]
+        B: usize,
+        #[strategy(1..=4usize)]
+        H: usize,
+        #[strategy(1..=96usize)]
+        M: usize,
+        #[strategy(1..=96usize)]
+        N: usize,
+        #[strategy(1..=128usize)]
+        D: usize,
+        causal: bool,
+    }
+
+    fn run_sdpa_trial(problem: SdpaProblem) -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let SdpaProblem {
+            B,
+            H,
+            M,
+            N,
+            D,
+            causal,
+        } = problem;
+        let q = Tensor::randn::<f32>(shape![B, H, M, D], Device::CPU);
+        let k = Tensor::randn::<f32>(shape![B, H, N, D], Device::CPU);
+        let v = Tensor::randn::<f32>(shape![B, H, N, D], Device::CPU);
+        let mask_data = (0..M)
+            .flat_map(|i| {
+                (0..N).map(move |j| {
+                    if causal && j > i + N.saturating_sub(M) {
+                        f32::NEG_INFINITY
+                    } else {
+                        0.
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let mask = Tensor::from_data(mask_data, shape![M, N], Device::CPU);
+        let ground = ground_truth(&q, &k, &v, &mask)?;
+
+        let (q, k, v, mask) = (
+            q.to(&device)?,
+            k.to(&device)?,
+            v.to(&device)?,
+            mask.to(&device)?,
+        );
+        let ours = q.sdpa(&k, &v, Some(&mask))?.resolve()?.to(&Device::CPU)?;
+        ground.all_close(&ours, 1e-4, 1e-4)?;
+        Ok(())
+    }
+
+    #[proptest(cases = 8)]
+    fn test_sdpa(prob: SdpaProblem) {
+        run_sdpa_trial(prob).unwrap();
+    }
+
+    #[test]
+    fn test_sdpa_unmasked_matches_composed() -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let (B, H, M, N, D) = (1, 2, 70, 33, 64);
+        let q = Tensor::randn::<f32>(shape![B, H, M, D], Device::CPU).to(&device)?;
+        let k = Tensor::randn::<f32>(shape![B, H, N, D], Device::CPU).to(&device)?;
+        let v = Tensor::randn::<f32>(shape![B, H, N, D], Device::CPU).to(&device)?;
+
+        let scale = Tensor::from_data([(D as f32).powf(-0.5)], shape![1], device.clone());
+        let composed = q
+            .matmul(&k.permute(&[0, 1, 3, 2])?)?
+            .mul(&scale)?
+            .softmax(3)?
+            .matmul(&v)?
+            .resolve()?
+            .to(&Device::CPU)?;
+        let fused = q.sdpa(&k, &v, None)?.resolve()?.to(&Device::CPU)?;
+        composed.all_close(&fused, 1e-4, 1e-4)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sdpa_rejects_non_contiguous() -> anyhow::Result<()> {
+        use crate::{Operation, Sdpa};
+        let q = Tensor::randn::<f32>(shape![1, 2, 4, 8], Device::CPU);
+        let mask = Tensor::randn::<f32>(shape![4, 4], Device::CPU);
+        assert!(Sdpa::check_invariants(&[&q, &q, &q, &mask]).is_ok());
+
+        //Broadcast over heads, the kernel assumes packed strides
+        let kv =
+            Tensor::randn::<f32>(shape![1, 1, 4, 8], Device::CPU).expand(shape![1, 2, 4, 8])?;
+        assert!(!kv.is_contiguous());
+        assert!(Sdpa::check_invariants(&[&q, &kv, &kv]).is_err());
+
+        let row = Tensor::randn::<f32>(shape![1, 4], Device::CPU).expand(shape![4, 4])?;
+        assert!(Sdpa::check_invariants(&[&q, &q, &q, &row]).is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
     rvec, wgc, DType, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
@@ -98,6 +99,16 @@ impl MetaOperation for IndexSelect {
             src_dim_numel,
         })
     }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let indices = cpu::read_i32(&srcs[1])?;
+        let dim = self.dim;
+        Ok(cpu::gather(&srcs[0], dst, |coords| {
+            let mut src = RVec::from(coords);
+            src[dim] = indices[coords[dim]] as usize;
+            src
+        })?)
+    }
 }
 
 #[cfg(test)]
@@ -107,7 +118,7 @@ mod tests {
     use test_strategy::proptest;
 
     use crate::test_util::run_py_prg;
-    use crate::{rvec, shape, Device, DeviceRequest, Shape, Tensor};
+    use crate::{shape, Device, DeviceRequest, Shape, Tensor};
 
     thread_local! {
         static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
@@ -1,10 +1,11 @@
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
-    rvec, wgc, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation, OperationError, RVec,
-    StorageView, Tensor,
+    rvec, wgc, Device, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
+    OperationError, RVec, StorageView, Tensor,
 };
 
 #[derive(new, Debug, Clone)]
@@ -96,6 +97,12 @@ impl MetaOperation for Softmax {
         let ND4 = N / 4;
         Ok(SoftmaxMeta { M, N, ND2, ND4 })
     }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let input = cpu::read_f32(&srcs[0])?;
+        let result = cpu::softmax(&input, srcs[0].shape(), self.dim);
+        Ok(Tensor::from_data(result, dst.shape().clone(), Device::CPU))
+    }
 }
 
 #[cfg(test)]
@@ -150,4 +157,19 @@ def softmax(a):
         println!("B = {}, M = {}, N = {}", B, M, N);
         run_softmax_trial(prob);
     }
+
+    #[test]
+    fn fully_masked_row_is_zero() -> anyhow::Result<()> {
+        let inf = f32::NEG_INFINITY;
+        let data = [0f32, inf, inf, inf, inf, inf, inf, inf, 1., 1., inf, inf];
+        let expected = [1f32, 0., 0., 0., 0., 0., 0., 0., 0.5, 0.5, 0., 0.];
+        let a = Tensor::from_data(data, shape![1, 3, 4], Device::CPU);
+
+        let cpu = a.softmax(2)?.resolve()?.to_vec::<f32>()?;
+        assert_eq!(cpu, expected);
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let gpu = a.to(&device)?.softmax(2)?.resolve()?.to(&Device::CPU)?;
+        assert_eq!(gpu.to_vec::<f32>()?, expected);
+        Ok(())
+    }
 }
@@ -0,0 +1,215 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, StorageView, Strides, Tensor,
+};
+
+/// Which half of the top-k result a [TopK] op produces.
+#[derive(Debug, Clone)]
+pub enum TopKOutput {
+    /// Gathered from the input at the given indices.
+    Values(Tensor),
+    Indices,
+}
+
+/// # TopK
+///
+/// The `k` largest elements along `dim`, in descending order.
+/// Ties are broken by the lower index, NaNs are skipped.
+///
+/// Ops have a single output, so [Tensor::topk] creates 2 of these sharing the same input.
+/// The I32 indices op runs the selection, the F32 values op then gathers the input at
+/// those indices, so the selection is dispatched once whichever of them is resolved.
+#[derive(new, Debug, Clone)]
+pub struct TopK {
+    input: Tensor,
+    k: usize,
+    dim: usize,
+    output: TopKOutput,
+}
+
+impl TopK {
+    pub fn name(&self) -> &'static str {
+        match self.output {
+            TopKOutput::Values(_) => "topk_values",
+            TopKOutput::Indices => "topk_indices",
+        }
+    }
+
+    fn num_lines(&self) -> usize {
+        let shape = self.input.shape();
+        shape.numel() / shape[self.dim]
+    }
+}
+
+#[derive(Debug, derive_new::new, ShaderType)]
+pub struct TopKMeta {
+    N: u32,
+    inner: u32,
+    num_lines: u32,
+    k: u32,
+}
+
+impl OpMetadata for TopKMeta {}
+
+impl Operation for TopK {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 1)?;
+        Enforcer::assert_dtype(srcs[0], DType::F32)?;
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        let input = srcs[0];
+        let rank = input.rank();
+        if self.dim >= rank {
+            return Err(InvariantError::DimOutOfRange {
+                dim: self.dim,
+                rank,
+            }
+            .into());
+        }
+        let mut shape = input.shape().clone();
+        shape[self.dim] = self.k;
+        let strides = Strides::from(&shape);
+        let dt = match self.output {
+            TopKOutput::Values(_) => DType::F32,
+            TopKOutput::Indices => DType::I32,
+        };
+        Ok(StorageView::new(shape, dt, strides))
+    }
+}
+
+impl MetaOperation for TopK {
+    type Meta = TopKMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        match &self.output {
+            TopKOutput::Values(indices) => rvec![&self.input, indices],
+            TopKOutput::Indices => rvec![&self.input],
+        }
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        match self.output {
+            TopKOutput::Values(_) => "topk_gather",
+            TopKOutput::Indices => "topk",
+        }
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        KernelElement::Scalar
+    }
+
+    fn calculate_dispatch(&self, _dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let num_lines = self.num_lines();
+        let (x_groups, y_groups) = if num_lines > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(num_lines, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (num_lines, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        match self.output {
+            TopKOutput::Values(_) => Ok(BindGroupLayoutDescriptor::binary()),
+            TopKOutput::Indices => Ok(BindGroupLayoutDescriptor::unary()),
+        }
+    }
+
+    fn metadata(
+        &self,
+        _dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let shape = self.input.shape();
+        let N = shape[self.dim];
+        let inner = shape.slice(self.dim + 1..shape.rank()).numel();
+        Ok(TopKMeta::new(
+            N as _,
+            inner as _,
+            self.num_lines() as _,
+            self.k as _,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::{shape, DType, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct TopKProblem {
+        #[strategy(1..=8usize)]
+        M: usize,
+        #[strategy(1..=2048usize)]
+        N: usize,
+        #[strategy(1..=64usize)]
+        k: usize,
+    }
+
+    fn run_topk_trial(prob: TopKProblem) -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let TopKProblem { M, N, k } = prob;
+        let a = Tensor::randn::<f32>(shape![M, N], Device::CPU);
+        let data = a.to_vec::<f32>()?;
+        let k = k.min(N);
+
+        let (values, indices) = a.to(&device)?.topk(k, 1)?;
+        Tensor::resolve_all(&[values.clone(), indices.clone()])?;
+        let values = values.to(&Device::CPU)?.to_vec::<f32>()?;
+        let indices = indices.to(&Device::CPU)?.to_vec::<i32>()?;
+
+        for m in 0..M {
+            let row = &data[m * N..(m + 1) * N];
+            let mut order = (0..N).collect::<Vec<_>>();
+            order.sort_by(|&x, &y| row[y].total_cmp(&row[x]).then(x.cmp(&y)));
+            let expected_idx = order[..k].iter().map(|&i| i as i32).collect::<Vec<_>>();
+            let expected_val = order[..k].iter().map(|&i| row[i]).collect::<Vec<_>>();
+            assert_eq!(&indices[m * k..(m + 1) * k], expected_idx.as_slice());
+            assert_eq!(&values[m * k..(m + 1) * k], expected_val.as_slice());
+        }
+        Ok(())
+    }
+
+    #[proptest(cases = 16)]
+    fn test_topk(prob: TopKProblem) {
+        run_topk_trial(prob).unwrap();
+    }
+
+    #[test]
+    fn test_topk_clamps_k() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![2, 5, 3], Device::CPU);
+        let (values, indices) = a.topk(10, 1)?;
+        assert_eq!(values.shape(), &shape![2, 5, 3]);
+        assert_eq!(indices.shape(), &shape![2, 5, 3]);
+        assert_eq!(values.dt(), DType::F32);
+        assert_eq!(indices.dt(), DType::I32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_topk_selects_once() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![4, 16], Device::CPU);
+        let (values, indices) = a.topk(3, 1)?;
+        let order = Tensor::execution_order_all(&[&values, &indices]);
+        let names = order.iter().map(|t| t.op().name()).collect::<Vec<_>>();
+        assert_eq!(names, ["Const", "topk_indices", "topk_values"]);
+        assert!(values.op().srcs().iter().any(|s| s.id() == indices.id()));
+        Ok(())
+    }
+}
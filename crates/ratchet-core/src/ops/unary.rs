@@ -1,10 +1,11 @@
 use derive_new::new;
 use encase::ShaderType;
 
+use super::cpu;
 use crate::{
     gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
-    rvec, wgc, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation, OperationError, RVec,
-    StorageView, Tensor,
+    rvec, wgc, Device, Enforcer, KernelElement, MetaOperation, OpMetadata, Operation,
+    OperationError, RVec, StorageView, Tensor,
 };
 
 #[cfg(test)]
@@ -132,6 +133,31 @@ impl MetaOperation for Unary {
         let numel = a.shape().numel() as u32;
         Ok(UnaryMeta { numel })
     }
+
+    fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+        let f: fn(f32) -> f32 = match self.op {
+            //tanh approximation, matching the kernel
+            UnaryOp::Gelu => |x| {
+                let inner = (2. / std::f32::consts::PI).sqrt() * (x + 0.044715 * x.powi(3));
+                0.5 * x * (1. + inner.tanh())
+            },
+            UnaryOp::Tanh => f32::tanh,
+            UnaryOp::Exp => f32::exp,
+            UnaryOp::Log => f32::ln,
+            UnaryOp::Sin => f32::sin,
+            UnaryOp::Cos => f32::cos,
+            UnaryOp::Abs => f32::abs,
+            UnaryOp::Sqrt => f32::sqrt,
+            UnaryOp::Relu => |x| x.max(0.),
+            UnaryOp::Floor => f32::floor,
+            UnaryOp::Ceil => f32::ceil,
+        };
+        let result = cpu::read_f32(&srcs[0])?
+            .into_iter()
+            .map(f)
+            .collect::<Vec<_>>();
+        Ok(Tensor::from_data(result, dst.shape().clone(), Device::CPU))
+    }
 }
 
 #[cfg(test)]
@@ -197,7 +223,7 @@ def {}(a):
         let ground = ground_truth(&a, &op, args)?;
 
         let a_gpu = a.to(&device)?;
-        let mut c_gpu = match op {
+        let c_gpu = match op {
             UnaryOp::Gelu => a_gpu.gelu()?,
             UnaryOp::Tanh => a_gpu.tanh()?,
             UnaryOp::Exp => a_gpu.exp()?,
@@ -0,0 +1,170 @@
+use derive_new::new;
+use encase::ShaderType;
+
+use crate::{
+    gpu::{BindGroupLayoutDescriptor, WorkgroupCount},
+    rvec, wgc, DType, Enforcer, InvariantError, KernelElement, MetaOperation, OpMetadata,
+    Operation, OperationError, RVec, StorageView, Tensor,
+};
+
+/// # Where
+///
+/// Elementwise selection, `cond != 0 ? on_true : on_false`.
+/// The condition may be F32, I32 or U32, the selected values must be F32.
+/// All 3 inputs must have the same shape.
+#[derive(new, Debug, Clone)]
+pub struct WhereCond {
+    cond: Tensor,
+    on_true: Tensor,
+    on_false: Tensor,
+}
+
+impl WhereCond {
+    pub fn name(&self) -> &'static str {
+        "where"
+    }
+}
+
+#[derive(Debug, ShaderType)]
+pub struct WhereCondMeta {
+    numel: u32,
+}
+
+impl OpMetadata for WhereCondMeta {}
+
+impl Operation for WhereCond {
+    fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+        Enforcer::check_input_arity(srcs, 3)?;
+        let cond_dt = srcs[0].dt();
+        if !matches!(cond_dt, DType::F32 | DType::I32 | DType::U32) {
+            return Err(InvariantError::UnsupportedDType(cond_dt).into());
+        }
+        Enforcer::assert_dtype(srcs[1], DType::F32)?;
+        Enforcer::assert_dtype(srcs[2], DType::F32)?;
+        let rank = Enforcer::assert_equal_ranks(srcs)?;
+        for dim in 0..rank {
+            Enforcer::check_shape_pair(srcs[0], srcs[1], dim, dim)?;
+            Enforcer::check_shape_pair(srcs[0], srcs[2], dim, dim)?;
+        }
+        Ok(())
+    }
+
+    fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+        Ok(srcs[1].storage_view().clone())
+    }
+}
+
+impl MetaOperation for WhereCond {
+    type Meta = WhereCondMeta;
+
+    fn srcs(&self) -> RVec<&Tensor> {
+        rvec![&self.cond, &self.on_true, &self.on_false]
+    }
+
+    fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+        let numel = self.cond.shape().numel();
+        if numel % 4 == 0 {
+            KernelElement::Vec4
+        } else if numel % 2 == 0 {
+            KernelElement::Vec2
+        } else {
+            KernelElement::Scalar
+        }
+    }
+
+    fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+        let numel = dst.shape().numel();
+        let x_groups = WorkgroupCount::div_ceil(numel as _, 64);
+        let (x_groups, y_groups) = if x_groups > WorkgroupCount::MAX_WGS_PER_DIM {
+            let y_groups = WorkgroupCount::div_ceil(x_groups, WorkgroupCount::MAX_WGS_PER_DIM);
+            (WorkgroupCount::MAX_WGS_PER_DIM, y_groups)
+        } else {
+            (x_groups, 1)
+        };
+        Ok(wgc![x_groups as _, y_groups as _, 1])
+    }
+
+    fn storage_bind_group_layout(
+        &self,
+        _inplace: bool,
+    ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+        Ok(BindGroupLayoutDescriptor::ternary())
+    }
+
+    fn kernel_name(&self) -> &'static str {
+        match self.cond.dt() {
+            DType::F32 => "where_f32",
+            DType::I32 => "where_i32",
+            DType::U32 => "where_u32",
+            dt => unreachable!("Unsupported condition dtype {:?}", dt),
+        }
+    }
+
+    fn metadata(
+        &self,
+        _dst: &Tensor,
+        _kernel_element: &KernelElement,
+    ) -> Result<Self::Meta, OperationError> {
+        let numel = self.cond.shape().numel() as u32;
+        Ok(WhereCondMeta { numel })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::{proptest, Arbitrary};
+
+    use crate::{shape, Device, DeviceRequest, Tensor};
+
+    thread_local! {
+        static GPU_DEVICE: Device = Device::request_device(DeviceRequest::GPU).unwrap();
+    }
+
+    #[derive(Arbitrary, Debug)]
+    struct WhereProblem {
+        #[strategy(1..=4usize)]
+        B: usize,
+        #[strategy(1..=256usize)]
+        N: usize,
+    }
+
+    fn run_where_trial(prob: WhereProblem) -> anyhow::Result<()> {
+        let device = GPU_DEVICE.with(|d| d.clone());
+        let WhereProblem { B, N } = prob;
+        let cond = Tensor::randn::<f32>(shape![B, N], Device::CPU);
+        let a = Tensor::randn::<f32>(shape![B, N], Device::CPU);
+        let b = Tensor::randn::<f32>(shape![B, N], Device::CPU);
+
+        //Keep roughly half, as we would for a threshold mask
+        let cond_data = cond
+            .to_vec::<f32>()?
+            .into_iter()
+            .map(|c| (c > 0.) as u32)
+            .collect::<Vec<_>>();
+        let expected = cond_data
+            .iter()
+            .zip(a.to_vec::<f32>()?.into_iter().zip(b.to_vec::<f32>()?))
+            .map(|(&c, (x, y))| if c != 0 { x } else { y })
+            .collect::<Vec<_>>();
+        let ground = Tensor::from_data(expected, shape![B, N], Device::CPU);
+
+        let cond = Tensor::from_data(cond_data, shape![B, N], Device::CPU).to(&device)?;
+        let (a, b) = (a.to(&device)?, b.to(&device)?);
+        let ours = cond.where_cond(&a, &b)?.resolve()?.to(&Device::CPU)?;
+        ground.all_close(&ours, 1e-6, 1e-6)?;
+        Ok(())
+    }
+
+    #[proptest(cases = 16)]
+    fn test_where(prob: WhereProblem) {
+        run_where_trial(prob).unwrap();
+    }
+
+    #[test]
+    fn test_where_shape_mismatch() {
+        let cond = Tensor::from_data(vec![1u32; 6], shape![2, 3], Device::CPU);
+        let a = Tensor::randn::<f32>(shape![2, 3], Device::CPU);
+        let b = Tensor::randn::<f32>(shape![3, 2], Device::CPU);
+        assert!(cond.where_cond(&a, &b).is_err());
+    }
+}
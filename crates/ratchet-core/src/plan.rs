@@ -0,0 +1,175 @@
+use crate::{gpu::BufferAllocator, Tensor, TensorError, TensorId};
+
+/// # Execution plan
+///
+/// The memory a graph would require, computed without creating any buffers.
+/// See [Tensor::plan].
+#[derive(Debug, Clone)]
+pub struct ExecutionPlan {
+    /// Tensors in the order they would be computed, alongside their op name.
+    pub execution_order: Vec<(TensorId, &'static str)>,
+    /// Size of each activation buffer that would be allocated.
+    pub buffer_sizes: Vec<u64>,
+    /// Index into `buffer_sizes` assigned to each unresolved tensor, in execution order.
+    /// Tensors sharing an index share a buffer.
+    pub buffer_assignments: Vec<(TensorId, usize)>,
+    /// Bytes already held by resolved tensors (e.g weights) in the graph.
+    pub const_bytes: u64,
+}
+
+impl ExecutionPlan {
+    /// Total bytes of activation buffers.
+    pub fn total_bytes(&self) -> u64 {
+        self.buffer_sizes.iter().sum()
+    }
+
+    pub fn num_buffers(&self) -> usize {
+        self.buffer_sizes.len()
+    }
+
+    /// Largest single buffer, to check against the device's max buffer size.
+    pub fn largest_buffer(&self) -> u64 {
+        self.buffer_sizes.iter().copied().max().unwrap_or(0)
+    }
+}
+
+impl Tensor {
+    /// # Plan
+    ///
+    /// Runs the buffer allocator over the graph in a sizing only mode,
+    /// no buffers are created and nothing is executed.
+    pub fn plan(&self) -> Result<ExecutionPlan, TensorError> {
+        let execution_order = self.execution_order();
+        let (buffer_sizes, buffer_assignments) = BufferAllocator::plan_cfg(&execution_order)?;
+        let const_bytes = execution_order
+            .iter()
+            .filter(|t| t.resolved())
+            .map(|t| t.num_bytes() as u64)
+            .sum();
+        Ok(ExecutionPlan {
+            execution_order: execution_order
+                .iter()
+                .map(|t| (t.id(), t.op().name()))
+                .collect(),
+            buffer_sizes,
+            buffer_assignments,
+            const_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{gpu::BufferAllocator, shape, Device, Tensor};
+
+    #[test]
+    fn plan_without_executing() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![64, 64], Device::CPU);
+        let b = Tensor::randn::<f32>(shape![64, 64], Device::CPU);
+        let mm = a.matmul(&b)?;
+        let c = mm.softmax(1)?;
+
+        let plan = c.plan()?;
+        let names = plan
+            .execution_order
+            .iter()
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[2], mm.op().name());
+        assert_eq!(names[3], c.op().name());
+        assert_eq!(plan.execution_order[2].0, mm.id());
+        assert_eq!(plan.execution_order[3].0, c.id());
+
+        //Only the unresolved tensors are assigned a buffer
+        let assigned = plan
+            .buffer_assignments
+            .iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        assert_eq!(assigned, vec![mm.id(), c.id()]);
+        assert!(plan
+            .buffer_assignments
+            .iter()
+            .all(|(_, idx)| *idx < plan.num_buffers()));
+        assert_eq!(plan.const_bytes, 2 * 64 * 64 * 4);
+        assert!(plan.num_buffers() >= 1);
+        assert_eq!(plan.largest_buffer(), 64 * 64 * 4);
+        assert!(!c.resolved());
+        Ok(())
+    }
+
+    #[test]
+    fn retained_buffers_are_not_reused() -> anyhow::Result<()> {
+        let build = || -> anyhow::Result<(Tensor, Tensor)> {
+            let a = Tensor::randn::<f32>(shape![64, 64], Device::CPU);
+            //Without retaining, `first` shares a buffer with the output
+            let first = a.matmul(&a)?;
+            let out = first.matmul(&a)?.matmul(&a)?;
+            Ok((first, out))
+        };
+
+        let (first, out) = build()?;
+        drop(first);
+        let baseline = out.plan()?.num_buffers();
+
+        let (first, out) = build()?;
+        first.retain();
+        let retained = out.plan()?.num_buffers();
+        assert_eq!(retained, baseline + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn plan_is_deterministic() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![32, 32], Device::CPU);
+        let b = Tensor::randn::<f32>(shape![32, 32], Device::CPU);
+        let x = a.matmul(&b)?;
+        let y = x.matmul(&a)?.softmax(1)?;
+        let c = y.matmul(&x)?.matmul(&b)?;
+
+        let first = c.plan()?;
+        for _ in 0..8 {
+            let again = c.plan()?;
+            assert_eq!(again.execution_order, first.execution_order);
+            assert_eq!(again.buffer_sizes, first.buffer_sizes);
+            assert_eq!(again.buffer_assignments, first.buffer_assignments);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn op_inspection() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![8, 8], Device::CPU);
+        let b = a.matmul(&a)?;
+        let c = b.softmax(1)?;
+        assert_eq!(a.op_name(), "Const");
+        assert!(!a.op_supports_inplace());
+        assert!(!b.op_supports_inplace());
+        assert_eq!(c.op_name(), c.op().name());
+        assert!(c.op_supports_inplace());
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_outputs_get_distinct_buffers() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![8, 32], Device::CPU);
+        let h = a.matmul(&Tensor::randn::<f32>(shape![32, 32], Device::CPU))?;
+        let (values, indices) = h.topk(4, 1)?;
+        let order = Tensor::execution_order_all(&[&values, &indices]);
+        assert_eq!(order.iter().filter(|t| t.id() == h.id()).count(), 1);
+
+        let (_, assignments) = BufferAllocator::plan_cfg(&order)?;
+        let buffer_of = |t: &Tensor| {
+            assignments
+                .iter()
+                .find(|(id, _)| *id == t.id())
+                .map(|(_, b)| *b)
+                .unwrap()
+        };
+        assert_ne!(buffer_of(&values), buffer_of(&indices));
+        assert_ne!(buffer_of(&values), buffer_of(&h));
+        assert_ne!(buffer_of(&indices), buffer_of(&h));
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{shape, RVec};
+use crate::{shape, InvariantError, RVec};
 use encase::impl_wrapper;
-use std::ops::{RangeFrom, RangeTo};
+use std::ops::{Range, RangeFrom, RangeTo};
 
 #[derive(Clone, PartialEq, Eq, Hash, Default)]
 pub struct Shape(RVec<usize>);
@@ -28,10 +28,20 @@ impl Shape {
         self.0.iter().product()
     }
 
+    /// Alias of [Shape::numel].
+    pub fn num_elements(&self) -> usize {
+        self.numel()
+    }
+
     pub fn to_vec(&self) -> Vec<usize> {
         self.0.to_vec()
     }
 
+    /// Dimensions as u32, the integer width JS bindings expect.
+    pub fn to_u32_vec(&self) -> Vec<u32> {
+        self.0.iter().map(|&d| d as u32).collect()
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &usize> {
         self.0.iter()
     }
@@ -85,6 +95,59 @@ impl Shape {
         Shape(self.0[range].to_vec().into())
     }
 
+    /// Validates `ranges` against this shape, returning the shape of the slice.
+    pub fn slice_shape(&self, ranges: &[Range<usize>]) -> Result<Shape, InvariantError> {
+        if ranges.len() != self.rank() {
+            return Err(InvariantError::RankMismatch {
+                accepted: self.rank()..=self.rank(),
+                actual: ranges.len(),
+            });
+        }
+        ranges
+            .iter()
+            .zip(self.iter())
+            .enumerate()
+            .map(|(dim, (range, &size))| {
+                if range.start > range.end || range.end > size {
+                    Err(InvariantError::InvalidSlice {
+                        dim,
+                        range: range.clone(),
+                        size,
+                    })
+                } else {
+                    Ok(range.end - range.start)
+                }
+            })
+            .collect::<Result<RVec<_>, _>>()
+            .map(Shape)
+    }
+
+    /// Returns true if slicing a contiguous tensor of this shape with `ranges`
+    /// yields a single contiguous region of memory.
+    ///
+    /// This is the case when all dimensions to the right of the first partial
+    /// dimension are taken in full, and all dimensions to its left have length 1.
+    pub fn is_contiguous_slice(&self, ranges: &[Range<usize>]) -> bool {
+        if ranges.len() != self.rank() {
+            return false;
+        }
+        let mut trailing_full = true;
+        for (range, &size) in ranges.iter().zip(self.0.iter()).rev() {
+            let len = range.end.saturating_sub(range.start);
+            if trailing_full {
+                trailing_full = len == size;
+            } else if len != 1 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Broadcasts this shape with `other`, following numpy broadcasting rules.
+    pub fn broadcast_with(&self, other: &Shape) -> Option<Shape> {
+        Self::multi_broadcast(&[self, other])
+    }
+
     pub fn multi_broadcast(shapes: &[&Shape]) -> Option<Shape> {
         let max_rank = shapes.iter().map(|shape| shape.rank()).max()?;
         let mut shape: Shape = shape![];
@@ -213,7 +276,7 @@ impl_try_into_for_shape!(0, 1, 2, 3, 4);
 
 #[cfg(test)]
 mod tests {
-    use crate::Shape;
+    use crate::{shape, Shape};
     use proptest::prelude::*;
     use proptest::strategy::{BoxedStrategy, Strategy};
     use std::ops::Range;
@@ -237,4 +300,33 @@ mod tests {
             shape
         }
     }
+
+    #[test]
+    fn test_broadcast_with() {
+        let a = shape![2, 1, 64];
+        assert_eq!(a.broadcast_with(&shape![5, 1]), Some(shape![2, 5, 64]));
+        assert_eq!(a.broadcast_with(&shape![3, 64]), Some(shape![2, 3, 64]));
+        assert_eq!(a.broadcast_with(&shape![2, 3, 32]), None);
+    }
+
+    #[test]
+    fn test_slice_shape() {
+        let s = shape![4, 8, 16];
+        assert_eq!(
+            s.slice_shape(&[1..3, 0..8, 4..12]).unwrap(),
+            shape![2, 8, 8]
+        );
+        assert!(s.slice_shape(&[0..4, 0..9, 0..16]).is_err());
+        assert!(s.slice_shape(&[0..4, 0..8]).is_err());
+    }
+
+    #[test]
+    fn test_is_contiguous_slice() {
+        let s = shape![4, 8, 16];
+        assert!(s.is_contiguous_slice(&[1..3, 0..8, 0..16]));
+        assert!(s.is_contiguous_slice(&[2..3, 3..5, 0..16]));
+        assert!(s.is_contiguous_slice(&[2..3, 3..4, 4..9]));
+        assert!(!s.is_contiguous_slice(&[0..4, 0..8, 0..8]));
+        assert!(!s.is_contiguous_slice(&[1..3, 3..5, 0..16]));
+    }
 }
@@ -66,6 +66,12 @@ unsafe impl Send for CPUBuffer {}
 unsafe impl Sync for CPUBuffer {}
 
 impl CPUBuffer {
+    /// Uploads without waiting on the write, see [GPUBuffer::from_bytes_nonblocking].
+    pub(crate) fn to_gpu_nonblocking(&self, device: &crate::gpu::WgpuDevice) -> GPUBuffer {
+        let layout = self.inner().1;
+        GPUBuffer::from_bytes_nonblocking(self.inner().as_bytes(), layout.align(), device)
+    }
+
     pub fn new(inner: RawCPUBuffer) -> Self {
         Self {
             inner: Arc::new(inner),
@@ -100,6 +106,13 @@ impl CPUBuffer {
         Self::new(raw)
     }
 
+    pub fn full<T: TensorDType>(value: T, shape: &Shape) -> Self {
+        let n_bytes = shape.numel() * T::dt().size_of();
+        let mut raw = RawCPUBuffer::uninitialized(n_bytes, std::mem::align_of::<T>());
+        bytemuck::cast_slice_mut::<u8, T>(raw.as_bytes_mut()).fill(value);
+        Self::new(raw)
+    }
+
     pub fn from_disk<T: TensorDType, R: std::io::BufRead + std::io::Seek>(
         reader: &mut R,
         shape: &Shape,
@@ -18,6 +18,8 @@ pub struct GPUBuffer {
 
 impl GPUBuffer {
     const MIN_SIZE: usize = 16;
+    /// Bytes read from the reader per copy into the mapped range.
+    const STREAM_CHUNK: usize = 1 << 20;
 
     pub fn from_slice<T: NoUninit>(data: &[T], shape: &Shape, device: &WgpuDevice) -> Self {
         assert_eq!(data.len(), shape.numel());
@@ -28,14 +30,56 @@ impl GPUBuffer {
         )
     }
 
-    //We have to use from_bytes here, as buffers may be reused and we need to
-    //ensure that the buffer is zeroed
+    //Buffers may be reused, so they are cleared on the device rather than relying on
+    //zero initialization at creation.
     pub fn zeros<T: TensorDType>(shape: &Shape, device: &WgpuDevice) -> Self {
-        Self::from_bytes(
-            vec![0; shape.numel() * T::dt().size_of()].as_slice(),
-            T::dt().size_of(),
-            device,
-        )
+        let inner = Self::allocate(shape.numel() * T::dt().size_of(), device);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.clear_buffer(&inner.inner, 0, None);
+        device.submit(encoder.finish());
+        Self {
+            inner,
+            alignment: T::dt().size_of(),
+        }
+    }
+
+    /// # Full
+    ///
+    /// A buffer with every element set to `value`. The value is written from a host chunk of
+    /// at most [GPUBuffer::STREAM_CHUNK] bytes, rather than a host copy of the whole buffer.
+    pub fn full<T: TensorDType>(value: T, shape: &Shape, device: &WgpuDevice) -> Self {
+        if value == T::zero() {
+            return Self::zeros::<T>(shape, device);
+        }
+        let inner = Self::allocate(shape.numel() * T::dt().size_of(), device);
+        let size = inner.size() as usize;
+        let chunk_len = size.min(Self::STREAM_CHUNK) / std::mem::size_of::<T>();
+        let chunk = vec![value; chunk_len];
+        let chunk: &[u8] = bytemuck::cast_slice(&chunk);
+        for offset in (0..size).step_by(chunk.len()) {
+            let len = chunk.len().min(size - offset);
+            device
+                .queue()
+                .write_buffer(&inner.inner, offset as _, &chunk[..len]);
+        }
+        Self {
+            inner,
+            alignment: T::dt().size_of(),
+        }
+    }
+
+    /// A buffer of at least `num_bytes`, rounded up to a whole number of words.
+    fn allocate(num_bytes: usize, device: &WgpuDevice) -> PooledGPUBuffer {
+        let size = num_bytes
+            .max(Self::MIN_SIZE)
+            .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as _);
+        device
+            .get_or_create_buffer(&BufferDescriptor::new(
+                size as _,
+                BufferUsages::standard(),
+                false,
+            ))
+            .unwrap()
     }
 
     pub(crate) fn from_bytes(bytes: &[u8], alignment: usize, device: &WgpuDevice) -> Self {
@@ -54,11 +98,65 @@ impl GPUBuffer {
                 bytes,
             )
             .unwrap();
-        device.queue().submit(None);
-        device.poll(wgpu::Maintain::Wait);
+        if !device.uploads_batched() {
+            device.queue().submit(None);
+            device.poll(wgpu::Maintain::Wait);
+        }
+        Self { inner, alignment }
+    }
+
+    /// Like [GPUBuffer::from_bytes], without waiting for the write to complete.
+    /// Queue writes are ordered before any later submission, so the buffer can be used immediately.
+    pub(crate) fn from_bytes_nonblocking(
+        bytes: &[u8],
+        alignment: usize,
+        device: &WgpuDevice,
+    ) -> Self {
+        let size = bytes.len().max(Self::MIN_SIZE);
+        let inner = device
+            .get_or_create_buffer(&BufferDescriptor::new(
+                size as _,
+                BufferUsages::standard(),
+                false,
+            ))
+            .unwrap();
+        device.queue().write_buffer(&inner.inner, 0, bytes);
         Self { inner, alignment }
     }
 
+    /// # Read async
+    ///
+    /// Queues a copy into a staging buffer and requests it be mapped, without waiting.
+    /// Work submitted before [PendingRead::wait] is awaited overlaps with the transfer.
+    pub(crate) fn read_async(&self, device: &WgpuDevice) -> Result<PendingRead, DeviceError> {
+        self.validate_usages(BufferUsages::COPY_SRC)?;
+        let size = self.inner.size();
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ratchet readback"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(&self.inner.inner, 0, &staging, 0, size);
+        device.submit(encoder.finish());
+
+        #[cfg(target_arch = "wasm32")]
+        let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+        #[cfg(not(target_arch = "wasm32"))]
+        let (tx, rx) = std::sync::mpsc::channel();
+        staging
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result);
+            });
+        Ok(PendingRead {
+            staging,
+            alignment: self.alignment,
+            rx,
+        })
+    }
+
     /// Returns true if the buffer has all the given usages.
     pub(crate) fn validate_usages(&self, usages: BufferUsages) -> Result<(), DeviceError> {
         match self.inner.usage().contains(usages) {
@@ -100,8 +198,49 @@ impl GPUBuffer {
         shape: &Shape,
         device: &Device,
     ) -> Result<Self, DeviceError> {
-        //There is no faster way to do this
-        CPUBuffer::from_disk::<T, R>(reader, shape)?.to_device(device)
+        let dt = T::dt();
+        Self::from_reader(
+            reader,
+            shape.numel() * dt.size_of(),
+            dt.size_of(),
+            device.try_gpu()?,
+        )
+    }
+
+    /// # From reader
+    ///
+    /// Reads `num_bytes` from `reader` straight into a buffer mapped at creation,
+    /// [GPUBuffer::STREAM_CHUNK] bytes at a time.
+    /// Unlike [GPUBuffer::from_bytes], the tensor is never held in host memory in full,
+    /// so peak memory during a load is the size of the reader's buffer rather than the weight.
+    ///
+    /// Mapped buffers are never reused by the pool, so this is intended for weights.
+    pub(crate) fn from_reader<R: std::io::Read>(
+        reader: &mut R,
+        num_bytes: usize,
+        alignment: usize,
+        device: &WgpuDevice,
+    ) -> Result<Self, DeviceError> {
+        //Mapped buffers must be a multiple of COPY_BUFFER_ALIGNMENT
+        let align = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let size = num_bytes.max(Self::MIN_SIZE).div_ceil(align) * align;
+        let inner = device.get_or_create_buffer(&BufferDescriptor::new(
+            size as _,
+            BufferUsages::standard(),
+            true,
+        ))?;
+        {
+            let mut mapped = inner.slice(..).get_mapped_range_mut();
+            let (data, padding) = mapped.split_at_mut(num_bytes);
+            for chunk in data.chunks_mut(Self::STREAM_CHUNK) {
+                reader
+                    .read_exact(chunk)
+                    .map_err(|e| DeviceError::ReadFailed(e.to_string()))?;
+            }
+            padding.fill(0);
+        }
+        inner.unmap();
+        Ok(Self { inner, alignment })
     }
 
     pub fn trim_id(id: wgpu::Id<wgpu::Buffer>) -> Option<String> {
@@ -121,6 +260,40 @@ impl GPUBuffer {
     }
 }
 
+type MapResult = Result<(), wgpu::BufferAsyncError>;
+
+/// A readback in flight, see [GPUBuffer::read_async].
+pub(crate) struct PendingRead {
+    staging: wgpu::Buffer,
+    alignment: usize,
+    #[cfg(target_arch = "wasm32")]
+    rx: futures_intrusive::channel::shared::OneshotReceiver<MapResult>,
+    #[cfg(not(target_arch = "wasm32"))]
+    rx: std::sync::mpsc::Receiver<MapResult>,
+}
+
+impl PendingRead {
+    /// On WASM the browser completes the mapping, natively we block on the device here.
+    pub(crate) async fn wait(self, device: &WgpuDevice) -> Result<CPUBuffer, DeviceError> {
+        #[cfg(target_arch = "wasm32")]
+        let mapped = {
+            device.poll(wgpu::Maintain::Poll);
+            self.rx.receive().await
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let mapped = {
+            device.poll(wgpu::Maintain::Wait);
+            self.rx.recv().ok()
+        };
+        mapped.expect("Readback callback dropped")?;
+
+        let cpu_buf =
+            CPUBuffer::from_bytes(&self.staging.slice(..).get_mapped_range(), self.alignment);
+        self.staging.unmap();
+        Ok(cpu_buf)
+    }
+}
+
 #[cfg_attr(target_arch = "wasm32", async_trait::async_trait)]
 impl DeviceStorage for GPUBuffer {
     fn to_device(&self, _: &Device) -> Result<GPUBuffer, DeviceError> {
@@ -1,7 +1,7 @@
 mod cpu_buffer;
 mod gpu_buffer;
 
-use std::io::{BufRead, Seek};
+use std::io::{BufRead, Read, Seek};
 
 use bytemuck::NoUninit;
 pub use cpu_buffer::*;
@@ -38,6 +38,27 @@ impl Storage {
         }
     }
 
+    /// Reads `num_bytes` from `reader`, on GPU without an intermediate host copy.
+    pub fn from_reader<R: Read>(
+        reader: &mut R,
+        num_bytes: usize,
+        alignment: usize,
+        device: &Device,
+    ) -> Result<Self, DeviceError> {
+        match device {
+            Device::CPU => {
+                let mut bytes = vec![0; num_bytes];
+                reader
+                    .read_exact(&mut bytes)
+                    .map_err(|e| DeviceError::ReadFailed(e.to_string()))?;
+                Ok(Storage::CPU(CPUBuffer::from_bytes(&bytes, alignment)))
+            }
+            Device::GPU(g) => Ok(Storage::GPU(GPUBuffer::from_reader(
+                reader, num_bytes, alignment, g,
+            )?)),
+        }
+    }
+
     pub fn zeros<T: TensorDType>(shape: &Shape, device: &Device) -> Self {
         match device {
             Device::CPU => Storage::CPU(CPUBuffer::zeros::<T>(shape)),
@@ -45,6 +66,13 @@ impl Storage {
         }
     }
 
+    pub fn full<T: TensorDType>(value: T, shape: &Shape, device: &Device) -> Self {
+        match device {
+            Device::CPU => Storage::CPU(CPUBuffer::full(value, shape)),
+            Device::GPU(g) => Storage::GPU(GPUBuffer::full(value, shape, g)),
+        }
+    }
+
     pub fn from_slice<T: NoUninit>(data: &[T], shape: &Shape, device: &Device) -> Self {
         match device {
             Device::CPU => Storage::CPU(CPUBuffer::from_slice(data, shape)),
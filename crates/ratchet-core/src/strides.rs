@@ -6,6 +6,12 @@ pub struct Strides(RVec<isize>);
 
 impl_wrapper!(Strides; using);
 
+impl From<RVec<isize>> for Strides {
+    fn from(strides: RVec<isize>) -> Self {
+        Self(strides)
+    }
+}
+
 impl Strides {
     pub fn to_vec(&self) -> Vec<isize> {
         self.0.to_vec()
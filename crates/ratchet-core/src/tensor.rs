@@ -1,16 +1,17 @@
-use crate::gpu::{BindGroupEntry, CpuUniform, WgpuDevice};
+use crate::gpu::{BindGroupEntry, BufferUsagesExt, CpuUniform, GraphAllocation, WgpuDevice};
 use crate::{
-    ops::*, rvec, CPUBuffer, CompiledOp, DType, Device, DeviceStorage, Executable, GPUBuffer,
-    InvariantError, MetaOperation, Operation, OperationError, RVec, RawCPUBuffer, Shape, Storage,
-    Strides, TensorDType, TensorId,
+    ops::*, rvec, shape, CPUBuffer, CompiledOp, DType, Device, DeviceError, DeviceStorage,
+    Executable, GPUBuffer, InvariantError, MetaOperation, Operation, OperationError, PendingRead,
+    RVec, RawCPUBuffer, Shape, Storage, Strides, TensorDType, TensorId,
 };
-use crate::{BinaryOp, LazyOp};
+use crate::{BinaryOp, CmpOp, Custom, LazyOp};
 use derive_new::new;
 use parking_lot::{RwLock, RwLockReadGuard};
 use std::collections::HashSet;
-use std::io::{BufRead, Seek};
+use std::io::{BufRead, Read, Seek};
 use std::ops::Bound;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 
 #[cfg(feature = "rand")]
@@ -34,6 +35,10 @@ pub enum TensorError {
     TransferError,
     #[error(transparent)]
     OperationError(#[from] OperationError),
+    #[error("Buffer of {actual} bytes is too small, {expected} bytes required")]
+    BufferTooSmall { expected: usize, actual: usize },
+    #[error("Tensor {0:?} is on another device than the graph, move it with `Tensor::to` first")]
+    CrossDevice(TensorId),
 }
 
 /// A multi-dimensional array of data.
@@ -110,7 +115,7 @@ pub struct StorageView {
 
 impl StorageView {
     pub fn is_contiguous(&self) -> bool {
-        todo!()
+        self.strides == Strides::from(&self.shape)
     }
 }
 
@@ -121,6 +126,9 @@ pub struct Inner {
     device: Device,
     view: StorageView,
     storage: Arc<RwLock<Option<Storage>>>,
+    retained: AtomicBool,
+    /// Bits of the [wgpu::BufferUsages] required on top of the standard usages.
+    usages: AtomicU32,
 }
 
 impl AsRef<Inner> for Inner {
@@ -137,6 +145,8 @@ impl Inner {
             op,
             device,
             storage: Arc::new(RwLock::new(storage)),
+            retained: AtomicBool::new(false),
+            usages: AtomicU32::new(0),
         }
     }
 
@@ -152,6 +162,8 @@ impl Inner {
             op,
             device,
             storage,
+            retained: AtomicBool::new(false),
+            usages: AtomicU32::new(0),
         }
     }
 }
@@ -197,10 +209,71 @@ impl Tensor {
         self.storage().is_some()
     }
 
+    /// # Retain
+    ///
+    /// Intermediate values share buffers, so after [Tensor::resolve] most of them have been
+    /// overwritten. A retained tensor is given a buffer of its own that is never reused,
+    /// so it can be read back afterwards, e.g to compare activations against a reference.
+    pub fn retain(&self) -> Tensor {
+        self.inner.retained.store(true, Ordering::Relaxed);
+        self.clone()
+    }
+
+    pub fn retained(&self) -> bool {
+        self.inner.retained.load(Ordering::Relaxed)
+    }
+
+    /// # With usages
+    ///
+    /// Requests that the buffer holding this tensor also supports `usages`, e.g
+    /// [wgpu::BufferUsages::VERTEX] for a tensor rendered directly.
+    /// The tensor is retained (see [Tensor::retain]), it gets a buffer of its own,
+    /// created with the requested usages, that no other tensor writes to.
+    pub fn with_usages(&self, usages: wgpu::BufferUsages) -> Tensor {
+        self.inner.usages.fetch_or(usages.bits(), Ordering::Relaxed);
+        self.retain()
+    }
+
+    /// Usages of the buffer this tensor is assigned, see [Tensor::with_usages].
+    pub fn buffer_usages(&self) -> wgpu::BufferUsages {
+        let extra =
+            wgpu::BufferUsages::from_bits_truncate(self.inner.usages.load(Ordering::Relaxed));
+        wgpu::BufferUsages::standard() | extra
+    }
+
+    /// # Detach
+    ///
+    /// A constant backed by the same buffer, without the graph that produced it.
+    /// Holding the detached tensor, e.g the encoder output across decoding steps,
+    /// no longer keeps the producer's sources and their buffers alive.
+    pub fn detach(&self) -> Result<Tensor, TensorError> {
+        if !self.resolved() {
+            return Err(TensorError::NotResolved);
+        }
+        Ok(Tensor::from_shallow(
+            LazyOp::Const,
+            self.view.clone(),
+            self.inner.storage.clone(),
+            self.device.clone(),
+        ))
+    }
+
     pub(crate) fn op(&self) -> &LazyOp {
         &self.inner.op
     }
 
+    /// Name of the op producing this tensor, e.g `"softmax"`, or `"Const"` for resolved inputs.
+    pub fn op_name(&self) -> &'static str {
+        self.op().name()
+    }
+
+    /// Whether the op producing this tensor can write over its source's buffer.
+    /// The allocator only leases the source buffer if the source also has a single consumer,
+    /// see `BufferAllocator::determine_tensor_source`.
+    pub fn op_supports_inplace(&self) -> bool {
+        self.op().supports_inplace()
+    }
+
     pub fn is_scalar(&self) -> bool {
         self.shape().is_scalar()
     }
@@ -232,16 +305,18 @@ macro_rules! impl_binary_op {
                 return Err(InvariantError::BroadcastingFailed(failed).into());
             }
             let broadcasted = broadcasted.unwrap();
-            let left_required = self.shape() != &broadcasted;
-            let right_required = other.shape() != &broadcasted;
-
-            let (lhs, rhs) = if left_required {
-                (self.broadcast_to(broadcasted.clone())?, other.clone())
-            } else if right_required {
-                (self.clone(), other.broadcast_to(broadcasted.clone())?)
-            } else {
-                (self.clone(), other.clone())
+            //Both operands may require broadcasting, e.g [B, 1, N] + [M, 1]
+            //Expanded operands are read through their strides, so stay views.
+            let broadcast = |t: &Tensor| {
+                if t.shape() == &broadcasted {
+                    Ok(t.clone())
+                } else if !t.is_contiguous() {
+                    t.expand(broadcasted.clone())
+                } else {
+                    t.broadcast_to(broadcasted.clone())
+                }
             };
+            let (lhs, rhs) = (broadcast(lhs)?, broadcast(rhs)?);
             let binary = Binary::new(lhs.clone(), rhs.clone(), $op);
             let new_view = binary.infer_output(&[&lhs, &rhs])?;
 
@@ -254,6 +329,44 @@ macro_rules! impl_binary_op {
     };
 }
 
+macro_rules! impl_cmp_op {
+    ($method_name:ident, $scalar_method_name:ident, $op:expr) => {
+        pub fn $method_name(&self, other: &Tensor) -> anyhow::Result<Tensor> {
+            Cmp::check_invariants(&[self, other])?;
+
+            let (lhs, rhs) = (self, other);
+            let shapes = &[lhs.shape(), rhs.shape()];
+            let broadcasted = Shape::multi_broadcast(shapes);
+            if broadcasted.is_none() {
+                let failed = shapes.iter().map(|s| (*s).clone()).collect::<Vec<_>>();
+                return Err(InvariantError::BroadcastingFailed(failed).into());
+            }
+            let broadcasted = broadcasted.unwrap();
+            let broadcast = |t: &Tensor| {
+                if t.shape() != &broadcasted {
+                    t.broadcast_to(broadcasted.clone())
+                } else {
+                    Ok(t.clone())
+                }
+            };
+            let (lhs, rhs) = (broadcast(lhs)?, broadcast(rhs)?);
+            let cmp = Cmp::new(lhs.clone(), rhs.clone(), $op);
+            let new_view = cmp.infer_output(&[&lhs, &rhs])?;
+
+            Ok(Tensor::lazy(
+                LazyOp::Cmp(cmp),
+                new_view,
+                self.device.clone(),
+            ))
+        }
+
+        pub fn $scalar_method_name(&self, value: f32) -> anyhow::Result<Tensor> {
+            let other = Tensor::from_data([value], shape![1], self.device.clone());
+            self.$method_name(&other)
+        }
+    };
+}
+
 macro_rules! impl_unary_op {
     ($method_name:ident, $op:expr) => {
         pub fn $method_name(&self) -> anyhow::Result<Tensor> {
@@ -276,6 +389,13 @@ impl Tensor {
     impl_binary_op!(mul, BinaryOp::Mul);
     impl_binary_op!(div, BinaryOp::Div);
 
+    impl_cmp_op!(eq, eq_scalar, CmpOp::Eq);
+    impl_cmp_op!(ne, ne_scalar, CmpOp::Ne);
+    impl_cmp_op!(lt, lt_scalar, CmpOp::Lt);
+    impl_cmp_op!(le, le_scalar, CmpOp::Le);
+    impl_cmp_op!(gt, gt_scalar, CmpOp::Gt);
+    impl_cmp_op!(ge, ge_scalar, CmpOp::Ge);
+
     impl_unary_op!(gelu, UnaryOp::Gelu);
     impl_unary_op!(tanh, UnaryOp::Tanh);
     impl_unary_op!(exp, UnaryOp::Exp);
@@ -288,6 +408,129 @@ impl Tensor {
     impl_unary_op!(floor, UnaryOp::Floor);
     impl_unary_op!(ceil, UnaryOp::Ceil);
 
+    /// # Cast
+    ///
+    /// Converts the tensor to `dt`, currently only between F32, I32 and U32.
+    pub fn cast(&self, dt: DType) -> anyhow::Result<Tensor> {
+        if self.dt() == dt {
+            return Ok(self.clone());
+        }
+        Cast::check_invariants(&[self])?;
+
+        let cast = Cast::new(self.clone(), dt);
+        let new_view = cast.infer_output(&[self])?;
+        Ok(Tensor::lazy(
+            LazyOp::Cast(cast),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
+    /// # Clamp
+    ///
+    /// Clamps all elements into the range [min, max].
+    pub fn clamp(&self, min: f32, max: f32) -> anyhow::Result<Tensor> {
+        Clamp::check_invariants(&[self])?;
+
+        let clamp = Clamp::new(self.clone(), min, max);
+        let new_view = clamp.infer_output(&[self])?;
+        Ok(Tensor::lazy(
+            LazyOp::Clamp(clamp),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
+    /// # Cumsum
+    ///
+    /// Inclusive cumulative sum along `dim`, e.g over sorted probabilities for top-p sampling.
+    pub fn cumsum(&self, dim: usize) -> anyhow::Result<Tensor> {
+        Cumsum::check_invariants(&[self])?;
+
+        let cumsum = Cumsum::new(self.clone(), dim);
+        let new_view = cumsum.infer_output(&[self])?;
+        Ok(Tensor::lazy(
+            LazyOp::Cumsum(cumsum),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
+    /// # TopK
+    ///
+    /// Returns the `k` largest values along `dim` in descending order, and their I32 indices.
+    /// If `k` exceeds the size of `dim` it is clamped.
+    pub fn topk(&self, k: usize, dim: usize) -> anyhow::Result<(Tensor, Tensor)> {
+        TopK::check_invariants(&[self])?;
+        if k == 0 {
+            anyhow::bail!("topk requires k > 0");
+        }
+        let size = self
+            .shape()
+            .get(dim)
+            .copied()
+            .ok_or(InvariantError::DimOutOfRange {
+                dim,
+                rank: self.rank(),
+            })?;
+        let k = if k > size {
+            log::warn!("topk: k={} exceeds dim size {}, clamping", k, size);
+            size
+        } else {
+            k
+        };
+
+        let lazy_topk = |output| -> anyhow::Result<Tensor> {
+            let topk = TopK::new(self.clone(), k, dim, output);
+            let new_view = topk.infer_output(&[self])?;
+            Ok(Tensor::lazy(
+                LazyOp::TopK(topk),
+                new_view,
+                self.device.clone(),
+            ))
+        };
+        let indices = lazy_topk(TopKOutput::Indices)?;
+        Ok((lazy_topk(TopKOutput::Values(indices.clone()))?, indices))
+    }
+
+    /// # Where
+    ///
+    /// Elementwise select using `self` as the condition,
+    /// taking `on_true` where `self` is nonzero and `on_false` elsewhere.
+    pub fn where_cond(&self, on_true: &Tensor, on_false: &Tensor) -> anyhow::Result<Tensor> {
+        let srcs = [self, on_true, on_false];
+        WhereCond::check_invariants(&srcs)?;
+
+        let where_cond = WhereCond::new(self.clone(), on_true.clone(), on_false.clone());
+        let new_view = where_cond.infer_output(&srcs)?;
+        Ok(Tensor::lazy(
+            LazyOp::WhereCond(where_cond),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
+    /// # Scaled Dot Product Attention
+    ///
+    /// `self` is the query of shape [B, H, M, D], `k` & `v` are [B, H, N, D].
+    /// The optional additive `mask` of shape [M, N] is broadcast over the batch and heads.
+    /// Fused into a single kernel, the [M, N] attention scores are never materialized.
+    pub fn sdpa(&self, k: &Tensor, v: &Tensor, mask: Option<&Tensor>) -> anyhow::Result<Tensor> {
+        let srcs = match mask {
+            Some(m) => rvec![self, k, v, m],
+            None => rvec![self, k, v],
+        };
+        Sdpa::check_invariants(&srcs)?;
+        let scale = (self.shape()[3] as f32).powf(-0.5);
+        let sdpa = Sdpa::new(self.clone(), k.clone(), v.clone(), mask.cloned(), scale);
+        let new_view = sdpa.infer_output(&srcs)?;
+        Ok(Tensor::lazy(
+            LazyOp::Sdpa(sdpa),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
     pub fn layer_norm(
         &self,
         weight: &Tensor,
@@ -309,6 +552,23 @@ impl Tensor {
         ))
     }
 
+    pub fn rms_norm(&self, weight: &Tensor, eps: f32) -> anyhow::Result<Tensor> {
+        let srcs = rvec![self, weight];
+        RMSNorm::check_invariants(&srcs)?;
+        let rms_norm = RMSNorm::new(weight.clone(), eps);
+        let new_view = rms_norm.infer_output(&srcs)?;
+        let norm = Norm::new(self.clone(), NormOp::RMSNorm(rms_norm));
+        Ok(Tensor::lazy(
+            LazyOp::Norm(norm),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
+    /// # Conv1d
+    ///
+    /// Convolves `[N, C_in, L]` with `weight` of shape `[C_out, C_in, KS]`.
+    /// If no bias is provided, a zero bias is bound in its place.
     pub fn conv1d(
         &self,
         weight: &Tensor,
@@ -321,7 +581,11 @@ impl Tensor {
             None => rvec![self, weight],
         };
         Conv::check_invariants(&srcs)?;
-        let conv = Conv::new(self.clone(), weight.clone(), bias.cloned(), stride, padding);
+        let bias = match bias {
+            Some(b) => b.clone(),
+            None => Tensor::zeros::<f32>(&shape![weight.shape()[0]], weight.device()),
+        };
+        let conv = Conv::new(self.clone(), weight.clone(), bias, stride, padding);
         let new_view = conv.infer_output(&[self, weight])?;
         Ok(Tensor::lazy(
             LazyOp::Conv(conv),
@@ -384,6 +648,82 @@ impl Tensor {
         Ok(Tensor::lazy(lazy_op, out_view, self.device.clone()))
     }
 
+    /// # Split
+    ///
+    /// Splits the tensor into consecutive slices along `dim`, with lengths `sizes`.
+    /// `sizes` must sum to the length of `dim`, e.g splitting a fused QKV projection.
+    pub fn split(&self, dim: usize, sizes: &[usize]) -> anyhow::Result<Vec<Tensor>> {
+        let shape = self.shape();
+        if dim >= shape.rank() {
+            return Err(InvariantError::DimOutOfRange {
+                dim,
+                rank: shape.rank(),
+            }
+            .into());
+        }
+        let total: usize = sizes.iter().sum();
+        if total != shape[dim] {
+            anyhow::bail!(
+                "Split sizes {:?} sum to {}, expected {} for dim {} of {:?}",
+                sizes,
+                total,
+                shape[dim],
+                dim,
+                shape
+            );
+        }
+
+        let mut start = 0;
+        let mut splits = Vec::with_capacity(sizes.len());
+        for &size in sizes {
+            let ranges = (0..shape.rank())
+                .map(|d| {
+                    if d == dim {
+                        start..start + size
+                    } else {
+                        0..shape[d]
+                    }
+                })
+                .collect::<RVec<_>>();
+            splits.push(self.slice(&ranges)?);
+            start += size;
+        }
+        Ok(splits)
+    }
+
+    /// # Narrow
+    ///
+    /// Slices `len` elements of `dim` beginning at `start`, leaving all other dimensions whole.
+    pub fn narrow(&self, dim: usize, start: usize, len: usize) -> anyhow::Result<Tensor> {
+        let shape = self.shape();
+        if dim >= shape.rank() {
+            return Err(InvariantError::DimOutOfRange {
+                dim,
+                rank: shape.rank(),
+            }
+            .into());
+        }
+        if start + len > shape[dim] {
+            anyhow::bail!(
+                "Cannot narrow dim {} of {:?} to {}..{}",
+                dim,
+                shape,
+                start,
+                start + len
+            );
+        }
+        let ranges = (0..shape.rank())
+            .map(|d| {
+                if d == dim {
+                    start..start + len
+                } else {
+                    0..shape[d]
+                }
+            })
+            .collect::<RVec<_>>();
+        self.slice(&ranges)
+    }
+
     /// # View
     ///
     /// Creates a new tensor with the same data, but a different shape.
@@ -402,7 +742,50 @@ impl Tensor {
         ))
     }
 
+    /// # Expand
+    ///
+    /// Broadcasts the tensor to `shape` without copying, expanded dims get a stride of 0.
+    /// Only size 1 dims can be expanded, and new dims can only be added on the left.
+    ///
+    /// The result is not contiguous, so far only binary ops read it directly.
+    /// Use [Tensor::broadcast_to] to materialize the broadcast instead.
+    pub fn expand(&self, shape: Shape) -> anyhow::Result<Tensor> {
+        Expand::check_invariants(&[self])?;
+        let expand = Expand::new(self.clone(), shape);
+        let out_view = expand.infer_output(&[self])?;
+
+        Ok(Tensor::from_shallow(
+            LazyOp::Expand(expand),
+            out_view,
+            self.storage.clone(),
+            self.device.clone(),
+        ))
+    }
+
+    pub fn is_contiguous(&self) -> bool {
+        self.view.is_contiguous()
+    }
+
+    /// # Contiguous
+    ///
+    /// Returns the tensor itself if it is already contiguous, otherwise a contiguous copy.
+    /// For kernels that can't read strided inputs, see [crate::Enforcer::assert_contiguous].
+    pub fn contiguous(&self) -> anyhow::Result<Tensor> {
+        if self.is_contiguous() {
+            return Ok(self.clone());
+        }
+        match self.op() {
+            //Expand is the only view with arbitrary strides
+            LazyOp::Expand(expand) => expand
+                .input()
+                .contiguous()?
+                .broadcast_to(self.shape().clone()),
+            op => anyhow::bail!("Cannot make the output of {} contiguous", op.name()),
+        }
+    }
+
     pub fn permute(&self, dims: &[usize]) -> anyhow::Result<Tensor> {
+        Permute::check_invariants(&[self])?;
         let permute = Permute::new(dims.to_vec());
         let out_view = permute.infer_output(&[self])?;
 
@@ -410,6 +793,45 @@ impl Tensor {
         Ok(Tensor::lazy(op, out_view, self.device.clone()))
     }
 
+    /// # Transpose
+    ///
+    /// Swaps `dim0` and `dim1`, leaving every other dim in place.
+    /// A [Tensor::permute] with the two dims exchanged.
+    pub fn transpose(&self, dim0: usize, dim1: usize) -> anyhow::Result<Tensor> {
+        let rank = self.rank();
+        if dim0 >= rank || dim1 >= rank {
+            anyhow::bail!(
+                "Cannot transpose dims {} and {} of a rank {} tensor",
+                dim0,
+                dim1,
+                rank
+            );
+        }
+        let mut dims = (0..rank).collect::<Vec<_>>();
+        dims.swap(dim0, dim1);
+        self.permute(&dims)
+    }
+
+    /// # Split heads
+    ///
+    /// Reshapes `[B, T, H * D]` into `[B, H, T, D]`, the layout attention operates on.
+    /// The last dim must divide evenly into `n_heads`.
+    pub fn split_heads(&self, n_heads: usize) -> anyhow::Result<Tensor> {
+        let [bs, n_ctx, n_state]: [usize; 3] = self.shape().try_into()?;
+        if n_heads == 0 || n_state % n_heads != 0 {
+            anyhow::bail!("Cannot split {} features into {} heads", n_state, n_heads);
+        }
+        self.view(shape![bs, n_ctx, n_heads, n_state / n_heads])?
+            .transpose(1, 2)
+    }
+
+    /// Inverse of [Tensor::split_heads], `[B, H, T, D]` to `[B, T, H * D]`.
+    pub fn merge_heads(&self) -> anyhow::Result<Tensor> {
+        let [bs, n_heads, n_ctx, hdim]: [usize; 4] = self.shape().try_into()?;
+        self.transpose(1, 2)?
+            .view(shape![bs, n_ctx, n_heads * hdim])
+    }
+
     pub fn broadcast_to(&self, shape: Shape) -> anyhow::Result<Tensor> {
         Broadcast::check_invariants(&[self])?;
         let broadcast = Broadcast::new(shape);
@@ -418,6 +840,33 @@ impl Tensor {
         Ok(Tensor::lazy(op, new_view, self.device.clone()))
     }
 
+    /// # Pad
+    ///
+    /// Pads every dimension with `value`, `padding[i]` is the (before, after) amount for dim `i`.
+    /// `padding` must have an entry for every dimension.
+    pub fn pad(&self, padding: &[(usize, usize)], value: f32) -> anyhow::Result<Tensor> {
+        Pad::check_invariants(&[self])?;
+        let pad = Pad::new(self.clone(), padding.into(), value);
+        let new_view = pad.infer_output(&[self])?;
+        Ok(Tensor::lazy(
+            LazyOp::Pad(pad),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
+    /// # Repeat
+    ///
+    /// Tiles the tensor `repeats[i]` times along each dimension, like `torch.Tensor.repeat`.
+    /// `repeats` must have an entry for every dimension.
+    pub fn repeat(&self, repeats: &[usize]) -> anyhow::Result<Tensor> {
+        Repeat::check_invariants(&[self])?;
+        let repeat = Repeat::new(repeats.into());
+        let new_view = repeat.infer_output(&[self])?;
+        let op = LazyOp::Reindex(Reindex::new(self.clone(), ReindexOp::Repeat(repeat)));
+        Ok(Tensor::lazy(op, new_view, self.device.clone()))
+    }
+
     pub fn index_select(&self, indices: &Tensor, dim: usize) -> anyhow::Result<Tensor> {
         IndexSelect::check_invariants(&[self, indices])?;
         let index_select = IndexSelect::new(self.clone(), indices.clone(), dim);
@@ -441,6 +890,44 @@ impl Tensor {
         ))
     }
 
+    /// # Index copy
+    ///
+    /// Writes slice `i` of `src` along `dim` into slice `indices[i]` of `self`.
+    /// Like [Tensor::index_write] this is inplace, use the resultant tensor.
+    pub fn index_copy(&self, dim: usize, indices: &Tensor, src: &Tensor) -> anyhow::Result<Tensor> {
+        let srcs = [self, indices, src];
+        IndexCopy::check_invariants(&srcs)?;
+        let index_copy = IndexCopy::new(self.clone(), indices.clone(), src.clone(), dim);
+        let new_view = index_copy.infer_output(&srcs)?;
+        Ok(Tensor::lazy(
+            LazyOp::IndexCopy(index_copy),
+            new_view,
+            self.device.clone(),
+        ))
+    }
+
+    /// # Custom
+    ///
+    /// Adds a user defined op to the graph, implemented the same way as the built in ops.
+    /// Its WGSL is provided with [crate::register_kernel], without a kernel the op
+    /// runs [MetaOperation::apply_cpu]. The output lives on the device of the first source.
+    pub fn custom<O: Operation + MetaOperation + Send + Sync>(op: O) -> anyhow::Result<Tensor> {
+        let srcs = MetaOperation::srcs(&op);
+        let device = srcs
+            .first()
+            .ok_or(anyhow::anyhow!("{} has no sources", op.kernel_name()))?
+            .device()
+            .clone();
+        O::check_invariants(&srcs)?;
+        let new_view = op.infer_output(&srcs)?;
+        drop(srcs);
+        Ok(Tensor::lazy(
+            LazyOp::Custom(Custom::new(op)),
+            new_view,
+            device,
+        ))
+    }
+
     #[cfg(feature = "rand")]
     pub fn randint<T: TensorDType + rand_distr::uniform::SampleUniform + PartialOrd>(
         low: T,
@@ -481,6 +968,7 @@ impl Tensor {
         Self::from_data(data, shape, device)
     }
 
+    /// Creates a zeroed tensor, cleared on `device` without staging any data on the host.
     pub fn zeros<T: TensorDType>(shape: &Shape, device: &Device) -> Tensor {
         let storage = Storage::zeros::<T>(shape, device);
         let strides = Strides::from(shape);
@@ -488,6 +976,21 @@ impl Tensor {
         Tensor::new(LazyOp::Const, meta, Some(storage), device.clone())
     }
 
+    pub fn ones<T: TensorDType>(shape: &Shape, device: &Device) -> Tensor {
+        Self::full(shape, T::one(), device)
+    }
+
+    /// Creates a tensor with every element set to `value`.
+    ///
+    /// Like [Tensor::zeros], the tensor is instantly resolved, and is filled in bounded
+    /// chunks rather than from a host vec the size of the tensor.
+    pub fn full<T: TensorDType>(shape: &Shape, value: T, device: &Device) -> Tensor {
+        let storage = Storage::full(value, shape, device);
+        let strides = Strides::from(shape);
+        let meta = StorageView::new(shape.clone(), T::dt(), strides);
+        Tensor::new(LazyOp::Const, meta, Some(storage), device.clone())
+    }
+
     /// Creates a new tensor from a chunk of data.
     ///
     /// The Tensor is instantly resolved.
@@ -538,6 +1041,24 @@ impl Tensor {
         Ok(Tensor::new(LazyOp::Const, meta, Some(storage), device))
     }
 
+    /// # From reader
+    ///
+    /// Reads a `dt` tensor of `shape` from the current position of `reader`.
+    /// On GPU the bytes are streamed into a mapped buffer as they are read,
+    /// so the full tensor is never held in host memory.
+    pub fn from_reader<R: Read>(
+        reader: &mut R,
+        dt: DType,
+        shape: Shape,
+        device: Device,
+    ) -> Result<Tensor, DeviceError> {
+        let num_bytes = shape.numel() * dt.size_of();
+        let storage = Storage::from_reader(reader, num_bytes, dt.size_of(), &device)?;
+        let strides = Strides::from(&shape);
+        let meta = StorageView::new(shape, dt, strides);
+        Ok(Tensor::new(LazyOp::Const, meta, Some(storage), device))
+    }
+
     /// # Bindings
     ///
     /// Only applicable to GPU tensors.
@@ -575,12 +1096,36 @@ impl Tensor {
         Ok(slice.to_vec())
     }
 
+    /// Topological order of the graph, sources before consumers.
+    /// Deterministic for a given graph, the sets below are only used for membership.
     pub(crate) fn execution_order(&self) -> Vec<&Tensor> {
+        Tensor::execution_order_all(&[self])
+    }
+
+    /// Topological order of the union of the graphs of `outputs`, each tensor appears once.
+    /// Graphs are traversed in the order of `outputs`.
+    pub(crate) fn execution_order_all<'a>(outputs: &[&'a Tensor]) -> Vec<&'a Tensor> {
         let mut done = HashSet::new();
         let mut pending = HashSet::new();
         let mut order = Vec::new();
 
-        let mut stack: Vec<(&Tensor, usize)> = vec![(self, 0)];
+        let mut stack: Vec<(&Tensor, usize)> = vec![];
+        for output in outputs {
+            if done.contains(&output.id()) {
+                continue;
+            }
+            stack.push((output, 0));
+            Self::visit(&mut stack, &mut done, &mut pending, &mut order);
+        }
+        order
+    }
+
+    fn visit<'a>(
+        stack: &mut Vec<(&'a Tensor, usize)>,
+        done: &mut HashSet<TensorId>,
+        pending: &mut HashSet<TensorId>,
+        order: &mut Vec<&'a Tensor>,
+    ) {
         while let Some((cur_t, cur_src)) = stack.pop() {
             let all_deps_done = cur_src == cur_t.op().srcs().len();
 
@@ -614,8 +1159,6 @@ impl Tensor {
                 stack.push((precursor, 0));
             }
         }
-
-        order
     }
 
     pub fn compile(
@@ -629,24 +1172,78 @@ impl Tensor {
             LazyOp::Matmul(m) => m.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::Softmax(s) => s.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::Unary(u) => u.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::Cast(c) => c.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::Clamp(c) => c.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::Cumsum(c) => c.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::TopK(t) => t.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::Sdpa(a) => a.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::WhereCond(w) => w.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::Pad(p) => p.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::Cmp(c) => c.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::Reindex(r) => r.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::Norm(n) => n.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::Conv(c) => c.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::Select(i) => i.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::IndexWrite(i) => i.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::IndexCopy(i) => i.compile(self, uniform, device, can_inplace).ok(),
+            LazyOp::Custom(c) => c.compile(self, uniform, device, can_inplace).ok(),
             LazyOp::Const => None,
             LazyOp::View(_) => None,
+            LazyOp::Expand(_) => None,
         }
     }
 
     pub fn resolve(self) -> Result<Tensor, TensorError> {
-        let mut uniform = CpuUniform::new();
-        let device = self.device().try_gpu()?;
+        Self::resolve_graph(&[&self])?;
+        Ok(self)
+    }
 
-        let execution_order = self.execution_order();
+    /// # Resolve all
+    ///
+    /// Resolves several tensors in a single pass, e.g both halves of [Tensor::topk],
+    /// or the 3 projections of a fused QKV. Shared parts of their graphs are computed once.
+    ///
+    /// Each output is retained (see [Tensor::retain]), as an output may be written before
+    /// the others are computed, it can't share or give up its buffer.
+    pub fn resolve_all(outputs: &[Tensor]) -> Result<(), TensorError> {
+        let Some(first) = outputs.first() else {
+            return Ok(());
+        };
+        if let Some(other) = outputs.iter().find(|t| t.device() != first.device()) {
+            return Err(crate::DeviceError::DeviceMismatch(
+                format!("{:?}", first.device()),
+                format!("{:?}", other.device()),
+            )
+            .into());
+        }
+        if outputs.len() > 1 {
+            outputs.iter().for_each(|t| {
+                t.retain();
+            });
+        }
+        Self::resolve_graph(&outputs.iter().collect::<Vec<_>>())
+    }
+
+    fn resolve_graph(outputs: &[&Tensor]) -> Result<(), TensorError> {
+        let execution_order = Tensor::execution_order_all(outputs);
+        //Buffers can't be shared between devices, every input has to be moved beforehand.
+        if let Some(t) = execution_order
+            .iter()
+            .find(|t| t.device() != outputs[0].device())
+        {
+            return Err(TensorError::CrossDevice(t.id()));
+        }
+        if outputs[0].device().is_cpu() {
+            return Self::resolve_cpu(&execution_order);
+        }
+
+        let device = outputs[0].device().try_gpu()?;
+        let mut uniform = CpuUniform::with_alignment(device.uniform_alignment());
 
         let mut compiled_ops = Vec::with_capacity(execution_order.len());
         let allocations = device.allocate_cfg(&execution_order, device)?;
+        //Buffers already written in this graph, see [WgpuDevice::set_zero_on_reuse]
+        let mut written = HashSet::new();
         //println!("Allocations: {:#?}", allocations);
 
         for t in execution_order.iter() {
@@ -656,17 +1253,34 @@ impl Tensor {
 
             let id = t.id();
             let graph_buffer = allocations.get(&id).ok_or(TensorError::NoStorage(id))?;
-            assert!(t.device().is_gpu());
             let storage = GPUBuffer {
                 inner: (**graph_buffer.inner()).clone(),
                 alignment: t.dt().size_of(),
             };
             t.update_storage(Storage::GPU(storage));
 
-            //Can inplace && only 1 consumer
-            let can_inplace = t.op().supports_inplace() && Arc::strong_count(&t.inner) == 1;
+            //Can inplace && only 1 consumer && not overwriting a retained value
+            let can_inplace = t.op().supports_inplace()
+                && Arc::strong_count(&t.inner) == 1
+                && !t.retained()
+                && !t.op().srcs().first().is_some_and(|s| s.retained());
+
+            if !t.op().has_gpu_kernel(t) {
+                //Everything before `t` has to be computed before its sources can be read.
+                let pending = std::mem::take(&mut compiled_ops);
+                let fresh = CpuUniform::with_alignment(device.uniform_alignment());
+                Self::dispatch(pending, std::mem::replace(&mut uniform, fresh), device)?;
+                Self::apply_cpu_fallback(t, device)?;
+                written.insert(graph_buffer.global_id());
+                continue;
+            }
 
-            if let Some(compiled_op) = t.compile(&mut uniform, device, can_inplace) {
+            if let Some(mut compiled_op) = t.compile(&mut uniform, device, can_inplace) {
+                //Inplace ops read what their source wrote, only fresh outputs are cleared
+                let first_write = written.insert(graph_buffer.global_id());
+                if device.zero_on_reuse() && !can_inplace && !first_write {
+                    compiled_op.clear_before((**graph_buffer.inner()).clone());
+                }
                 compiled_ops.push(compiled_op);
             }
         }
@@ -674,8 +1288,165 @@ impl Tensor {
         //let last = execution_order.last().unwrap();
         //crate::plot::render_to_file(last, "allocations.svg").unwrap();
 
-        let executable = Executable::new(compiled_ops, uniform.into_gpu(device)?);
-        let index = executable.dispatch_operations(device).unwrap();
+        Self::dispatch(compiled_ops, uniform, device)
+    }
+
+    /// # CPU resolution
+    ///
+    /// Computes each op on the host with [LazyOp::apply_cpu], in execution order.
+    /// Views share the storage of their source, so are resolved along with it.
+    fn resolve_cpu(execution_order: &[&Tensor]) -> Result<(), TensorError> {
+        for t in execution_order.iter().filter(|t| !t.resolved()) {
+            let srcs = t.op().srcs().into_iter().cloned().collect::<Vec<_>>();
+            let result = t.op().apply_cpu(&srcs, t)?;
+            let guard = result.storage();
+            let buffer = guard
+                .as_ref()
+                .ok_or(TensorError::NoStorage(result.id()))?
+                .try_cpu()?;
+            //The GPU kernel writes into its destination, e.g a KV cache, so we do the same
+            if let LazyOp::IndexWrite(_) = t.op() {
+                srcs[0].update_storage(Storage::CPU(buffer.clone()));
+            }
+            t.update_storage(Storage::CPU(buffer.clone()));
+        }
+        Ok(())
+    }
+
+    fn dispatch(
+        compiled_ops: Vec<CompiledOp>,
+        uniform: CpuUniform,
+        device: &WgpuDevice,
+    ) -> Result<(), TensorError> {
+        if compiled_ops.is_empty() {
+            return Ok(());
+        }
+        let index = {
+            let _submission = device.lock_submission();
+            let executable = Executable::new(compiled_ops, uniform.into_gpu(device)?);
+            executable.dispatch_operations(device).unwrap()
+        };
+        device.poll(wgpu::MaintainBase::WaitForSubmissionIndex(index));
+        Ok(())
+    }
+
+    /// # CPU fallback
+    ///
+    /// Computes `t` on the host, for ops without a GPU kernel (see [LazyOp::has_gpu_kernel]).
+    /// The sources are downloaded, and the result is written into the buffer assigned to `t`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_cpu_fallback(t: &Tensor, device: &WgpuDevice) -> Result<(), TensorError> {
+        log::warn!("{} has no GPU kernel, running it on the CPU", t.op().name());
+        let srcs = t
+            .op()
+            .srcs()
+            .iter()
+            .map(|s| {
+                //Pooled buffers can be larger than the tensor they hold
+                let downloaded = s.to_cpu()?;
+                let guard = downloaded.storage();
+                let buffer = guard
+                    .as_ref()
+                    .ok_or(TensorError::TransferError)?
+                    .try_cpu()?;
+                let bytes = &buffer.inner().as_bytes()[..s.num_bytes()];
+                Ok(Tensor::new(
+                    LazyOp::Const,
+                    s.view.clone(),
+                    Some(Storage::CPU(CPUBuffer::from_bytes(bytes, s.dt().size_of()))),
+                    Device::CPU,
+                ))
+            })
+            .collect::<Result<Vec<_>, TensorError>>()?;
+
+        let result = t.op().apply_cpu(&srcs, t)?;
+        let result_guard = result.storage();
+        let bytes = result_guard
+            .as_ref()
+            .ok_or(TensorError::TransferError)?
+            .try_cpu()?
+            .inner()
+            .as_bytes();
+        let dst_guard = t.storage();
+        let dst = dst_guard.as_ref().ok_or(TensorError::NoStorage(t.id()))?;
+        device
+            .queue()
+            .write_buffer(&dst.try_gpu()?.inner, 0, &bytes[..t.num_bytes()]);
+        Ok(())
+    }
+
+    //Reading back is asynchronous on the web, and resolution is not.
+    #[cfg(target_arch = "wasm32")]
+    fn apply_cpu_fallback(t: &Tensor, _device: &WgpuDevice) -> Result<(), TensorError> {
+        Err(OperationError::CompileError(format!(
+            "{} has no GPU kernel, and CPU fallback isn't supported on wasm",
+            t.op().name()
+        ))
+        .into())
+    }
+
+    /// Resolves the graph, retaining each of `keep` so their values can be read afterwards.
+    /// See [Tensor::retain].
+    pub fn resolve_keeping(self, keep: &[&Tensor]) -> Result<Tensor, TensorError> {
+        for t in keep {
+            t.retain();
+        }
+        self.resolve()
+    }
+
+    /// # With buffer
+    ///
+    /// Creates a resolved tensor with the shape & dtype of `self`, backed by `buffer`.
+    /// Used to construct op inputs with known contents, see [Tensor::run_isolated].
+    #[cfg(feature = "testing")]
+    pub fn with_buffer(&self, buffer: GPUBuffer) -> Result<Tensor, TensorError> {
+        self.device().try_gpu()?;
+        let actual = buffer.inner().size() as usize;
+        if actual < self.num_bytes() {
+            return Err(TensorError::BufferTooSmall {
+                expected: self.num_bytes(),
+                actual,
+            });
+        }
+        Ok(Tensor::new(
+            LazyOp::Const,
+            self.view.clone(),
+            Some(Storage::GPU(buffer)),
+            self.device.clone(),
+        ))
+    }
+
+    /// # Run isolated
+    ///
+    /// Executes only this tensor's op, bypassing graph traversal and buffer assignment.
+    /// Every source must already be resolved, the output is written to a fresh buffer.
+    #[cfg(feature = "testing")]
+    pub fn run_isolated(self) -> Result<Tensor, TensorError> {
+        use crate::gpu::{BufferDescriptor, BufferUsagesExt};
+        let device = self.device().try_gpu()?;
+        if let Some(src) = self.op().srcs().iter().find(|s| !s.resolved()) {
+            return Err(TensorError::NoStorage(src.id()));
+        }
+
+        let output = device.get_or_create_buffer(&BufferDescriptor::new(
+            self.num_bytes() as _,
+            wgpu::BufferUsages::standard(),
+            false,
+        ))?;
+        self.update_storage(Storage::GPU(GPUBuffer {
+            inner: output,
+            alignment: self.dt().size_of(),
+        }));
+
+        let mut uniform = CpuUniform::with_alignment(device.uniform_alignment());
+        let compiled_op = self.compile(&mut uniform, device, false).ok_or_else(|| {
+            OperationError::CompileError(format!("Failed to compile {}", self.op().name()))
+        })?;
+        let index = {
+            let _submission = device.lock_submission();
+            let executable = Executable::new(vec![compiled_op], uniform.into_gpu(device)?);
+            executable.dispatch_operations(device).unwrap()
+        };
         device.poll(wgpu::MaintainBase::WaitForSubmissionIndex(index));
         Ok(self)
     }
@@ -700,6 +1471,66 @@ impl Tensor {
         ))
     }
 
+    /// # To async
+    ///
+    /// Transfers the tensor to `device` without stalling on the copy.
+    /// The transfer is started when this is called, only awaiting the returned future waits on it,
+    /// so GPU work submitted in between (e.g the next decoding pass) overlaps with the readback.
+    ///
+    /// Uploads are never waited on, the data is queued ahead of any later pass.
+    pub fn to_async(
+        &self,
+        device: &Device,
+    ) -> impl std::future::Future<Output = Result<Tensor, TensorError>> {
+        enum Started {
+            Done(Tensor),
+            Reading(Box<(PendingRead, WgpuDevice, StorageView)>),
+        }
+        let started = || -> Result<Started, TensorError> {
+            if !self.resolved() {
+                return Ok(Started::Done(self.clone()));
+            }
+            let storage_guard = self.storage();
+            let storage = storage_guard.as_ref().ok_or(TensorError::TransferError)?;
+            match (self.device(), device) {
+                (Device::GPU(src), Device::CPU) => {
+                    let pending = storage.try_gpu()?.read_async(src)?;
+                    Ok(Started::Reading(Box::new((
+                        pending,
+                        src.clone(),
+                        self.view.clone(),
+                    ))))
+                }
+                (Device::CPU, Device::GPU(dst)) => {
+                    let gpu_buf = storage.try_cpu()?.to_gpu_nonblocking(dst);
+                    Ok(Started::Done(Tensor::new(
+                        LazyOp::Const,
+                        self.view.clone(),
+                        Some(Storage::GPU(gpu_buf)),
+                        device.clone(),
+                    )))
+                }
+                _ => Ok(Started::Done(self.clone())),
+            }
+        };
+        let started = started();
+        async move {
+            match started? {
+                Started::Done(t) => Ok(t),
+                Started::Reading(reading) => {
+                    let (pending, src, view) = *reading;
+                    let cpu_buf = pending.wait(&src).await?;
+                    Ok(Tensor::new(
+                        LazyOp::Const,
+                        view,
+                        Some(Storage::CPU(cpu_buf)),
+                        Device::CPU,
+                    ))
+                }
+            }
+        }
+    }
+
     pub fn deep_clone(&self) -> Tensor {
         let storage_guard = self.storage();
         let storage = storage_guard.as_ref().unwrap();
@@ -739,10 +1570,14 @@ impl Tensor {
     /// If the tensor is already on the specified device, it will be returned as-is,
     /// and the underlying storage will not be copied.
     /// If the tensor is on a different device, it will be copied to the specified device.
+    /// Transfers between 2 GPU devices go through the host.
     pub async fn to(&self, device: &Device) -> Result<Tensor, TensorError> {
         match (self.device(), device) {
             (Device::GPU(_), Device::CPU) => self.to_cpu().await,
             (Device::CPU, Device::GPU(_)) => self.to_gpu(device),
+            (Device::GPU(src), Device::GPU(dst)) if src != dst => {
+                self.to_cpu().await?.to_gpu(device)
+            }
             _ => Ok(self.clone()),
         }
     }
@@ -755,10 +1590,12 @@ impl Tensor {
     /// If the tensor is already on the specified device, it will be returned as-is,
     /// and the underlying storage will not be copied.
     /// If the tensor is on a different device, it will be copied to the specified device.
+    /// Transfers between 2 GPU devices go through the host.
     pub fn to(&self, device: &Device) -> Result<Tensor, TensorError> {
         match (self.device(), device) {
             (Device::GPU(_), Device::CPU) => self.to_cpu(),
             (Device::CPU, Device::GPU(_)) => self.to_gpu(device),
+            (Device::GPU(src), Device::GPU(dst)) if src != dst => self.to_cpu()?.to_gpu(device),
             _ => Ok(self.clone()),
         }
     }
@@ -875,6 +1712,68 @@ impl Tensor {
         Ok(Tensor::from_data(data, shape, device.clone()))
     }
 
+    /// # To npy bytes
+    ///
+    /// Serializes the tensor as a `.npy` file, e.g to compare an intermediate against
+    /// a numpy reference. The tensor must be resolved, contiguous and on the CPU.
+    pub fn to_npy_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let descr = match self.dt() {
+            DType::F32 => "<f4",
+            DType::F16 => "<f2",
+            DType::I32 => "<i4",
+            DType::U32 => "<u4",
+            dt => anyhow::bail!("{:?} has no numpy equivalent", dt),
+        };
+        anyhow::ensure!(
+            self.device().is_cpu() && self.resolved() && self.is_contiguous(),
+            "Only resolved, contiguous CPU tensors can be serialized"
+        );
+        let dims = self
+            .shape()
+            .iter()
+            .map(|d| format!("{d},"))
+            .collect::<Vec<_>>();
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': ({}), }}",
+            descr,
+            dims.join(" ")
+        );
+        //The magic, version and header length take 10 bytes, the data starts 64 byte aligned
+        let header_len = (10 + header.len() + 1).next_multiple_of(64) - 10;
+        header.push_str(&" ".repeat(header_len - header.len() - 1));
+        header.push('\n');
+
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header_len as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        let guard = self.storage();
+        let buffer = guard
+            .as_ref()
+            .ok_or(TensorError::NoStorage(self.id()))?
+            .try_cpu()?;
+        bytes.extend(&buffer.inner().as_bytes()[..self.num_bytes()]);
+        Ok(bytes)
+    }
+
+    /// Writes [Tensor::to_npy_bytes] to `path`, reading the tensor back if it's on the GPU.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_npy<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        std::fs::write(path, self.to(&Device::CPU)?.to_npy_bytes()?)?;
+        Ok(())
+    }
+
+    /// # From ndarray
+    ///
+    /// Copies `array` into a new tensor on `device`, with the same shape.
+    /// Non-contiguous arrays, e.g transposed or strided views, are copied in logical order.
+    pub fn from_ndarray<T: TensorDType>(array: ArrayViewD<T>, device: Device) -> Tensor {
+        let shape: Shape = array.shape().to_vec().into();
+        match array.as_slice() {
+            Some(data) => Tensor::from_data(data, shape, device),
+            None => Tensor::from_data(array.iter().copied().collect::<Vec<_>>(), shape, device),
+        }
+    }
+
     pub fn into_ndarray<T: TensorDType>(self) -> ArrayD<T> {
         self.to_ndarray_view().into_owned()
     }
@@ -956,3 +1855,243 @@ impl<T: TensorDType> From<ArrayD<T>> for Tensor {
         }
     }
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{shape, DType, Device, DeviceError, DeviceRequest, Tensor, TensorError};
+
+    #[test]
+    fn to_async_roundtrip() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let a = Tensor::randn::<f32>(shape![17, 33], Device::CPU);
+        let gpu = pollster::block_on(a.to_async(&device))?;
+        let pending = gpu.to_async(&Device::CPU);
+        let b = pollster::block_on(pending)?;
+        assert_eq!(a.to_vec::<f32>()?, b.to_vec::<f32>()?);
+        Ok(())
+    }
+
+    #[test]
+    fn read_back_many() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let a = Tensor::randn::<f32>(shape![4, 8], Device::CPU);
+        let b = Tensor::randn::<f32>(shape![3], Device::CPU);
+        let (a_gpu, b_gpu) = (a.to(&device)?, b.to(&device)?);
+        let host = device.read_back(&[&a_gpu, &b_gpu])?;
+        assert_eq!(host[0].to_vec::<f32>()?, a.to_vec::<f32>()?);
+        assert_eq!(host[1].to_vec::<f32>()?, b.to_vec::<f32>()?);
+        assert!(Device::CPU.read_back(&[&a_gpu]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn poll_nonblocking_after_resolve() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        assert!(!Device::CPU.poll_nonblocking());
+        let a = Tensor::randn::<f32>(shape![64, 64], Device::CPU).to(&device)?;
+        let pending = a.gelu()?.resolve()?.to_async(&Device::CPU);
+        let _ = pollster::block_on(pending)?;
+        assert!(!device.poll_nonblocking());
+        Ok(())
+    }
+
+    #[test]
+    fn npy_roundtrip() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![2, 3, 5], Device::CPU);
+        let bytes = a.to_npy_bytes()?;
+        assert_eq!((bytes.len() - a.num_bytes()) % 64, 0);
+        let b = Tensor::from_npy_bytes::<f32>(&bytes, &Device::CPU)?;
+        assert_eq!(b.shape(), a.shape());
+        assert_eq!(b.to_vec::<f32>()?, a.to_vec::<f32>()?);
+
+        let path = std::env::temp_dir().join("ratchet_npy_roundtrip.npy");
+        let c = Tensor::from_data([3i32, -1, 4], shape![3], Device::CPU);
+        c.save_npy(&path)?;
+        let d = Tensor::from_npy_path::<i32, _>(&path, &Device::CPU)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(d.shape(), &shape![3]);
+        assert_eq!(d.to_vec::<i32>()?, [3, -1, 4]);
+
+        assert!(a.permute(&[2, 0, 1])?.to_npy_bytes().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn full_and_ones() -> anyhow::Result<()> {
+        let a = Tensor::full(&shape![2, 3], 1.5f32, &Device::CPU);
+        assert_eq!(a.to_vec::<f32>()?, vec![1.5; 6]);
+        assert_eq!(
+            Tensor::ones::<i32>(&shape![5], &Device::CPU).to_vec::<i32>()?,
+            [1; 5]
+        );
+
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        //Reuses the buffer freed by `dirty`, which must be cleared
+        let dirty = Tensor::full(&shape![3, 5], 7f32, &device);
+        drop(dirty);
+        let zeros = Tensor::zeros::<f32>(&shape![3, 5], &device).to(&Device::CPU)?;
+        assert_eq!(zeros.to_vec::<f32>()?, vec![0.; 15]);
+        let full = Tensor::full(&shape![3, 5], -2f32, &device).to(&Device::CPU)?;
+        assert_eq!(full.to_vec::<f32>()?, vec![-2.; 15]);
+        Ok(())
+    }
+
+    #[test]
+    fn split_and_merge_heads() -> anyhow::Result<()> {
+        let data = (0..12).map(|i| i as f32).collect::<Vec<_>>();
+        let a = Tensor::from_data(data.clone(), shape![1, 2, 6], Device::CPU);
+        let heads = a.split_heads(3)?;
+        assert_eq!(heads.shape(), &shape![1, 3, 2, 2]);
+        //Head 1 holds features 2..4 of each position
+        let split = heads.resolve()?.to_vec::<f32>()?;
+        assert_eq!(split[4..8], [2., 3., 8., 9.]);
+
+        let merged = a.split_heads(3)?.merge_heads()?.resolve()?;
+        assert_eq!(merged.shape(), a.shape());
+        assert_eq!(merged.to_vec::<f32>()?, data);
+        assert!(a.split_heads(4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn batched_uploads() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let data = (0..4)
+            .map(|i| (0..64).map(|j| (i * 64 + j) as f32).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let uploaded = device.batch_uploads(|| {
+            let nested =
+                device.batch_uploads(|| Tensor::from_data(&data[0], shape![64], device.clone()));
+            let mut uploaded = vec![nested];
+            for d in &data[1..] {
+                uploaded.push(Tensor::from_data(d, shape![64], device.clone()));
+            }
+            uploaded
+        });
+        let uploaded = uploaded.iter().collect::<Vec<_>>();
+        for (host, expected) in device.read_back(&uploaded)?.into_iter().zip(&data) {
+            assert_eq!(&host.to_vec::<f32>()?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn transpose_swaps_two_dims() -> anyhow::Result<()> {
+        let data = (0..24).map(|i| i as f32).collect::<Vec<_>>();
+        let a = Tensor::from_data(data, shape![2, 3, 4], Device::CPU);
+        let t = a.transpose(0, 2)?;
+        assert_eq!(t.shape(), &shape![4, 3, 2]);
+        let expected = a.permute(&[2, 1, 0])?.resolve()?.to_vec::<f32>()?;
+        assert_eq!(t.resolve()?.to_vec::<f32>()?, expected);
+        assert!(a.transpose(1, 3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn expand_is_zero_stride() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![3, 1], Device::CPU);
+        let b = a.expand(shape![2, 3, 4])?;
+        assert_eq!(b.strides().to_vec(), vec![0, 1, 0]);
+        assert!(!b.is_contiguous());
+        assert!(a.expand(shape![2, 4]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn contiguous_materializes_expand() -> anyhow::Result<()> {
+        let a = Tensor::from_data([1f32, 2., 3.], shape![3, 1], Device::CPU);
+        assert_eq!(a.contiguous()?.id(), a.id());
+
+        let expanded = a.expand(shape![2, 3, 2])?;
+        //Shapes line up, only the strides are rejected
+        let rhs = Tensor::randn::<f32>(shape![2, 2, 4], Device::CPU);
+        let err = expanded.matmul(&rhs).unwrap_err();
+        assert!(format!("{err:#}").contains("is not contiguous"));
+        let b = expanded.contiguous()?;
+        assert!(b.is_contiguous());
+        assert_eq!(b.matmul(&rhs)?.shape(), &shape![2, 3, 4]);
+        let b = b.resolve()?.to_vec::<f32>()?;
+        assert_eq!(b, [1., 1., 2., 2., 3., 3.].repeat(2));
+        Ok(())
+    }
+
+    #[test]
+    fn cross_device_transfer() -> anyhow::Result<()> {
+        let a_device = Device::request_device(DeviceRequest::GPU)?;
+        let b_device = Device::request_device(DeviceRequest::GPU)?;
+        let x = Tensor::randn::<f32>(shape![4, 8], Device::CPU);
+        let y = Tensor::randn::<f32>(shape![4, 8], Device::CPU);
+
+        let on_a = x.to(&a_device)?.add(&y.to(&a_device)?)?.resolve()?;
+        let y_b = y.to(&b_device)?;
+        assert!(on_a.add(&y_b)?.resolve().is_err());
+
+        let moved = on_a.to(&b_device)?;
+        assert_eq!(moved.device(), &b_device);
+        let on_b = moved.sub(&y_b)?.resolve()?.to(&Device::CPU)?;
+        x.all_close(&on_b, 1e-5, 1e-5)?;
+        Ok(())
+    }
+
+    #[test]
+    fn from_ndarray_transposed() {
+        let array = ndarray::Array::from_shape_vec((2, 3), vec![1f32, 2., 3., 4., 5., 6.])
+            .unwrap()
+            .into_dyn();
+        let transposed = array.t();
+        let tensor = Tensor::from_ndarray(transposed.view(), Device::CPU);
+        assert_eq!(tensor.shape(), &shape![3, 2]);
+        assert_eq!(
+            tensor.to_vec::<f32>().unwrap(),
+            vec![1., 4., 2., 5., 3., 6.]
+        );
+        assert_eq!(tensor.to_ndarray_view::<f32>(), transposed);
+    }
+
+    #[test]
+    fn narrow_single_dim() -> anyhow::Result<()> {
+        let a = Tensor::from_data(
+            (0..12).map(|x| x as f32).collect::<Vec<_>>(),
+            shape![3, 4],
+            Device::CPU,
+        );
+        let b = a.narrow(1, 1, 2)?.resolve()?;
+        assert_eq!(b.shape(), &shape![3, 2]);
+        assert_eq!(b.to_vec::<f32>()?, vec![1., 2., 5., 6., 9., 10.]);
+        assert!(a.narrow(0, 2, 2).is_err());
+        assert!(a.narrow(2, 0, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn detach_drops_history() -> anyhow::Result<()> {
+        let a = Tensor::randn::<f32>(shape![4, 4], Device::CPU);
+        let b = a.add(&a)?;
+        assert!(matches!(b.detach(), Err(TensorError::NotResolved)));
+
+        let b = b.resolve()?;
+        let detached = b.detach()?;
+        assert!(detached.op().srcs().is_empty());
+        assert_eq!(detached.to_vec::<f32>()?, b.to_vec::<f32>()?);
+
+        drop(b);
+        assert_eq!(Arc::strong_count(&a.inner), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn from_reader_short_read() {
+        let data: Vec<u8> = [1f32, 2., 3., 4.]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let tensor =
+            Tensor::from_reader(&mut &data[..], DType::F32, shape![2, 2], Device::CPU).unwrap();
+        assert_eq!(tensor.to_vec::<f32>().unwrap(), vec![1., 2., 3., 4.]);
+
+        let short = Tensor::from_reader(&mut &data[..12], DType::F32, shape![2, 2], Device::CPU);
+        assert!(matches!(short, Err(DeviceError::ReadFailed(_))));
+    }
+}
@@ -0,0 +1,227 @@
+#[cfg(test)]
+mod tests {
+    use encase::ShaderType;
+    use ratchet::{
+        register_kernel, rvec, shape, wgc, BindGroupLayoutDescriptor, Device, DeviceRequest,
+        KernelElement, MetaOperation, OpMetadata, Operation, OperationError, RVec, StorageView,
+        Tensor, WorkgroupCount,
+    };
+
+    const SCALE_KERNEL: &str = r#"
+@group(0) @binding(0)
+var<storage, read> X: array<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> Y: array<f32>;
+
+struct Meta {
+    numel: u32,
+    factor: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> metadata: Meta;
+
+@compute @workgroup_size(64,1,1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= metadata.numel) {
+        return;
+    }
+    Y[index] = X[index] * metadata.factor;
+}
+"#;
+
+    /// Multiplies every element by a constant, standing in for a new activation.
+    #[derive(Debug, Clone)]
+    struct Scale {
+        input: Tensor,
+        factor: f32,
+        host_only: bool,
+    }
+
+    #[derive(Debug, ShaderType)]
+    struct ScaleMeta {
+        numel: u32,
+        factor: f32,
+    }
+
+    impl OpMetadata for ScaleMeta {}
+
+    impl Operation for Scale {
+        fn check_invariants(srcs: &[&Tensor]) -> Result<(), OperationError> {
+            if srcs.len() != 1 {
+                return Err(OperationError::CompileError("Scale takes one input".into()));
+            }
+            Ok(())
+        }
+
+        fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+            Ok(srcs[0].storage_view().clone())
+        }
+    }
+
+    impl MetaOperation for Scale {
+        type Meta = ScaleMeta;
+
+        fn kernel_name(&self) -> &'static str {
+            "test_scale"
+        }
+
+        fn srcs(&self) -> RVec<&Tensor> {
+            rvec![&self.input]
+        }
+
+        fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+            KernelElement::Scalar
+        }
+
+        fn calculate_dispatch(&self, dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+            let numel = dst.shape().numel();
+            Ok(wgc![WorkgroupCount::div_ceil(numel, 64) as _, 1, 1])
+        }
+
+        fn storage_bind_group_layout(
+            &self,
+            _inplace: bool,
+        ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+            Ok(BindGroupLayoutDescriptor::unary())
+        }
+
+        fn metadata(
+            &self,
+            dst: &Tensor,
+            _kernel_element: &KernelElement,
+        ) -> Result<Self::Meta, OperationError> {
+            Ok(ScaleMeta {
+                numel: dst.shape().numel() as _,
+                factor: self.factor,
+            })
+        }
+
+        fn has_gpu_kernel(&self, _dst: &Tensor) -> bool {
+            !self.host_only
+        }
+
+        fn apply_cpu(&self, srcs: &[Tensor], dst: &Tensor) -> Result<Tensor, OperationError> {
+            let data = srcs[0]
+                .to_vec::<f32>()?
+                .into_iter()
+                .map(|x| x * self.factor)
+                .collect::<Vec<_>>();
+            Ok(Tensor::from_data(data, dst.shape().clone(), Device::CPU))
+        }
+    }
+
+    fn scale(input: &Tensor, factor: f32) -> anyhow::Result<Tensor> {
+        Tensor::custom(Scale {
+            input: input.clone(),
+            factor,
+            host_only: false,
+        })
+    }
+
+    /// Has no kernel registered, nor a CPU implementation.
+    #[derive(Debug, Clone)]
+    struct Unimplemented(Tensor);
+
+    impl Operation for Unimplemented {
+        fn check_invariants(_srcs: &[&Tensor]) -> Result<(), OperationError> {
+            Ok(())
+        }
+
+        fn infer_output(&self, srcs: &[&Tensor]) -> Result<StorageView, OperationError> {
+            Ok(srcs[0].storage_view().clone())
+        }
+    }
+
+    impl MetaOperation for Unimplemented {
+        type Meta = ScaleMeta;
+
+        fn kernel_name(&self) -> &'static str {
+            "test_unimplemented"
+        }
+
+        fn srcs(&self) -> RVec<&Tensor> {
+            rvec![&self.0]
+        }
+
+        fn kernel_element(&self, _dst: &Tensor) -> KernelElement {
+            KernelElement::Scalar
+        }
+
+        fn calculate_dispatch(&self, _dst: &Tensor) -> Result<WorkgroupCount, OperationError> {
+            Ok(wgc![1, 1, 1])
+        }
+
+        fn storage_bind_group_layout(
+            &self,
+            _inplace: bool,
+        ) -> Result<BindGroupLayoutDescriptor, OperationError> {
+            Ok(BindGroupLayoutDescriptor::unary())
+        }
+
+        fn metadata(
+            &self,
+            dst: &Tensor,
+            _kernel_element: &KernelElement,
+        ) -> Result<Self::Meta, OperationError> {
+            Ok(ScaleMeta {
+                numel: dst.shape().numel() as _,
+                factor: 1.,
+            })
+        }
+    }
+
+    #[test]
+    fn custom_op_on_cpu() -> anyhow::Result<()> {
+        let input = Tensor::from_data(vec![1f32, -2., 3., 4.], shape![2, 2], Device::CPU);
+        let result = scale(&input, 2.)?.resolve()?;
+        assert_eq!(result.shape(), &shape![2, 2]);
+        assert_eq!(result.to_vec::<f32>()?, vec![2., -4., 6., 8.]);
+        Ok(())
+    }
+
+    #[test]
+    fn custom_op_on_gpu() -> anyhow::Result<()> {
+        register_kernel("test_scale", KernelElement::Scalar, SCALE_KERNEL)?;
+        assert!(register_kernel("test_scale", KernelElement::Scalar, "").is_err());
+        assert!(register_kernel("clamp", KernelElement::Scalar, SCALE_KERNEL).is_err());
+
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let input = Tensor::randn::<f32>(shape![3, 67], Device::CPU);
+        let expected = scale(&input, 0.5)?.resolve()?;
+        let ours = scale(&input.to(&device)?, 0.5)?
+            .resolve()?
+            .to(&Device::CPU)?;
+        expected.all_close(&ours, 1e-6, 1e-6)?;
+        Ok(())
+    }
+
+    #[test]
+    fn host_only_op_in_gpu_graph() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let input = Tensor::randn::<f32>(shape![3, 67], Device::CPU);
+        let expected = input.clone().add(&input)?.resolve()?;
+        let expected = scale(&expected, 3.)?.add(&expected)?.resolve()?;
+
+        //GPU ops either side of the fallback, so it has to read and write device buffers
+        let doubled = input.to(&device)?;
+        let doubled = doubled.clone().add(&doubled)?;
+        let scaled = Tensor::custom(Scale {
+            input: doubled.clone(),
+            factor: 3.,
+            host_only: true,
+        })?;
+        let ours = scaled.add(&doubled)?.resolve()?.to(&Device::CPU)?;
+        expected.all_close(&ours, 1e-6, 1e-6)?;
+
+        let err = Tensor::custom(Unimplemented(input.to(&device)?))?
+            .resolve()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("test_unimplemented has no CPU implementation"));
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use half::f16;
 use ratchet::{DType, Device, Shape, Tensor};
 use std::{
     collections::HashMap,
-    io::{BufRead, Seek, SeekFrom},
+    io::{BufRead, Read, Seek, SeekFrom},
     mem::MaybeUninit,
 };
 
@@ -108,13 +108,98 @@ impl TensorHeader {
     }
 
     fn data_size(&self) -> usize {
-        self.numel * self.dtype.type_size() / self.dtype.block_size()
+        let params = match self.dtype {
+            GgmlDType::PerChannelU8 => self.channels() * (4 + 1),
+            _ => 0,
+        };
+        params + self.numel * self.dtype.type_size() / self.dtype.block_size()
+    }
+
+    fn channels(&self) -> usize {
+        self.shape.get(0).copied().unwrap_or(1)
+    }
+
+    /// # Per-channel dequantization
+    ///
+    /// The data of a [GgmlDType::PerChannelU8] tensor is laid out as:
+    ///
+    /// - `shape[0]` f32 scales
+    /// - `shape[0]` u8 zero points
+    /// - the u8 values, channel after channel
+    ///
+    /// Each value is restored as `(q - zero_point) * scale`, the F32 bytes are returned.
+    pub fn dequantize_per_channel<R: BufRead + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Vec<u8>, LoadError> {
+        let data = self.read_data(reader)?;
+        let channels = self.channels();
+        let (scales, rest) = data.split_at(channels * 4);
+        let (zero_points, values) = rest.split_at(channels);
+        let per_channel = self.numel / channels.max(1);
+
+        let mut dequantized = Vec::with_capacity(self.numel * 4);
+        for (c, channel) in values.chunks_exact(per_channel.max(1)).enumerate() {
+            let scale = f32::from_le_bytes(scales[c * 4..][..4].try_into().unwrap());
+            let zero_point = zero_points[c] as f32;
+            for &q in channel {
+                dequantized.extend(((q as f32 - zero_point) * scale).to_le_bytes());
+            }
+        }
+        Ok(dequantized)
     }
 
-    pub fn read_data<R: BufRead + Seek>(&self, reader: &mut R) -> std::io::Result<Vec<u8>> {
+    /// Reads the tensor's data, erroring if fewer bytes than the header declares remain.
+    pub fn read_data<R: BufRead + Seek>(&self, reader: &mut R) -> Result<Vec<u8>, LoadError> {
         let n_bytes = self.data_size();
         reader.seek(SeekFrom::Start(self.start_offset))?;
-        reader.read_bytes_with_len(n_bytes)
+        let mut data = Vec::with_capacity(n_bytes);
+        reader.take(n_bytes as u64).read_to_end(&mut data)?;
+        if data.len() != n_bytes {
+            return Err(LoadError::SizeMismatch {
+                name: self.name.clone(),
+                expected: n_bytes,
+                actual: data.len(),
+            });
+        }
+        Ok(data)
+    }
+
+    /// Reads the tensor's data directly into a tensor on `device` without converting it.
+    /// On GPU the bytes go straight from the reader into the buffer, see [Tensor::from_reader].
+    ///
+    /// Only valid for unquantized types, where the data is exactly `numel` elements.
+    fn stream_to<R: BufRead + Seek>(
+        &self,
+        reader: &mut R,
+        device: &Device,
+    ) -> Result<Tensor, LoadError> {
+        let dtype: DType = self.dtype.into();
+        reader.seek(SeekFrom::Start(self.start_offset))?;
+        let mut data = reader.take(self.data_size() as u64);
+        Tensor::from_reader(&mut data, dtype, self.shape.clone(), device.clone()).map_err(|e| {
+            LoadError::InvariantBroken(format!("Failed to load tensor {}: {}", self.name, e))
+        })
+    }
+
+    /// Checks the header describes a whole number of blocks that fits before `end`.
+    fn validate(&self, end: u64) -> Result<(), LoadError> {
+        let block_size = self.dtype.block_size();
+        if self.numel % block_size != 0 {
+            return Err(LoadError::InvariantBroken(format!(
+                "Tensor {} has {} elements, not a multiple of the {:?} block size {}",
+                self.name, self.numel, self.dtype, block_size
+            )));
+        }
+        let available = end.saturating_sub(self.start_offset) as usize;
+        if self.data_size() > available {
+            return Err(LoadError::SizeMismatch {
+                name: self.name.clone(),
+                expected: self.data_size(),
+                actual: available,
+            });
+        }
+        Ok(())
     }
 }
 
@@ -125,28 +210,118 @@ pub struct GGMLModel<M: GGMLCompatible> {
 }
 
 impl<M: GGMLCompatible> GGMLModel<M> {
+    /// Looks up `key`, suggesting the closest tensor names if it's missing,
+    /// e.g when loading a different variant of the model.
+    fn header(&self, key: &str) -> Result<&TensorHeader, LoadError> {
+        self.tensors
+            .get(key)
+            .ok_or_else(|| LoadError::MissingTensor {
+                name: key.to_string(),
+                suggestions: closest_names(key, self.tensors.keys()),
+            })
+    }
+
     pub fn load_tensor<R: BufRead + Seek>(
         &self,
         key: &str,
         reader: &mut R,
         device: &Device,
     ) -> Result<Tensor, LoadError> {
-        let header = self.tensors.get(key).ok_or(LoadError::MissingTensor {
-            name: key.to_string(),
-        })?;
-        let mut data = header.read_data(reader)?;
-        let shape = header.shape.clone();
-        let mut dt: DType = header.dtype.into();
-        if dt == DType::F16 {
-            log::error!("F16 is not supported by wgpu, converting to F32");
-            //TODO: terrible cast whilst wgpu doesn't support F16
-            let f16_data = bytemuck::cast_slice::<u8, f16>(&data);
-            let f32_data = f16_data.iter().map(|f| f.to_f32()).collect::<Vec<_>>();
-            data = bytemuck::cast_slice::<f32, u8>(&f32_data).to_vec();
-            dt = DType::F32;
+        let header = self.header(key)?;
+        let dt = match header.dtype.into() {
+            DType::F16 => {
+                //TODO: terrible cast whilst wgpu doesn't support F16
+                log::error!("F16 is not supported by wgpu, converting to F32");
+                DType::F32
+            }
+            dt => dt,
+        };
+        self.load_tensor_as(key, reader, device, dt)
+    }
+
+    /// # Load tensor as
+    ///
+    /// Loads `key`, converting it to `dtype` as it is read.
+    /// Only conversions between F32 and F16 are supported, [GgmlDType::PerChannelU8]
+    /// tensors are dequantized to F32 first.
+    pub fn load_tensor_as<R: BufRead + Seek>(
+        &self,
+        key: &str,
+        reader: &mut R,
+        device: &Device,
+        dtype: DType,
+    ) -> Result<Tensor, LoadError> {
+        let header = self.header(key)?;
+        let (stored, data) = if header.dtype == GgmlDType::PerChannelU8 {
+            (DType::F32, header.dequantize_per_channel(reader)?)
+        } else {
+            let stored: DType = header.dtype.into();
+            if stored == dtype && !dtype.is_quantized() {
+                return header.stream_to(reader, device);
+            }
+            (stored, header.read_data(reader)?)
+        };
+        let data = match (stored, dtype) {
+            (from, to) if from == to => data,
+            (DType::F16, DType::F32) => data
+                .chunks_exact(2)
+                .flat_map(|b| f16::from_le_bytes([b[0], b[1]]).to_f32().to_le_bytes())
+                .collect(),
+            (DType::F32, DType::F16) => data
+                .chunks_exact(4)
+                .flat_map(|b| {
+                    f16::from_f32(f32::from_le_bytes([b[0], b[1], b[2], b[3]])).to_le_bytes()
+                })
+                .collect(),
+            (from, to) => {
+                return Err(LoadError::UnsupportedConversion {
+                    name: key.to_string(),
+                    from,
+                    to,
+                })
+            }
+        };
+        let expected = header.numel * dtype.size_of();
+        if matches!(dtype, DType::F32 | DType::F16) && data.len() != expected {
+            return Err(LoadError::SizeMismatch {
+                name: key.to_string(),
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(Tensor::from_bytes(&data, dtype, header.shape.clone(), device.clone()).unwrap())
+    }
+}
+
+/// Up to 3 of `names` within a few edits of `key`, closest first.
+fn closest_names<'a>(key: &str, names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let max_distance = (key.chars().count() / 4).max(2);
+    let mut close = names
+        .map(|name| (edit_distance(key, name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    close.sort();
+    close
+        .into_iter()
+        .take(3)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// Levenshtein distance, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
         }
-        Ok(Tensor::from_bytes(&data, dt, shape, device.clone()).unwrap())
     }
+    row[b.len()]
 }
 
 struct GGMLLoader {}
@@ -163,6 +338,7 @@ impl GGMLLoader {
         let mut total_size = 0;
         while reader.stream_position()? != last_position {
             let header = Self::load_single(reader)?;
+            header.validate(last_position)?;
             total_size += header.data_size() as u64;
             tensor_map.insert(header.name.clone(), header);
         }
@@ -231,3 +407,80 @@ impl GGMLWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Cursor, Seek};
+
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use ratchet::{shape, DType, Device};
+
+    use super::{GGMLCompatible, GGMLModel, TensorHeader};
+    use crate::{GgmlDType, LoadError};
+
+    struct Headerless;
+
+    impl GGMLCompatible for Headerless {
+        type ModelHeader = ();
+
+        fn load_header<R: BufRead + Seek>(_: &mut R) -> Result<(), LoadError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn per_channel_dequant() -> anyhow::Result<()> {
+        let mut bytes = vec![];
+        for scale in [0.5f32, 2.] {
+            bytes.write_f32::<LittleEndian>(scale)?;
+        }
+        bytes.extend([128u8, 10]);
+        bytes.extend([128u8, 130, 126, 10, 11, 9]);
+        let header = TensorHeader::new("w".into(), shape![2, 3], GgmlDType::PerChannelU8, 0);
+        assert_eq!(header.data_size(), bytes.len());
+
+        let dequantized = header.dequantize_per_channel(&mut Cursor::new(&bytes))?;
+        let values = dequantized
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![0., 1., -1., 0., 2., -2.]);
+
+        let model = GGMLModel::<Headerless>::new((), [("w".to_string(), header)].into());
+        let t = model.load_tensor("w", &mut Cursor::new(&bytes), &Device::CPU)?;
+        assert_eq!(t.dt(), DType::F32);
+        assert_eq!(t.to_vec::<f32>()?, values);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_tensor_suggestions() {
+        let model = GGMLModel::<Headerless>::new(
+            (),
+            ["decoder.token_embeddings.weight", "decoder.ln.weight"]
+                .into_iter()
+                .map(|name| {
+                    let header = TensorHeader::new(name.into(), shape![1], GgmlDType::F32, 0);
+                    (name.to_string(), header)
+                })
+                .collect(),
+        );
+        let err = model
+            .load_tensor(
+                "decoder.token_embedding.weight",
+                &mut Cursor::new(vec![]),
+                &Device::CPU,
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Tensor 'decoder.token_embedding.weight' not found; \
+             did you mean 'decoder.token_embeddings.weight'?"
+        );
+
+        let err = model
+            .load_tensor("encoder.conv1.bias", &mut Cursor::new(vec![]), &Device::CPU)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Tensor 'encoder.conv1.bias' not found");
+    }
+}
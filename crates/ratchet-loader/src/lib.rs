@@ -19,10 +19,35 @@ pub enum LoadError {
     InvariantBroken(String),
     #[error("invalid data type {0}")]
     InvalidDType(u32),
-    #[error("Missing tensor {name}")]
-    MissingTensor { name: String },
+    #[error("Tensor '{name}' not found{}", did_you_mean(.suggestions))]
+    MissingTensor {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    #[error("Tensor {name} should be {expected} bytes, read {actual}")]
+    SizeMismatch {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Cannot convert tensor {name} from {from:?} to {to:?}")]
+    UnsupportedConversion {
+        name: String,
+        from: ratchet::DType,
+        to: ratchet::DType,
+    },
 }
 
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        _ => format!("; did you mean '{}'?", suggestions.join("', '")),
+    }
+}
+
+/// Type id of [GgmlDType::PerChannelU8], well clear of the ids GGML assigns.
+pub const PER_CHANNEL_U8: u32 = 0x100;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GgmlDType {
     F32,
@@ -39,6 +64,9 @@ pub enum GgmlDType {
     Q5K,
     Q6K,
     Q8K,
+    /// Not a GGML type. Asymmetric per-channel u8, each of the `shape[0]` channels has an
+    /// f32 scale and a u8 zero point, see [TensorHeader::dequantize_per_channel].
+    PerChannelU8,
 }
 
 impl From<GgmlDType> for ratchet::DType {
@@ -46,6 +74,8 @@ impl From<GgmlDType> for ratchet::DType {
         match val {
             GgmlDType::F32 => ratchet::DType::F32,
             GgmlDType::F16 => ratchet::DType::F16,
+            //Dequantized as it is loaded
+            GgmlDType::PerChannelU8 => ratchet::DType::F32,
             _ => unimplemented!(),
         }
     }
@@ -70,6 +100,7 @@ impl TryFrom<u32> for GgmlDType {
             13 => Self::Q5K,
             14 => Self::Q6K,
             15 => Self::Q8K,
+            PER_CHANNEL_U8 => Self::PerChannelU8,
             _ => return Err(LoadError::InvalidDType(u)),
         };
         Ok(dtype)
@@ -93,6 +124,7 @@ impl GgmlDType {
             Self::Q5K => 13,
             Self::Q6K => 14,
             Self::Q8K => 15,
+            Self::PerChannelU8 => PER_CHANNEL_U8,
         }
     }
 
@@ -115,6 +147,8 @@ impl GgmlDType {
             Self::Q5K => std::mem::size_of::<BlockQ5K>(),
             Self::Q6K => std::mem::size_of::<BlockQ6K>(),
             Self::Q8K => std::mem::size_of::<BlockQ8K>(),
+            //Excludes the per-channel parameters
+            Self::PerChannelU8 => 1,
         }
     }
 
@@ -123,6 +157,7 @@ impl GgmlDType {
         match self {
             Self::F32 => 1,
             Self::F16 => 1,
+            Self::PerChannelU8 => 1,
             Self::Q4_0 => k_quants::QK4_0,
             Self::Q4_1 => k_quants::QK4_1,
             Self::Q5_0 => k_quants::QK5_0,
@@ -0,0 +1,102 @@
+//! Encoder and greedy decode throughput for whisper-tiny on the JFK sample.
+//!
+//! `cargo bench -p ratchet-models --bench decode`
+//!
+//! `decode` runs a fixed number of greedy steps, so its throughput is tokens/second
+//! and its time divided by [N_TOKENS] is the per-token decode time.
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use hf_hub::api::sync::Api;
+use ndarray::s;
+use ndarray_stats::QuantileExt;
+use ratchet::{shape, Device, DeviceRequest, Tensor};
+use ratchet_loader::GGMLCompatible;
+use ratchet_models::{Whisper, WhisperDecoder, WhisperEncoder};
+use ratchet_nn::Module;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const N_TOKENS: usize = 32;
+const SOT_SEQUENCE: [i32; 3] = [50258, 50259, 50359];
+//Pass indices must increase across iterations, or the buffer pool stops aging
+static PASS: AtomicU64 = AtomicU64::new(0);
+
+fn load_npy(path: PathBuf) -> Vec<f32> {
+    let bytes = std::fs::read(path).unwrap();
+    npyz::NpyFile::new(&bytes[..]).unwrap().into_vec().unwrap()
+}
+
+/// Runs [N_TOKENS] greedy steps from the SOT sequence, feeding one token at a time.
+fn greedy_decode(decoder: &mut WhisperDecoder, audio_ctx: &Tensor, device: &Device) -> Vec<i32> {
+    decoder.cache_mut().reset();
+    let mut tokens = SOT_SEQUENCE.to_vec();
+    let mut all_tokens = tokens.clone();
+    for _ in 0..N_TOKENS {
+        let pass = PASS.fetch_add(1, Ordering::Relaxed);
+        device.try_gpu().unwrap().begin_pass(pass);
+        let token_t = Tensor::from_data(tokens.clone(), shape![1, tokens.len()], device.clone());
+        let logits = decoder
+            .forward(&[audio_ctx.clone(), token_t])
+            .unwrap()
+            .resolve()
+            .unwrap()
+            .to(&Device::CPU)
+            .unwrap();
+        decoder.cache_mut().update(tokens.len());
+
+        let nd_logits = logits.to_ndarray_view::<f32>();
+        let last = nd_logits.slice(s![0, -1, ..]);
+        let next = last.argmax_skipnan().unwrap() as i32;
+        tokens = vec![next];
+        all_tokens.push(next);
+    }
+    all_tokens
+}
+
+fn whisper_tiny(c: &mut Criterion) {
+    let api = Api::new().unwrap();
+    let model = api.model("ggerganov/whisper.cpp".to_string());
+    let path = model.get("ggml-tiny.bin").unwrap();
+    let dataset = api.dataset("FL33TW00D-HF/ratchet-util".to_string());
+    let mel = load_npy(dataset.get("jfk_tiny_encoder_input.npy").unwrap());
+    let hs = load_npy(dataset.get("jfk_tiny_encoder_hs.npy").unwrap());
+
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+    let gg_disk = Whisper::load_ggml(&mut reader).unwrap();
+    let device = Device::request_device(DeviceRequest::GPU).unwrap();
+    let encoder = WhisperEncoder::load(&gg_disk, &mut reader, &device).unwrap();
+    let mut decoder = WhisperDecoder::load(&gg_disk, &mut reader, &device).unwrap();
+
+    let mel = Tensor::from_data(mel, shape![1, 80, 3000], device.clone());
+    let audio_ctx = Tensor::from_data(hs, shape![1, 1500, 384], device.clone());
+
+    let mut group = c.benchmark_group("whisper-tiny");
+    group.sample_size(10);
+
+    group.bench_function("encoder", |b| {
+        b.iter(|| {
+            encoder
+                .forward(&mel)
+                .unwrap()
+                .resolve()
+                .unwrap()
+                .to(&Device::CPU)
+                .unwrap()
+        })
+    });
+
+    group.throughput(Throughput::Elements(N_TOKENS as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| greedy_decode(&mut decoder, &audio_ctx, &device))
+    });
+
+    group.bench_function("total", |b| {
+        b.iter(|| {
+            let audio_ctx = encoder.forward(&mel).unwrap().resolve().unwrap();
+            greedy_decode(&mut decoder, &audio_ctx, &device)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, whisper_tiny);
+criterion_main!(benches);
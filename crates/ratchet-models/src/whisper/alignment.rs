@@ -0,0 +1,241 @@
+//Adapted from: https://github.com/openai/whisper/blob/1cea4357687b676b293cb5473e1ade25f5b1cef7/whisper/timing.py
+use ndarray::{s, Array2, Array3, ArrayView2, Axis};
+
+use crate::{WhisperTokenizer, HOP_LENGTH, SAMPLE_RATE};
+
+const MEDFILT_WIDTH: usize = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordTiming {
+    pub word: String,
+    pub tokens: Vec<i32>,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Dynamic time warping over a [n_tokens, n_frames] cost matrix.
+/// Returns the token and frame index of each step along the optimal path.
+pub fn dtw(x: ArrayView2<f32>) -> (Vec<usize>, Vec<usize>) {
+    let (n, m) = x.dim();
+    let mut cost = Array2::<f32>::from_elem((n + 1, m + 1), f32::INFINITY);
+    let mut trace = Array2::<i8>::from_elem((n + 1, m + 1), -1);
+    cost[[0, 0]] = 0.0;
+
+    for j in 1..m + 1 {
+        for i in 1..n + 1 {
+            let c0 = cost[[i - 1, j - 1]];
+            let c1 = cost[[i - 1, j]];
+            let c2 = cost[[i, j - 1]];
+
+            let (c, t) = if c0 < c1 && c0 < c2 {
+                (c0, 0)
+            } else if c1 < c0 && c1 < c2 {
+                (c1, 1)
+            } else {
+                (c2, 2)
+            };
+
+            cost[[i, j]] = x[[i - 1, j - 1]] + c;
+            trace[[i, j]] = t;
+        }
+    }
+
+    //Backtrace
+    let (mut i, mut j) = (n, m);
+    trace.slice_mut(s![0, ..]).fill(2);
+    trace.slice_mut(s![.., 0]).fill(1);
+
+    let mut text_indices = vec![];
+    let mut time_indices = vec![];
+    while i > 0 || j > 0 {
+        text_indices.push(i - 1);
+        time_indices.push(j - 1);
+        match trace[[i, j]] {
+            0 => {
+                i -= 1;
+                j -= 1;
+            }
+            1 => i -= 1,
+            2 => j -= 1,
+            _ => unreachable!("Unexpected trace value"),
+        }
+    }
+    text_indices.reverse();
+    time_indices.reverse();
+    (text_indices, time_indices)
+}
+
+/// Median filter along the last axis, with reflection padding.
+fn median_filter(x: &Array3<f32>, width: usize) -> Array3<f32> {
+    let pad = width / 2;
+    let (n_heads, n_tokens, n_frames) = x.dim();
+    if n_frames <= pad {
+        return x.clone();
+    }
+    let reflect = |idx: isize| -> usize {
+        if idx < 0 {
+            (-idx) as usize
+        } else if idx >= n_frames as isize {
+            (2 * (n_frames as isize - 1) - idx) as usize
+        } else {
+            idx as usize
+        }
+    };
+
+    let mut filtered = x.clone();
+    let mut window = Vec::with_capacity(width);
+    for h in 0..n_heads {
+        for t in 0..n_tokens {
+            let lane = x.slice(s![h, t, ..]);
+            for f in 0..n_frames {
+                window.clear();
+                window.extend(
+                    (0..width).map(|k| lane[reflect(f as isize + k as isize - pad as isize)]),
+                );
+                window.sort_by(|a, b| a.total_cmp(b));
+                filtered[[h, t, f]] = window[pad];
+            }
+        }
+    }
+    filtered
+}
+
+/// # Word level timestamps
+///
+/// `weights` are the cross attention weights of the alignment heads,
+/// of shape [n_heads, n_tokens, n_audio_ctx], computed over the sequence
+/// `[sot_sequence.., NO_TIMESTAMPS, text_tokens.., EOT]`.
+/// `num_frames` is the number of mel frames in the segment.
+pub fn find_alignment(
+    weights: Array3<f32>,
+    text_tokens: &[i32],
+    num_frames: usize,
+    sot_len: usize,
+    tokenizer: &WhisperTokenizer,
+) -> anyhow::Result<Vec<WordTiming>> {
+    if text_tokens.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut weights = weights.slice(s![.., .., ..num_frames / 2]).to_owned();
+    //The weights were softmaxed over the entire context, renormalize over the audio we keep
+    let row_sums = weights.sum_axis(Axis(2)).insert_axis(Axis(2));
+    weights /= &row_sums.mapv(|x| x.max(f32::EPSILON));
+
+    let mean = weights.mean_axis(Axis(1)).unwrap().insert_axis(Axis(1));
+    let std = weights.std_axis(Axis(1), 0.0).insert_axis(Axis(1));
+    weights = (weights - &mean) / &std.mapv(|x| x.max(f32::EPSILON));
+    let weights = median_filter(&weights, MEDFILT_WIDTH);
+
+    let matrix = weights.mean_axis(Axis(0)).unwrap();
+    let n_tokens = matrix.shape()[0];
+    let matrix = matrix.slice(s![sot_len..n_tokens - 1, ..]).mapv(|x| -x);
+    let (text_indices, time_indices) = dtw(matrix.view());
+
+    let mut with_eot = text_tokens.to_vec();
+    with_eot.push(tokenizer.special().eot);
+    let (words, word_tokens) = tokenizer
+        .split_to_word_tokens(&with_eot)
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(align_words(
+        words,
+        word_tokens,
+        &text_indices,
+        &time_indices,
+    ))
+}
+
+/// Times each word by where the alignment path reaches its first token, and the token after
+/// its last. `words` end with EOT, which only bounds the last word.
+fn align_words(
+    words: Vec<String>,
+    word_tokens: Vec<Vec<i32>>,
+    text_indices: &[usize],
+    time_indices: &[usize],
+) -> Vec<WordTiming> {
+    let tokens_per_second = (SAMPLE_RATE / (HOP_LENGTH * 2)) as f32;
+    let mut word_boundaries = vec![0];
+    for t in &word_tokens[..word_tokens.len() - 1] {
+        word_boundaries.push(word_boundaries[word_boundaries.len() - 1] + t.len());
+    }
+
+    let jump_times = text_indices
+        .iter()
+        .enumerate()
+        .filter(|(idx, &ti)| *idx == 0 || ti != text_indices[idx - 1])
+        .map(|(idx, _)| time_indices[idx] as f32 / tokens_per_second)
+        .collect::<Vec<_>>();
+
+    words
+        .into_iter()
+        .zip(word_tokens)
+        .zip(word_boundaries.windows(2))
+        .filter_map(|((word, tokens), bounds)| {
+            let start = *jump_times.get(bounds[0])?;
+            let end = *jump_times.get(bounds[1])?;
+            Some(WordTiming {
+                word,
+                tokens,
+                start,
+                end,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{align_words, dtw, median_filter, WordTiming};
+    use ndarray::{array, Array3};
+
+    #[test]
+    fn dtw_follows_diagonal() {
+        let cost = array![
+            [0.0f32, 1.0, 1.0, 1.0],
+            [1.0, 0.0, 1.0, 1.0],
+            [1.0, 1.0, 0.0, 0.0],
+        ];
+        let (text_indices, time_indices) = dtw(cost.view());
+        assert_eq!(text_indices, vec![0, 1, 2, 2]);
+        assert_eq!(time_indices, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn median_filter_reflects_edges() {
+        let x = Array3::from_shape_vec((1, 1, 5), vec![1.0f32, 9.0, 2.0, 3.0, 8.0]).unwrap();
+        let filtered = median_filter(&x, 3);
+        assert_eq!(filtered.into_raw_vec(), vec![9.0, 2.0, 3.0, 3.0, 3.0]);
+
+        //Too short to pad, left as is
+        let x = Array3::from_shape_vec((1, 1, 2), vec![1.0f32, 9.0]).unwrap();
+        assert_eq!(median_filter(&x, 7), x);
+    }
+
+    #[test]
+    fn words_span_their_tokens() {
+        //" hello" is two tokens, " world" one, followed by EOT
+        let words = vec![" hello".to_string(), " world".to_string(), "".to_string()];
+        let word_tokens = vec![vec![1, 2], vec![3], vec![50257]];
+        let text_indices = [0, 0, 1, 2, 2, 3];
+        let time_indices = [0, 25, 50, 75, 100, 125];
+
+        let timings = align_words(words, word_tokens, &text_indices, &time_indices);
+        assert_eq!(
+            timings,
+            vec![
+                WordTiming {
+                    word: " hello".to_string(),
+                    tokens: vec![1, 2],
+                    start: 0.0,
+                    end: 1.5,
+                },
+                WordTiming {
+                    word: " world".to_string(),
+                    tokens: vec![3],
+                    start: 1.5,
+                    end: 2.5,
+                },
+            ]
+        );
+    }
+}
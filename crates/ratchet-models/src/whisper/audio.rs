@@ -0,0 +1,442 @@
+use std::f64::consts::PI;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{AudioError, SAMPLE_RATE};
+
+/// Zero crossings of the sinc on either side of the interpolation point.
+/// Higher is sharper but more expensive, 16 keeps aliasing well below the spectrogram floor.
+const ZERO_CROSSINGS: f64 = 16.0;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Decoded WAV file, samples are interleaved and normalized to [-1, 1].
+#[derive(Debug, Clone)]
+pub struct WavAudio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+impl WavAudio {
+    /// Downmixes and resamples to the 16kHz mono Whisper expects.
+    pub fn into_whisper_input(self) -> Result<Vec<f32>, AudioError> {
+        let mut input = AudioInput::new(self.sample_rate, self.channels)?;
+        input.push(&self.samples);
+        Ok(input.finish())
+    }
+}
+
+/// Decodes a RIFF WAV file.
+///
+/// Supports integer PCM of 8, 16, 24 & 32 bits and 32 bit float, including
+/// `WAVE_FORMAT_EXTENSIBLE` headers wrapping either.
+pub fn decode_wav(bytes: &[u8]) -> Result<WavAudio, AudioError> {
+    let invalid = |msg: &str| AudioError::InvalidWav(msg.to_string());
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid("Missing RIFF/WAVE header"));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut cursor = 12;
+    while cursor + 8 <= bytes.len() {
+        let id = &bytes[cursor..cursor + 4];
+        let len = u32::from_le_bytes(bytes[cursor + 4..cursor + 8].try_into().unwrap()) as usize;
+        let body_start = cursor + 8;
+        //Streams written on the fly may leave the data length unset (0xFFFFFFFF)
+        let remaining = bytes.len() - body_start;
+        let body = &bytes[body_start..body_start + len.min(remaining)];
+        match id {
+            b"fmt " => format = Some(WavFormat::parse(body)?),
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        if len >= remaining {
+            break;
+        }
+        //Chunks are padded to an even number of bytes
+        cursor = body_start + len + (len & 1);
+    }
+
+    let format = format.ok_or_else(|| invalid("Missing fmt chunk"))?;
+    let data = data.ok_or_else(|| invalid("Missing data chunk"))?;
+    let samples = format.decode(data)?;
+    Ok(WavAudio {
+        samples,
+        sample_rate: format.sample_rate,
+        channels: format.channels as usize,
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WavFormat {
+    tag: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+}
+
+impl WavFormat {
+    fn parse(body: &[u8]) -> Result<Self, AudioError> {
+        if body.len() < 16 {
+            return Err(AudioError::InvalidWav("fmt chunk too short".to_string()));
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+        let mut tag = u16_at(0);
+        if tag == WAVE_FORMAT_EXTENSIBLE && body.len() >= 26 {
+            //First 2 bytes of the SubFormat GUID hold the actual format tag
+            tag = u16_at(24);
+        }
+        let format = Self {
+            tag,
+            channels: u16_at(2),
+            sample_rate: u32::from_le_bytes(body[4..8].try_into().unwrap()),
+            bits_per_sample: u16_at(14),
+        };
+        if format.channels == 0 || format.sample_rate == 0 {
+            return Err(AudioError::InvalidWav(format!(
+                "{} channels at {}Hz",
+                format.channels, format.sample_rate
+            )));
+        }
+        Ok(format)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<f32>, AudioError> {
+        let samples = match (self.tag, self.bits_per_sample) {
+            (WAVE_FORMAT_PCM, 8) => data.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+            (WAVE_FORMAT_PCM, 16) => data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+            (WAVE_FORMAT_PCM, 24) => data
+                .chunks_exact(3)
+                .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.0)
+                .collect(),
+            (WAVE_FORMAT_PCM, 32) => data
+                .chunks_exact(4)
+                .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.0)
+                .collect(),
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => data
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            (format, bits) => return Err(AudioError::UnsupportedWav { format, bits }),
+        };
+        Ok(samples)
+    }
+}
+
+/// Converts interleaved PCM at an arbitrary rate into 16kHz mono, incrementally.
+///
+/// Audio can be pushed as it arrives (e.g from a Web Audio worklet), converted samples
+/// are available from [AudioInput::take] as soon as the resampling filter has enough context.
+#[derive(Debug, Clone)]
+pub struct AudioInput {
+    channels: usize,
+    partial_frame: Vec<f32>,
+    resampler: Resampler,
+    output: Vec<f32>,
+}
+
+impl AudioInput {
+    pub fn new(sample_rate: u32, channels: usize) -> Result<Self, AudioError> {
+        if sample_rate == 0 || channels == 0 {
+            return Err(AudioError::InvalidAudio(anyhow::anyhow!(
+                "Invalid PCM format: {} channels at {}Hz",
+                channels,
+                sample_rate
+            )));
+        }
+        Ok(Self {
+            channels,
+            partial_frame: Vec::with_capacity(channels),
+            resampler: Resampler::new(sample_rate, SAMPLE_RATE as u32),
+            output: vec![],
+        })
+    }
+
+    /// Push interleaved samples, frames may be split across calls.
+    pub fn push(&mut self, interleaved: &[f32]) {
+        let mut mono = Vec::with_capacity(interleaved.len() / self.channels + 1);
+        for &sample in interleaved {
+            self.partial_frame.push(sample);
+            if self.partial_frame.len() == self.channels {
+                mono.push(self.partial_frame.iter().sum::<f32>() / self.channels as f32);
+                self.partial_frame.clear();
+            }
+        }
+        self.resampler.process(&mono, &mut self.output);
+    }
+
+    /// Takes the 16kHz mono samples produced so far.
+    pub fn take(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Flushes the resampler, returning all remaining 16kHz mono samples.
+    pub fn finish(mut self) -> Vec<f32> {
+        self.resampler.flush(&mut self.output);
+        self.output
+    }
+}
+
+/// Resamples mono audio from `from_hz` to `to_hz`, e.g 48kHz from an `AudioContext`
+/// to the 16kHz required by the spectrogram. Both rates must be non-zero.
+///
+/// For audio arriving in chunks, or with multiple channels, see [AudioInput].
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn resample(samples: &[f32], from_hz: u32, to_hz: u32) -> Vec<f32> {
+    assert!(from_hz > 0 && to_hz > 0, "Sample rates must be non-zero");
+    let mut resampler = Resampler::new(from_hz, to_hz);
+    let mut output = Vec::with_capacity(samples.len() * to_hz as usize / from_hz as usize + 1);
+    resampler.process(samples, &mut output);
+    resampler.flush(&mut output);
+    output
+}
+
+/// Streaming windowed sinc resampler.
+///
+/// Output sample `n` sits at input position `n * from / to`, and is the sum of the surrounding
+/// input samples weighted by a Hann windowed sinc. When downsampling the sinc is widened
+/// so that it also low-pass filters below the new Nyquist frequency.
+#[derive(Debug, Clone)]
+pub(crate) struct Resampler {
+    from_hz: u64,
+    to_hz: u64,
+    cutoff: f64,
+    half_width: f64,
+    pending: Vec<f32>,
+    pending_start: u64,
+    consumed: u64,
+    next_out: u64,
+}
+
+impl Resampler {
+    pub(crate) fn new(from_hz: u32, to_hz: u32) -> Self {
+        let cutoff = (to_hz as f64 / from_hz as f64).min(1.0);
+        Self {
+            from_hz: from_hz as u64,
+            to_hz: to_hz as u64,
+            cutoff,
+            half_width: ZERO_CROSSINGS / cutoff,
+            pending: vec![],
+            pending_start: 0,
+            consumed: 0,
+            next_out: 0,
+        }
+    }
+
+    pub(crate) fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        if self.from_hz == self.to_hz {
+            output.extend_from_slice(input);
+            return;
+        }
+        self.pending.extend_from_slice(input);
+        self.consumed += input.len() as u64;
+        self.drain(output, false);
+    }
+
+    /// Emits the remaining samples, treating everything after the end of the input as silence.
+    pub(crate) fn flush(&mut self, output: &mut Vec<f32>) {
+        if self.from_hz != self.to_hz {
+            self.drain(output, true);
+        }
+    }
+
+    fn position(&self, n: u64) -> f64 {
+        (n * self.from_hz) as f64 / self.to_hz as f64
+    }
+
+    fn drain(&mut self, output: &mut Vec<f32>, flush: bool) {
+        let total_out = (self.consumed * self.to_hz).div_ceil(self.from_hz);
+        loop {
+            let t = self.position(self.next_out);
+            let ready = if flush {
+                self.next_out < total_out
+            } else {
+                t + self.half_width < self.consumed as f64
+            };
+            if !ready {
+                break;
+            }
+            output.push(self.interpolate(t));
+            self.next_out += 1;
+        }
+
+        //Discard input that no future output sample can reach
+        let keep_from = (self.position(self.next_out) - self.half_width)
+            .floor()
+            .max(0.0) as u64;
+        if keep_from > self.pending_start {
+            let discard = ((keep_from - self.pending_start) as usize).min(self.pending.len());
+            self.pending.drain(..discard);
+            self.pending_start += discard as u64;
+        }
+    }
+
+    fn interpolate(&self, t: f64) -> f32 {
+        let lo = ((t - self.half_width).ceil().max(0.0) as u64).max(self.pending_start);
+        let hi = ((t + self.half_width).floor() as u64).min(self.consumed.saturating_sub(1));
+        let mut acc = 0f64;
+        for i in lo..=hi {
+            let sample = self.pending[(i - self.pending_start) as usize] as f64;
+            acc += sample * self.kernel(t - i as f64);
+        }
+        acc as f32
+    }
+
+    fn kernel(&self, x: f64) -> f64 {
+        let u = x / self.half_width;
+        if u.abs() >= 1.0 {
+            return 0.0;
+        }
+        let window = 0.5 * (1.0 + (PI * u).cos());
+        let arg = PI * self.cutoff * x;
+        let sinc = if arg.abs() < 1e-9 {
+            1.0
+        } else {
+            arg.sin() / arg
+        };
+        self.cutoff * sinc * window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav_bytes(format: u16, channels: u16, rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&format.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&rate.to_le_bytes());
+        let block_align = channels * bits / 8;
+        bytes.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn decodes_pcm16_stereo() {
+        let data = [i16::MAX, i16::MIN, 0, 16384]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect::<Vec<_>>();
+        let wav = decode_wav(&wav_bytes(WAVE_FORMAT_PCM, 2, 16000, 16, &data)).unwrap();
+        assert_eq!(wav.channels, 2);
+        assert_eq!(wav.sample_rate, 16000);
+        assert_eq!(wav.samples, vec![32767. / 32768., -1., 0., 0.5]);
+
+        let mono = wav.into_whisper_input().unwrap();
+        assert_eq!(mono, vec![(32767. / 32768. - 1.) / 2., 0.25]);
+    }
+
+    #[test]
+    fn decodes_float() {
+        let data = [0.25f32, -0.75]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect::<Vec<_>>();
+        let wav = decode_wav(&wav_bytes(WAVE_FORMAT_IEEE_FLOAT, 1, 8000, 32, &data)).unwrap();
+        assert_eq!(wav.samples, vec![0.25, -0.75]);
+    }
+
+    #[test]
+    fn unset_data_length_reads_to_eof() {
+        let data = [0.25f32, -0.75]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut bytes = wav_bytes(WAVE_FORMAT_IEEE_FLOAT, 1, 8000, 32, &data);
+        let len_at = bytes.len() - data.len() - 4;
+        bytes[len_at..len_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let wav = decode_wav(&bytes).unwrap();
+        assert_eq!(wav.samples, vec![0.25, -0.75]);
+    }
+
+    #[test]
+    fn rejects_unsupported() {
+        assert!(decode_wav(b"not a wav file").is_err());
+        let wav = wav_bytes(WAVE_FORMAT_IEEE_FLOAT, 1, 8000, 64, &[0; 8]);
+        assert!(matches!(
+            decode_wav(&wav),
+            Err(AudioError::UnsupportedWav {
+                format: 3,
+                bits: 64
+            })
+        ));
+    }
+
+    fn tone(freq: f32, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2. * std::f32::consts::PI * freq * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn resample_preserves_passband() {
+        for (from, to) in [(48000, 16000), (44100, 16000), (8000, 16000)] {
+            let input = tone(1000., from, from as usize);
+            let ours = resample(&input, from, to);
+            assert_eq!(ours.len(), to as usize);
+
+            //Skip the edges, where the filter sees the implicit silence
+            let expected = tone(1000., to, to as usize);
+            let interior = 256..to as usize - 256;
+            let max_err = ours[interior.clone()]
+                .iter()
+                .zip(&expected[interior])
+                .map(|(a, b)| (a - b).abs())
+                .fold(0f32, f32::max);
+            assert!(max_err < 1e-2, "{}->{}: max error {}", from, to, max_err);
+        }
+    }
+
+    #[test]
+    fn resample_removes_aliases() {
+        //10kHz is above the 8kHz Nyquist limit at 16kHz, it must not fold back to 6kHz
+        let input = tone(10000., 48000, 48000);
+        let ours = resample(&input, 48000, 16000);
+        let attenuated = rms(&ours[256..ours.len() - 256]);
+        assert!(attenuated < 1e-2, "Alias energy {}", attenuated);
+    }
+
+    #[test]
+    fn streaming_matches_oneshot() {
+        let rate = 44100;
+        let tone = (0..rate)
+            .map(|i| (2. * std::f32::consts::PI * 440. * i as f32 / rate as f32).sin())
+            .collect::<Vec<_>>();
+
+        let mut oneshot = AudioInput::new(rate as u32, 1).unwrap();
+        oneshot.push(&tone);
+        let expected = oneshot.finish();
+        assert_eq!(expected.len(), SAMPLE_RATE);
+
+        let mut streaming = AudioInput::new(rate as u32, 1).unwrap();
+        let mut ours = vec![];
+        for chunk in tone.chunks(1000) {
+            streaming.push(chunk);
+            ours.extend(streaming.take());
+        }
+        ours.extend(streaming.finish());
+        assert_eq!(ours, expected);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use ratchet::{DType, Device, Tensor};
+
+use crate::Whisper;
+
+/// # Compare models
+///
+/// Pairs the tensors of `a` and `b` by name, and reports every tensor whose maximum absolute
+/// difference exceeds `tol`, in the order `a` holds them. Meant to validate a conversion
+/// between formats, e.g a byte order, transpose or scale bug shows up as a single tensor.
+///
+/// Tensors missing from either model, or with mismatched shapes, are reported as infinitely
+/// different.
+pub fn compare_models(a: &Whisper, b: &Whisper, tol: f32) -> anyhow::Result<Vec<(String, f32)>> {
+    compare_named(
+        &Whisper::named_tensors(&a.encoder, &a.decoder),
+        &Whisper::named_tensors(&b.encoder, &b.decoder),
+        tol,
+    )
+}
+
+pub(crate) fn compare_named(
+    a: &[(String, Tensor)],
+    b: &[(String, Tensor)],
+    tol: f32,
+) -> anyhow::Result<Vec<(String, f32)>> {
+    let mut b = b.iter().cloned().collect::<HashMap<_, _>>();
+    let mut diffs = vec![];
+    for (name, a_tensor) in a {
+        let diff = match b.remove(name) {
+            Some(b_tensor) if b_tensor.shape() == a_tensor.shape() => {
+                let (a_host, b_host) = (host_f32(a_tensor)?, host_f32(&b_tensor)?);
+                a_host
+                    .iter()
+                    .zip(&b_host)
+                    .map(|(x, y)| (x - y).abs())
+                    //NaN on either side never matches
+                    .fold(0f32, |max, d| {
+                        max.max(if d.is_nan() { f32::INFINITY } else { d })
+                    })
+            }
+            _ => f32::INFINITY,
+        };
+        if diff > tol {
+            diffs.push((name.clone(), diff));
+        }
+    }
+    let mut missing_from_a = b.into_keys().collect::<Vec<_>>();
+    missing_from_a.sort();
+    diffs.extend(missing_from_a.into_iter().map(|name| (name, f32::INFINITY)));
+    Ok(diffs)
+}
+
+fn host_f32(tensor: &Tensor) -> anyhow::Result<Vec<f32>> {
+    let host = tensor.cast(DType::F32)?.resolve()?.to(&Device::CPU)?;
+    host.to_vec::<f32>()
+}
@@ -0,0 +1,26 @@
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+/// # Compression ratio
+///
+/// Ratio between the size of the UTF-8 text and its zlib compressed size.
+/// Repetitive hallucinations compress very well, a ratio above ~2.4 is a strong signal.
+pub fn compression_ratio(text: &str) -> f32 {
+    let bytes = text.as_bytes();
+    //Same level as python's zlib.compress default
+    let compressed = compress_to_vec_zlib(bytes, 6);
+    bytes.len() as f32 / compressed.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compression_ratio;
+
+    #[test]
+    fn repetitive_text_compresses() {
+        let natural = "And so my fellow Americans, ask not what your country can do for you.";
+        assert!(compression_ratio(natural) < 2.4);
+
+        let repetitive = " Thank you.".repeat(20);
+        assert!(compression_ratio(&repetitive) > 2.4);
+    }
+}
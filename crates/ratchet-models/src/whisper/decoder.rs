@@ -4,15 +4,26 @@ use ratchet::prelude::*;
 use ratchet_loader::GGMLModel;
 use ratchet_nn::{Embedding, KVCache, LayerNorm, Module};
 
-use crate::{ResidualAttentionBlock, ResidualAttentionBlockInputs, Whisper};
+use crate::{
+    push_weights, DecodeError, ResidualAttentionBlock, ResidualAttentionBlockInputs, Whisper,
+};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct DecoderStem {
     pub token_embed: Embedding,
     pub pos_embed: Tensor,
 }
 
 impl DecoderStem {
+    fn named_tensors(&self, out: &mut Vec<(String, Tensor)>) {
+        let token_embed = self.token_embed.weight.clone();
+        out.push(("decoder.token_embedding.weight".into(), token_embed));
+        out.push((
+            "decoder.positional_embedding".into(),
+            self.pos_embed.clone(),
+        ));
+    }
+
     pub fn load<R: BufRead + Seek>(
         disk_model: &GGMLModel<Whisper>,
         reader: &mut R,
@@ -32,6 +43,8 @@ impl DecoderStem {
 #[derive(Debug)]
 pub struct StemInput {
     pub tokens: Tensor,
+    /// Position of the first token in `tokens`, i.e the number of tokens already in the KV cache.
+    /// Positional embeddings `[offset, offset + num_tokens)` are applied.
     pub offset: usize,
 }
 
@@ -41,13 +54,19 @@ impl Module for DecoderStem {
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
         let StemInput { tokens, offset } = input;
         let num_tokens = tokens.shape()[tokens.rank() - 1];
-        let start = *offset;
-        let end = *offset + num_tokens;
-        let sliced = self
-            .pos_embed
-            .slice(&[start..end, 0..self.pos_embed.shape()[1]])?;
+        let limit = self.pos_embed.shape()[0];
+        if offset + num_tokens > limit {
+            return Err(DecodeError::ContextExceeded {
+                requested: offset + num_tokens,
+                limit,
+            }
+            .into());
+        }
+        let sliced = self.pos_embed.narrow(0, *offset, num_tokens)?;
         self.token_embed.forward(tokens)?.add(&sliced)
     }
+
+    impl_parameters!(module(token_embed), tensor(pos_embed));
 }
 
 #[derive(Debug)]
@@ -64,11 +83,49 @@ impl Module for WhisperDecoder {
     type Input = [Tensor; 2];
 
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
+        Ok(self.forward_with_x_attn(input)?.0)
+    }
+
+    impl_parameters!(module(stem), each(blocks), module(ln_post));
+
+    /// Also moves the causal mask and the KV cache, so decoding can resume on `device`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn to(&mut self, device: &Device) -> anyhow::Result<()> {
+        for param in self.parameters_mut() {
+            *param = param.to(device)?;
+        }
+        self.mask = self.mask.to(device)?;
+        self.cache = self.cache.to(device)?;
+        self.device = device.clone();
+        Ok(())
+    }
+}
+
+impl WhisperDecoder {
+    pub const MAX_CACHE: usize = 512;
+
+    /// Computes the logits, alongside the cross attention weights of every block.
+    /// Each weight tensor has shape [bs, n_heads, n_tokens, n_audio_ctx].
+    ///
+    /// The encoder may run on another device, `audio_ctx` must then be moved to
+    /// [WhisperDecoder::device] with [Tensor::to].
+    pub fn forward_with_x_attn(
+        &self,
+        input: &[Tensor; 2],
+    ) -> anyhow::Result<(Tensor, Vec<Tensor>)> {
         let [audio_ctx, tokens] = input;
+        if audio_ctx.device() != &self.device {
+            anyhow::bail!(
+                "audio_ctx is on {:?}, but the decoder is on {:?}, move it with `Tensor::to`",
+                audio_ctx.device(),
+                self.device
+            );
+        }
         let mut x = self.stem.forward(&StemInput {
             tokens: tokens.clone(),
             offset: self.cache.entries(0),
         })?;
+        let mut x_attn_weights = Vec::with_capacity(self.blocks.len());
         for (block_idx, block) in self.blocks.iter().enumerate() {
             let block_input = ResidualAttentionBlockInputs {
                 x,
@@ -76,21 +133,110 @@ impl Module for WhisperDecoder {
                 mask: Some(self.mask.clone()),
                 cache: Some(self.cache[block_idx].clone()),
             };
-            x = block.forward(&block_input)?;
+            let (out, weights) = block.forward_with_x_attn(&block_input)?;
+            x = out;
+            x_attn_weights.extend(weights);
         }
         x = self.ln_post.forward(&x)?;
         let logits = x.matmul(&self.stem.token_embed.weight.permute(&[1, 0])?)?;
-        Ok(logits)
+        Ok((logits, x_attn_weights))
     }
-}
 
-impl WhisperDecoder {
-    pub const MAX_CACHE: usize = 512;
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
 
     pub fn cache_mut(&mut self) -> &mut KVCache {
         &mut self.cache
     }
 
+    /// Replaces the KV cache with an empty one holding `max_len` positions,
+    /// see [KVCache::with_capacity].
+    pub fn set_cache_capacity(&mut self, max_len: usize) {
+        let mut cache_shape = self.cache[0].k_cache.shape().clone();
+        cache_shape[1] = max_len;
+        self.cache = KVCache::new(self.blocks.len() as _, &cache_shape, &self.device);
+    }
+
+    /// # Fork
+    ///
+    /// A decoder sharing this one's weights, with an empty KV cache of its own.
+    /// Each concurrent transcription stream needs its own cache, the weights are never
+    /// written so any number of forks can decode on the same device at once.
+    pub fn fork(&self) -> Self {
+        let cache_shape = self.cache[0].k_cache.shape().clone();
+        Self {
+            stem: self.stem.clone(),
+            blocks: self.blocks.clone(),
+            mask: self.mask.clone(),
+            ln_post: self.ln_post.clone(),
+            cache: KVCache::new(self.blocks.len() as _, &cache_shape, &self.device),
+            device: self.device.clone(),
+        }
+    }
+
+    /// Width of the logits, one per row of the token embedding.
+    pub fn n_vocab(&self) -> usize {
+        self.stem.token_embed.weight.shape()[0]
+    }
+
+    /// Number of positions the decoder can attend over, `n_text_ctx` unless extended.
+    pub fn n_ctx(&self) -> usize {
+        self.stem.pos_embed.shape()[0]
+    }
+
+    /// # Extend context
+    ///
+    /// Linearly interpolates the positional embeddings to `n_ctx` positions, so more tokens
+    /// than the model was trained on can be decoded. The trained positions are stretched
+    /// over the new range rather than extrapolated, which degrades gracefully for modest
+    /// extensions but quality drops the further it goes.
+    ///
+    /// The causal mask, and the KV cache if it is too small, are rebuilt. Clears the cache.
+    pub fn extend_context(&mut self, n_ctx: usize) -> anyhow::Result<()> {
+        let trained = self.n_ctx();
+        if n_ctx < trained {
+            anyhow::bail!("Cannot shrink the context from {} to {}", trained, n_ctx);
+        }
+        let interpolation = Tensor::from_data(
+            Self::interpolation_weights(trained, n_ctx),
+            shape![n_ctx, trained],
+            self.device.clone(),
+        );
+        self.stem.pos_embed = interpolation
+            .matmul(&self.stem.pos_embed)?
+            .resolve()?
+            .detach()?;
+        self.mask = Self::load_mask(n_ctx, &self.device);
+
+        self.set_cache_capacity(self.cache.capacity().max(n_ctx));
+        Ok(())
+    }
+
+    /// Row-major `[to, from]` matrix, row `i` samples position `i * (from - 1) / (to - 1)`.
+    fn interpolation_weights(from: usize, to: usize) -> Vec<f32> {
+        let scale = (from - 1) as f32 / (to - 1).max(1) as f32;
+        let mut weights = vec![0f32; to * from];
+        for (i, row) in weights.chunks_exact_mut(from).enumerate() {
+            let position = i as f32 * scale;
+            let lower = (position.floor() as usize).min(from - 1);
+            let upper = (lower + 1).min(from - 1);
+            let frac = position - lower as f32;
+            row[lower] += 1. - frac;
+            row[upper] += frac;
+        }
+        weights
+    }
+
+    pub(crate) fn named_tensors(&self, out: &mut Vec<(String, Tensor)>) {
+        self.stem.named_tensors(out);
+        for (i, block) in self.blocks.iter().enumerate() {
+            block.named_tensors(&format!("decoder.blocks.{i}"), out);
+        }
+        let ln_post = &self.ln_post;
+        push_weights(out, "decoder.ln", ln_post.weight(), ln_post.bias());
+    }
+
     fn load_mask(n_ctx: usize, device: &Device) -> Tensor {
         let mask: Vec<_> = (0..n_ctx)
             .flat_map(|i| (0..n_ctx).map(move |j| if j > i { f32::NEG_INFINITY } else { 0f32 }))
@@ -134,7 +280,13 @@ impl WhisperDecoder {
             blocks,
             mask: Self::load_mask(hparams.n_text_ctx as _, device),
             ln_post: LayerNorm::new(lt("weight")?, Some(lt("bias")?), 1e-5),
-            cache: KVCache::new(n_layers, &shape![1, Self::MAX_CACHE, n_state], device),
+            cache: KVCache::with_capacity(
+                n_layers as _,
+                n_heads as _,
+                n_state / n_heads as usize,
+                Self::MAX_CACHE,
+                device,
+            ),
             device: device.clone(),
         })
     }
@@ -142,7 +294,9 @@ impl WhisperDecoder {
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
-    use crate::{DecodingOptions, DecodingOptionsBuilder, Whisper, WhisperDecoder};
+    use crate::{
+        DecoderStem, DecodingOptions, DecodingOptionsBuilder, StemInput, Whisper, WhisperDecoder,
+    };
     use hf_hub::api::sync::Api;
     use ndarray::{s, Axis};
     use ndarray_stats::QuantileExt;
@@ -153,7 +307,7 @@ mod tests {
     };
     use ratchet::{shape, Device, DeviceRequest, Tensor};
     use ratchet_loader::GGMLCompatible;
-    use ratchet_nn::Module;
+    use ratchet_nn::{Embedding, Module};
     use std::path::PathBuf;
     use tokenizers::Tokenizer;
 
@@ -246,4 +400,27 @@ def ground(options):
         */
         Ok(())
     }
+
+    #[test]
+    fn stem_applies_position_offset() -> anyhow::Result<()> {
+        let device = Device::request_device(DeviceRequest::GPU)?;
+        let (n_ctx, n_state) = (6, 8);
+        let pos_data = (0..n_ctx * n_state).map(|x| x as f32).collect::<Vec<_>>();
+        let stem = DecoderStem {
+            token_embed: Embedding::new(Tensor::zeros::<f32>(&shape![4, n_state], &device)),
+            pos_embed: Tensor::from_data(&pos_data, shape![n_ctx, n_state], device.clone()),
+        };
+
+        //A single cached step at position 3 must use position 3's embedding
+        let tokens = Tensor::from_data([1i32, 2], shape![1, 2], device.clone());
+        let x = stem
+            .forward(&StemInput { tokens, offset: 3 })?
+            .resolve()?
+            .to(&Device::CPU)?;
+        assert_eq!(
+            x.to_vec::<f32>()?,
+            pos_data[3 * n_state..5 * n_state].to_vec()
+        );
+        Ok(())
+    }
 }
@@ -2,26 +2,33 @@ use std::io::{BufRead, Seek};
 
 use ratchet::{Device, Tensor};
 use ratchet_loader::GGMLModel;
-use ratchet_nn::{LayerNorm, Module};
+use ratchet_nn::{Conv1d, LayerNorm, Module};
 
-use crate::{ResidualAttentionBlock, ResidualAttentionBlockInputs, Whisper};
+use crate::{
+    features_to_bytes, push_weights, ResidualAttentionBlock, ResidualAttentionBlockInputs, Whisper,
+};
 
-#[derive(Debug, derive_new::new)]
+#[derive(Debug)]
 struct ConvBlock {
-    w: Tensor,
-    b: Tensor,
-    stride: usize,
-    padding: usize,
+    conv: Conv1d,
+}
+
+impl ConvBlock {
+    fn new(w: Tensor, b: Tensor, stride: usize, padding: usize) -> Self {
+        Self {
+            conv: Conv1d::new(w, Some(b), stride, padding),
+        }
+    }
 }
 
 impl Module for ConvBlock {
     type Input = Tensor;
 
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
-        input
-            .conv1d(&self.w, Some(&self.b), self.stride, self.padding)?
-            .gelu()
+        self.conv.forward(input)?.gelu()
     }
+
+    impl_parameters!(module(conv));
 }
 
 #[derive(Debug)]
@@ -35,12 +42,31 @@ impl Module for EncoderStem {
     type Input = Tensor;
 
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
+        //Input is [B, n_mels, n_frames], conv1 weight is [n_state, n_mels, kernel_size]
+        let expected = self.conv1.conv.weight().shape()[1];
+        let n_mels = input.shape()[1];
+        if n_mels != expected {
+            anyhow::bail!("Encoder expects {expected} mel bins, got {n_mels}");
+        }
         let convolved = self.conv2.forward(&self.conv1.forward(input)?)?;
         convolved.permute(&[0, 2, 1])?.add(&self.pos_embed)
     }
+
+    impl_parameters!(module(conv1), module(conv2), tensor(pos_embed));
 }
 
 impl EncoderStem {
+    fn named_tensors(&self, out: &mut Vec<(String, Tensor)>) {
+        for (name, block) in [("conv1", &self.conv1), ("conv2", &self.conv2)] {
+            let conv = &block.conv;
+            push_weights(out, &format!("encoder.{name}"), conv.weight(), conv.bias());
+        }
+        out.push((
+            "encoder.positional_embedding".into(),
+            self.pos_embed.clone(),
+        ));
+    }
+
     pub fn load<R: BufRead + Seek>(
         disk_model: &GGMLModel<Whisper>,
         reader: &mut R,
@@ -82,9 +108,36 @@ impl Module for WhisperEncoder {
         }
         self.ln_post.forward(&x)
     }
+
+    impl_parameters!(module(stem), each(blocks), module(ln_post));
 }
 
 impl WhisperEncoder {
+    /// Number of audio positions, the mel input has twice as many frames.
+    pub fn n_ctx(&self) -> usize {
+        self.stem.pos_embed.shape()[0]
+    }
+
+    /// Runs the encoder over `mel`, and serializes the resulting features with [features_to_bytes].
+    /// These can be decoded elsewhere, see [crate::decode_features].
+    pub async fn export_features(&self, mel: &Tensor) -> anyhow::Result<Vec<u8>> {
+        let features = self.forward(mel)?.resolve()?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let features = features.to(&Device::CPU)?;
+        #[cfg(target_arch = "wasm32")]
+        let features = features.to(&Device::CPU).await?;
+        features_to_bytes(&features)
+    }
+
+    pub(crate) fn named_tensors(&self, out: &mut Vec<(String, Tensor)>) {
+        self.stem.named_tensors(out);
+        for (i, block) in self.blocks.iter().enumerate() {
+            block.named_tensors(&format!("encoder.blocks.{i}"), out);
+        }
+        let ln_post = &self.ln_post;
+        push_weights(out, "encoder.ln_post", ln_post.weight(), ln_post.bias());
+    }
+
     pub fn load<R: BufRead + Seek>(
         disk_model: &GGMLModel<Whisper>,
         reader: &mut R,
@@ -0,0 +1,71 @@
+use std::io::Cursor;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ratchet::{shape, DType, Device, Shape, Tensor};
+
+/// # Feature export
+///
+/// Serializes the encoder output (`audio_ctx`), so the encoder and the decoder can run on
+/// different machines. The layout is the rank, each dimension and then the data,
+/// all as little endian u32/f32.
+///
+/// `features` must be a resolved F32 tensor on the CPU.
+pub fn features_to_bytes(features: &Tensor) -> anyhow::Result<Vec<u8>> {
+    if features.dt() != DType::F32 {
+        anyhow::bail!("Expected F32 features, got {:?}", features.dt());
+    }
+    let shape = features.shape();
+    let data = features.to_vec::<f32>()?;
+
+    let mut bytes = Vec::with_capacity(4 * (1 + shape.rank() + data.len()));
+    bytes.write_u32::<LittleEndian>(shape.rank() as u32)?;
+    for &dim in shape.iter() {
+        bytes.write_u32::<LittleEndian>(dim as u32)?;
+    }
+    for x in data {
+        bytes.write_f32::<LittleEndian>(x)?;
+    }
+    Ok(bytes)
+}
+
+/// Inverse of [features_to_bytes], the features are placed on `device`.
+pub fn features_from_bytes(bytes: &[u8], device: &Device) -> anyhow::Result<Tensor> {
+    let mut reader = Cursor::new(bytes);
+    let rank = reader.read_u32::<LittleEndian>()? as usize;
+    let mut shape: Shape = shape![];
+    for _ in 0..rank {
+        shape.push(reader.read_u32::<LittleEndian>()? as usize);
+    }
+
+    let data_start = reader.position() as usize;
+    let expected = shape.numel() * DType::F32.size_of();
+    if bytes.len() - data_start != expected {
+        anyhow::bail!(
+            "Feature data has {} bytes, expected {} for shape {:?}",
+            bytes.len() - data_start,
+            expected,
+            shape
+        );
+    }
+    Tensor::from_bytes(&bytes[data_start..], DType::F32, shape, device.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{features_from_bytes, features_to_bytes};
+    use ratchet::{shape, Device, Tensor};
+
+    #[test]
+    fn features_roundtrip() -> anyhow::Result<()> {
+        let data = (0..24).map(|x| x as f32 * 0.5).collect::<Vec<_>>();
+        let features = Tensor::from_data(&data, shape![1, 4, 6], Device::CPU);
+
+        let bytes = features_to_bytes(&features)?;
+        let restored = features_from_bytes(&bytes, &Device::CPU)?;
+        assert_eq!(restored.shape(), &shape![1, 4, 6]);
+        assert_eq!(restored.to_vec::<f32>()?, data);
+
+        assert!(features_from_bytes(&bytes[..bytes.len() - 4], &Device::CPU).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use ndarray::Axis;
+use ratchet::Tensor;
+
+use crate::LogitMutator;
+
+/// Adds a fixed bias to the logits of the given tokens at every step.
+/// A bias of `f32::NEG_INFINITY` bans a token outright.
+#[derive(Debug, derive_new::new)]
+pub struct ApplyLogitBias {
+    pub bias: HashMap<u32, f32>,
+}
+
+impl LogitMutator for ApplyLogitBias {
+    fn apply(&self, logits: Tensor, _tokens: &Tensor) -> anyhow::Result<Tensor> {
+        let mut nd_logits = logits.into_ndarray::<f32>();
+        let vocab_axis = Axis(nd_logits.ndim() - 1);
+        for mut row in nd_logits.lanes_mut(vocab_axis) {
+            for (&token, &bias) in &self.bias {
+                if let Some(logit) = row.get_mut(token as usize) {
+                    *logit += bias;
+                }
+            }
+        }
+        Ok(Tensor::from(nd_logits))
+    }
+
+    fn has_device_impl(&self) -> bool {
+        true
+    }
+}
+
+/// Forces `token` to be sampled when `position` tokens have been sampled,
+/// positions are counted from `sample_begin`.
+#[derive(Debug, derive_new::new)]
+pub struct ApplyForcedTokens {
+    pub sample_begin: usize,
+    pub forced: Vec<(usize, u32)>,
+}
+
+impl LogitMutator for ApplyForcedTokens {
+    fn apply(&self, logits: Tensor, tokens: &Tensor) -> anyhow::Result<Tensor> {
+        let position = tokens.shape()[1].saturating_sub(self.sample_begin);
+        let Some(&(_, token)) = self.forced.iter().find(|(p, _)| *p == position) else {
+            return Ok(logits);
+        };
+        let mut nd_logits = logits.into_ndarray::<f32>();
+        let vocab_axis = Axis(nd_logits.ndim() - 1);
+        for mut row in nd_logits.lanes_mut(vocab_axis) {
+            row.fill(f32::NEG_INFINITY);
+            if let Some(logit) = row.get_mut(token as usize) {
+                *logit = 0.0;
+            }
+        }
+        Ok(Tensor::from(nd_logits))
+    }
+
+    fn has_device_impl(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratchet::{shape, Device};
+
+    fn logits() -> Tensor {
+        Tensor::from_data(vec![0f32; 8], shape![1, 2, 4], Device::CPU)
+    }
+
+    #[test]
+    fn bias_and_force() -> anyhow::Result<()> {
+        let tokens = Tensor::from_data(vec![1i32, 2, 3], shape![1, 3], Device::CPU);
+
+        let bias = ApplyLogitBias::new(HashMap::from([(1, 2.0), (3, f32::NEG_INFINITY)]));
+        let biased = bias.apply(logits(), &tokens)?.to_vec::<f32>()?;
+        assert_eq!(&biased[4..], &[0.0, 2.0, 0.0, f32::NEG_INFINITY]);
+
+        let force = ApplyForcedTokens::new(2, vec![(0, 3), (1, 2)]);
+        let forced = force.apply(logits(), &tokens)?.to_vec::<f32>()?;
+        let ninf = f32::NEG_INFINITY;
+        assert_eq!(&forced[4..], &[ninf, ninf, 0.0, ninf]);
+
+        let tokens = Tensor::from_data(vec![1i32, 2, 3, 4], shape![1, 4], Device::CPU);
+        let untouched = force.apply(logits(), &tokens)?.to_vec::<f32>()?;
+        assert_eq!(untouched, vec![0.0; 8]);
+        Ok(())
+    }
+}
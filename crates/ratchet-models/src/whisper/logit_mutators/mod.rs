@@ -1,9 +1,17 @@
+mod constraints;
 mod timestamp_rules;
 
+pub use constraints::*;
 pub use timestamp_rules::*;
 
 use ratchet::Tensor;
 
 pub trait LogitMutator {
     fn apply(&self, logits: Tensor, tokens: &Tensor) -> anyhow::Result<Tensor>;
+
+    /// Whether decoding applies this mutator on the device itself, so greedy decoding on the
+    /// GPU can skip reading the logits back. Any mutator that doesn't keeps decoding on the host.
+    fn has_device_impl(&self) -> bool {
+        false
+    }
 }
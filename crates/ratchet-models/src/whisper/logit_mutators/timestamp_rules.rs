@@ -2,12 +2,13 @@ use ndarray::s;
 use ndarray_stats::QuantileExt;
 use ratchet::{NDArrayExt, Tensor};
 
-use crate::{LogitMutator, WhisperTokenizer};
+use crate::{LogitMutator, SpecialTokens};
 
 #[derive(Debug, derive_new::new)]
 pub struct ApplyTimestampRules {
     pub sample_begin: usize,
     pub max_initial_timestamp_index: Option<usize>,
+    pub special: SpecialTokens,
 }
 
 impl LogitMutator for ApplyTimestampRules {
@@ -16,7 +17,7 @@ impl LogitMutator for ApplyTimestampRules {
         let mut nd_logits = logits.into_ndarray::<f32>();
 
         nd_logits
-            .slice_mut(s![.., WhisperTokenizer::NO_TIMESTAMPS as usize])
+            .slice_mut(s![.., self.special.no_timestamps as usize])
             .map_inplace(move |el| *el = f32::NEG_INFINITY);
 
         for k in 0..nd_tokens.shape()[0] {
@@ -24,25 +25,25 @@ impl LogitMutator for ApplyTimestampRules {
             let sample_len = sampled_tokens.len();
 
             let last_was_timestamp = !sampled_tokens.is_empty()
-                && sampled_tokens[sample_len - 1] >= WhisperTokenizer::TS_BEGIN;
+                && sampled_tokens[sample_len - 1] >= self.special.timestamp_begin;
             let penultimate_was_timestamp = sampled_tokens.len() < 2
-                || sampled_tokens[sample_len - 2] >= WhisperTokenizer::TS_BEGIN;
+                || sampled_tokens[sample_len - 2] >= self.special.timestamp_begin;
 
             if last_was_timestamp {
                 if penultimate_was_timestamp {
                     nd_logits
-                        .slice_mut(s![k, WhisperTokenizer::TS_BEGIN..])
+                        .slice_mut(s![k, self.special.timestamp_begin..])
                         .map_inplace(move |el| *el = f32::NEG_INFINITY);
                 } else {
                     nd_logits
-                        .slice_mut(s![k, ..WhisperTokenizer::EOT])
+                        .slice_mut(s![k, ..self.special.eot])
                         .map_inplace(move |el| *el = f32::NEG_INFINITY);
                 }
             }
 
             let timestamps = sampled_tokens
                 .iter()
-                .filter(|x| **x >= WhisperTokenizer::TS_BEGIN)
+                .filter(|x| **x >= self.special.timestamp_begin)
                 .collect::<Vec<_>>();
 
             if !timestamps.is_empty() {
@@ -54,18 +55,18 @@ impl LogitMutator for ApplyTimestampRules {
                     timestamps[timestamps.len() - 1] + 1
                 };
                 nd_logits
-                    .slice_mut(s![k, WhisperTokenizer::TS_BEGIN..timestamp_last])
+                    .slice_mut(s![k, self.special.timestamp_begin..timestamp_last])
                     .map_inplace(move |el| *el = f32::NEG_INFINITY);
             }
         }
         if nd_tokens.shape()[1] == self.sample_begin {
             // suppress generating non-timestamp tokens at the beginning
             nd_logits
-                .slice_mut(s![.., ..WhisperTokenizer::TS_BEGIN])
+                .slice_mut(s![.., ..self.special.timestamp_begin])
                 .map_inplace(move |el| *el = f32::NEG_INFINITY);
 
             if self.max_initial_timestamp_index.is_some() {
-                let last_allowed = (WhisperTokenizer::TS_BEGIN as usize)
+                let last_allowed = (self.special.timestamp_begin as usize)
                     + self.max_initial_timestamp_index.unwrap();
                 nd_logits
                     .slice_mut(s![.., last_allowed + 1..])
@@ -76,13 +77,13 @@ impl LogitMutator for ApplyTimestampRules {
         let logprobs = nd_logits.log_softmax(1);
         for _k in 0..nd_tokens.shape()[0] {
             let timestamp_logprob = logprobs
-                .slice(s![.., WhisperTokenizer::TS_BEGIN..])
+                .slice(s![.., self.special.timestamp_begin..])
                 .logsumexp(1);
-            let text_logprobs = logprobs.slice(s![.., ..WhisperTokenizer::TS_BEGIN]);
+            let text_logprobs = logprobs.slice(s![.., ..self.special.timestamp_begin]);
             let max_text_token_logprob = text_logprobs.max()?;
             if timestamp_logprob > *max_text_token_logprob {
                 nd_logits
-                    .slice_mut(s![.., ..WhisperTokenizer::TS_BEGIN])
+                    .slice_mut(s![.., ..self.special.timestamp_begin])
                     .map_inplace(move |el| *el = f32::NEG_INFINITY);
             }
         }
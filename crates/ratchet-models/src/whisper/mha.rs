@@ -1,7 +1,9 @@
 use ratchet::{rvec, shape, Tensor};
 use ratchet_nn::{KVEntry, Linear, Module};
 
-#[derive(Debug, derive_new::new)]
+use crate::{push_weights, DecodeError};
+
+#[derive(Clone, Debug, derive_new::new)]
 pub struct MultiHeadAttention {
     q: Linear,
     k: Linear,
@@ -23,6 +25,33 @@ impl Module for MultiHeadAttention {
     type Input = MHAInputs;
 
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
+        Ok(self.forward_with_weights(input)?.0)
+    }
+
+    impl_parameters!(module(q), module(k), module(v), module(o));
+}
+
+impl MultiHeadAttention {
+    pub(crate) fn named_tensors(&self, prefix: &str, out: &mut Vec<(String, Tensor)>) {
+        let projections = [
+            ("query", &self.q),
+            ("key", &self.k),
+            ("value", &self.v),
+            ("out", &self.o),
+        ];
+        for (name, linear) in projections {
+            push_weights(
+                out,
+                &format!("{prefix}.{name}"),
+                linear.weight(),
+                linear.bias(),
+            );
+        }
+    }
+
+    /// Returns the attention output alongside the post-softmax attention weights,
+    /// of shape [bs, n_heads, n_ctx, n_kv].
+    pub fn forward_with_weights(&self, input: &MHAInputs) -> anyhow::Result<(Tensor, Tensor)> {
         let MHAInputs {
             x,
             xa,
@@ -41,6 +70,14 @@ impl Module for MultiHeadAttention {
         let (k, v) = if let Some(kv) = cache {
             let prev_entries = kv.entries;
             let new_entries = prev_entries + n_ctx;
+            let capacity = kv.k_cache.shape()[1];
+            if new_entries > capacity {
+                return Err(DecodeError::ContextExceeded {
+                    requested: new_entries,
+                    limit: capacity,
+                }
+                .into());
+            }
             let k_cache = kv
                 .k_cache
                 .index_write(&k, rvec![0, prev_entries, 0])?
@@ -56,9 +93,7 @@ impl Module for MultiHeadAttention {
 
         self.qkv_attention(q, k, v, mask, xa.is_some(), *is_causal)
     }
-}
 
-impl MultiHeadAttention {
     fn qkv_attention(
         &self,
         q: Tensor,
@@ -67,21 +102,18 @@ impl MultiHeadAttention {
         mask: &Option<Tensor>,
         x_attn: bool,
         is_causal: bool,
-    ) -> anyhow::Result<Tensor> {
-        let [bs, n_ctx, n_state]: [usize; 3] = q.shape().try_into()?;
+    ) -> anyhow::Result<(Tensor, Tensor)> {
+        let [_, n_ctx, n_state]: [usize; 3] = q.shape().try_into()?;
         let [k0, k1, _]: [usize; 3] = k.shape().try_into()?;
-        let [v0, v1, _]: [usize; 3] = v.shape().try_into()?;
 
         let hdim = n_state / self.n_heads;
         let dk = Tensor::from_data([(hdim as f32).powf(-0.25)], shape![1], q.device().clone());
 
-        let qs = shape![bs, n_ctx, self.n_heads, hdim];
+        let q = q.split_heads(self.n_heads)?.mul(&dk)?;
+        //K is consumed transposed, [B, H, D, T], so split in a single permute
         let ks = shape![k0, k1, self.n_heads, hdim];
-        let vs = shape![v0, v1, self.n_heads, hdim];
-
-        let q = q.view(qs)?.permute(&[0, 2, 1, 3])?.mul(&dk)?;
         let k = k.view(ks)?.permute(&[0, 2, 3, 1])?.mul(&dk)?;
-        let v = v.view(vs)?.permute(&[0, 2, 1, 3])?;
+        let v = v.split_heads(self.n_heads)?;
 
         if x_attn {
             //TODO: static caching
@@ -91,7 +123,8 @@ impl MultiHeadAttention {
 
         if let Some(ref m) = mask {
             let prepared_mask = if is_causal {
-                m.slice(&[0..n_ctx, 0..n_ctx])?
+                //The queries are the last n_ctx of the k1 positions, earlier ones are cached
+                m.slice(&[k1 - n_ctx..k1, 0..k1])?
             } else {
                 m.clone()
             };
@@ -99,12 +132,9 @@ impl MultiHeadAttention {
         }
 
         let w = qk.softmax(3)?;
-        let wv = w
-            .matmul(&v)?
-            .permute(&[0, 2, 1, 3])?
-            .view(shape![bs, n_ctx, n_state])?;
+        let wv = w.matmul(&v)?.merge_heads()?;
 
         let dbg = self.o.forward(&wv)?;
-        Ok(dbg)
+        Ok((dbg, w))
     }
 }
@@ -1,15 +1,36 @@
 use ratchet::Tensor;
 use ratchet_nn::{Linear, Module};
 
-#[derive(Debug, derive_new::new)]
+use crate::push_weights;
+
+#[derive(Clone, Debug, derive_new::new)]
 pub struct MLP {
     l1: Linear,
     l2: Linear,
 }
 
+impl MLP {
+    pub(crate) fn named_tensors(&self, prefix: &str, out: &mut Vec<(String, Tensor)>) {
+        push_weights(
+            out,
+            &format!("{prefix}.0"),
+            self.l1.weight(),
+            self.l1.bias(),
+        );
+        push_weights(
+            out,
+            &format!("{prefix}.2"),
+            self.l2.weight(),
+            self.l2.bias(),
+        );
+    }
+}
+
 impl Module for MLP {
     type Input = Tensor;
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
         self.l2.forward(&self.l1.forward(input)?.gelu()?)
     }
+
+    impl_parameters!(module(l1), module(l2));
 }
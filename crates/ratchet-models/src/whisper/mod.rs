@@ -1,9 +1,44 @@
+/// Implements [ratchet_nn::Module::parameters] & [ratchet_nn::Module::parameters_mut] from a
+/// single list of fields, in order. A field is a `module`, an iterable of modules (`each`, e.g an
+/// `Option` or a `Vec`), or a bare `tensor`.
+macro_rules! impl_parameters {
+    ($($kind:ident($field:ident)),* $(,)?) => {
+        fn parameters(&self) -> Vec<&ratchet::Tensor> {
+            let mut params = vec![];
+            $(impl_parameters!(@$kind params, &self.$field, parameters, iter);)*
+            params
+        }
+
+        fn parameters_mut(&mut self) -> Vec<&mut ratchet::Tensor> {
+            let mut params = vec![];
+            $(impl_parameters!(@$kind params, &mut self.$field, parameters_mut, iter_mut);)*
+            params
+        }
+    };
+    (@module $params:ident, $field:expr, $method:ident, $iter:ident) => {
+        $params.extend(ratchet_nn::Module::$method($field))
+    };
+    (@each $params:ident, $field:expr, $method:ident, $iter:ident) => {
+        $params.extend($field.$iter().flat_map(ratchet_nn::Module::$method))
+    };
+    (@tensor $params:ident, $field:expr, $method:ident, $iter:ident) => {
+        $params.push($field)
+    };
+}
+
+mod alignment;
+mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+mod compare;
+mod compression;
 mod decoder;
 mod encoder;
+mod features;
 mod logit_mutators;
 mod mha;
 mod mlp;
 mod options;
+mod resident;
 mod residual_block;
 mod samplers;
 mod spectrogram;
@@ -12,12 +47,19 @@ mod tokenizer;
 mod transcribe;
 mod whisper;
 
+pub use alignment::*;
+pub use audio::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use compare::*;
+pub use compression::*;
 pub use decoder::*;
 pub use encoder::*;
+pub use features::*;
 pub use logit_mutators::*;
 pub use mha::*;
 pub use mlp::*;
 pub use options::*;
+pub(crate) use resident::*;
 pub use residual_block::*;
 pub use samplers::*;
 pub use spectrogram::*;
@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use crate::{SpecialTokens, HOP_LENGTH, N_FRAMES, SAMPLE_RATE};
+
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
@@ -26,12 +30,10 @@ pub enum Task {
     Translate,
 }
 
+//The multilingual ids, see [SpecialTokens::task] for those of a loaded vocab
 impl From<Task> for i32 {
     fn from(val: Task) -> Self {
-        match val {
-            Task::Transcribe => 50359,
-            Task::Translate => 50358,
-        }
+        SpecialTokens::MULTILINGUAL.task(val)
     }
 }
 
@@ -48,12 +50,44 @@ pub struct DecodingOptions {
     pub(crate) patience: Option<f32>,              // default: None
     pub(crate) length_penalty: Option<f32>,        // default: None
     pub(crate) prompt: Option<Prompt>,             // default: None
+    pub(crate) initial_prompt: Option<String>,     // default: None
     pub(crate) prefix: Option<String>,             // default: None
     pub(crate) suppress_tokens: Option<Vec<i32>>,  // default: Some("-1".to_string())
     pub(crate) suppress_blank: bool,               // default: true
     pub(crate) without_timestamps: bool,           // default: false
     pub(crate) max_initial_timestamp: Option<f32>, // default: Some(1.0)
     pub(crate) time_offset: Option<f64>,           // default: None
+    pub(crate) word_timestamps: bool,              // default: false
+    pub(crate) temperature_increment_on_fallback: Option<f32>, // default: Some(0.2)
+    pub(crate) logprob_threshold: Option<f32>,     // default: Some(-1.0)
+    pub(crate) compression_ratio_threshold: Option<f32>, // default: Some(2.4)
+    pub(crate) logit_bias: HashMap<u32, f32>,      // default: empty
+    pub(crate) forced_decoder_ids: Vec<(usize, u32)>, // default: empty
+    pub(crate) chunk_overlap_seconds: f32,         // default: 0.0
+}
+
+impl DecodingOptions {
+    /// Temperatures to try, in order, until a decode passes the fallback thresholds.
+    pub(crate) fn temperature_schedule(&self) -> Vec<f32> {
+        match self.temperature_increment_on_fallback {
+            Some(increment) if increment > 0.0 => {
+                let mut schedule = vec![];
+                let mut t = self.temperature;
+                while t <= 1.0 + 1e-6 {
+                    schedule.push(t);
+                    t += increment;
+                }
+                schedule
+            }
+            _ => vec![self.temperature],
+        }
+    }
+
+    /// Mel frames shared by adjacent windows, at most half a window.
+    pub(crate) fn overlap_frames(&self) -> usize {
+        let frames = self.chunk_overlap_seconds.max(0.0) * (SAMPLE_RATE / HOP_LENGTH) as f32;
+        (frames.round() as usize).min(N_FRAMES / 2)
+    }
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
@@ -67,12 +101,20 @@ pub struct DecodingOptionsBuilder {
     patience: Option<f32>,
     length_penalty: Option<f32>,
     prompt: Option<String>,
+    initial_prompt: Option<String>,
     prefix: Option<String>,
     suppress_tokens: Option<Vec<i32>>,
     suppress_blank: Option<bool>,
     without_timestamps: Option<bool>,
     max_initial_timestamp: Option<f32>,
     time_offset: Option<f64>,
+    word_timestamps: Option<bool>,
+    temperature_increment_on_fallback: Option<f32>,
+    logprob_threshold: Option<f32>,
+    compression_ratio_threshold: Option<f32>,
+    logit_bias: HashMap<u32, f32>,
+    forced_decoder_ids: Vec<(usize, u32)>,
+    chunk_overlap_seconds: Option<f32>,
 }
 
 impl Default for DecodingOptionsBuilder {
@@ -95,12 +137,20 @@ impl DecodingOptionsBuilder {
             patience: None,
             length_penalty: None,
             prompt: None,
+            initial_prompt: None,
             prefix: None,
             suppress_tokens: Some(vec![-1]),
             suppress_blank: Some(true),
             max_initial_timestamp: Some(1.0),
             without_timestamps: Some(false),
             time_offset: None,
+            word_timestamps: Some(false),
+            temperature_increment_on_fallback: Some(0.2),
+            logprob_threshold: Some(-1.0),
+            compression_ratio_threshold: Some(2.4),
+            logit_bias: HashMap::new(),
+            forced_decoder_ids: vec![],
+            chunk_overlap_seconds: Some(0.0),
         }
     }
 
@@ -158,6 +208,12 @@ impl DecodingOptionsBuilder {
         self
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = "setInitialPrompt"))]
+    pub fn initial_prompt(mut self, initial_prompt: String) -> Self {
+        self.initial_prompt = Some(initial_prompt);
+        self
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = "setPrefix"))]
     pub fn prefix(mut self, prefix: String) -> Self {
         self.prefix = Some(prefix);
@@ -197,6 +253,68 @@ impl DecodingOptionsBuilder {
         self
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = "setWordTimestamps"))]
+    pub fn word_timestamps(mut self, word_timestamps: bool) -> Self {
+        self.word_timestamps = Some(word_timestamps);
+        self
+    }
+
+    #[cfg_attr(
+        target_arch = "wasm32",
+        wasm_bindgen(js_name = "setTemperatureIncrementOnFallback")
+    )]
+    pub fn temperature_increment_on_fallback(mut self, increment: f32) -> Self {
+        self.temperature_increment_on_fallback = Some(increment);
+        self
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = "setLogprobThreshold"))]
+    pub fn logprob_threshold(mut self, logprob_threshold: f32) -> Self {
+        self.logprob_threshold = Some(logprob_threshold);
+        self
+    }
+
+    #[cfg_attr(
+        target_arch = "wasm32",
+        wasm_bindgen(js_name = "setCompressionRatioThreshold")
+    )]
+    pub fn compression_ratio_threshold(mut self, compression_ratio_threshold: f32) -> Self {
+        self.compression_ratio_threshold = Some(compression_ratio_threshold);
+        self
+    }
+
+    /// Seconds of audio shared by adjacent 30s windows, capped at 15s.
+    /// Text transcribed twice in the overlap is merged, so words at the boundary aren't lost.
+    #[cfg_attr(
+        target_arch = "wasm32",
+        wasm_bindgen(js_name = "setChunkOverlapSeconds")
+    )]
+    pub fn chunk_overlap_seconds(mut self, chunk_overlap_seconds: f32) -> Self {
+        self.chunk_overlap_seconds = Some(chunk_overlap_seconds);
+        self
+    }
+
+    /// `logit_bias` is a `Map` of token id to bias, see [DecodingOptionsBuilder::logit_bias].
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = "setLogitBias")]
+    pub fn set_logit_bias(self, logit_bias: JsValue) -> Result<DecodingOptionsBuilder, JsError> {
+        let logit_bias: HashMap<u32, f32> = serde_wasm_bindgen::from_value(logit_bias)?;
+        Ok(self.logit_bias(logit_bias))
+    }
+
+    /// `forced_decoder_ids` is an array of `[position, token]` pairs,
+    /// see [DecodingOptionsBuilder::forced_decoder_ids].
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = "setForcedDecoderIds")]
+    pub fn set_forced_decoder_ids(
+        self,
+        forced_decoder_ids: JsValue,
+    ) -> Result<DecodingOptionsBuilder, JsError> {
+        let forced_decoder_ids: Vec<(usize, u32)> =
+            serde_wasm_bindgen::from_value(forced_decoder_ids)?;
+        Ok(self.forced_decoder_ids(forced_decoder_ids))
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub fn build(&self) -> DecodingOptions {
         DecodingOptions {
@@ -209,12 +327,20 @@ impl DecodingOptionsBuilder {
             patience: self.patience,
             length_penalty: self.length_penalty,
             prompt: self.prompt.clone().map(Prompt::Text),
+            initial_prompt: self.initial_prompt.clone(),
             prefix: self.prefix.clone(),
             suppress_tokens: self.suppress_tokens.clone(),
             suppress_blank: self.suppress_blank.unwrap_or(true),
             without_timestamps: self.without_timestamps.unwrap_or(false),
             max_initial_timestamp: self.max_initial_timestamp,
             time_offset: self.time_offset,
+            word_timestamps: self.word_timestamps.unwrap_or(false),
+            temperature_increment_on_fallback: self.temperature_increment_on_fallback,
+            logprob_threshold: self.logprob_threshold,
+            compression_ratio_threshold: self.compression_ratio_threshold,
+            logit_bias: self.logit_bias.clone(),
+            forced_decoder_ids: self.forced_decoder_ids.clone(),
+            chunk_overlap_seconds: self.chunk_overlap_seconds.unwrap_or(0.0),
         }
     }
 
@@ -230,17 +356,41 @@ impl DecodingOptionsBuilder {
             patience: self.patience,
             length_penalty: self.length_penalty,
             prompt: self.prompt.clone().map(|p| Prompt::Text(p)),
+            initial_prompt: self.initial_prompt.clone(),
             prefix: self.prefix.clone(),
             suppress_tokens: self.suppress_tokens.clone(),
             suppress_blank: self.suppress_blank.unwrap_or(true),
             without_timestamps: self.without_timestamps.unwrap_or(false),
             max_initial_timestamp: self.max_initial_timestamp,
             time_offset: self.time_offset,
+            word_timestamps: self.word_timestamps.unwrap_or(false),
+            temperature_increment_on_fallback: self.temperature_increment_on_fallback,
+            logprob_threshold: self.logprob_threshold,
+            compression_ratio_threshold: self.compression_ratio_threshold,
+            logit_bias: self.logit_bias.clone(),
+            forced_decoder_ids: self.forced_decoder_ids.clone(),
+            chunk_overlap_seconds: self.chunk_overlap_seconds.unwrap_or(0.0),
         };
         serde_wasm_bindgen::to_value(&options).unwrap()
     }
 }
 
+impl DecodingOptionsBuilder {
+    /// Added to the logits of each token id before sampling, at every step.
+    /// Use `f32::NEG_INFINITY` to ban a token.
+    pub fn logit_bias(mut self, logit_bias: HashMap<u32, f32>) -> Self {
+        self.logit_bias = logit_bias;
+        self
+    }
+
+    /// `(position, token)` pairs, `token` is sampled once `position` tokens have been sampled.
+    /// Positions count from the end of the initial tokens, so `0` forces the first sampled token.
+    pub fn forced_decoder_ids(mut self, forced_decoder_ids: Vec<(usize, u32)>) -> Self {
+        self.forced_decoder_ids = forced_decoder_ids;
+        self
+    }
+}
+
 cfg_if::cfg_if! {
     if #[cfg(all(not(target_arch = "wasm32"), test))] {
         use pyo3::types::{IntoPyDict, PyDict};
@@ -292,11 +442,16 @@ cfg_if::cfg_if! {
                 let _ = dict.set_item("patience", self.patience.map_or_else(|| py.None(), |v| v.into_py(py)));
                 let _ = dict.set_item("length_penalty", self.length_penalty.map_or_else(|| py.None(), |v| v.into_py(py)));
                 let _ = dict.set_item("prompt", self.prompt.map_or_else(|| py.None(), |v| v.into_py(py)));
+                let _ = dict.set_item("initial_prompt", self.initial_prompt.map_or_else(|| py.None(), |v| v.into_py(py)));
                 let _ = dict.set_item("prefix", self.prefix.map_or_else(|| py.None(), |v| v.into_py(py)));
                 let _ = dict.set_item("suppress_tokens", supress_tokens_string.map_or_else(|| py.None(), |v| v.into_py(py)));
                 let _ = dict.set_item("suppress_blank", self.suppress_blank.into_py(py));
                 let _ = dict.set_item("without_timestamps", self.without_timestamps.into_py(py));
                 let _ = dict.set_item("max_initial_timestamp", self.max_initial_timestamp.map_or_else(|| py.None(), |v| v.into_py(py)));
+                let _ = dict.set_item("word_timestamps", self.word_timestamps.into_py(py));
+                let _ = dict.set_item("temperature_increment_on_fallback", self.temperature_increment_on_fallback.map_or_else(|| py.None(), |v| v.into_py(py)));
+                let _ = dict.set_item("logprob_threshold", self.logprob_threshold.map_or_else(|| py.None(), |v| v.into_py(py)));
+                let _ = dict.set_item("compression_ratio_threshold", self.compression_ratio_threshold.map_or_else(|| py.None(), |v| v.into_py(py)));
 
                 dict
             }
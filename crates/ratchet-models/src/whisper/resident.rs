@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ratchet::{shape, DType, Device, Shape, Tensor};
+use ratchet_loader::{GGMLFormat, GGMLModel, GgmlDType, TensorHeader, MAGIC_GGML};
+
+use crate::{
+    HyperParameters, MelFilters, Whisper, WhisperDecoder, WhisperEncoder, WhisperGGMLHeader,
+};
+
+// A resident snapshot holds the tensors of a loaded model as they were uploaded, so a warm
+// start skips reading and converting the GGML file. The layout is:
+//
+// - magic & version
+// - hyperparameters
+// - manifest: tensor count, then for each tensor its name, GGML dtype and shape
+// - the tensor data, back to back in manifest order
+//
+// Names match the GGML keys, so restoring reuses the regular loaders.
+const RESIDENT_MAGIC: u32 = u32::from_le_bytes(*b"rtwr");
+const RESIDENT_VERSION: u32 = 1;
+
+/// Pushes `{prefix}.weight`, and `{prefix}.bias` if present.
+pub(crate) fn push_weights(
+    out: &mut Vec<(String, Tensor)>,
+    prefix: &str,
+    weight: &Tensor,
+    bias: Option<&Tensor>,
+) {
+    out.push((format!("{prefix}.weight"), weight.clone()));
+    if let Some(b) = bias {
+        out.push((format!("{prefix}.bias"), b.clone()));
+    }
+}
+
+impl Whisper {
+    /// Every tensor held by the encoder & decoder, keyed by its GGML name.
+    pub(crate) fn named_tensors(
+        encoder: &WhisperEncoder,
+        decoder: &WhisperDecoder,
+    ) -> Vec<(String, Tensor)> {
+        let mut named = vec![];
+        encoder.named_tensors(&mut named);
+        decoder.named_tensors(&mut named);
+        named
+    }
+
+    /// # Serialize resident
+    ///
+    /// Serializes the weights as they are held on the device, e.g to cache them in IndexedDB.
+    /// [Whisper::restore] uploads them again without parsing or converting the GGML file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn serialize_resident(&self) -> anyhow::Result<Vec<u8>> {
+        let named = Self::named_tensors(&self.encoder, &self.decoder);
+        let tensors = named.iter().map(|(_, t)| t).collect::<Vec<_>>();
+        let host = self.device.read_back(&tensors)?;
+        Self::write_resident(&self.hparams, &self.decoder, &named, &host)
+    }
+
+    /// Serializes the loaded weights, see [Whisper::restore].
+    #[cfg(target_arch = "wasm32")]
+    pub async fn serialize_resident(&self) -> anyhow::Result<Vec<u8>> {
+        let named = Self::named_tensors(&self.encoder, &self.decoder);
+        let tensors = named.iter().map(|(_, t)| t).collect::<Vec<_>>();
+        let host = self.device.read_back(&tensors).await?;
+        Self::write_resident(&self.hparams, &self.decoder, &named, &host)
+    }
+
+    /// `host` holds the CPU copy of each tensor in `named`.
+    pub(crate) fn write_resident(
+        hparams: &HyperParameters,
+        decoder: &WhisperDecoder,
+        named: &[(String, Tensor)],
+        host: &[Tensor],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        bytes.write_u32::<LittleEndian>(RESIDENT_MAGIC)?;
+        bytes.write_u32::<LittleEndian>(RESIDENT_VERSION)?;
+        //The positional embeddings may have been extended since loading
+        HyperParameters {
+            n_text_ctx: decoder.n_ctx() as _,
+            ..*hparams
+        }
+        .write(&mut bytes)?;
+
+        bytes.write_u32::<LittleEndian>(named.len() as _)?;
+        for (name, tensor) in named {
+            let dtype = match tensor.dt() {
+                DType::F32 => 0,
+                dt => anyhow::bail!("Cannot serialize {} of type {:?}", name, dt),
+            };
+            bytes.write_u32::<LittleEndian>(name.len() as _)?;
+            bytes.write_all(name.as_bytes())?;
+            bytes.write_u32::<LittleEndian>(dtype)?;
+            bytes.write_u32::<LittleEndian>(tensor.rank() as _)?;
+            for &dim in tensor.shape().iter() {
+                bytes.write_u32::<LittleEndian>(dim as _)?;
+            }
+        }
+        for tensor in host {
+            for x in tensor.to_vec::<f32>()? {
+                bytes.write_f32::<LittleEndian>(x)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// # Restore
+    ///
+    /// Uploads the weights written by [Whisper::serialize_resident] to `device`.
+    /// The tokenizer & spectrogram generator are not part of the snapshot, they are built
+    /// as they would be alongside [Whisper::load_all].
+    pub fn restore(
+        bytes: &[u8],
+        device: &Device,
+    ) -> anyhow::Result<(WhisperEncoder, WhisperDecoder)> {
+        let mut reader = Cursor::new(bytes);
+        let magic = reader.read_u32::<LittleEndian>()?;
+        let version = reader.read_u32::<LittleEndian>()?;
+        if magic != RESIDENT_MAGIC || version != RESIDENT_VERSION {
+            anyhow::bail!("Not a resident snapshot, or written by another version");
+        }
+        let hparams = HyperParameters::read(&mut reader)?;
+
+        let n_tensors = reader.read_u32::<LittleEndian>()?;
+        let mut headers = Vec::with_capacity(n_tensors as _);
+        for _ in 0..n_tensors {
+            let name_len = reader.read_u32::<LittleEndian>()?;
+            let mut name = String::new();
+            (&mut reader)
+                .take(name_len as _)
+                .read_to_string(&mut name)?;
+            let dtype = GgmlDType::try_from(reader.read_u32::<LittleEndian>()?)?;
+            let rank = reader.read_u32::<LittleEndian>()?;
+            let mut shape: Shape = shape![];
+            for _ in 0..rank {
+                shape.push(reader.read_u32::<LittleEndian>()? as _);
+            }
+            headers.push((name, dtype, shape));
+        }
+
+        let mut offset = reader.position();
+        let mut tensors = HashMap::with_capacity(headers.len());
+        for (name, dtype, shape) in headers {
+            let numel = shape.numel();
+            let header = TensorHeader {
+                name: name.clone(),
+                shape,
+                dtype,
+                start_offset: offset,
+                numel,
+            };
+            offset += (numel * dtype.type_size() / dtype.block_size()) as u64;
+            tensors.insert(name, header);
+        }
+        if offset != bytes.len() as u64 {
+            anyhow::bail!(
+                "Snapshot holds {} bytes, manifest describes {}",
+                bytes.len(),
+                offset
+            );
+        }
+
+        let header = WhisperGGMLHeader {
+            format: GGMLFormat::GGML(MAGIC_GGML),
+            filters: MelFilters {
+                n_mel: hparams.n_mels,
+                n_fft: 0,
+                mels: vec![],
+            },
+            hparams,
+            n_tokens: 0,
+        };
+        let disk_model = GGMLModel::<Whisper>::new(header, tensors);
+        device.batch_uploads(|| {
+            let encoder = WhisperEncoder::load(&disk_model, &mut reader, device)?;
+            let decoder = WhisperDecoder::load(&disk_model, &mut reader, device)?;
+            Ok((encoder, decoder))
+        })
+    }
+}
@@ -4,9 +4,9 @@ use ratchet::{Device, Tensor};
 use ratchet_loader::GGMLModel;
 use ratchet_nn::{KVEntry, LayerNorm, Linear, Module};
 
-use crate::{MHAInputs, MultiHeadAttention, Whisper, MLP};
+use crate::{push_weights, MHAInputs, MultiHeadAttention, Whisper, MLP};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ResidualAttentionBlock {
     attn_ln: LayerNorm,
     attn: MultiHeadAttention,
@@ -27,6 +27,25 @@ pub struct ResidualAttentionBlockInputs {
 impl Module for ResidualAttentionBlock {
     type Input = ResidualAttentionBlockInputs;
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
+        Ok(self.forward_with_x_attn(input)?.0)
+    }
+
+    impl_parameters!(
+        module(attn_ln),
+        module(attn),
+        each(x_attn_ln),
+        each(x_attn),
+        module(mlp_ln),
+        module(mlp),
+    );
+}
+
+impl ResidualAttentionBlock {
+    /// Forward pass that also returns the cross attention weights, if the block has them.
+    pub fn forward_with_x_attn(
+        &self,
+        input: &ResidualAttentionBlockInputs,
+    ) -> anyhow::Result<(Tensor, Option<Tensor>)> {
         let ResidualAttentionBlockInputs { x, xa, mask, cache } = input;
         let attn_ln = self.attn_ln.forward(x)?;
         let self_attn = self.attn.forward(&MHAInputs::new(
@@ -39,21 +58,41 @@ impl Module for ResidualAttentionBlock {
 
         let mut attn = self_attn.add(x)?;
 
+        let mut x_attn_weights = None;
         if let Some(ref xa_blck) = self.x_attn {
             if let Some(xa_ln) = &self.x_attn_ln {
                 let x_attn_ln = xa_ln.forward(&attn)?;
-                let x_attn =
-                    xa_blck.forward(&MHAInputs::new(x_attn_ln, xa.clone(), None, None, false))?;
+                let (x_attn, weights) = xa_blck.forward_with_weights(&MHAInputs::new(
+                    x_attn_ln,
+                    xa.clone(),
+                    None,
+                    None,
+                    false,
+                ))?;
                 attn = x_attn.add(&attn)?;
+                x_attn_weights = Some(weights);
             }
         }
         let mlp_ln = self.mlp_ln.forward(&attn)?;
         let mlp = self.mlp.forward(&mlp_ln)?;
-        mlp.add(&attn)
+        Ok((mlp.add(&attn)?, x_attn_weights))
+    }
+
+    /// `prefix` is the block's GGML key, e.g `decoder.blocks.0`.
+    pub(crate) fn named_tensors(&self, prefix: &str, out: &mut Vec<(String, Tensor)>) {
+        let ln = |out: &mut Vec<_>, name: &str, ln: &LayerNorm| {
+            push_weights(out, &format!("{prefix}.{name}"), ln.weight(), ln.bias())
+        };
+        ln(out, "attn_ln", &self.attn_ln);
+        self.attn.named_tensors(&format!("{prefix}.attn"), out);
+        if let (Some(x_attn_ln), Some(x_attn)) = (&self.x_attn_ln, &self.x_attn) {
+            ln(out, "cross_attn_ln", x_attn_ln);
+            x_attn.named_tensors(&format!("{prefix}.cross_attn"), out);
+        }
+        ln(out, "mlp_ln", &self.mlp_ln);
+        self.mlp.named_tensors(&format!("{prefix}.mlp"), out);
     }
-}
 
-impl ResidualAttentionBlock {
     pub fn load<R: BufRead + Seek>(
         disk_model: &GGMLModel<Whisper>,
         reader: &mut R,
@@ -1,27 +1,119 @@
-use ndarray::Axis;
+use ndarray::{s, Ix2};
 use ndarray_stats::QuantileExt;
-use ratchet::Tensor;
+use rand::distributions::{Distribution, WeightedIndex};
+use ratchet::{NDArrayExt, Tensor};
 
-use crate::{DecodeError, WhisperTokenizer};
+use crate::{DecodeError, SpecialTokens};
 
 pub struct GreedySampler;
 
 impl GreedySampler {
+    /// Samples the next token from the logits of the final position.
+    /// At temperature 0 this is the argmax, otherwise we sample from softmax(logits / temperature).
+    ///
+    /// Returns the extended tokens, the logprob of the sampled token and whether EOT was reached.
     pub fn sample(
         mut tokens: Vec<i32>,
         logits: Tensor,
-    ) -> Result<(Tensor, Vec<i32>, bool), DecodeError> {
+        temperature: f32,
+        special: &SpecialTokens,
+    ) -> Result<(Vec<i32>, f32, bool), DecodeError> {
         let nd_logits = logits.to_ndarray_view::<f32>();
-        let next_tokens = nd_logits
-            .map_axis(Axis(1), |row| {
-                row.argmax_skipnan().expect("Sampling failed.")
-            })
-            .iter()
-            .map(|&x| x as i32)
-            .collect::<Vec<_>>();
-
-        tokens.extend_from_slice(&next_tokens);
-        let completed = tokens[tokens.len() - 1] == WhisperTokenizer::EOT;
-        Ok((logits, tokens, completed))
+        let n_vocab = nd_logits.shape()[nd_logits.ndim() - 1];
+        let rows = nd_logits.len() / n_vocab;
+        let nd_logits = nd_logits
+            .into_shape((rows, n_vocab))
+            .map_err(anyhow::Error::from)?
+            .into_dimensionality::<Ix2>()
+            .map_err(anyhow::Error::from)?;
+        let last = nd_logits.slice(s![rows - 1.., ..special.n_vocab().min(n_vocab)]);
+
+        let next_token = if temperature == 0.0 {
+            last.row(0).argmax_skipnan().expect("Sampling failed.")
+        } else {
+            let probs = last.mapv(|l| l / temperature).softmax(1);
+            let dist = WeightedIndex::new(probs.row(0)).map_err(anyhow::Error::from)?;
+            dist.sample(&mut rand::thread_rng())
+        };
+        let logprob = last.log_softmax(1)[[0, next_token]];
+
+        tokens.push(next_token as i32);
+        let completed = next_token as i32 == special.eot;
+        Ok((tokens, logprob, completed))
+    }
+
+    /// # Sample on device
+    ///
+    /// The argmax of [GreedySampler::sample], computed where `logits` live. The final position
+    /// is offset by the additive `mask` of shape [1, 1, n_vocab], then only the chosen token
+    /// and its probability are read back instead of every logit.
+    ///
+    /// The argmax is taken with [Tensor::topk], which has no CPU kernel, so `logits` must
+    /// be on the GPU.
+    pub async fn sample_on_device(
+        mut tokens: Vec<i32>,
+        logits: &Tensor,
+        mask: Option<&Tensor>,
+        special: &SpecialTokens,
+    ) -> Result<(Vec<i32>, f32, bool), DecodeError> {
+        let (n_ctx, n_vocab) = (logits.shape()[1], logits.shape()[2]);
+        let n_vocab = special.n_vocab().min(n_vocab);
+        let mut last = logits.slice(&[0..1, n_ctx - 1..n_ctx, 0..n_vocab])?;
+        if let Some(mask) = mask {
+            last = last.add(mask)?;
+        }
+        //Softmax keeps the argmax, and its max is the probability we need for the logprob
+        let (probs, indices) = last.softmax(2)?.topk(1, 2)?;
+        Tensor::resolve_all(&[probs.clone(), indices.clone()])?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let host = logits.device().read_back(&[&probs, &indices])?;
+        #[cfg(target_arch = "wasm32")]
+        let host = logits.device().read_back(&[&probs, &indices]).await?;
+        let prob = host[0].to_vec::<f32>()?[0];
+        let next_token = host[1].to_vec::<i32>()?[0];
+
+        tokens.push(next_token);
+        Ok((tokens, prob.ln(), next_token == special.eot))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use ratchet::{shape, Device, DeviceRequest, Tensor};
+
+    use super::GreedySampler;
+    use crate::SpecialTokens;
+
+    #[test]
+    fn device_argmax_matches_host() -> anyhow::Result<()> {
+        let special = SpecialTokens::MULTILINGUAL;
+        let n_vocab = special.n_vocab();
+        let logits = Tensor::randn::<f32>(shape![1, 3, n_vocab], Device::CPU);
+        let mut bias = vec![0f32; n_vocab];
+        //Ban whichever token would otherwise win
+        let (banned, _, _) = GreedySampler::sample(vec![], logits.clone(), 0.0, &special)?;
+        bias[banned[0] as usize] = f32::NEG_INFINITY;
+
+        let mut biased = logits.to_vec::<f32>()?;
+        for row in biased.chunks_mut(n_vocab) {
+            row[banned[0] as usize] = f32::NEG_INFINITY;
+        }
+        let biased = Tensor::from_data(biased, shape![1, 3, n_vocab], Device::CPU);
+        let (expected, expected_logprob, _) =
+            GreedySampler::sample(vec![7], biased, 0.0, &special)?;
+
+        let gpu = Device::request_device(DeviceRequest::GPU)?;
+        let mask = Tensor::from_data(bias, shape![1, 1, n_vocab], gpu.clone());
+        let (tokens, logprob, completed) = pollster::block_on(GreedySampler::sample_on_device(
+            vec![7],
+            &logits.to(&gpu)?,
+            Some(&mask),
+            &special,
+        ))?;
+        assert_eq!(tokens, expected);
+        assert!((logprob - expected_logprob).abs() < 1e-4);
+        assert!(!completed);
+        Ok(())
     }
 }
@@ -23,27 +23,56 @@ pub enum AudioError {
     InvalidLength(usize, usize),
     #[error("Invalid audio provided: {0}")]
     InvalidAudio(#[from] anyhow::Error),
+    #[error("Invalid WAV file: {0}")]
+    InvalidWav(String),
+    #[error("Unsupported WAV encoding: format tag {format} with {bits} bits per sample")]
+    UnsupportedWav { format: u16, bits: u16 },
 }
 
 pub struct SpectrogramGenerator {
     fft_plan: Arc<dyn RealToComplex<f32>>,
     hann_window: Array1<f32>,
     mels: Array2<f32>,
+    n_fft: usize,
 }
 
 impl SpectrogramGenerator {
+    /// `mels` is a precomputed [n_mels, N_FFT / 2 + 1] filterbank, e.g from a GGML header.
     pub fn new(mels: Vec<f32>) -> Self {
+        let n_freqs = N_FFT / 2 + 1;
+        let n_mels = mels.len() / n_freqs;
+        Self::from_filters(
+            Array2::from_shape_vec((n_mels, n_freqs), mels).unwrap(),
+            N_FFT,
+        )
+    }
+
+    /// # Mel filters
+    ///
+    /// Computes the filterbank instead of loading `mel_filters.npy`.
+    /// Matches `librosa.filters.mel(sr=sample_rate, n_fft=n_fft, n_mels=n_mels)`, which uses the
+    /// Slaney mel scale and area normalization. Whisper uses 80 mels, large-v3 uses 128.
+    pub fn with_mel_filters(n_mels: usize, sample_rate: usize, n_fft: usize) -> Self {
+        Self::from_filters(mel_filters(n_mels, sample_rate, n_fft), n_fft)
+    }
+
+    fn from_filters(mels: Array2<f32>, n_fft: usize) -> Self {
         let mut planner = RealFftPlanner::new();
         Self {
-            fft_plan: planner.plan_fft_forward(N_FFT),
-            hann_window: Self::hann_window(),
-            mels: Array2::from_shape_vec((N_MELS, N_FFT / 2 + 1), mels).unwrap(),
+            fft_plan: planner.plan_fft_forward(n_fft),
+            hann_window: Self::hann_window(n_fft),
+            mels,
+            n_fft,
         }
     }
 
-    fn hann_window() -> Array1<f32> {
-        let window = (0..N_FFT)
-            .map(|i| (i as f32 * 2.0 * PI) / N_FFT as f32)
+    pub fn n_mels(&self) -> usize {
+        self.mels.nrows()
+    }
+
+    fn hann_window(n_fft: usize) -> Array1<f32> {
+        let window = (0..n_fft)
+            .map(|i| (i as f32 * 2.0 * PI) / n_fft as f32)
             .map(|i| (1.0 - i.cos()) / 2.0)
             .collect::<Vec<_>>();
         Array1::from(window)
@@ -60,15 +89,16 @@ impl SpectrogramGenerator {
     }
 
     fn mel_spectrogram(&self, audio: &[f32]) -> Tensor {
-        let n_frames = (audio.len() - N_FFT) / HOP_LENGTH;
-        let right_padding = N_SAMPLES + FFT_PAD; //padding is all 0s, so we can ignore it
+        let n_fft = self.n_fft;
+        let n_frames = (audio.len() - n_fft) / HOP_LENGTH;
+        let right_padding = N_SAMPLES + n_fft / 2; //padding is all 0s, so we can ignore it
 
-        let mut spectrogram = Array2::<f32>::zeros((201, n_frames));
+        let mut spectrogram = Array2::<f32>::zeros((n_fft / 2 + 1, n_frames));
         for i in (0..audio.len() - right_padding).step_by(HOP_LENGTH) {
             if i / HOP_LENGTH >= n_frames {
                 break;
             }
-            let fft = self.fft(&audio[i..i + N_FFT]);
+            let fft = self.fft(&audio[i..i + n_fft]);
             let spectrogram_col = fft.iter().map(|c| c.norm_sqr()).collect::<Array1<f32>>();
             spectrogram
                 .column_mut(i / HOP_LENGTH)
@@ -89,7 +119,7 @@ impl SpectrogramGenerator {
                 "Audio must be non-empty"
             )));
         }
-        let padded = Self::pad_audio(audio, N_SAMPLES);
+        let padded = Self::reflect_pad(audio, N_SAMPLES, self.n_fft / 2);
         Ok(self.mel_spectrogram(&padded))
     }
 
@@ -99,20 +129,77 @@ impl SpectrogramGenerator {
     //   This must be done with care, because we have already performed the explicit padding
     //   the pre-padding will contain non-zero values, but the post-padding must be zero
     pub fn pad_audio(audio: Vec<f32>, padding: usize) -> Vec<f32> {
-        let padded_len = FFT_PAD + audio.len() + padding + FFT_PAD;
+        Self::reflect_pad(audio, padding, FFT_PAD)
+    }
+
+    fn reflect_pad(audio: Vec<f32>, padding: usize, fft_pad: usize) -> Vec<f32> {
+        let padded_len = fft_pad + audio.len() + padding + fft_pad;
         let mut padded_samples = vec![0.0; padded_len];
 
-        let mut reflect_padding = vec![0.0; FFT_PAD];
-        for i in 0..FFT_PAD {
-            reflect_padding[i] = audio[FFT_PAD - i];
+        let mut reflect_padding = vec![0.0; fft_pad];
+        for i in 0..fft_pad {
+            reflect_padding[i] = audio[fft_pad - i];
         }
 
-        padded_samples[0..FFT_PAD].copy_from_slice(&reflect_padding);
-        padded_samples[FFT_PAD..(FFT_PAD + audio.len())].copy_from_slice(&audio);
+        padded_samples[0..fft_pad].copy_from_slice(&reflect_padding);
+        padded_samples[fft_pad..(fft_pad + audio.len())].copy_from_slice(&audio);
         padded_samples
     }
 }
 
+//Slaney mel scale: linear below 1kHz, logarithmic above.
+const MIN_LOG_HZ: f64 = 1000.0;
+const F_SP: f64 = 200.0 / 3.0;
+const MIN_LOG_MEL: f64 = MIN_LOG_HZ / F_SP;
+
+fn log_step() -> f64 {
+    6.4f64.ln() / 27.0
+}
+
+fn hz_to_mel(hz: f64) -> f64 {
+    if hz >= MIN_LOG_HZ {
+        MIN_LOG_MEL + (hz / MIN_LOG_HZ).ln() / log_step()
+    } else {
+        hz / F_SP
+    }
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+    if mel >= MIN_LOG_MEL {
+        MIN_LOG_HZ * (log_step() * (mel - MIN_LOG_MEL)).exp()
+    } else {
+        F_SP * mel
+    }
+}
+
+/// Triangular filters evenly spaced on the mel scale between 0 and the Nyquist frequency,
+/// each normalized to unit area. Shape [n_mels, n_fft / 2 + 1].
+fn mel_filters(n_mels: usize, sample_rate: usize, n_fft: usize) -> Array2<f32> {
+    let n_freqs = n_fft / 2 + 1;
+    let nyquist = sample_rate as f64 / 2.0;
+    let fft_freqs = (0..n_freqs)
+        .map(|i| i as f64 * nyquist / (n_freqs - 1) as f64)
+        .collect::<Vec<_>>();
+
+    //n_mels + 2 edges, each filter spans 3 consecutive edges
+    let max_mel = hz_to_mel(nyquist);
+    let edges = (0..n_mels + 2)
+        .map(|i| mel_to_hz(i as f64 * max_mel / (n_mels + 1) as f64))
+        .collect::<Vec<_>>();
+
+    let mut filters = Array2::<f32>::zeros((n_mels, n_freqs));
+    for m in 0..n_mels {
+        let (left, center, right) = (edges[m], edges[m + 1], edges[m + 2]);
+        let norm = 2.0 / (right - left);
+        for (f, &freq) in fft_freqs.iter().enumerate() {
+            let lower = (freq - left) / (center - left);
+            let upper = (right - freq) / (right - center);
+            filters[[m, f]] = (lower.min(upper).max(0.0) * norm) as f32;
+        }
+    }
+    filters
+}
+
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use std::path::PathBuf;
@@ -128,11 +215,30 @@ mod tests {
     }
 
     fn load_sample(path: PathBuf) -> Vec<f32> {
-        let mut reader = hound::WavReader::open(path).unwrap();
-        reader
-            .samples::<i16>()
-            .map(|x| x.unwrap() as f32 / 32768.0)
-            .collect::<Vec<_>>()
+        let bytes = std::fs::read(path).unwrap();
+        crate::decode_wav(&bytes).unwrap().samples
+    }
+
+    #[test]
+    fn mel_filters_match() {
+        let api = Api::new().unwrap();
+        let repo = api.dataset("FL33TW00D-HF/ratchet-util".to_string());
+        let ground = load_npy(repo.get("mel_filters.npy").unwrap());
+        let ours = super::mel_filters(crate::N_MELS, crate::SAMPLE_RATE, crate::N_FFT);
+        assert_eq!(ours.len(), ground.len());
+        for (o, g) in ours.iter().zip(ground.iter()) {
+            assert!((o - g).abs() < 1e-6, "{o} != {g}");
+        }
+    }
+
+    #[test]
+    fn generates_128_mels() {
+        let generator = crate::SpectrogramGenerator::with_mel_filters(128, 16000, 400);
+        let audio = (0..16000)
+            .map(|i| (i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 16000.0).sin())
+            .collect::<Vec<_>>();
+        let mel = generator.generate(audio).unwrap();
+        assert_eq!(mel.shape()[1], 128);
     }
 
     #[test]
@@ -1,14 +1,22 @@
+use ndarray::{Array3, Axis};
 use ratchet::prelude::shape;
 use ratchet::Device;
 use ratchet::Tensor;
+use ratchet::TensorError;
 use ratchet_nn::Module;
 
+use crate::compression_ratio;
+use crate::find_alignment;
+use crate::ApplyForcedTokens;
+use crate::ApplyLogitBias;
 use crate::DecodingOptions;
 use crate::GreedySampler;
 use crate::LogitMutator;
 use crate::Prompt;
+use crate::SpecialTokens;
 use crate::WhisperDecoder;
 use crate::WhisperTokenizer;
+use crate::WordTiming;
 use crate::CHUNK_LENGTH;
 use crate::N_AUDIO_CTX;
 
@@ -16,14 +24,63 @@ use crate::N_AUDIO_CTX;
 pub enum DecodeError {
     #[error("No valid logits found")]
     NoValidLogitsFound,
+    #[error("Position {requested} is beyond the decoder's context of {limit} tokens, see `WhisperDecoder::extend_context`")]
+    ContextExceeded { requested: usize, limit: usize },
     #[error("Tokenizer error: {0}")]
     TokenizerError(#[from] tokenizers::Error),
+    #[error("Tensor error: {0}")]
+    TensorError(#[from] TensorError),
     #[error("Unknown error: {0}")]
     UnknownError(#[from] anyhow::Error),
 }
 
+#[derive(Debug, Clone)]
+pub struct DecodingResult {
+    pub tokens: Vec<i32>,
+    pub text: String,
+    pub avg_logprob: f32,
+    pub compression_ratio: f32,
+    pub temperature: f32,
+    /// Set when decoding with [crate::DecodingOptionsBuilder::word_timestamps],
+    /// relative to the start of the window.
+    pub words: Vec<WordTiming>,
+}
+
+/// Turns the growing token sequence into text deltas.
+/// The sampled tokens are decoded together each step, as a single byte level BPE token
+/// may be an incomplete UTF-8 sequence.
+struct TextStream {
+    start: usize,
+    emitted: String,
+}
+
+impl TextStream {
+    fn new(start: usize) -> Self {
+        Self {
+            start,
+            emitted: String::new(),
+        }
+    }
+
+    fn delta(&mut self, tokens: &[i32], tokenizer: &WhisperTokenizer) -> Option<String> {
+        let text_tokens = tokens[self.start..]
+            .iter()
+            .filter(|&&t| tokenizer.special().is_text(t))
+            .map(|&t| t as u32)
+            .collect::<Vec<_>>();
+        let text = tokenizer.decode(&text_tokens, true).ok()?;
+        if text.ends_with(char::REPLACEMENT_CHARACTER) || text.len() <= self.emitted.len() {
+            return None;
+        }
+        let delta = text.strip_prefix(self.emitted.as_str())?.to_string();
+        self.emitted = text;
+        Some(delta)
+    }
+}
+
 pub struct DecodingTask {
     options: DecodingOptions,
+    special: SpecialTokens,
     sample_len: u32,
     logit_mutators: Vec<Box<dyn LogitMutator>>,
     initial_tokens: Option<Vec<i32>>,
@@ -32,17 +89,24 @@ pub struct DecodingTask {
 
 impl DecodingTask {
     fn get_initial_tokens(&self, tokenizer: &WhisperTokenizer) -> Vec<i32> {
-        let mut init_tokens = tokenizer.sot_sequence();
-        if let Some(prompt) = &self.options.prompt {
+        let mut init_tokens = tokenizer.sot_sequence_for(self.options.task);
+        //The initial prompt only conditions the first window, once we have
+        //previous text it is passed through `prompt` instead.
+        let prompt = self
+            .options
+            .prompt
+            .clone()
+            .or_else(|| self.options.initial_prompt.clone().map(Prompt::Text));
+        if let Some(prompt) = prompt {
             let prompt_tokens = match prompt {
-                Prompt::Tokens(tokens) => tokens.clone(),
+                Prompt::Tokens(tokens) => tokens,
                 Prompt::Text(text) => tokenizer
                     .encode(format!(" {}", text).as_str(), false)
                     .unwrap(),
             };
             let max_prompt_length = 448 / 2 - 1; // equivalent to self.n_ctx // 2 - 1 in python
             let prompt_length = prompt_tokens.len().min(max_prompt_length);
-            let mut tokens = vec![WhisperTokenizer::START_OF_PREV];
+            let mut tokens = vec![self.special.start_of_prev];
             tokens.extend_from_slice(&prompt_tokens[prompt_tokens.len() - prompt_length..]);
             tokens.extend(init_tokens);
             init_tokens = tokens;
@@ -56,6 +120,7 @@ impl DecodingTask {
         let max_initial_timestamp = options.max_initial_timestamp;
         let mut task = DecodingTask {
             options,
+            special: *tokenizer.special(),
             logit_mutators: vec![],
             sample_len,
             initial_tokens: None,
@@ -64,6 +129,19 @@ impl DecodingTask {
         task.initial_tokens = Some(task.get_initial_tokens(tokenizer));
         task.initial_tokens_len = Some(task.initial_tokens.as_ref().unwrap().len());
 
+        let sample_begin = task.initial_tokens_len.unwrap();
+        if !task.options.logit_bias.is_empty() {
+            let bias = task.options.logit_bias.clone();
+            task.logit_mutators
+                .push(Box::new(ApplyLogitBias::new(bias)));
+        }
+        //Forced tokens come last, so they override any bias
+        if !task.options.forced_decoder_ids.is_empty() {
+            let forced = task.options.forced_decoder_ids.clone();
+            task.logit_mutators
+                .push(Box::new(ApplyForcedTokens::new(sample_begin, forced)));
+        }
+
         let mut max_initial_timestamp_index = None;
         if let Some(max_initial_timestamp) = max_initial_timestamp {
             let precision = CHUNK_LENGTH as f32 / N_AUDIO_CTX as f32;
@@ -73,55 +151,216 @@ impl DecodingTask {
         task
     }
 
-    fn main_loop(
+    /// The token forced by `forced_decoder_ids` once `n_tokens` are in the sequence.
+    fn forced_token(&self, n_tokens: usize) -> Option<i32> {
+        let position = n_tokens.checked_sub(self.initial_tokens_len?)?;
+        self.options
+            .forced_decoder_ids
+            .iter()
+            .find(|(p, _)| *p == position)
+            .map(|&(_, token)| token as i32)
+    }
+
+    /// `logit_bias` as an additive [1, 1, n_vocab] mask, for [GreedySampler::sample_on_device].
+    fn bias_mask(&self, n_vocab: usize, device: &Device) -> Option<Tensor> {
+        if self.options.logit_bias.is_empty() {
+            return None;
+        }
+        let mut mask = vec![0f32; n_vocab];
+        for (&token, &bias) in &self.options.logit_bias {
+            if let Some(m) = mask.get_mut(token as usize) {
+                *m += bias;
+            }
+        }
+        Some(Tensor::from_data(
+            mask,
+            shape![1, 1, n_vocab],
+            device.clone(),
+        ))
+    }
+
+    /// Greedy decoding on the GPU stays on the device, see [GreedySampler::sample_on_device],
+    /// if every logit mutator can be applied there. Otherwise the logits are read back
+    /// and sampled on the host.
+    async fn main_loop(
         &self,
-        decoder: &WhisperDecoder,
+        decoder: &mut WhisperDecoder,
         audio_ctx: Tensor,
         mut tokens: Vec<i32>,
-    ) -> Result<(), DecodeError> {
+        on_step: &mut dyn FnMut(&[i32]),
+    ) -> Result<(Vec<i32>, f32), DecodeError> {
         let _timestamps_seen = 0;
         let device = audio_ctx.device().clone();
+        let mut sum_logprobs = 0.0;
+        let on_device = self.options.temperature == 0.0
+            && device.is_gpu()
+            && self.logit_mutators.iter().all(|m| m.has_device_impl());
+        //Matches the logits as sliced by the sampler
+        let n_vocab = self.special.n_vocab().min(decoder.n_vocab());
+        let mask = self.bias_mask(n_vocab, &device).filter(|_| on_device);
+
+        //Number of leading tokens whose keys and values are in the KV cache
+        let mut cached = 0;
+        decoder.cache_mut().reset();
 
         for _ in 0..self.sample_len {
-            let input_tokens = if tokens.len() > self.initial_tokens_len.unwrap() {
-                &tokens[tokens.len() - 1..]
-            } else {
-                &tokens
-            };
-            let input_t =
-                Tensor::from_data(input_tokens, shape![1, input_tokens.len()], device.clone());
+            //A forced token needs no logits, its logprob is 0 as every other token is banned
+            let forced = self.forced_token(tokens.len()).filter(|_| on_device);
+            if let Some(token) = forced {
+                tokens.push(token);
+                on_step(&tokens);
+                if token == self.special.eot {
+                    break;
+                }
+                continue;
+            }
 
-            let mut logits = decoder.forward(&[audio_ctx.clone(), input_t])?;
+            //Only the tokens since the last step are fed, earlier ones are attended to via the cache
+            let input = tokens[cached..].to_vec();
+            let input_t =
+                Tensor::from_data(input, shape![1, tokens.len() - cached], device.clone());
+            let logits = decoder.forward(&[audio_ctx.clone(), input_t])?;
+            decoder.cache_mut().update(tokens.len() - cached);
+            cached = tokens.len();
 
-            let token_t = Tensor::from_data(tokens.clone(), shape![1, tokens.len()], Device::CPU);
-            for m in &self.logit_mutators {
-                logits = m.apply(logits, &token_t)?;
-            }
+            let (new_tokens, logprob, completed) = if on_device {
+                GreedySampler::sample_on_device(tokens, &logits, mask.as_ref(), &self.special)
+                    .await?
+            } else {
+                let logits = logits.resolve()?;
+                #[cfg(not(target_arch = "wasm32"))]
+                let mut logits = logits.to(&Device::CPU)?;
+                #[cfg(target_arch = "wasm32")]
+                let mut logits = logits.to(&Device::CPU).await?;
 
-            let (_, new_tokens, completed) = GreedySampler::sample(tokens, logits)?;
+                let token_t =
+                    Tensor::from_data(tokens.clone(), shape![1, tokens.len()], Device::CPU);
+                for m in &self.logit_mutators {
+                    logits = m.apply(logits, &token_t)?;
+                }
+                GreedySampler::sample(tokens, logits, self.options.temperature, &self.special)?
+            };
+            sum_logprobs += logprob;
 
             tokens = new_tokens;
+            on_step(&tokens);
             if completed {
                 break;
             }
         }
-        Ok(())
+        decoder.cache_mut().reset();
+        Ok((tokens, sum_logprobs))
     }
 
-    pub fn run(
+    pub async fn run(
         &self,
-        decoder: &WhisperDecoder,
+        decoder: &mut WhisperDecoder,
         audio_ctx: &Tensor,
         tokenizer: &WhisperTokenizer,
-    ) -> Result<Vec<i32>, DecodeError> {
-        let mut tokens = self.get_initial_tokens(tokenizer);
-        let _result = self.main_loop(decoder, audio_ctx.clone(), tokens.clone());
+    ) -> Result<DecodingResult, DecodeError> {
+        self.run_streaming(decoder, audio_ctx, tokenizer, &mut |_| {})
+            .await
+    }
+
+    /// Like [DecodingTask::run], calling `on_text` with the text added by each sampled token.
+    /// Special and timestamp tokens add no text.
+    pub async fn run_streaming(
+        &self,
+        decoder: &mut WhisperDecoder,
+        audio_ctx: &Tensor,
+        tokenizer: &WhisperTokenizer,
+        on_text: &mut dyn FnMut(&str),
+    ) -> Result<DecodingResult, DecodeError> {
+        let initial_tokens = self.get_initial_tokens(tokenizer);
+        let mut stream = TextStream::new(initial_tokens.len());
+        let mut on_step = |tokens: &[i32]| {
+            if let Some(delta) = stream.delta(tokens, tokenizer) {
+                on_text(&delta);
+            }
+        };
+        let (mut tokens, sum_logprobs) = self
+            .main_loop(decoder, audio_ctx.clone(), initial_tokens, &mut on_step)
+            .await?;
 
         tokens = tokens.drain(self.initial_tokens_len.unwrap()..).collect();
-        let eot_index = tokens.iter().position(|x| *x == WhisperTokenizer::EOT);
+        let eot_index = tokens.iter().position(|x| *x == self.special.eot);
         if let Some(eot_index) = eot_index {
             tokens.truncate(eot_index);
         }
-        Ok(tokens)
+        let u32_tokens = tokens.iter().map(|&t| t as u32).collect::<Vec<_>>();
+        let text = tokenizer.decode(&u32_tokens, true)?;
+        Ok(DecodingResult {
+            avg_logprob: sum_logprobs / (tokens.len() + 1) as f32,
+            compression_ratio: compression_ratio(&text),
+            text,
+            temperature: self.options.temperature,
+            tokens,
+            words: vec![],
+        })
+    }
+
+    /// # Word level timestamps
+    ///
+    /// Runs the decoder once over the complete sequence, and aligns each word
+    /// to the audio using the cross attention weights of the latter half of the decoder layers.
+    /// `num_frames` is the number of mel frames in the segment.
+    pub async fn word_timings(
+        &self,
+        decoder: &WhisperDecoder,
+        audio_ctx: &Tensor,
+        tokenizer: &WhisperTokenizer,
+        tokens: &[i32],
+        num_frames: usize,
+    ) -> Result<Vec<WordTiming>, DecodeError> {
+        let text_tokens = tokens
+            .iter()
+            .copied()
+            .filter(|&t| self.special.is_text(t))
+            .collect::<Vec<_>>();
+
+        let sot_sequence = tokenizer.sot_sequence_for(self.options.task);
+        let mut input = sot_sequence.clone();
+        input.push(self.special.no_timestamps);
+        input.extend_from_slice(&text_tokens);
+        input.push(self.special.eot);
+
+        let device = audio_ctx.device().clone();
+        let input_t = Tensor::from_data(input.clone(), shape![1, input.len()], device);
+        let (_, x_attn_weights) = decoder.forward_with_x_attn(&[audio_ctx.clone(), input_t])?;
+
+        let n_layers = x_attn_weights.len();
+        let resolved = x_attn_weights
+            .into_iter()
+            .skip(n_layers / 2)
+            .collect::<Vec<_>>();
+        //One pass, the decoder layers they share are computed once
+        Tensor::resolve_all(&resolved)?;
+        let resolved = resolved.iter().collect::<Vec<_>>();
+        #[cfg(not(target_arch = "wasm32"))]
+        let host = audio_ctx.device().read_back(&resolved)?;
+        #[cfg(target_arch = "wasm32")]
+        let host = audio_ctx.device().read_back(&resolved).await?;
+
+        let mut heads = Vec::with_capacity(host.len());
+        for w in host {
+            //[1, n_heads, n_tokens, n_audio_ctx] -> [n_heads, n_tokens, n_audio_ctx]
+            let w = w
+                .into_ndarray::<f32>()
+                .remove_axis(Axis(0))
+                .into_dimensionality::<ndarray::Ix3>()
+                .map_err(anyhow::Error::from)?;
+            heads.push(w);
+        }
+        let views = heads.iter().map(|h| h.view()).collect::<Vec<_>>();
+        let weights: Array3<f32> =
+            ndarray::concatenate(Axis(0), &views).map_err(anyhow::Error::from)?;
+
+        Ok(find_alignment(
+            weights,
+            &text_tokens,
+            num_frames,
+            sot_sequence.len(),
+            tokenizer,
+        )?)
     }
 }
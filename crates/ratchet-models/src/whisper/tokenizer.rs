@@ -1,6 +1,10 @@
 use crate::{Language, Task};
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::ops::RangeInclusive;
-use tokenizers::Tokenizer;
+use tokenizers::models::bpe::BPE;
+use tokenizers::pre_tokenizers::byte_level::ByteLevel;
+use tokenizers::{AddedToken, Tokenizer};
 
 lazy_static::lazy_static! {
     pub static ref LANGUAGES: [&'static str; 99] = {
@@ -17,14 +21,158 @@ lazy_static::lazy_static! {
     };
 }
 
+// The parts of tokenizer.json Whisper needs. Deserializing `Tokenizer` directly buffers the
+// whole model section into an intermediate tree before building the BPE, which doubles peak
+// memory for large vocabs. Here serde skips every other field, and the vocab and merges are
+// built straight from the input.
+#[derive(Deserialize)]
+struct TokenizerJson {
+    #[serde(default)]
+    added_tokens: Vec<AddedTokenJson>,
+    model: BpeJson,
+}
+
+#[derive(Deserialize)]
+struct AddedTokenJson {
+    id: u32,
+    content: String,
+    special: bool,
+}
+
+#[derive(Deserialize)]
+struct BpeJson {
+    vocab: HashMap<String, u32>,
+    merges: Vec<String>,
+}
+
+impl TokenizerJson {
+    fn into_tokenizer(self) -> anyhow::Result<Tokenizer> {
+        let TokenizerJson {
+            added_tokens,
+            model: BpeJson { mut vocab, merges },
+        } = self;
+        let merges = merges
+            .into_iter()
+            .map(|merge| match merge.split_once(' ') {
+                Some((a, b)) => Ok((a.to_string(), b.to_string())),
+                None => Err(anyhow::anyhow!("Invalid merge: {}", merge)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        //Added tokens keep the ids from the file, rather than being appended to the vocab
+        for token in &added_tokens {
+            vocab.insert(token.content.clone(), token.id);
+        }
+        let bpe = BPE::builder()
+            .vocab_and_merges(vocab, merges)
+            .build()
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        let byte_level = ByteLevel::default().add_prefix_space(false);
+        let mut tokenizer = Tokenizer::new(bpe);
+        tokenizer
+            .with_pre_tokenizer(byte_level)
+            .with_decoder(byte_level);
+        let (special, added): (Vec<_>, Vec<_>) = added_tokens
+            .into_iter()
+            .map(|t| AddedToken::from(t.content, t.special))
+            .partition(|t| t.special);
+        tokenizer.add_special_tokens(&special);
+        tokenizer.add_tokens(&added);
+        Ok(tokenizer)
+    }
+}
+
+/// # Special tokens
+///
+/// The ids of the control tokens, which differ between the multilingual and the
+/// English-only (`.en`) vocabularies. English-only models have no language tokens,
+/// so every id from SOT onwards is shifted down by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpecialTokens {
+    pub eot: i32,
+    pub sot: i32,
+    pub translate: i32,
+    pub transcribe: i32,
+    pub start_of_prev: i32,
+    pub no_captions: i32,
+    pub no_timestamps: i32,
+    pub timestamp_begin: i32,
+    pub multilingual: bool,
+}
+
+impl SpecialTokens {
+    pub const MULTILINGUAL: Self = Self {
+        eot: 50257,
+        sot: 50258,
+        translate: 50358,
+        transcribe: 50359,
+        start_of_prev: 50361,
+        no_captions: 50362,
+        no_timestamps: 50363,
+        timestamp_begin: 50364,
+        multilingual: true,
+    };
+
+    pub const ENGLISH: Self = Self {
+        eot: 50256,
+        sot: 50257,
+        translate: 50357,
+        transcribe: 50358,
+        start_of_prev: 50360,
+        no_captions: 50361,
+        no_timestamps: 50362,
+        timestamp_begin: 50363,
+        multilingual: false,
+    };
+
+    /// Looks up the ids by content, `None` if the vocab lacks any of them.
+    pub fn from_tokenizer(tokenizer: &Tokenizer) -> Option<Self> {
+        let id = |token: &str| tokenizer.token_to_id(token).map(|id| id as i32);
+        Some(Self {
+            eot: id("<|endoftext|>")?,
+            sot: id("<|startoftranscript|>")?,
+            translate: id("<|translate|>")?,
+            transcribe: id("<|transcribe|>")?,
+            start_of_prev: id("<|startofprev|>")?,
+            //Renamed to nospeech in later versions of the vocab
+            no_captions: id("<|nocaptions|>").or_else(|| id("<|nospeech|>"))?,
+            no_timestamps: id("<|notimestamps|>")?,
+            timestamp_begin: id("<|0.00|>")?,
+            multilingual: id("<|en|>").is_some(),
+        })
+    }
+
+    /// The token selecting `task`.
+    pub fn task(&self, task: Task) -> i32 {
+        match task {
+            Task::Transcribe => self.transcribe,
+            Task::Translate => self.translate,
+        }
+    }
+
+    /// Size of the vocab, the 1501 timestamps (0.00 to 30.00 in steps of 0.02) come last.
+    pub fn n_vocab(&self) -> usize {
+        self.timestamp_begin as usize + 1501
+    }
+
+    /// Every id up to EOT is text, EOT and beyond are control or timestamp tokens.
+    #[inline]
+    pub fn is_text(&self, token: i32) -> bool {
+        token < self.eot
+    }
+}
+
 //Wrapper around tokenizers::Tokenizer with helpers
 #[derive(Clone)]
 pub struct WhisperTokenizer {
     inner: Tokenizer,
+    special: SpecialTokens,
     language: i32,
     task: Task,
 }
 
+//The constants below are the multilingual ids, see [WhisperTokenizer::special] for the ids
+//of the loaded vocab.
 impl WhisperTokenizer {
     pub const SOT: i32 = 50258;
     pub const EOT: i32 = 50257;
@@ -53,19 +201,27 @@ impl WhisperTokenizer {
         49870, 50254,
     ];
 
+    /// The special token ids are read from the vocab, falling back to the defaults for
+    /// `is_multilingual` if it lacks any of them.
     pub fn load(
         bytes: Option<Vec<u8>>,
-        _is_multilingual: bool,
+        is_multilingual: bool,
         language: Language,
         task: Task,
     ) -> Self {
         let inner = if let Some(bytes) = bytes {
-            Tokenizer::from_bytes(bytes).unwrap()
+            Self::parse(&bytes).unwrap()
         } else {
-            Tokenizer::from_file("tokenizer.json").unwrap()
+            let file = std::fs::File::open("tokenizer.json").unwrap();
+            Self::parse_reader(std::io::BufReader::new(file)).unwrap()
         };
+        let special = SpecialTokens::from_tokenizer(&inner).unwrap_or(match is_multilingual {
+            true => SpecialTokens::MULTILINGUAL,
+            false => SpecialTokens::ENGLISH,
+        });
         let mut tokenizer = Self {
             inner,
+            special,
             language: -1,
             task,
         };
@@ -73,6 +229,20 @@ impl WhisperTokenizer {
         tokenizer
     }
 
+    /// # Parse
+    ///
+    /// Builds the BPE tokenizer from the contents of a `tokenizer.json`, reading only
+    /// the vocab, merges & added tokens. Normalizers and post processors are ignored,
+    /// Whisper's tokenizer uses neither.
+    pub fn parse(json: &[u8]) -> anyhow::Result<Tokenizer> {
+        serde_json::from_slice::<TokenizerJson>(json)?.into_tokenizer()
+    }
+
+    /// [WhisperTokenizer::parse], reading incrementally from `reader`.
+    pub fn parse_reader<R: std::io::Read>(reader: R) -> anyhow::Result<Tokenizer> {
+        serde_json::from_reader::<_, TokenizerJson>(reader)?.into_tokenizer()
+    }
+
     pub fn set_language(&mut self, language: Language) {
         let token = match language {
             Language::String(s) => {
@@ -81,16 +251,32 @@ impl WhisperTokenizer {
                     panic!("Language {} not found", s);
                 }
 
-                WhisperTokenizer::SOT + 1 + lang_position.unwrap() as i32
+                self.special.sot + 1 + lang_position.unwrap() as i32
             }
             Language::Token(t) => t,
         };
         self.language = token;
     }
 
+    #[inline]
+    pub fn special(&self) -> &SpecialTokens {
+        &self.special
+    }
+
     #[inline]
     pub fn sot_sequence(&self) -> Vec<i32> {
-        vec![Self::SOT, self.language, self.task.into()]
+        self.sot_sequence_for(self.task)
+    }
+
+    /// The start of transcript sequence, ending in the token for `task`
+    /// rather than the task the tokenizer was loaded with.
+    /// English-only models take neither a language nor a task, the sequence is just SOT.
+    #[inline]
+    pub fn sot_sequence_for(&self, task: Task) -> Vec<i32> {
+        match self.special.multilingual {
+            true => vec![self.special.sot, self.language, self.special.task(task)],
+            false => vec![self.special.sot],
+        }
     }
 
     #[inline]
@@ -100,7 +286,7 @@ impl WhisperTokenizer {
 
     #[inline]
     pub fn is_multilingual(&self) -> bool {
-        self.inner.get_vocab_size(true) == Self::SIZE
+        self.special.multilingual
     }
 
     pub fn encode(&self, text: &str, skip_special: bool) -> Result<Vec<i32>, tokenizers::Error> {
@@ -116,4 +302,185 @@ impl WhisperTokenizer {
     pub fn decode(&self, tokens: &[u32], skip_special: bool) -> Result<String, tokenizers::Error> {
         self.inner.decode(tokens, skip_special)
     }
+
+    //https://github.com/openai/whisper/blob/1cea4357687b676b293cb5473e1ade25f5b1cef7/whisper/tokenizer.py#L277
+    pub fn split_to_word_tokens(
+        &self,
+        tokens: &[i32],
+    ) -> Result<(Vec<String>, Vec<Vec<i32>>), tokenizers::Error> {
+        let no_spaces = ["zh", "ja", "th", "lo", "my"]
+            .iter()
+            .filter_map(|l| LANGUAGES.iter().position(|x| x == l))
+            .any(|p| self.special.sot + 1 + p as i32 == self.language);
+        if no_spaces {
+            self.split_tokens_on_unicode(tokens)
+        } else {
+            self.split_tokens_on_spaces(tokens)
+        }
+    }
+
+    fn split_tokens_on_unicode(
+        &self,
+        tokens: &[i32],
+    ) -> Result<(Vec<String>, Vec<Vec<i32>>), tokenizers::Error> {
+        let mut words = vec![];
+        let mut word_tokens = vec![];
+        let mut current = vec![];
+        for &token in tokens {
+            current.push(token);
+            let u32_tokens = current.iter().map(|&t| t as u32).collect::<Vec<_>>();
+            let decoded = self.decode(&u32_tokens, false)?;
+            //Incomplete UTF-8 sequences are decoded to the replacement character
+            if !decoded.contains('\u{fffd}') {
+                words.push(decoded);
+                word_tokens.push(std::mem::take(&mut current));
+            }
+        }
+        Ok((words, word_tokens))
+    }
+
+    fn split_tokens_on_spaces(
+        &self,
+        tokens: &[i32],
+    ) -> Result<(Vec<String>, Vec<Vec<i32>>), tokenizers::Error> {
+        let (subwords, subword_tokens) = self.split_tokens_on_unicode(tokens)?;
+        let mut words: Vec<String> = vec![];
+        let mut word_tokens: Vec<Vec<i32>> = vec![];
+        for (subword, tokens) in subwords.into_iter().zip(subword_tokens) {
+            let special = !self.special.is_text(tokens[0]);
+            let with_space = subword.starts_with(' ');
+            let punctuation = subword.trim().chars().all(|c| c.is_ascii_punctuation());
+            if special || with_space || punctuation || words.is_empty() {
+                words.push(subword);
+                word_tokens.push(tokens);
+            } else {
+                words.last_mut().unwrap().push_str(&subword);
+                word_tokens.last_mut().unwrap().extend(tokens);
+            }
+        }
+        Ok((words, word_tokens))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{SpecialTokens, WhisperTokenizer};
+    use crate::{Language, Task};
+    use tokenizers::Tokenizer;
+
+    pub(crate) const TOKENIZER_JSON: &str = r#"{
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": [
+            {"id": 9, "content": "<|endoftext|>", "single_word": false, "lstrip": false,
+             "rstrip": false, "normalized": false, "special": true}
+        ],
+        "normalizer": null,
+        "pre_tokenizer": {"type": "ByteLevel", "add_prefix_space": false, "trim_offsets": true},
+        "post_processor": null,
+        "decoder": {"type": "ByteLevel", "add_prefix_space": true, "trim_offsets": true},
+        "model": {
+            "type": "BPE",
+            "dropout": null,
+            "unk_token": null,
+            "continuing_subword_prefix": "",
+            "end_of_word_suffix": "",
+            "fuse_unk": false,
+            "vocab": {"h": 0, "e": 1, "l": 2, "o": 3, "\u0120": 4, "he": 5, "ll": 6, "hell": 7, "hello": 8},
+            "merges": ["h e", "l l", "he ll", "hell o"]
+        }
+    }"#;
+
+    #[test]
+    fn parse_matches_full_deserialization() -> anyhow::Result<()> {
+        let parsed = WhisperTokenizer::parse(TOKENIZER_JSON.as_bytes())?;
+        let full = Tokenizer::from_bytes(TOKENIZER_JSON.as_bytes()).unwrap();
+        let text = "hello hello<|endoftext|>";
+        let ids = parsed.encode(text, false).unwrap().get_ids().to_vec();
+        assert_eq!(ids, [8, 4, 8, 9]);
+        assert_eq!(ids, full.encode(text, false).unwrap().get_ids());
+        assert_eq!(parsed.decode(&ids, true).unwrap(), "hello hello");
+        assert_eq!(
+            parsed.decode(&ids, false).unwrap(),
+            full.decode(&ids, false).unwrap()
+        );
+
+        let streamed = WhisperTokenizer::parse_reader(TOKENIZER_JSON.as_bytes())?;
+        assert_eq!(streamed.get_vocab(true), parsed.get_vocab(true));
+        Ok(())
+    }
+
+    #[test]
+    fn special_tokens_follow_the_vocab() {
+        let control = [
+            "<|startoftranscript|>",
+            "<|translate|>",
+            "<|transcribe|>",
+            "<|startofprev|>",
+            "<|nospeech|>",
+            "<|notimestamps|>",
+            "<|0.00|>",
+        ];
+        let added = control
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                format!(
+                    r#"{{"id": {}, "content": "{}", "special": true}},"#,
+                    10 + i,
+                    t
+                )
+            })
+            .collect::<String>();
+        let json = TOKENIZER_JSON.replacen(
+            "\"added_tokens\": [",
+            &format!("\"added_tokens\": [{added}"),
+            1,
+        );
+        let load = |json: &str, is_multilingual| {
+            let bytes = Some(json.as_bytes().to_vec());
+            WhisperTokenizer::load(
+                bytes,
+                is_multilingual,
+                Language::String("en".into()),
+                Task::Transcribe,
+            )
+        };
+
+        //No language tokens, so English-only whatever the caller expects
+        let english = load(&json, true);
+        let expected = SpecialTokens {
+            eot: 9,
+            sot: 10,
+            translate: 11,
+            transcribe: 12,
+            start_of_prev: 13,
+            no_captions: 14,
+            no_timestamps: 15,
+            timestamp_begin: 16,
+            multilingual: false,
+        };
+        assert_eq!(english.special(), &expected);
+        assert_eq!(english.sot_sequence(), [10]);
+
+        let json = json.replacen(
+            "\"added_tokens\": [",
+            r#""added_tokens": [{"id": 17, "content": "<|en|>", "special": true},"#,
+            1,
+        );
+        let multilingual = load(&json, false);
+        assert!(multilingual.is_multilingual());
+        assert_eq!(multilingual.sot_sequence_for(Task::Translate), [10, 11, 11]);
+
+        //Vocabs without the control tokens fall back to the defaults
+        assert_eq!(
+            load(TOKENIZER_JSON, false).special(),
+            &SpecialTokens::ENGLISH
+        );
+        assert_eq!(
+            load(TOKENIZER_JSON, true).special(),
+            &SpecialTokens::MULTILINGUAL
+        );
+    }
 }
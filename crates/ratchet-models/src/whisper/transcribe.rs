@@ -1,67 +1,306 @@
 use std::cmp::min;
 
+use ratchet::{shape, Tensor};
 use ratchet_nn::Module;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
 
 use crate::{
-    DecodingOptions, DecodingTask, Language, Prompt, Whisper, HOP_LENGTH, N_AUDIO_CTX, N_FRAMES,
+    DecodingOptions, DecodingResult, DecodingTask, GreedySampler, Language, Prompt, SpecialTokens,
+    Task, Whisper, WhisperDecoder, WhisperEncoder, WhisperTokenizer, HOP_LENGTH, N_FRAMES,
     SAMPLE_RATE,
 };
 
+/// Runs a silent window and a single decode step of `tokens`, see [Whisper::warmup].
+/// On the GPU the step is sampled as decoding does, so its pipelines are compiled too.
+pub(crate) async fn warm_pipelines(
+    encoder: &WhisperEncoder,
+    decoder: &WhisperDecoder,
+    n_mels: usize,
+    tokens: &[i32],
+    special: &SpecialTokens,
+) -> anyhow::Result<()> {
+    let device = decoder.device().clone();
+    let mel = Tensor::zeros::<f32>(&shape![1, n_mels, 2 * encoder.n_ctx()], &device);
+    let audio_ctx = encoder.forward(&mel)?.resolve()?;
+
+    let input = Tensor::from_data(tokens, shape![1, tokens.len()], device.clone());
+    let logits = decoder.forward(&[audio_ctx, input])?;
+    if device.is_gpu() {
+        GreedySampler::sample_on_device(tokens.to_vec(), &logits, None, special).await?;
+    } else {
+        logits.resolve()?;
+    }
+    Ok(())
+}
+
+/// Shorter runs aren't merged, a shared `.` or ` the` is as likely to be chance as a repeat.
+const MIN_OVERLAP_TOKENS: usize = 2;
+
+/// # Overlap merge
+///
+/// Adjacent windows overlap by [DecodingOptions::overlap_frames], so the end of `previous`
+/// is transcribed again at the start of `next`. Removes the longest run of text tokens that
+/// both ends `previous` and begins `next` from `next`, returning its length.
+/// Timestamp tokens are relative to their window so never match, those in `next` are kept.
+pub(crate) fn merge_overlap(
+    previous: &[i32],
+    next: &mut Vec<i32>,
+    special: &SpecialTokens,
+) -> usize {
+    let previous = previous
+        .iter()
+        .filter(|&&t| special.is_text(t))
+        .collect::<Vec<_>>();
+    let text_positions = (0..next.len())
+        .filter(|&i| special.is_text(next[i]))
+        .collect::<Vec<_>>();
+
+    let longest = min(previous.len(), text_positions.len());
+    let Some(overlap) = (MIN_OVERLAP_TOKENS..=longest).rev().find(|&n| {
+        previous[previous.len() - n..]
+            .iter()
+            .zip(&text_positions[..n])
+            .all(|(&&p, &i)| p == next[i])
+    }) else {
+        return 0;
+    };
+    for &i in text_positions[..overlap].iter().rev() {
+        next.remove(i);
+    }
+    overlap
+}
+
+/// # Temperature fallback
+///
+/// Decode at each temperature in the schedule, until the result passes the thresholds.
+/// If none do, the result from the highest temperature is returned.
+async fn decode_with_fallback(
+    decoder: &mut WhisperDecoder,
+    tokenizer: &WhisperTokenizer,
+    audio_ctx: &Tensor,
+    options: &DecodingOptions,
+    on_text: &mut dyn FnMut(&str),
+) -> anyhow::Result<(DecodingTask, DecodingResult)> {
+    let mut result = None;
+    for temperature in options.temperature_schedule() {
+        let mut options = options.clone();
+        options.temperature = temperature;
+        let logprob_threshold = options.logprob_threshold;
+        let compression_ratio_threshold = options.compression_ratio_threshold;
+
+        let task = DecodingTask::new(options, tokenizer);
+        let decoded = task
+            .run_streaming(decoder, audio_ctx, tokenizer, on_text)
+            .await?;
+
+        //Too repetitive, or too unlikely
+        let needs_fallback = compression_ratio_threshold
+            .is_some_and(|t| decoded.compression_ratio > t)
+            || logprob_threshold.is_some_and(|t| decoded.avg_logprob < t);
+        result = Some((task, decoded));
+        if !needs_fallback {
+            break;
+        }
+        log::warn!(
+            "Decoding failed at temperature {}, falling back",
+            temperature
+        );
+    }
+    result.ok_or_else(|| anyhow::anyhow!("Empty temperature schedule"))
+}
+
+/// # Decode precomputed features
+///
+/// Decodes a single window of encoder features, e.g produced elsewhere by
+/// [crate::WhisperEncoder::export_features], without requiring an encoder.
+/// The language must be specified, as detection requires the encoder.
+pub async fn decode_features(
+    decoder: &mut WhisperDecoder,
+    tokenizer: &WhisperTokenizer,
+    audio_ctx: &Tensor,
+    options: DecodingOptions,
+) -> anyhow::Result<DecodingResult> {
+    if options.language.is_none() {
+        anyhow::bail!("A language must be specified when decoding precomputed features");
+    }
+    let (_, decoded) =
+        decode_with_fallback(decoder, tokenizer, audio_ctx, &options, &mut |_| {}).await?;
+    Ok(decoded)
+}
+
+/// Transcribes `audio` window by window, returning the result of every window.
+/// See [transcribe_streaming], which this runs without a callback.
 pub async fn transcribe(
-    model: &Whisper,
+    model: &mut Whisper,
+    audio: Vec<f32>,
+    decode_options: DecodingOptions,
+) -> anyhow::Result<Vec<DecodingResult>> {
+    transcribe_streaming(model, audio, decode_options, |_| {}).await
+}
+
+/// # Streaming transcription
+///
+/// Transcribes `audio` window by window, calling `on_token` with the text of each token
+/// as it is sampled. Returns the result of every window.
+///
+/// If a window falls back to a higher temperature, its text is streamed again from the start
+/// of the window. With [crate::DecodingOptionsBuilder::chunk_overlap_seconds] set, text
+/// repeated at the start of a window is merged out of its result, but is still streamed.
+pub async fn transcribe_streaming(
+    model: &mut Whisper,
     audio: Vec<f32>,
     mut decode_options: DecodingOptions,
-) -> anyhow::Result<()> {
+    mut on_token: impl FnMut(&str),
+) -> anyhow::Result<Vec<DecodingResult>> {
     #[cfg(not(target_arch = "wasm32"))]
     let mel = model.specgen.generate(audio)?.to(&model.device)?;
     #[cfg(target_arch = "wasm32")]
     let mel = model.specgen.generate(audio)?.to(&model.device).await?;
 
+    let n_mels = model.n_mels();
     let content_frames = mel.shape()[mel.rank() - 1] - N_FRAMES;
-
     if decode_options.language.is_none() {
         if !model.is_multilingual() {
             log::error!("No language specified, using English");
             decode_options.language = Some(Language::String("en".to_string()));
         } else {
             log::error!("No language specified, using language detection");
-            let mel = mel.slice(&[0..80, 0..3000])?;
+            let mel = mel.slice(&[0..1, 0..n_mels, 0..N_FRAMES])?;
             decode_options.language = Some(model.detect_language(mel)?);
         }
     }
+    if matches!(decode_options.task, Task::Translate) && !model.is_multilingual() {
+        anyhow::bail!("English-only models cannot translate, use a multilingual model");
+    }
 
-    let _language = decode_options.language.as_ref().unwrap();
-    let _task = decode_options.task;
-
-    let seek = 0;
-    let all_tokens = Vec::with_capacity(512);
-    let _input_stride = N_FRAMES / N_AUDIO_CTX;
-    let prompt_since_reset = 0;
-
+    let overlap_frames = decode_options.overlap_frames();
+    let mut seek = 0;
+    let mut all_tokens: Vec<i32> = Vec::with_capacity(512);
+    let mut results: Vec<DecodingResult> = vec![];
     while seek < content_frames {
-        let mut decode_options = decode_options.clone();
-        let time_offset = (seek * HOP_LENGTH) as f64 / SAMPLE_RATE as f64;
-        decode_options.time_offset = Some(time_offset);
-        let mel_segment = mel.slice(&[0..80, 0..3000])?;
+        let mut options = decode_options.clone();
+        options.time_offset = Some((seek * HOP_LENGTH) as f64 / SAMPLE_RATE as f64);
+        if !all_tokens.is_empty() {
+            options.prompt = Some(Prompt::Tokens(all_tokens.clone()));
+        }
+
         log::info!(
             "processing segment - from: {}, to: {}",
             seek,
             seek + N_FRAMES
         );
-
+        let mel_segment = mel.slice(&[0..1, 0..n_mels, seek..seek + N_FRAMES])?;
+        let hs = model.encoder.forward(&mel_segment)?.resolve()?.detach()?;
+        let (task, mut decoded) = decode_with_fallback(
+            &mut model.decoder,
+            &model.tokenizer,
+            &hs,
+            &options,
+            &mut on_token,
+        )
+        .await?;
         let segment_size = min(N_FRAMES, content_frames - seek);
-        let _segment_duration = segment_size * HOP_LENGTH / SAMPLE_RATE;
 
-        if !all_tokens.is_empty() {
-            decode_options.prompt = Some(Prompt::Tokens(all_tokens[prompt_since_reset..].to_vec()));
+        if let Some(previous) = results.last().filter(|_| overlap_frames > 0) {
+            let special = model.tokenizer.special();
+            if merge_overlap(&previous.tokens, &mut decoded.tokens, special) > 0 {
+                let tokens = decoded.tokens.iter().map(|&t| t as u32).collect::<Vec<_>>();
+                decoded.text = model
+                    .tokenizer
+                    .decode(&tokens, true)
+                    .map_err(anyhow::Error::msg)?;
+            }
         }
 
-        let hs = model.encoder.forward(&mel_segment)?.resolve()?;
+        if options.word_timestamps {
+            decoded.words = task
+                .word_timings(
+                    &model.decoder,
+                    &hs,
+                    &model.tokenizer,
+                    &decoded.tokens,
+                    segment_size,
+                )
+                .await?;
+        }
 
-        let task = DecodingTask::new(decode_options, &model.tokenizer);
-        let decoded = task.run(&model.decoder, &hs, &model.tokenizer)?;
-        println!("{}: {:?}", time_offset, decoded);
+        all_tokens.extend_from_slice(&decoded.tokens);
+        results.push(decoded);
+        //Step back by the overlap, unless this was the last window
+        seek += if seek + segment_size < content_frames {
+            segment_size - overlap_frames
+        } else {
+            segment_size
+        };
     }
+    Ok(results)
+}
 
-    Ok(())
+/// A [Whisper] model for JS, see [WhisperModel::transcribe_streaming].
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub struct WhisperModel {
+    inner: Whisper,
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl WhisperModel {
+    /// Loads a GGML model, e.g `ggml-tiny.bin`, onto the GPU, alongside the contents of
+    /// its `tokenizer.json`.
+    pub async fn load(model: Vec<u8>, tokenizer: Vec<u8>) -> Result<WhisperModel, JsError> {
+        let device = ratchet::Device::request_device(ratchet::DeviceRequest::GPU)
+            .await
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let inner = Whisper::load(&mut std::io::Cursor::new(model), tokenizer, &device)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// [transcribe_streaming] with a JS callback, invoked with each token's text.
+    /// `options` are those built by [crate::DecodingOptionsBuilder].
+    /// Resolves to the text of every window.
+    pub async fn transcribe_streaming(
+        &mut self,
+        audio: Vec<f32>,
+        options: JsValue,
+        on_token: js_sys::Function,
+    ) -> Result<Vec<String>, JsError> {
+        let options: DecodingOptions = serde_wasm_bindgen::from_value(options)?;
+        let results = transcribe_streaming(&mut self.inner, audio, options, |text| {
+            let _ = on_token.call1(&JsValue::NULL, &text.into());
+        })
+        .await
+        .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(results.into_iter().map(|r| r.text).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_overlap;
+    use crate::SpecialTokens;
+
+    #[test]
+    fn merges_repeated_text() {
+        let special = SpecialTokens::MULTILINGUAL;
+        let ts = special.timestamp_begin;
+        let previous = [ts, 10, 11, 12, 13, ts + 100];
+
+        //The repeat is removed, timestamps in the next window are kept
+        let mut next = vec![ts, 12, 13, 14, ts + 50];
+        assert_eq!(merge_overlap(&previous, &mut next, &special), 2);
+        assert_eq!(next, vec![ts, 14, ts + 50]);
+
+        //Prefers the longest run
+        let mut next = vec![11, 12, 13, 11, 12];
+        assert_eq!(merge_overlap(&previous, &mut next, &special), 3);
+        assert_eq!(next, vec![11, 12]);
+
+        //A single shared token is left alone
+        let mut next = vec![13, 20];
+        assert_eq!(merge_overlap(&previous, &mut next, &special), 0);
+        assert_eq!(next, vec![13, 20]);
+    }
 }
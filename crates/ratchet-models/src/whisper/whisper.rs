@@ -2,9 +2,12 @@ use std::io::{BufRead, Seek, SeekFrom};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use ratchet::{Device, Tensor};
-use ratchet_loader::{GGMLCompatible, GGMLFormat, LoadError};
+use ratchet_loader::{GGMLCompatible, GGMLFormat, GGMLModel, LoadError};
 
-use crate::{Language, SpectrogramGenerator, WhisperDecoder, WhisperEncoder, WhisperTokenizer};
+use crate::{
+    warm_pipelines, Language, SpectrogramGenerator, Task, WhisperDecoder, WhisperEncoder,
+    WhisperTokenizer,
+};
 
 pub struct WhisperGGMLHeader {
     pub format: GGMLFormat,
@@ -29,6 +32,24 @@ pub struct HyperParameters {
 }
 
 impl HyperParameters {
+    /// Number of tensors a GGML file with these hyperparameters should contain.
+    ///
+    /// Each stem contributes its embeddings (plus the 2 convs for the encoder) and a final LayerNorm,
+    /// encoder blocks hold 15 tensors and decoder blocks 24, as they also carry cross attention.
+    pub fn n_tensors(&self) -> usize {
+        const ENCODER_BLOCK: usize = 15;
+        const DECODER_BLOCK: usize = 24;
+        let encoder = 5 + 2 + ENCODER_BLOCK * self.n_audio_layer as usize;
+        let decoder = 2 + 2 + DECODER_BLOCK * self.n_text_layer as usize;
+        encoder + decoder
+    }
+
+    /// English-only models have a vocab of 51864, multilingual ones 51865, or 51866 for large-v3
+    /// which adds a token for Cantonese.
+    pub fn is_multilingual(&self) -> bool {
+        self.n_vocab >= 51865
+    }
+
     pub fn read<R: BufRead>(reader: &mut R) -> Result<Self, std::io::Error> {
         let n_vocab = reader.read_i32::<LittleEndian>()?;
         let n_audio_ctx = reader.read_i32::<LittleEndian>()?;
@@ -146,11 +167,543 @@ impl GGMLCompatible for Whisper {
 }
 
 impl Whisper {
+    /// Load both the encoder and decoder from a GGML file.
+    ///
+    /// The tensor count is validated against the hyperparameters before any
+    /// tensor data is read, so a truncated or mismatched file fails early.
+    /// Weights are uploaded in a single batch, see [Device::batch_uploads].
+    pub fn load_all<R: BufRead + Seek>(
+        reader: &mut R,
+        device: &Device,
+    ) -> anyhow::Result<(WhisperEncoder, WhisperDecoder)> {
+        let disk_model: GGMLModel<Whisper> = Whisper::load_ggml(reader)?;
+        let expected = disk_model.header.hparams.n_tensors();
+        let found = disk_model.tensors.len();
+        if found != expected {
+            anyhow::bail!(
+                "Expected {} tensors for the given hyperparameters, found {}",
+                expected,
+                found
+            );
+        }
+        device.batch_uploads(|| {
+            let encoder = WhisperEncoder::load(&disk_model, reader, device)?;
+            let decoder = WhisperDecoder::load(&disk_model, reader, device)?;
+            Ok((encoder, decoder))
+        })
+    }
+
+    /// # Load
+    ///
+    /// Loads a complete model from a GGML file, see [Whisper::load_all]. The spectrogram
+    /// generator uses the mel filters from the file's header. `tokenizer` holds the contents
+    /// of the model's `tokenizer.json`.
+    pub fn load<R: BufRead + Seek>(
+        reader: &mut R,
+        tokenizer: Vec<u8>,
+        device: &Device,
+    ) -> anyhow::Result<Self> {
+        let start = reader.stream_position()?;
+        let header = Whisper::load_header(reader)?;
+        reader.seek(SeekFrom::Start(start))?;
+        let (encoder, decoder) = Self::load_all(reader, device)?;
+
+        let hparams = header.hparams;
+        let tokenizer = WhisperTokenizer::load(
+            Some(tokenizer),
+            hparams.is_multilingual(),
+            Language::String("en".to_string()),
+            Task::Transcribe,
+        );
+        Ok(Self {
+            specgen: SpectrogramGenerator::new(header.filters.mels),
+            encoder,
+            decoder,
+            hparams,
+            device: device.clone(),
+            tokenizer,
+        })
+    }
+
+    /// Mel bins the encoder expects, 80 for most models and 128 for large-v3.
+    pub fn n_mels(&self) -> usize {
+        self.hparams.n_mels as usize
+    }
+
     pub fn is_multilingual(&self) -> bool {
-        self.hparams.n_vocab == 51865
+        self.tokenizer.is_multilingual()
+    }
+
+    /// # Warmup
+    ///
+    /// Compiles the pipelines transcription needs ahead of time, e.g right after loading,
+    /// so the first transcription doesn't stall on them. Runs a silent window through the
+    /// encoder and a single greedy decode step, the results are discarded.
+    pub async fn warmup(&self) -> anyhow::Result<()> {
+        let sot_sequence = self.tokenizer.sot_sequence_for(Task::Transcribe);
+        warm_pipelines(
+            &self.encoder,
+            &self.decoder,
+            self.n_mels(),
+            &sot_sequence,
+            self.tokenizer.special(),
+        )
+        .await
     }
 
     pub fn detect_language(&self, _mel: Tensor) -> anyhow::Result<Language> {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use ratchet::{shape, Device, DeviceRequest, Tensor};
+    use ratchet_loader::{GGMLCompatible, GGMLFormat, MAGIC_GGML};
+    use ratchet_nn::Module;
+
+    use super::{HyperParameters, MelFilters, Whisper, WhisperGGMLHeader};
+    use crate::whisper::tokenizer::tests::TOKENIZER_JSON;
+    use crate::{
+        compare_named, warm_pipelines, DecodeError, SpecialTokens, WhisperDecoder, WhisperEncoder,
+    };
+
+    #[test]
+    fn tiny_tensor_count() {
+        let tiny = HyperParameters {
+            n_vocab: 51865,
+            n_audio_ctx: 1500,
+            n_audio_state: 384,
+            n_audio_head: 6,
+            n_audio_layer: 4,
+            n_text_ctx: 448,
+            n_text_state: 384,
+            n_text_head: 6,
+            n_text_layer: 4,
+            n_mels: 80,
+            ftype: 1,
+        };
+        assert_eq!(tiny.n_tensors(), 167);
+    }
+
+    #[test]
+    fn large_v3_is_multilingual() {
+        let large_v3 = HyperParameters {
+            n_vocab: 51866,
+            n_audio_ctx: 1500,
+            n_audio_state: 1280,
+            n_audio_head: 20,
+            n_audio_layer: 32,
+            n_text_ctx: 448,
+            n_text_state: 1280,
+            n_text_head: 20,
+            n_text_layer: 32,
+            n_mels: 128,
+            ftype: 1,
+        };
+        assert!(large_v3.is_multilingual());
+        let tiny_en = HyperParameters {
+            n_vocab: 51864,
+            ..large_v3
+        };
+        assert!(!tiny_en.is_multilingual());
+    }
+
+    #[test]
+    fn large_v3_vocab_loads_multilingual() -> anyhow::Result<()> {
+        //The vocab lacks the special tokens, so the model's own vocab size decides
+        let hparams = HyperParameters {
+            n_vocab: 51866,
+            ..synthetic_hparams()
+        };
+        let mut reader = Cursor::new(synthetic_ggml(hparams)?);
+        let tokenizer = TOKENIZER_JSON.as_bytes().to_vec();
+        let model = Whisper::load(&mut reader, tokenizer, &Device::CPU)?;
+        assert!(model.is_multilingual());
+        assert_eq!(model.n_mels(), model.specgen.n_mels());
+        Ok(())
+    }
+
+    fn synthetic_hparams() -> HyperParameters {
+        HyperParameters {
+            n_vocab: 16,
+            n_audio_ctx: 6,
+            n_audio_state: 8,
+            n_audio_head: 2,
+            n_audio_layer: 1,
+            n_text_ctx: 8,
+            n_text_state: 8,
+            n_text_head: 2,
+            n_text_layer: 1,
+            n_mels: 4,
+            ftype: 0,
+        }
+    }
+
+    /// Tensor names & shapes of a whisper model, in the order whisper.cpp writes them.
+    fn synthetic_tensors(hp: &HyperParameters) -> Vec<(String, Vec<usize>)> {
+        let n_mels = hp.n_mels as usize;
+        let (n_state, n_mlp) = (hp.n_audio_state as usize, 4 * hp.n_audio_state as usize);
+        let mut tensors = vec![
+            (
+                "encoder.positional_embedding".into(),
+                vec![hp.n_audio_ctx as usize, n_state],
+            ),
+            ("encoder.conv1.weight".into(), vec![n_state, n_mels, 3]),
+            ("encoder.conv1.bias".into(), vec![n_state, 1]),
+            ("encoder.conv2.weight".into(), vec![n_state, n_state, 3]),
+            ("encoder.conv2.bias".into(), vec![n_state, 1]),
+            ("encoder.ln_post.weight".into(), vec![n_state]),
+            ("encoder.ln_post.bias".into(), vec![n_state]),
+            (
+                "decoder.token_embedding.weight".into(),
+                vec![hp.n_vocab as usize, n_state],
+            ),
+            (
+                "decoder.positional_embedding".into(),
+                vec![hp.n_text_ctx as usize, n_state],
+            ),
+            ("decoder.ln.weight".into(), vec![n_state]),
+            ("decoder.ln.bias".into(), vec![n_state]),
+        ];
+        let attention = |prefix: &str| {
+            vec![
+                (format!("{prefix}.query.weight"), vec![n_state, n_state]),
+                (format!("{prefix}.query.bias"), vec![n_state]),
+                (format!("{prefix}.key.weight"), vec![n_state, n_state]),
+                (format!("{prefix}.value.weight"), vec![n_state, n_state]),
+                (format!("{prefix}.value.bias"), vec![n_state]),
+                (format!("{prefix}.out.weight"), vec![n_state, n_state]),
+                (format!("{prefix}.out.bias"), vec![n_state]),
+            ]
+        };
+        let blocks = [("encoder", hp.n_audio_layer), ("decoder", hp.n_text_layer)];
+        for (stem, n_layers) in blocks {
+            for layer in 0..n_layers {
+                let block = format!("{stem}.blocks.{layer}");
+                let mut lns = vec!["attn_ln", "mlp_ln"];
+                tensors.extend(attention(&format!("{block}.attn")));
+                if stem == "decoder" {
+                    lns.push("cross_attn_ln");
+                    tensors.extend(attention(&format!("{block}.cross_attn")));
+                }
+                for ln in lns {
+                    tensors.push((format!("{block}.{ln}.weight"), vec![n_state]));
+                    tensors.push((format!("{block}.{ln}.bias"), vec![n_state]));
+                }
+                tensors.extend([
+                    (format!("{block}.mlp.0.weight"), vec![n_mlp, n_state]),
+                    (format!("{block}.mlp.0.bias"), vec![n_mlp]),
+                    (format!("{block}.mlp.2.weight"), vec![n_state, n_mlp]),
+                    (format!("{block}.mlp.2.bias"), vec![n_state]),
+                ]);
+            }
+        }
+        tensors
+    }
+
+    /// A GGML file for a tiny whisper with seeded random F32 weights.
+    fn synthetic_ggml(hparams: HyperParameters) -> anyhow::Result<Vec<u8>> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let tensors = synthetic_tensors(&hparams);
+        let header = WhisperGGMLHeader {
+            format: GGMLFormat::GGML(MAGIC_GGML),
+            //As whisper.cpp writes them, n_fft is the number of frequency bins
+            filters: MelFilters {
+                n_mel: hparams.n_mels,
+                n_fft: 201,
+                mels: vec![0.; hparams.n_mels as usize * 201],
+            },
+            hparams,
+            n_tokens: 0,
+        };
+        let mut bytes = vec![];
+        Whisper::write_header(&header, &mut bytes)?;
+        for (name, shape) in tensors {
+            bytes.write_i32::<LittleEndian>(shape.len() as _)?;
+            bytes.write_i32::<LittleEndian>(name.len() as _)?;
+            bytes.write_u32::<LittleEndian>(0)?; //F32
+            for dim in shape.iter().rev() {
+                bytes.write_u32::<LittleEndian>(*dim as _)?;
+            }
+            bytes.write_all(name.as_bytes())?;
+            for _ in 0..shape.iter().product() {
+                bytes.write_f32::<LittleEndian>(rng.gen_range(-0.5..0.5))?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn synthetic_model() -> anyhow::Result<(WhisperEncoder, WhisperDecoder)> {
+        let mut reader = Cursor::new(synthetic_ggml(synthetic_hparams())?);
+        Whisper::load_all(&mut reader, &Device::CPU)
+    }
+
+    /// A constant mel spectrogram spanning the synthetic encoder's context.
+    fn synthetic_mel() -> Tensor {
+        let hparams = synthetic_hparams();
+        let (n_mels, n_frames) = (hparams.n_mels as usize, 2 * hparams.n_audio_ctx as usize);
+        Tensor::from_data(
+            vec![0.5f32; n_mels * n_frames],
+            shape![1, n_mels, n_frames],
+            Device::CPU,
+        )
+    }
+
+    fn synthetic_audio_features(encoder: &WhisperEncoder) -> anyhow::Result<Tensor> {
+        Ok(encoder.forward(&synthetic_mel())?.resolve()?)
+    }
+
+    /// Hermetic end to end run, no GPU or network required.
+    /// Decoding a sequence in one pass and token by token through the KV cache must agree.
+    #[test]
+    fn synthetic_end_to_end_cpu() -> anyhow::Result<()> {
+        let hparams = synthetic_hparams();
+        let (n_mels, n_frames) = (hparams.n_mels as usize, 2 * hparams.n_audio_ctx as usize);
+        let (encoder, mut decoder) = synthetic_model()?;
+
+        let mel = (0..n_mels * n_frames)
+            .map(|i| (i as f32 * 0.37).sin())
+            .collect::<Vec<_>>();
+        let mel = Tensor::from_data(mel, shape![1, n_mels, n_frames], Device::CPU);
+        let audio_ctx = encoder.forward(&mel)?.resolve()?;
+        assert_eq!(audio_ctx.shape(), &shape![1, 6, 8]);
+
+        let tokens = vec![1i32, 5, 9];
+        let input = Tensor::from_data(tokens.clone(), shape![1, tokens.len()], Device::CPU);
+        let logits = decoder.forward(&[audio_ctx.clone(), input])?.resolve()?;
+        assert_eq!(logits.shape(), &shape![1, 3, 16]);
+        let logits = logits.to_vec::<f32>()?;
+        assert!(logits.iter().all(|l| l.is_finite()));
+
+        decoder.cache_mut().reset();
+        for (step, token) in tokens.into_iter().enumerate() {
+            let input = Tensor::from_data(vec![token], shape![1, 1], Device::CPU);
+            let step_logits = decoder
+                .forward(&[audio_ctx.clone(), input])?
+                .resolve()?
+                .to_vec::<f32>()?;
+            decoder.cache_mut().update(1);
+            let expected = &logits[step * 16..(step + 1) * 16];
+            for (a, b) in step_logits.iter().zip(expected) {
+                assert!((a - b).abs() < 1e-4, "step {step}: {a} != {b}");
+            }
+        }
+
+        //Several tokens at once on top of the cache, as after a forced token
+        decoder.cache_mut().reset();
+        let first = Tensor::from_data(vec![1i32], shape![1, 1], Device::CPU);
+        decoder.forward(&[audio_ctx.clone(), first])?.resolve()?;
+        decoder.cache_mut().update(1);
+        let rest = Tensor::from_data(vec![5i32, 9], shape![1, 2], Device::CPU);
+        let rest_logits = decoder
+            .forward(&[audio_ctx.clone(), rest])?
+            .resolve()?
+            .to_vec::<f32>()?;
+        for (a, b) in rest_logits.iter().zip(&logits[16..]) {
+            assert!((a - b).abs() < 1e-4, "{a} != {b}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn warmup_runs_encoder_and_decoder() -> anyhow::Result<()> {
+        let n_mels = synthetic_hparams().n_mels as usize;
+        let (encoder, decoder) = synthetic_model()?;
+        assert_eq!(encoder.n_ctx(), 6);
+        let special = SpecialTokens::MULTILINGUAL;
+        pollster::block_on(warm_pipelines(
+            &encoder,
+            &decoder,
+            n_mels,
+            &[1, 5],
+            &special,
+        ))?;
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_reference_moved_to_gpu() -> anyhow::Result<()> {
+        let (mut encoder, mut decoder) = synthetic_model()?;
+        let mel = synthetic_mel();
+        let tokens = Tensor::from_data(vec![1i32, 5, 9], shape![1, 3], Device::CPU);
+        let decode = |encoder: &WhisperEncoder,
+                      decoder: &WhisperDecoder,
+                      device: &Device|
+         -> anyhow::Result<Tensor> {
+            let audio_ctx = encoder.forward(&mel.to(device)?)?.resolve()?;
+            let logits = decoder
+                .forward(&[audio_ctx, tokens.to(device)?])?
+                .resolve()?;
+            Ok(logits.to(&Device::CPU)?)
+        };
+        let expected = decode(&encoder, &decoder, &Device::CPU)?;
+
+        let gpu = Device::request_device(DeviceRequest::GPU)?;
+        encoder.to(&gpu)?;
+        decoder.to(&gpu)?;
+        let mut params = encoder.parameters().into_iter().chain(decoder.parameters());
+        assert!(params.all(|p| p.device() == &gpu));
+        decode(&encoder, &decoder, &gpu)?.all_close(&expected, 1e-4, 1e-4)
+    }
+
+    #[test]
+    fn forked_decoders_run_concurrently() -> anyhow::Result<()> {
+        let (encoder, decoder) = synthetic_model()?;
+        let audio_ctx = synthetic_audio_features(&encoder)?;
+
+        let decode = |mut decoder: WhisperDecoder| -> anyhow::Result<Vec<f32>> {
+            let mut logits = vec![];
+            for token in [1i32, 5, 9] {
+                let input = Tensor::from_data(vec![token], shape![1, 1], Device::CPU);
+                let step = decoder.forward(&[audio_ctx.clone(), input])?.resolve()?;
+                logits.extend(step.to_vec::<f32>()?);
+                decoder.cache_mut().update(1);
+            }
+            Ok(logits)
+        };
+        let streams = std::thread::scope(|scope| {
+            let handles = (0..3)
+                .map(|_| {
+                    let fork = decoder.fork();
+                    scope.spawn(move || decode(fork))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+        let expected = decode(decoder)?;
+        assert!(streams.iter().all(|logits| logits == &expected));
+        Ok(())
+    }
+
+    #[test]
+    fn context_exceeded_and_extended() -> anyhow::Result<()> {
+        let n_text_ctx = synthetic_hparams().n_text_ctx as usize;
+        let (encoder, mut decoder) = synthetic_model()?;
+        let audio_ctx = synthetic_audio_features(&encoder)?;
+        let tokens = vec![1i32; n_text_ctx + 2];
+        let input = || Tensor::from_data(tokens.clone(), shape![1, tokens.len()], Device::CPU);
+
+        let err = decoder.forward(&[audio_ctx.clone(), input()]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DecodeError>(),
+            Some(DecodeError::ContextExceeded { requested, limit })
+                if *requested == n_text_ctx + 2 && *limit == n_text_ctx
+        ));
+
+        let original = decoder.forward(&[audio_ctx.clone(), input().narrow(1, 0, 1)?])?;
+        let original = original.resolve()?.to_vec::<f32>()?;
+        decoder.extend_context(2 * n_text_ctx)?;
+        assert_eq!(decoder.n_ctx(), 2 * n_text_ctx);
+        let logits = decoder.forward(&[audio_ctx.clone(), input()])?.resolve()?;
+        let logits = logits.to_vec::<f32>()?;
+        assert!(logits.iter().all(|l| l.is_finite()));
+        //The first position is unchanged by interpolation
+        for (a, b) in original.iter().zip(&logits) {
+            assert!((a - b).abs() < 1e-4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn preallocated_cache_bounds_decoding() -> anyhow::Result<()> {
+        let (encoder, mut decoder) = synthetic_model()?;
+        let audio_ctx = synthetic_audio_features(&encoder)?;
+
+        decoder.set_cache_capacity(3);
+        assert_eq!(decoder.cache_mut().capacity(), 3);
+        for step in 0..4 {
+            let input = Tensor::from_data(vec![1i32], shape![1, 1], Device::CPU);
+            let result = decoder.forward(&[audio_ctx.clone(), input]);
+            match result {
+                Ok(_) => assert!(step < 3),
+                Err(e) => assert!(matches!(
+                    e.downcast_ref::<DecodeError>(),
+                    Some(DecodeError::ContextExceeded {
+                        requested: 4,
+                        limit: 3
+                    })
+                )),
+            }
+            decoder.cache_mut().update(1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn resident_snapshot_round_trip() -> anyhow::Result<()> {
+        let hparams = synthetic_hparams();
+        let n_tensors = hparams.n_tensors();
+        let (encoder, mut decoder) = synthetic_model()?;
+        decoder.extend_context(2 * hparams.n_text_ctx as usize)?;
+
+        let named = Whisper::named_tensors(&encoder, &decoder);
+        assert_eq!(named.len(), n_tensors);
+        let host = named.iter().map(|(_, t)| t.clone()).collect::<Vec<_>>();
+        let snapshot = Whisper::write_resident(&hparams, &decoder, &named, &host)?;
+        let (restored_encoder, mut restored_decoder) = Whisper::restore(&snapshot, &Device::CPU)?;
+        assert_eq!(restored_decoder.n_ctx(), decoder.n_ctx());
+
+        let mel = synthetic_mel();
+        let tokens = || Tensor::from_data(vec![1i32, 5, 9], shape![1, 3], Device::CPU);
+        let decode = |encoder: &WhisperEncoder, decoder: &mut WhisperDecoder| {
+            let audio_ctx = encoder.forward(&mel)?.resolve()?;
+            decoder
+                .forward(&[audio_ctx, tokens()])?
+                .resolve()?
+                .to_vec::<f32>()
+        };
+        assert_eq!(
+            decode(&encoder, &mut decoder)?,
+            decode(&restored_encoder, &mut restored_decoder)?
+        );
+
+        assert!(Whisper::restore(&snapshot[..snapshot.len() - 4], &Device::CPU).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compare_reports_mismatched_tensors() -> anyhow::Result<()> {
+        let load = || -> anyhow::Result<Vec<(String, Tensor)>> {
+            let (encoder, decoder) = synthetic_model()?;
+            Ok(Whisper::named_tensors(&encoder, &decoder))
+        };
+        let (a, mut b) = (load()?, load()?);
+        assert!(compare_named(&a, &b, 0.)?.is_empty());
+
+        //Doubling a tensor differs from the original by its largest magnitude
+        let (name, t) = b[2].clone();
+        let max_abs = t.to_vec::<f32>()?.iter().fold(0f32, |m, x| m.max(x.abs()));
+        b[2].1 = t.add(&t)?.resolve()?;
+        let (last, _) = b.pop().unwrap();
+        let diffs = compare_named(&a, &b, 1e-6)?;
+        assert_eq!(diffs, [(name, max_abs), (last, f32::INFINITY)]);
+        Ok(())
+    }
+
+    #[test]
+    fn parameters_match_named_tensors() -> anyhow::Result<()> {
+        let (encoder, decoder) = synthetic_model()?;
+
+        let params = encoder
+            .parameters()
+            .into_iter()
+            .chain(decoder.parameters())
+            .map(Tensor::id)
+            .collect::<Vec<_>>();
+        let named = Whisper::named_tensors(&encoder, &decoder)
+            .iter()
+            .map(|(_, t)| t.id())
+            .collect::<Vec<_>>();
+        assert_eq!(params.len(), synthetic_hparams().n_tensors());
+        assert_eq!(params, named);
+        Ok(())
+    }
+}
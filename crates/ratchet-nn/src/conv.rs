@@ -0,0 +1,39 @@
+use ratchet::Tensor;
+
+use crate::Module;
+
+/// 1D convolution, see [Tensor::conv1d] for the supported configurations.
+#[derive(derive_new::new, Debug)]
+pub struct Conv1d {
+    weight: Tensor,
+    bias: Option<Tensor>,
+    stride: usize,
+    padding: usize,
+}
+
+impl Conv1d {
+    pub fn weight(&self) -> &Tensor {
+        &self.weight
+    }
+
+    pub fn bias(&self) -> Option<&Tensor> {
+        self.bias.as_ref()
+    }
+}
+
+impl Module for Conv1d {
+    type Input = Tensor;
+    fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
+        input.conv1d(&self.weight, self.bias.as_ref(), self.stride, self.padding)
+    }
+
+    fn parameters(&self) -> Vec<&Tensor> {
+        std::iter::once(&self.weight).chain(&self.bias).collect()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut Tensor> {
+        std::iter::once(&mut self.weight)
+            .chain(&mut self.bias)
+            .collect()
+    }
+}
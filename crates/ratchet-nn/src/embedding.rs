@@ -1,7 +1,7 @@
 use crate::Module;
 use ratchet::{shape, Tensor};
 
-#[derive(Debug, derive_new::new)]
+#[derive(Clone, Debug, derive_new::new)]
 pub struct Embedding {
     pub weight: Tensor,
 }
@@ -19,6 +19,14 @@ impl Module for Embedding {
         let x = indexed.view(output_shape)?;
         Ok(x)
     }
+
+    fn parameters(&self) -> Vec<&Tensor> {
+        vec![&self.weight]
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut Tensor> {
+        vec![&mut self.weight]
+    }
 }
 
 #[cfg(test)]
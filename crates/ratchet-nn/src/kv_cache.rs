@@ -1,4 +1,4 @@
-use ratchet::{Device, Shape, Tensor};
+use ratchet::{shape, Device, Shape, Tensor};
 
 #[derive(Clone, Debug)]
 pub struct KVEntry {
@@ -15,6 +15,19 @@ impl KVEntry {
             entries: 0,
         }
     }
+
+    /// Row `i` of the gathered cache is row `indices[i]` of `cache`, along the batch dim.
+    fn gather_rows(cache: &Tensor, indices: &Tensor) -> anyhow::Result<Tensor> {
+        let mut shape = cache.shape().clone();
+        let rows = shape[0];
+        let flat = cache.view(shape![rows, shape.numel() / rows])?;
+        shape[0] = indices.shape()[0];
+        Ok(flat
+            .index_select(indices, 0)?
+            .view(shape)?
+            .resolve()?
+            .detach()?)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -37,13 +50,129 @@ impl KVCache {
         KVCache(entries)
     }
 
+    /// # With capacity
+    ///
+    /// Preallocates `max_len` positions for every layer, laid out as `[1, max_len, n_heads * head_dim]`.
+    /// Decoding writes each step into this region, the cache never grows, so its footprint
+    /// ([KVCache::size_in_bytes]) is known before decoding starts.
+    pub fn with_capacity(
+        n_layers: usize,
+        n_heads: usize,
+        head_dim: usize,
+        max_len: usize,
+        device: &Device,
+    ) -> Self {
+        Self::new(
+            n_layers as _,
+            &shape![1, max_len, n_heads * head_dim],
+            device,
+        )
+    }
+
+    /// Positions each layer can hold.
+    pub fn capacity(&self) -> usize {
+        self.0.first().map_or(0, |entry| entry.k_cache.shape()[1])
+    }
+
+    /// Bytes held by the keys and values of every layer.
+    pub fn size_in_bytes(&self) -> usize {
+        self.0
+            .iter()
+            .map(|entry| entry.k_cache.num_bytes() + entry.v_cache.num_bytes())
+            .sum()
+    }
+
     pub fn update(&mut self, offset: usize) {
         for entry in &mut self.0 {
             entry.entries += offset;
         }
     }
 
+    /// Empties every layer, keeping the allocated buffers.
+    pub fn reset(&mut self) {
+        for entry in &mut self.0 {
+            entry.entries = 0;
+        }
+    }
+
     pub fn entries(&self, layer: usize) -> usize {
         self.0[layer].entries
     }
+
+    /// Transfers every layer to `device`, keeping the entries written so far.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to(&self, device: &Device) -> anyhow::Result<Self> {
+        let mut entries = Vec::with_capacity(self.0.len());
+        for entry in &self.0 {
+            entries.push(KVEntry {
+                k_cache: entry.k_cache.to(device)?,
+                v_cache: entry.v_cache.to(device)?,
+                entries: entry.entries,
+            });
+        }
+        Ok(KVCache(entries))
+    }
+
+    /// # Reorder
+    ///
+    /// Gathers every layer's cache along the batch (beam) dim, so that beam `i` continues
+    /// from the cache of beam `beam_indices[i]`. Beam search calls this after pruning,
+    /// a beam may be duplicated or dropped, and the number of beams may change.
+    pub fn reorder(&mut self, beam_indices: &[usize]) -> anyhow::Result<()> {
+        let Some(first) = self.0.first() else {
+            return Ok(());
+        };
+        let n_beams = first.k_cache.shape()[0];
+        if let Some(&bad) = beam_indices.iter().find(|&&i| i >= n_beams) {
+            anyhow::bail!("Beam index {} out of range for {} beams", bad, n_beams);
+        }
+        let indices = Tensor::from_data(
+            beam_indices.iter().map(|&i| i as i32).collect::<Vec<_>>(),
+            shape![beam_indices.len()],
+            first.k_cache.device().clone(),
+        );
+        for entry in &mut self.0 {
+            entry.k_cache = KVEntry::gather_rows(&entry.k_cache, &indices)?;
+            entry.v_cache = KVEntry::gather_rows(&entry.v_cache, &indices)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratchet::{shape, Device, Tensor};
+
+    use super::{KVCache, KVEntry};
+
+    #[test]
+    fn reorder_gathers_beams() -> anyhow::Result<()> {
+        let beam = |b: usize| (0..6).map(move |i| (b * 10 + i) as f32);
+        let data = (0..2).flat_map(beam).collect::<Vec<_>>();
+        let entry = KVEntry {
+            k_cache: Tensor::from_data(data.clone(), shape![2, 3, 2], Device::CPU),
+            v_cache: Tensor::from_data(data, shape![2, 3, 2], Device::CPU),
+            entries: 1,
+        };
+        let mut cache = KVCache(vec![entry.clone(), entry]);
+
+        cache.reorder(&[1, 1, 0])?;
+        let expected = [1, 1, 0].into_iter().flat_map(beam).collect::<Vec<_>>();
+        for layer in 0..2 {
+            assert_eq!(cache[layer].k_cache.shape(), &shape![3, 3, 2]);
+            assert_eq!(cache[layer].k_cache.to_vec::<f32>()?, expected);
+            assert_eq!(cache[layer].v_cache.to_vec::<f32>()?, expected);
+            assert_eq!(cache.entries(layer), 1);
+        }
+        assert!(cache.reorder(&[3]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn with_capacity_footprint() {
+        let cache = KVCache::with_capacity(4, 6, 64, 448, &Device::CPU);
+        assert_eq!(cache.capacity(), 448);
+        assert_eq!(cache[3].k_cache.shape(), &shape![1, 448, 384]);
+        assert_eq!(cache.size_in_bytes(), 4 * 2 * 448 * 384 * 4);
+    }
 }
@@ -1,16 +1,44 @@
+mod conv;
 mod embedding;
 mod kv_cache;
 mod linear;
 mod norm;
 
+pub use conv::*;
 pub use embedding::*;
 pub use kv_cache::*;
 pub use linear::*;
 pub use norm::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+use ratchet::Device;
 use ratchet::Tensor;
 
 pub trait Module {
     type Input;
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor>;
+
+    /// Every tensor the module holds, including those of its submodules.
+    /// Caches and masks computed at load are state, not parameters, and are excluded.
+    /// Defaults to none, modules holding tensors override this and [Module::parameters_mut].
+    fn parameters(&self) -> Vec<&Tensor> {
+        vec![]
+    }
+
+    /// Mutable counterpart of [Module::parameters], in the same order.
+    fn parameters_mut(&mut self) -> Vec<&mut Tensor> {
+        vec![]
+    }
+
+    /// # To
+    ///
+    /// Transfers every parameter to `device` in place, see [Tensor::to].
+    /// Modules holding state that must follow their weights (e.g a KV cache) override this.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn to(&mut self, device: &Device) -> anyhow::Result<()> {
+        for param in self.parameters_mut() {
+            *param = param.to(device)?;
+        }
+        Ok(())
+    }
 }
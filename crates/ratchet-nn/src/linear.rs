@@ -2,12 +2,22 @@ use ratchet::Tensor;
 
 use crate::Module;
 
-#[derive(derive_new::new, Debug)]
+#[derive(derive_new::new, Clone, Debug)]
 pub struct Linear {
     w: Tensor,
     b: Option<Tensor>,
 }
 
+impl Linear {
+    pub fn weight(&self) -> &Tensor {
+        &self.w
+    }
+
+    pub fn bias(&self) -> Option<&Tensor> {
+        self.b.as_ref()
+    }
+}
+
 impl Module for Linear {
     type Input = Tensor;
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
@@ -18,4 +28,12 @@ impl Module for Linear {
             Ok(y)
         }
     }
+
+    fn parameters(&self) -> Vec<&Tensor> {
+        std::iter::once(&self.w).chain(&self.b).collect()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut Tensor> {
+        std::iter::once(&mut self.w).chain(&mut self.b).collect()
+    }
 }
@@ -47,4 +47,47 @@ impl crate::Module for LayerNorm {
     fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
         input.layer_norm(&self.weight, self.bias.as_ref(), self.eps)
     }
+
+    fn parameters(&self) -> Vec<&Tensor> {
+        std::iter::once(&self.weight).chain(&self.bias).collect()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut Tensor> {
+        std::iter::once(&mut self.weight)
+            .chain(&mut self.bias)
+            .collect()
+    }
+}
+
+/// Root mean square normalization, as used by Llama style models.
+/// Unlike [LayerNorm] there is no mean subtraction and no bias.
+#[derive(Clone, Debug)]
+pub struct RMSNorm {
+    weight: Tensor,
+    eps: f32,
+}
+
+impl RMSNorm {
+    pub fn new(weight: Tensor, eps: f32) -> Self {
+        Self { weight, eps }
+    }
+
+    pub fn weight(&self) -> &Tensor {
+        &self.weight
+    }
+}
+
+impl crate::Module for RMSNorm {
+    type Input = Tensor;
+    fn forward(&self, input: &Self::Input) -> anyhow::Result<Tensor> {
+        input.rms_norm(&self.weight, self.eps)
+    }
+
+    fn parameters(&self) -> Vec<&Tensor> {
+        vec![&self.weight]
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut Tensor> {
+        vec![&mut self.weight]
+    }
 }